@@ -1,13 +1,22 @@
-use super::{Backend, ManageBackend, QueryBackend};
+use std::collections::BTreeMap;
+use std::sync::Weak;
+
+use super::{Backend, ManageBackend, QueryBackend, SchemaMigration};
 use crate::{
+    crypto::buffer::SecretBytes,
     error::Error,
     future::BoxFuture,
     protect::{PassKey, StoreKeyMethod},
     storage::{
-        Entry, EntryKind, EntryOperation, EntryTag, IntoOptions, Scan, Session, Store, TagFilter,
+        CategoryStats, Entry, EntryChange, EntryKind, EntryOperation, EntryTag, EntryUpdate,
+        IntoOptions, OrderBy, Scan, Session, Store, TagFilter, TagNameStats, TagQueryLimits,
+        VerifyReport, WatchRegistry,
     },
 };
 
+#[cfg(feature = "memory")]
+use super::memory::{self, MemoryStore};
+
 #[cfg(feature = "postgres")]
 use super::postgres::{self, PostgresStore};
 
@@ -23,6 +32,10 @@ pub type AnySession = Session<AnyQueryBackend>;
 /// An enumeration of supported store backends
 #[derive(Debug)]
 pub enum AnyBackend {
+    /// An in-memory store
+    #[cfg(feature = "memory")]
+    Memory(MemoryStore),
+
     /// A PostgreSQL store
     #[cfg(feature = "postgres")]
     Postgres(PostgresStore),
@@ -39,6 +52,9 @@ pub enum AnyBackend {
 macro_rules! with_backend {
     ($slf:ident, $ident:ident, $body:expr) => {
         match $slf {
+            #[cfg(feature = "memory")]
+            Self::Memory($ident) => $body,
+
             #[cfg(feature = "postgres")]
             Self::Postgres($ident) => $body,
 
@@ -61,28 +77,103 @@ impl Backend for AnyBackend {
         with_backend!(self, store, store.get_profile_name())
     }
 
+    fn is_read_only(&self) -> bool {
+        with_backend!(self, store, store.is_read_only())
+    }
+
+    fn query_limits(&self) -> TagQueryLimits {
+        with_backend!(self, store, store.query_limits())
+    }
+
     fn remove_profile(&self, name: String) -> BoxFuture<'_, Result<bool, Error>> {
         with_backend!(self, store, store.remove_profile(name))
     }
 
+    fn rename_profile(
+        &self,
+        from_name: String,
+        to_name: String,
+    ) -> BoxFuture<'_, Result<bool, Error>> {
+        with_backend!(self, store, store.rename_profile(from_name, to_name))
+    }
+
+    fn copy_profile(
+        &self,
+        from_name: String,
+        to_name: String,
+    ) -> BoxFuture<'_, Result<bool, Error>> {
+        with_backend!(self, store, store.copy_profile(from_name, to_name))
+    }
+
+    fn rotate_profile_key(&self, name: String) -> BoxFuture<'_, Result<i64, Error>> {
+        with_backend!(self, store, store.rotate_profile_key(name))
+    }
+
+    fn load_profile_key(&self, name: String) -> BoxFuture<'_, Result<SecretBytes, Error>> {
+        with_backend!(self, store, store.load_profile_key(name))
+    }
+
+    fn import_profile(&self, name: String, key: SecretBytes) -> BoxFuture<'_, Result<(), Error>> {
+        with_backend!(self, store, store.import_profile(name, key))
+    }
+
+    fn list_profiles(&self) -> BoxFuture<'_, Result<Vec<String>, Error>> {
+        with_backend!(self, store, store.list_profiles())
+    }
+
+    fn list_categories(
+        &self,
+        profile: Option<String>,
+        kind: EntryKind,
+    ) -> BoxFuture<'_, Result<Vec<String>, Error>> {
+        with_backend!(self, store, store.list_categories(profile, kind))
+    }
+
+    fn purge_expired(&self) -> BoxFuture<'_, Result<BTreeMap<String, i64>, Error>> {
+        with_backend!(self, store, store.purge_expired())
+    }
+
+    fn verify(
+        &self,
+        profile: Option<String>,
+        repair: bool,
+    ) -> BoxFuture<'_, Result<VerifyReport, Error>> {
+        with_backend!(self, store, store.verify(profile, repair))
+    }
+
     fn scan(
         &self,
         profile: Option<String>,
         kind: EntryKind,
         category: String,
         tag_filter: Option<TagFilter>,
+        order_by: Option<OrderBy>,
         offset: Option<i64>,
         limit: Option<i64>,
     ) -> BoxFuture<'_, Result<Scan<'static, Entry>, Error>> {
         with_backend!(
             self,
             store,
-            store.scan(profile, kind, category, tag_filter, offset, limit)
+            store.scan(profile, kind, category, tag_filter, order_by, offset, limit)
         )
     }
 
+    fn resume_scan(
+        &self,
+        token: String,
+        limit: Option<i64>,
+    ) -> BoxFuture<'_, Result<Scan<'static, Entry>, Error>> {
+        with_backend!(self, store, store.resume_scan(token, limit))
+    }
+
     fn session(&self, profile: Option<String>, transaction: bool) -> Result<Self::Session, Error> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::Memory(store) => {
+                let session = store.session(profile, transaction)?;
+                Ok(AnyQueryBackend::MemorySession(session))
+            }
+
             #[cfg(feature = "postgres")]
             Self::Postgres(store) => {
                 let session = store.session(profile, transaction)?;
@@ -99,6 +190,30 @@ impl Backend for AnyBackend {
         }
     }
 
+    fn snapshot_session(&self, profile: Option<String>) -> Result<Self::Session, Error> {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::Memory(store) => {
+                let session = store.snapshot_session(profile)?;
+                Ok(AnyQueryBackend::MemorySession(session))
+            }
+
+            #[cfg(feature = "postgres")]
+            Self::Postgres(store) => {
+                let session = store.snapshot_session(profile)?;
+                Ok(AnyQueryBackend::PostgresSession(session))
+            }
+
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(store) => {
+                let session = store.snapshot_session(profile)?;
+                Ok(AnyQueryBackend::SqliteSession(session))
+            }
+
+            _ => unreachable!(),
+        }
+    }
+
     fn rekey_backend(
         &mut self,
         method: StoreKeyMethod,
@@ -107,6 +222,14 @@ impl Backend for AnyBackend {
         with_backend!(self, store, store.rekey_backend(method, pass_key))
     }
 
+    fn publishes_changes(&self) -> bool {
+        with_backend!(self, store, store.publishes_changes())
+    }
+
+    fn listen_changes(&self, registry: Weak<WatchRegistry>) -> BoxFuture<'_, Result<(), Error>> {
+        with_backend!(self, store, store.listen_changes(registry))
+    }
+
     fn close(&self) -> BoxFuture<'_, Result<(), Error>> {
         with_backend!(self, store, store.close())
     }
@@ -115,6 +238,10 @@ impl Backend for AnyBackend {
 /// An enumeration of supported backend session types
 #[derive(Debug)]
 pub enum AnyQueryBackend {
+    /// An in-memory store session
+    #[cfg(feature = "memory")]
+    MemorySession(<MemoryStore as Backend>::Session),
+
     /// A PostgreSQL store session
     #[cfg(feature = "postgres")]
     PostgresSession(<PostgresStore as Backend>::Session),
@@ -136,6 +263,9 @@ impl QueryBackend for AnyQueryBackend {
         tag_filter: Option<TagFilter>,
     ) -> BoxFuture<'q, Result<i64, Error>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.count(kind, category, tag_filter),
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => session.count(kind, category, tag_filter),
 
@@ -146,6 +276,43 @@ impl QueryBackend for AnyQueryBackend {
         }
     }
 
+    fn category_stats(
+        &mut self,
+        kind: EntryKind,
+    ) -> BoxFuture<'_, Result<BTreeMap<String, CategoryStats>, Error>> {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.category_stats(kind),
+
+            #[cfg(feature = "postgres")]
+            Self::PostgresSession(session) => session.category_stats(kind),
+
+            #[cfg(feature = "sqlite")]
+            Self::SqliteSession(session) => session.category_stats(kind),
+
+            _ => unreachable!(),
+        }
+    }
+
+    fn tag_names<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+    ) -> BoxFuture<'q, Result<Vec<TagNameStats>, Error>> {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.tag_names(kind, category),
+
+            #[cfg(feature = "postgres")]
+            Self::PostgresSession(session) => session.tag_names(kind, category),
+
+            #[cfg(feature = "sqlite")]
+            Self::SqliteSession(session) => session.tag_names(kind, category),
+
+            _ => unreachable!(),
+        }
+    }
+
     fn fetch<'q>(
         &'q mut self,
         kind: EntryKind,
@@ -154,6 +321,9 @@ impl QueryBackend for AnyQueryBackend {
         for_update: bool,
     ) -> BoxFuture<'q, Result<Option<Entry>, Error>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.fetch(kind, category, name, for_update),
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => session.fetch(kind, category, name, for_update),
 
@@ -169,18 +339,24 @@ impl QueryBackend for AnyQueryBackend {
         kind: EntryKind,
         category: &'q str,
         tag_filter: Option<TagFilter>,
+        order_by: Option<OrderBy>,
         limit: Option<i64>,
         for_update: bool,
     ) -> BoxFuture<'q, Result<Vec<Entry>, Error>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => {
+                session.fetch_all(kind, category, tag_filter, order_by, limit, for_update)
+            }
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => {
-                session.fetch_all(kind, category, tag_filter, limit, for_update)
+                session.fetch_all(kind, category, tag_filter, order_by, limit, for_update)
             }
 
             #[cfg(feature = "sqlite")]
             Self::SqliteSession(session) => {
-                session.fetch_all(kind, category, tag_filter, limit, for_update)
+                session.fetch_all(kind, category, tag_filter, order_by, limit, for_update)
             }
 
             _ => unreachable!(),
@@ -194,6 +370,9 @@ impl QueryBackend for AnyQueryBackend {
         tag_filter: Option<TagFilter>,
     ) -> BoxFuture<'q, Result<i64, Error>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.remove_all(kind, category, tag_filter),
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => session.remove_all(kind, category, tag_filter),
 
@@ -215,6 +394,11 @@ impl QueryBackend for AnyQueryBackend {
         expiry_ms: Option<i64>,
     ) -> BoxFuture<'q, Result<(), Error>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => {
+                session.update(kind, operation, category, name, value, tags, expiry_ms)
+            }
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => {
                 session.update(kind, operation, category, name, value, tags, expiry_ms)
@@ -229,8 +413,120 @@ impl QueryBackend for AnyQueryBackend {
         }
     }
 
+    fn update_tags<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+        add: &'q [EntryTag],
+        remove: &'q [&'q str],
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.update_tags(kind, category, name, add, remove),
+
+            #[cfg(feature = "postgres")]
+            Self::PostgresSession(session) => {
+                session.update_tags(kind, category, name, add, remove)
+            }
+
+            #[cfg(feature = "sqlite")]
+            Self::SqliteSession(session) => session.update_tags(kind, category, name, add, remove),
+
+            _ => unreachable!(),
+        }
+    }
+
+    fn set_expiry<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.set_expiry(kind, category, name, expiry_ms),
+
+            #[cfg(feature = "postgres")]
+            Self::PostgresSession(session) => session.set_expiry(kind, category, name, expiry_ms),
+
+            #[cfg(feature = "sqlite")]
+            Self::SqliteSession(session) => session.set_expiry(kind, category, name, expiry_ms),
+
+            _ => unreachable!(),
+        }
+    }
+
+    fn set_expiry_all<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        tag_filter: Option<TagFilter>,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<i64, Error>> {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => {
+                session.set_expiry_all(kind, category, tag_filter, expiry_ms)
+            }
+
+            #[cfg(feature = "postgres")]
+            Self::PostgresSession(session) => {
+                session.set_expiry_all(kind, category, tag_filter, expiry_ms)
+            }
+
+            #[cfg(feature = "sqlite")]
+            Self::SqliteSession(session) => {
+                session.set_expiry_all(kind, category, tag_filter, expiry_ms)
+            }
+
+            _ => unreachable!(),
+        }
+    }
+
+    fn apply_batch<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        updates: Vec<EntryUpdate>,
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.apply_batch(kind, updates),
+
+            #[cfg(feature = "postgres")]
+            Self::PostgresSession(session) => session.apply_batch(kind, updates),
+
+            #[cfg(feature = "sqlite")]
+            Self::SqliteSession(session) => session.apply_batch(kind, updates),
+
+            _ => unreachable!(),
+        }
+    }
+
+    fn publish_changes<'q>(
+        &'q mut self,
+        changes: &'q [EntryChange],
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => session.publish_changes(changes),
+
+            #[cfg(feature = "postgres")]
+            Self::PostgresSession(session) => session.publish_changes(changes),
+
+            #[cfg(feature = "sqlite")]
+            Self::SqliteSession(session) => session.publish_changes(changes),
+
+            _ => unreachable!(),
+        }
+    }
+
     fn close(self, commit: bool) -> BoxFuture<'static, Result<(), Error>> {
         match self {
+            #[cfg(feature = "memory")]
+            Self::MemorySession(session) => Box::pin(session.close(commit)),
+
             #[cfg(feature = "postgres")]
             Self::PostgresSession(session) => Box::pin(session.close(commit)),
 
@@ -242,6 +538,38 @@ impl QueryBackend for AnyQueryBackend {
     }
 }
 
+impl AnyStore {
+    /// Write a consistent copy of the store to a new database file
+    ///
+    /// This is only supported by Sqlite stores
+    pub async fn backup_to(&self, path: &str) -> Result<(), Error> {
+        match self.inner() {
+            #[cfg(feature = "sqlite")]
+            AnyBackend::Sqlite(store) => store.backup_to(path).await,
+
+            _ => Err(err_msg!(
+                Unsupported,
+                "Backup is not supported by this backend"
+            )),
+        }
+    }
+
+    /// Rebuild the database file to reclaim unused space
+    ///
+    /// This is only supported by Sqlite stores
+    pub async fn compact(&self) -> Result<(), Error> {
+        match self.inner() {
+            #[cfg(feature = "sqlite")]
+            AnyBackend::Sqlite(store) => store.compact().await,
+
+            _ => Err(err_msg!(
+                Unsupported,
+                "Compaction is not supported by this backend"
+            )),
+        }
+    }
+}
+
 impl<'a> ManageBackend<'a> for &'a str {
     type Store = AnyStore;
 
@@ -250,23 +578,34 @@ impl<'a> ManageBackend<'a> for &'a str {
         method: Option<StoreKeyMethod>,
         pass_key: PassKey<'a>,
         profile: Option<&'a str>,
+        read_only: bool,
     ) -> BoxFuture<'a, Result<Self::Store, Error>> {
         Box::pin(async move {
             let opts = self.into_options()?;
             debug!("Open store with options: {:?}", &opts);
 
             match opts.schema.as_ref() {
+                #[cfg(feature = "memory")]
+                "memory" => {
+                    let mgr = memory::MemoryStoreOptions::new(opts)?
+                        .open_backend(method, pass_key, profile, read_only)
+                        .await?;
+                    Ok(Store::new(AnyBackend::Memory(mgr.into_inner())))
+                }
+
                 #[cfg(feature = "postgres")]
                 "postgres" => {
-                    let opts = postgres::PostgresStoreOptions::new(opts)?;
-                    let mgr = opts.open(method, pass_key, profile).await?;
+                    let mgr = postgres::PostgresStoreOptions::new(opts)?
+                        .open_backend(method, pass_key, profile, read_only)
+                        .await?;
                     Ok(Store::new(AnyBackend::Postgres(mgr.into_inner())))
                 }
 
                 #[cfg(feature = "sqlite")]
                 "sqlite" => {
-                    let opts = sqlite::SqliteStoreOptions::new(opts)?;
-                    let mgr = opts.open(method, pass_key, profile).await?;
+                    let mgr = sqlite::SqliteStoreOptions::new(opts)?
+                        .open_backend(method, pass_key, profile, read_only)
+                        .await?;
                     Ok(Store::new(AnyBackend::Sqlite(mgr.into_inner())))
                 }
 
@@ -287,6 +626,13 @@ impl<'a> ManageBackend<'a> for &'a str {
             debug!("Provision store with options: {:?}", &opts);
 
             match opts.schema.as_ref() {
+                #[cfg(feature = "memory")]
+                "memory" => {
+                    let opts = memory::MemoryStoreOptions::new(opts)?;
+                    let mgr = opts.provision(method, pass_key, profile, recreate).await?;
+                    Ok(Store::new(AnyBackend::Memory(mgr.into_inner())))
+                }
+
                 #[cfg(feature = "postgres")]
                 "postgres" => {
                     let opts = postgres::PostgresStoreOptions::new(opts)?;
@@ -312,6 +658,12 @@ impl<'a> ManageBackend<'a> for &'a str {
             debug!("Remove store with options: {:?}", &opts);
 
             match opts.schema.as_ref() {
+                #[cfg(feature = "memory")]
+                "memory" => {
+                    let opts = memory::MemoryStoreOptions::new(opts)?;
+                    Ok(opts.remove().await?)
+                }
+
                 #[cfg(feature = "postgres")]
                 "postgres" => {
                     let opts = postgres::PostgresStoreOptions::new(opts)?;
@@ -328,4 +680,33 @@ impl<'a> ManageBackend<'a> for &'a str {
             }
         })
     }
+
+    fn pending_migrations_backend(self) -> BoxFuture<'a, Result<Vec<SchemaMigration>, Error>> {
+        Box::pin(async move {
+            let opts = self.into_options()?;
+            debug!("Check store migrations with options: {:?}", &opts);
+
+            match opts.schema.as_ref() {
+                #[cfg(feature = "memory")]
+                "memory" => {
+                    let opts = memory::MemoryStoreOptions::new(opts)?;
+                    Ok(opts.pending_migrations_backend().await?)
+                }
+
+                #[cfg(feature = "postgres")]
+                "postgres" => {
+                    let opts = postgres::PostgresStoreOptions::new(opts)?;
+                    Ok(opts.pending_migrations().await?)
+                }
+
+                #[cfg(feature = "sqlite")]
+                "sqlite" => {
+                    let opts = sqlite::SqliteStoreOptions::new(opts)?;
+                    Ok(opts.pending_migrations().await?)
+                }
+
+                _ => Err(err_msg!(Unsupported, "Invalid backend: {}", &opts.schema)),
+            }
+        })
+    }
 }
@@ -3,25 +3,36 @@ use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 
 use sqlx::{
-    database::HasArguments, pool::PoolConnection, Arguments, Database, Encode, Error as SqlxError,
-    IntoArguments, Pool, TransactionManager, Type,
+    database::HasArguments, pool::PoolConnection, Arguments, ColumnIndex, Database, Decode, Encode,
+    Error as SqlxError, Executor, IntoArguments, Pool, Row, Transaction, TransactionManager, Type,
 };
 
+use super::types::SchemaMigration;
 use crate::{
     error::Error,
-    future::BoxFuture,
+    future::{unblock, BoxFuture},
     protect::{EntryEncryptor, KeyCache, PassKey, ProfileId, ProfileKey, StoreKey, StoreKeyMethod},
     storage::{
+        verify_entries,
         wql::{
             sql::TagSqlEncoder,
-            tags::{tag_query, TagQueryEncoder},
+            tags::{tag_query, TagName, TagQueryEncoder, TagType},
+        },
+        {
+            EncEntry, EncEntryTag, Entry, EntryKind, EntryTag, OrderBy, ScanCursor, TagFilter,
+            VerifyIssue, VerifyReport,
         },
-        {EncEntryTag, Entry, EntryTag, TagFilter},
     },
 };
 
 pub const PAGE_SIZE: usize = 32;
 
+/// The maximum number of rows written by a single multi-row insert
+pub const BATCH_ROWS: usize = 100;
+
+/// The schema version created by the initial release of the SQL backends
+pub const BASE_SCHEMA_VERSION: u32 = 1;
+
 pub type Expiry = chrono::DateTime<chrono::Utc>;
 
 #[derive(Debug)]
@@ -37,6 +48,7 @@ pub struct DbSession<DB: ExtDatabase> {
     profile_key: DbSessionKey,
     state: DbSessionState<DB>,
     transaction: bool,
+    snapshot: bool,
 }
 
 impl<DB: ExtDatabase> DbSession<DB> {
@@ -53,9 +65,20 @@ impl<DB: ExtDatabase> DbSession<DB> {
             profile_key: DbSessionKey::Pending { cache, profile },
             state: DbSessionState::Pending { pool },
             transaction,
+            snapshot: false,
         }
     }
 
+    /// Create a read-only transaction session with a consistent view of the store
+    pub(crate) fn snapshot(pool: Pool<DB>, cache: Arc<KeyCache>, profile: String) -> Self
+    where
+        DB: Database,
+    {
+        let mut session = Self::new(pool, cache, profile, true);
+        session.snapshot = true;
+        session
+    }
+
     #[inline]
     fn connection_mut(&mut self) -> Option<&mut PoolConnection<DB>> {
         if let DbSessionState::Active { conn } = &mut self.state {
@@ -83,6 +106,7 @@ impl<DB: ExtDatabase> DbSession<DB> {
         if let DbSessionKey::Active {
             profile_id,
             ref key,
+            ..
         } = self.profile_key
         {
             Some((profile_id, key.clone()))
@@ -101,7 +125,10 @@ impl<DB: ExtDatabase> DbSession<DB> {
         if matches!(self.state, DbSessionState::Pending { .. }) {
             info!("Acquire pool connection");
             let mut conn = self.pool().unwrap().acquire().await?;
-            if self.transaction {
+            if self.snapshot {
+                info!("Start snapshot transaction");
+                DB::start_snapshot(&mut conn).await?;
+            } else if self.transaction {
                 info!("Start transaction");
                 DB::start_transaction(&mut conn, false).await?;
             }
@@ -113,9 +140,14 @@ impl<DB: ExtDatabase> DbSession<DB> {
                 let mut get_profile = String::new();
                 std::mem::swap(profile, &mut get_profile);
                 let (profile_id, key) = init_key
-                    .call_once(self.connection_mut().unwrap(), cache, get_profile)
+                    .call_once(self.connection_mut().unwrap(), cache.clone(), get_profile)
                     .await?;
-                self.profile_key = DbSessionKey::Active { profile_id, key };
+                self.profile_key = DbSessionKey::Active {
+                    profile_id,
+                    key,
+                    cache,
+                    checked_key: None,
+                };
                 profile_id
             }
             DbSessionKey::Active { profile_id, .. } => *profile_id,
@@ -201,6 +233,9 @@ pub(crate) enum DbSessionKey {
     Active {
         profile_id: ProfileId,
         key: Arc<ProfileKey>,
+        cache: Arc<KeyCache>,
+        /// The stored profile key last found to match `key`
+        checked_key: Option<Vec<u8>>,
     },
     Pending {
         cache: Arc<KeyCache>,
@@ -215,6 +250,15 @@ pub trait ExtDatabase: Database {
     ) -> BoxFuture<'_, Result<(), SqlxError>> {
         <Self as Database>::TransactionManager::begin(conn)
     }
+
+    /// Start a read-only transaction in which every query observes the same
+    /// state of the database
+    fn start_snapshot(conn: &mut PoolConnection<Self>) -> BoxFuture<'_, Result<(), SqlxError>> {
+        <Self as Database>::TransactionManager::begin(conn)
+    }
+
+    /// Get the number of rows changed by a query
+    fn rows_affected(result: &<Self as Database>::QueryResult) -> u64;
 }
 
 pub enum DbSessionRef<'q, DB: ExtDatabase> {
@@ -265,13 +309,21 @@ impl<'q, DB: ExtDatabase> DbSessionActive<'q, DB> {
         Ok(())
     }
 
-    #[allow(unused)]
+    pub async fn rollback(mut self) -> Result<(), Error> {
+        if self.txn_depth > 0 && !self.false_txn {
+            let conn = self.connection_mut();
+            info!("Roll-back transaction");
+            DB::TransactionManager::rollback(conn).await?;
+            self.txn_depth = 0;
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn is_transaction(&self) -> bool {
         self.txn_depth > 0
     }
 
-    #[allow(unused)]
     pub async fn transaction<'t>(&'t mut self) -> Result<DbSessionActive<'t, DB>, Error>
     where
         'q: 't,
@@ -315,6 +367,11 @@ impl<'a, DB: ExtDatabase> Drop for DbSessionActive<'a, DB> {
         if self.txn_depth > 1 && !self.false_txn {
             info!("Roll-back dropped nested transaction");
             DB::TransactionManager::start_rollback(self.connection_mut());
+        } else if self.txn_depth == 1 && !self.false_txn && !self.inner.transaction {
+            // a transaction started by `as_transaction` must not be left open
+            // when the connection is returned to the pool
+            info!("Roll-back dropped transaction");
+            DB::TransactionManager::start_rollback(self.connection_mut());
         }
     }
 }
@@ -336,9 +393,14 @@ where
 }
 
 pub struct EncScanEntry {
+    pub id: i64,
     pub name: Vec<u8>,
     pub value: Vec<u8>,
     pub tags: Vec<u8>,
+    pub expiry: Option<i64>,
+    pub version: i64,
+    pub created: Option<i64>,
+    pub updated: Option<i64>,
 }
 
 pub struct QueryParams<'q, DB: Database> {
@@ -395,6 +457,28 @@ pub trait QueryPrepare {
         format!("?{}", index)
     }
 
+    fn numeric_value(expr: &str) -> String {
+        format!("CAST({} AS NUMERIC)", expr)
+    }
+
+    // text which is not a well-formed number keeps its type when numeric
+    // affinity is applied for the comparison, so it is not equal to the cast
+    fn is_numeric(expr: &str) -> String {
+        format!(
+            "CAST(CAST({0} AS TEXT) AS NUMERIC) = CAST({0} AS TEXT) \
+            AND CAST({0} AS TEXT) NOT GLOB '*[^0-9.eE+-]*'",
+            expr
+        )
+    }
+
+    fn is_date(expr: &str) -> String {
+        format!(
+            "CAST({} AS TEXT) GLOB \
+            '[0-9][0-9][0-9][0-9]-[0-1][0-9]-[0-3][0-9]T[0-2][0-9]:[0-5][0-9]:[0-6][0-9]Z'",
+            expr
+        )
+    }
+
     fn limit_query<'q>(
         mut query: String,
         args: &mut QueryParams<'q, Self::DB>,
@@ -510,6 +594,20 @@ pub fn decrypt_scan_batch(
     Ok(batch)
 }
 
+pub fn decrypt_scan_page(
+    cursor: &ScanCursor,
+    enc_rows: Vec<EncScanEntry>,
+    key: &ProfileKey,
+    store_key: &StoreKey,
+) -> Result<(Vec<Entry>, String), Error> {
+    let last_id = enc_rows.last().map(|row| row.id).unwrap_or(cursor.last_id);
+    let token = cursor.token_after(last_id, store_key)?;
+    Ok((
+        decrypt_scan_batch(cursor.category.clone(), enc_rows, key)?,
+        token,
+    ))
+}
+
 pub fn decrypt_scan_entry(
     category: String,
     enc_entry: EncScanEntry,
@@ -520,7 +618,10 @@ pub fn decrypt_scan_entry(
     let tags = key.decrypt_entry_tags(
         decode_tags(enc_entry.tags).map_err(|_| err_msg!(Unexpected, "Error decoding tags"))?,
     )?;
-    Ok(Entry::new(category.to_string(), name, value, tags))
+    Ok(Entry::new(category.to_string(), name, value, tags)
+        .with_expiry(enc_entry.expiry)
+        .with_version(enc_entry.version)
+        .with_timestamps(enc_entry.created, enc_entry.updated))
 }
 
 pub fn expiry_timestamp(expire_ms: i64) -> Result<Expiry, Error> {
@@ -529,6 +630,14 @@ pub fn expiry_timestamp(expire_ms: i64) -> Result<Expiry, Error> {
         .ok_or_else(|| err_msg!(Unexpected, "Invalid expiry timestamp"))
 }
 
+pub fn millis_timestamp(ms: i64) -> Result<Expiry, Error> {
+    use chrono::TimeZone;
+    chrono::Utc
+        .timestamp_millis_opt(ms)
+        .single()
+        .ok_or_else(|| err_msg!(Unexpected, "Invalid timestamp"))
+}
+
 pub fn encode_tag_filter<Q: QueryPrepare>(
     tag_filter: Option<TagFilter>,
     key: &ProfileKey,
@@ -539,6 +648,9 @@ pub fn encode_tag_filter<Q: QueryPrepare>(
         let mut enc = TagSqlEncoder::new(
             |name| Ok(key.encrypt_tag_name(ProfileKey::prepare_input(name.as_bytes()))?),
             |value| Ok(key.encrypt_tag_value(ProfileKey::prepare_input(value.as_bytes()))?),
+            Q::numeric_value,
+            Q::is_numeric,
+            Q::is_date,
         );
         if let Some(filter) = enc.encode_query(&tag_query)? {
             let filter = replace_arg_placeholders::<Q>(&filter, (offset as i64) + 1);
@@ -582,6 +694,12 @@ pub fn prepare_tags(tags: &[EntryTag]) -> Result<Vec<EntryTag>, Error> {
     Ok(result)
 }
 
+/// Format the placeholders for a multi-row insert, starting from the first argument
+pub fn multi_row_values<Q: QueryPrepare>(rows: usize, columns: usize) -> String {
+    let row = format!("({})", vec!["$$"; columns].join(", "));
+    replace_arg_placeholders::<Q>(&vec![row; rows].join(", "), 1)
+}
+
 pub fn extend_query<'q, Q: QueryPrepare>(
     query: &str,
     args: &mut QueryParams<'q, Q::DB>,
@@ -605,6 +723,73 @@ where
     Ok(query)
 }
 
+/// A record ordering, with the name of the ordering tag encrypted
+#[derive(Debug, Default)]
+pub struct EncOrderBy {
+    /// The encrypted tag name, and the type of the tag
+    tag: Option<(Vec<u8>, TagType)>,
+    descending: bool,
+}
+
+pub fn encode_order_by(order_by: Option<&OrderBy>, key: &ProfileKey) -> Result<EncOrderBy, Error> {
+    if let Some(order_by) = order_by {
+        let tag = if let Some(name) = order_by.plaintext_tag()? {
+            let enc_name = key.encrypt_tag_name(ProfileKey::prepare_input(name.as_bytes()))?;
+            Some((enc_name, TagName::Plaintext(name.to_string()).tag_type()))
+        } else {
+            None
+        };
+        Ok(EncOrderBy {
+            tag,
+            descending: order_by.is_descending(),
+        })
+    } else {
+        Ok(EncOrderBy::default())
+    }
+}
+
+/// Append an ORDER BY clause to a record query, which must select from
+/// `items i`
+pub fn order_by_query<'q, Q: QueryPrepare>(
+    mut query: String,
+    args: &mut QueryParams<'q, Q::DB>,
+    order_by: EncOrderBy,
+) -> String
+where
+    Vec<u8>: for<'e> Encode<'e, Q::DB> + Type<Q::DB>,
+{
+    let dir = if order_by.descending { " DESC" } else { "" };
+    if let Some((enc_name, tag_type)) = order_by.tag {
+        // values of numeric and date tags which are not well-formed are ignored
+        let (value, cond) = match tag_type {
+            TagType::Numeric => (
+                Q::numeric_value("it.value"),
+                format!(" AND {}", Q::is_numeric("it.value")),
+            ),
+            TagType::Date => (
+                "it.value".to_string(),
+                format!(" AND {}", Q::is_date("it.value")),
+            ),
+            _ => ("it.value".to_string(), String::new()),
+        };
+        // a record may have several values for the same tag name, so the
+        // first value in the requested order is used
+        let clause = format!(
+            " ORDER BY (SELECT {} FROM items_tags it \
+            WHERE it.item_id = i.id AND it.name = $$ AND it.plaintext = 1{} \
+            ORDER BY 1{} LIMIT 1){} NULLS LAST, i.id{}",
+            value, cond, dir, dir, dir
+        );
+        let idx = (args.len() + 1) as i64;
+        args.push(enc_name);
+        query.push_str(&replace_arg_placeholders::<Q>(&clause, idx));
+    } else {
+        query.push_str(" ORDER BY i.id");
+        query.push_str(dir);
+    }
+    query
+}
+
 pub fn init_keys<'a>(
     method: StoreKeyMethod,
     pass_key: PassKey<'a>,
@@ -620,6 +805,36 @@ pub fn init_keys<'a>(
     ))
 }
 
+/// Get the schema version produced by an ordered list of migrations
+pub fn latest_schema_version(migrations: &[SchemaMigration]) -> u32 {
+    migrations
+        .last()
+        .map(|m| m.version)
+        .unwrap_or(BASE_SCHEMA_VERSION)
+}
+
+/// Select the migrations to be applied to a store with the given schema version
+pub fn select_migrations<'m>(
+    migrations: &'m [SchemaMigration],
+    version: &str,
+) -> Result<&'m [SchemaMigration], Error> {
+    let version: u32 = version
+        .parse()
+        .map_err(err_map!(Unsupported, "Invalid store version"))?;
+    if version < BASE_SCHEMA_VERSION || version > latest_schema_version(migrations) {
+        return Err(err_msg!(
+            Unsupported,
+            "Unsupported store version: {}",
+            version
+        ));
+    }
+    let start = migrations
+        .iter()
+        .position(|m| m.version > version)
+        .unwrap_or(migrations.len());
+    Ok(&migrations[start..])
+}
+
 pub fn encode_profile_key(
     profile_key: &ProfileKey,
     store_key: &StoreKey,
@@ -631,3 +846,418 @@ pub fn encode_profile_key(
 pub fn random_profile_name() -> String {
     uuid::Uuid::new_v4().to_string()
 }
+
+/// Decode the id and encrypted content of a record from a result row
+pub(crate) type DecodeEncRow<DB> = fn(&<DB as Database>::Row) -> Result<(i64, EncEntry), Error>;
+
+/// The backend-specific queries used to check the integrity of a store
+pub(crate) struct VerifyQueries {
+    /// Select the id, name, profile key and rotation status of a named profile
+    pub profile: &'static str,
+    /// Select the id, name, profile key and rotation status of every profile
+    pub profiles: &'static str,
+    /// Count the records and tags belonging to a profile
+    pub profile_rows: &'static str,
+    /// Remove a profile, along with its records and tags
+    pub delete_profile: &'static str,
+    /// Fetch a page of encrypted records of one kind following a record id
+    pub fetch: &'static str,
+    /// Count the tags belonging to a record
+    pub item_tags: &'static str,
+    /// Remove a record by id, along with its tags
+    pub delete_item: &'static str,
+    /// Remove a tag of a record by its encrypted name and value
+    pub delete_tag_value: &'static str,
+    /// Select the ids of the records which do not belong to any profile
+    pub orphan_items: &'static str,
+    /// Select the ids of the tags which do not belong to any record
+    pub orphan_tags: &'static str,
+    /// Remove a tag by id
+    pub delete_tag: &'static str,
+}
+
+/// Check the integrity of the profiles, records and tags of a database store
+///
+/// Each profile is checked within a transaction, so that its repairs are
+/// applied together. Rows are decoded from the `fetch` query by `decode_row`.
+pub(crate) async fn verify_store<DB>(
+    pool: &Pool<DB>,
+    key_cache: &KeyCache,
+    default_profile: &str,
+    queries: &VerifyQueries,
+    decode_row: DecodeEncRow<DB>,
+    profile: Option<String>,
+    repair: bool,
+) -> Result<VerifyReport, Error>
+where
+    DB: ExtDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    bool: for<'r> Decode<'r, DB> + Type<DB>,
+    i16: for<'e> Encode<'e, DB> + Type<DB>,
+    i64: for<'e> Encode<'e, DB> + for<'r> Decode<'r, DB> + Type<DB>,
+    String: for<'e> Encode<'e, DB> + for<'r> Decode<'r, DB> + Type<DB>,
+    Vec<u8>: for<'e> Encode<'e, DB> + for<'r> Decode<'r, DB> + Type<DB>,
+    usize: ColumnIndex<DB::Row>,
+{
+    let mut report = VerifyReport::default();
+    let profiles = if let Some(name) = profile.as_ref() {
+        let profiles = sqlx::query(queries.profile)
+            .bind(name.clone())
+            .fetch_all(pool)
+            .await?;
+        if profiles.is_empty() {
+            return Err(err_msg!(NotFound, "Profile not found"));
+        }
+        profiles
+    } else {
+        sqlx::query(queries.profiles).fetch_all(pool).await?
+    };
+
+    for row in profiles {
+        let pid: ProfileId = row.try_get(0)?;
+        let name: String = row.try_get(1)?;
+        if row.try_get(3)? {
+            report
+                .issues
+                .push(VerifyIssue::ProfileKeyRotation { profile: name });
+            continue;
+        }
+        report.profiles_checked += 1;
+        let mut txn = pool.begin().await?;
+        // records are always checked against the stored profile key
+        let key = match row.try_get::<Option<Vec<u8>>, _>(2)? {
+            Some(enc_key) => key_cache.load_key(enc_key).await.ok(),
+            None => None,
+        };
+        let key = match key {
+            Some(key) => Arc::new(key),
+            None => {
+                report.issues.push(VerifyIssue::ProfileKey {
+                    profile: name.clone(),
+                });
+                // the default profile is never removed
+                if repair && name != default_profile {
+                    let cascade: i64 = sqlx::query(queries.profile_rows)
+                        .bind(pid)
+                        .fetch_one(&mut *txn)
+                        .await?
+                        .try_get(0)?;
+                    sqlx::query(queries.delete_profile)
+                        .bind(pid)
+                        .execute(&mut *txn)
+                        .await?;
+                    txn.commit().await?;
+                    key_cache.remove_profile(&name).await;
+                    report.rows_removed += 1 + cascade as u64;
+                }
+                continue;
+            }
+        };
+        // a profile key held by this instance which does not match the
+        // stored key prevents any repairs to the profile
+        let mismatch =
+            matches!(key_cache.get_profile(&name).await, Some((_, cached)) if cached != key);
+        if mismatch {
+            report.issues.push(VerifyIssue::ProfileKeyMismatch {
+                profile: name.clone(),
+            });
+        }
+        let repair = repair && !mismatch;
+
+        for kind in [EntryKind::Kms, EntryKind::Item].iter().copied() {
+            let mut last_id = 0i64;
+            loop {
+                let rows = sqlx::query(queries.fetch)
+                    .bind(pid)
+                    .bind(kind as i16)
+                    .bind(last_id)
+                    .bind(PAGE_SIZE as i64)
+                    .fetch_all(&mut *txn)
+                    .await?;
+                if rows.is_empty() {
+                    break;
+                }
+                let enc_rows = rows.iter().map(decode_row).collect::<Result<Vec<_>, _>>()?;
+                last_id = enc_rows[enc_rows.len() - 1].0;
+                let batch = unblock({
+                    let (key, name) = (key.clone(), name.clone());
+                    move || verify_entries(&key, &name, kind, enc_rows)
+                })
+                .await;
+                let batch = report.merge(batch);
+                if repair {
+                    for item_id in batch.invalid_entries {
+                        report.rows_removed += delete_item_rows(&mut txn, queries, item_id).await?;
+                    }
+                    for (item_id, tag) in batch.invalid_tags {
+                        report.rows_removed += DB::rows_affected(
+                            &sqlx::query(queries.delete_tag_value)
+                                .bind(item_id)
+                                .bind(tag.name)
+                                .bind(tag.value)
+                                .bind(tag.plaintext as i16)
+                                .execute(&mut *txn)
+                                .await?,
+                        );
+                    }
+                }
+            }
+        }
+        txn.commit().await?;
+    }
+
+    // rows which do not belong to any profile are only found when
+    // checking the whole store
+    if profile.is_none() {
+        let mut txn = pool.begin().await?;
+        let orphan_items = sqlx::query(queries.orphan_items)
+            .fetch_all(&mut *txn)
+            .await?;
+        for row in orphan_items {
+            let id = row.try_get(0)?;
+            report.issues.push(VerifyIssue::OrphanEntry { id });
+            if repair {
+                report.rows_removed += delete_item_rows(&mut txn, queries, id).await?;
+            }
+        }
+        let orphan_tags = sqlx::query(queries.orphan_tags)
+            .fetch_all(&mut *txn)
+            .await?;
+        for row in orphan_tags {
+            let id = row.try_get(0)?;
+            report.issues.push(VerifyIssue::OrphanTag { id });
+            if repair {
+                report.rows_removed += DB::rows_affected(
+                    &sqlx::query(queries.delete_tag)
+                        .bind(id)
+                        .execute(&mut *txn)
+                        .await?,
+                );
+            }
+        }
+        txn.commit().await?;
+    }
+    Ok(report)
+}
+
+/// Remove a record, returning the number of rows removed including its tags
+async fn delete_item_rows<DB>(
+    txn: &mut Transaction<'static, DB>,
+    queries: &VerifyQueries,
+    item_id: i64,
+) -> Result<u64, Error>
+where
+    DB: ExtDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    i64: for<'e> Encode<'e, DB> + for<'r> Decode<'r, DB> + Type<DB>,
+    usize: ColumnIndex<DB::Row>,
+{
+    let tags: i64 = sqlx::query(queries.item_tags)
+        .bind(item_id)
+        .fetch_one(&mut **txn)
+        .await?
+        .try_get(0)?;
+    let removed = DB::rows_affected(
+        &sqlx::query(queries.delete_item)
+            .bind(item_id)
+            .execute(&mut **txn)
+            .await?,
+    );
+    Ok(if removed > 0 {
+        removed + tags as u64
+    } else {
+        0
+    })
+}
+
+/// Ensure that the profile key held by a session may be used to write records
+///
+/// The `query` selects the stored profile key and whether a key rotation is
+/// pending, and must run within the transaction performing the write. Writes
+/// are rejected while the profile key is being rotated, and when the key held
+/// by the session has since been replaced.
+pub(crate) async fn check_profile_key<DB>(
+    active: &mut DbSessionActive<'_, DB>,
+    query: &'static str,
+) -> Result<(), Error>
+where
+    DB: ExtDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    bool: for<'r> Decode<'r, DB> + Type<DB>,
+    i64: for<'e> Encode<'e, DB> + Type<DB>,
+    Vec<u8>: for<'r> Decode<'r, DB> + Type<DB>,
+    usize: ColumnIndex<DB::Row>,
+{
+    let row = sqlx::query(query)
+        .bind(active.profile_id)
+        .fetch_optional(&mut **active.connection_mut())
+        .await?
+        .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+    if row.try_get(1)? {
+        return Err(err_msg!(Busy, "Profile key rotation in progress"));
+    }
+    let enc_key: Vec<u8> = row.try_get(0)?;
+    if let DbSessionKey::Active {
+        key,
+        cache,
+        checked_key,
+        ..
+    } = &mut active.inner.profile_key
+    {
+        if checked_key.as_ref() != Some(&enc_key) {
+            if cache.load_key(enc_key.clone()).await? != **key {
+                return Err(err_msg!(
+                    Conflict,
+                    "Profile key was replaced after the session was started"
+                ));
+            }
+            checked_key.replace(enc_key);
+        }
+    }
+    Ok(())
+}
+
+/// The backend-specific queries used to replace the key of a profile
+pub(crate) struct RotateQueries {
+    /// Select the id, profile key, pending key and rotation position of a named profile
+    pub profile: &'static str,
+    /// Record the pending key of a profile which is not already being rotated
+    pub start: &'static str,
+    /// Fetch a page of encrypted records of a profile following a record id
+    pub fetch: &'static str,
+    /// Replace the encrypted category, name and value of a record
+    pub update_item: &'static str,
+    /// Remove the tags of a record
+    pub delete_tags: &'static str,
+    /// Insert a tag for a record
+    pub insert_tag: &'static str,
+    /// Record the id of the last re-encrypted record
+    pub update_pos: &'static str,
+    /// Replace the profile key with the pending key
+    pub finish: &'static str,
+}
+
+/// Replace the key of a profile in a database store, re-encrypting its records
+///
+/// Each batch of records is committed along with the position of its last
+/// record, so that an interrupted rotation is resumed from that point. Writes
+/// to the profile are rejected by [`check_profile_key`] while the pending key
+/// is recorded. Rows are decoded from the `fetch` query by `decode_row`.
+pub(crate) async fn rotate_profile_key<DB>(
+    pool: &Pool<DB>,
+    key_cache: &KeyCache,
+    queries: &RotateQueries,
+    decode_row: DecodeEncRow<DB>,
+    name: String,
+) -> Result<i64, Error>
+where
+    DB: ExtDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    i16: for<'e> Encode<'e, DB> + Type<DB>,
+    i64: for<'e> Encode<'e, DB> + for<'r> Decode<'r, DB> + Type<DB>,
+    String: for<'e> Encode<'e, DB> + Type<DB>,
+    Vec<u8>: for<'e> Encode<'e, DB> + for<'r> Decode<'r, DB> + Type<DB>,
+    Option<i64>: for<'r> Decode<'r, DB>,
+    Option<Vec<u8>>: for<'r> Decode<'r, DB>,
+    usize: ColumnIndex<DB::Row>,
+{
+    let row = sqlx::query(queries.profile)
+        .bind(name.clone())
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+    let pid: ProfileId = row.try_get(0)?;
+    let key = Arc::new(key_cache.load_key(row.try_get(1)?).await?);
+    let (new_key, mut last_id) = match row.try_get::<Option<Vec<u8>>, _>(2)? {
+        Some(enc_key) => (
+            key_cache.load_key(enc_key).await?,
+            row.try_get::<Option<i64>, _>(3)?.unwrap_or(0),
+        ),
+        None => {
+            let store_key = key_cache.store_key.clone();
+            let (new_key, enc_key) = unblock(move || {
+                let key = ProfileKey::new()?;
+                let enc_key = encode_profile_key(&key, &store_key)?;
+                Result::<_, Error>::Ok((key, enc_key))
+            })
+            .await?;
+            let started = sqlx::query(queries.start)
+                .bind(enc_key)
+                .bind(pid)
+                .execute(pool)
+                .await?;
+            if DB::rows_affected(&started) != 1 {
+                return Err(err_msg!(Busy, "Profile key rotation in progress"));
+            }
+            (new_key, 0)
+        }
+    };
+    // new sessions for the profile are rejected until the rotation completes
+    key_cache.remove_profile(&name).await;
+    let new_key = Arc::new(new_key);
+
+    let mut updated = 0;
+    loop {
+        let mut txn = pool.begin().await?;
+        let rows = sqlx::query(queries.fetch)
+            .bind(pid)
+            .bind(last_id)
+            .bind(PAGE_SIZE as i64)
+            .fetch_all(&mut *txn)
+            .await?;
+        if rows.is_empty() {
+            break;
+        }
+        let enc_rows = rows.iter().map(decode_row).collect::<Result<Vec<_>, _>>()?;
+        let upd_rows = unblock({
+            let (key, new_key) = (key.clone(), new_key.clone());
+            move || {
+                enc_rows
+                    .into_iter()
+                    .map(|(item_id, enc_entry)| {
+                        Ok((item_id, key.reencrypt_entry(&*new_key, enc_entry)?))
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            }
+        })
+        .await?;
+        for (item_id, enc_entry) in upd_rows {
+            sqlx::query(queries.update_item)
+                .bind(enc_entry.category)
+                .bind(enc_entry.name)
+                .bind(enc_entry.value)
+                .bind(item_id)
+                .execute(&mut *txn)
+                .await?;
+            sqlx::query(queries.delete_tags)
+                .bind(item_id)
+                .execute(&mut *txn)
+                .await?;
+            for tag in enc_entry.tags {
+                sqlx::query(queries.insert_tag)
+                    .bind(item_id)
+                    .bind(tag.name)
+                    .bind(tag.value)
+                    .bind(tag.plaintext as i16)
+                    .execute(&mut *txn)
+                    .await?;
+            }
+            last_id = item_id;
+            updated += 1;
+        }
+        sqlx::query(queries.update_pos)
+            .bind(last_id)
+            .bind(pid)
+            .execute(&mut *txn)
+            .await?;
+        txn.commit().await?;
+    }
+
+    sqlx::query(queries.finish).bind(pid).execute(pool).await?;
+    key_cache.add_profile(name, pid, new_key).await;
+    Ok(updated)
+}
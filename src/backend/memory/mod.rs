@@ -0,0 +1,1688 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, Weak};
+
+use async_mutex::{Mutex, MutexGuardArc};
+use futures_lite::stream::{self, StreamExt};
+
+use crate::{
+    backend::types::{Backend, QueryBackend},
+    crypto::buffer::SecretBytes,
+    error::Error,
+    future::{unblock, BoxFuture},
+    protect::{
+        encrypt_entry_updates, encrypt_tag_update, EntryEncryptor, KeyCache, PassKey, ProfileId,
+        ProfileKey, StoreKeyMethod,
+    },
+    storage::{
+        verify_entries,
+        wql::{
+            filter::{is_date, parse_numeric, TagMatch, TagMatchEncoder},
+            tags::{tag_query, TagName, TagQueryEncoder, TagType},
+        },
+        CategoryStats, EncEntry, EncEntryTag, Entry, EntryChange, EntryKind, EntryOperation,
+        EntryTag, EntryUpdate, OrderBy, Scan, ScanCursor, TagFilter, TagNameStats, TagQueryLimits,
+        VerifyIssue, VerifyReport, WatchRegistry,
+    },
+};
+
+mod provision;
+pub use self::provision::MemoryStoreOptions;
+
+const PAGE_SIZE: usize = 32;
+
+type Expiry = chrono::DateTime<chrono::Utc>;
+
+type ItemKey = (ProfileId, EntryKind, Vec<u8>, Vec<u8>);
+
+pub(crate) type MemoryDb = Arc<Mutex<MemoryTables>>;
+
+/// A change to be reverted when a batch of updates fails
+enum BatchUndo {
+    Inserted(ItemKey),
+    Removed(i64, MemoryItem),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct MemoryProfile {
+    pub id: ProfileId,
+    pub profile_key: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct MemoryItem {
+    pub profile_id: ProfileId,
+    pub kind: EntryKind,
+    pub category: Vec<u8>,
+    pub name: Vec<u8>,
+    pub value: Vec<u8>,
+    pub tags: Vec<EncEntryTag>,
+    pub expiry: Option<Expiry>,
+    pub version: i64,
+    pub created: Expiry,
+    pub updated: Expiry,
+}
+
+impl MemoryItem {
+    fn key(&self) -> ItemKey {
+        (
+            self.profile_id,
+            self.kind,
+            self.category.clone(),
+            self.name.clone(),
+        )
+    }
+
+    fn is_expired(&self, now: &Expiry) -> bool {
+        matches!(self.expiry, Some(ref exp) if exp <= now)
+    }
+}
+
+/// A record ordering, with the name of the ordering tag encrypted
+#[derive(Debug, Default)]
+struct MemoryOrder {
+    /// The encrypted tag name, and the type of the tag
+    tag: Option<(Vec<u8>, TagType)>,
+    descending: bool,
+}
+
+impl MemoryOrder {
+    fn new(order_by: Option<&OrderBy>, key: &ProfileKey) -> Result<Self, Error> {
+        if let Some(order_by) = order_by {
+            let tag = if let Some(name) = order_by.plaintext_tag()? {
+                let enc_name = key.encrypt_tag_name(ProfileKey::prepare_input(name.as_bytes()))?;
+                Some((enc_name, TagName::Plaintext(name.to_string()).tag_type()))
+            } else {
+                None
+            };
+            Ok(Self {
+                tag,
+                descending: order_by.is_descending(),
+            })
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Sort records selected in insertion order
+    fn sort(&self, rows: &mut Vec<(i64, &MemoryItem)>) {
+        match &self.tag {
+            Some((name, TagType::Numeric)) => self.sort_by_tag(rows, name, parse_numeric),
+            Some((name, TagType::Date)) => self.sort_by_tag(rows, name, |value| {
+                Some(value.to_vec()).filter(|value| is_date(value))
+            }),
+            Some((name, _)) => self.sort_by_tag(rows, name, |value| Some(value.to_vec())),
+            None if self.descending => rows.reverse(),
+            None => (),
+        }
+    }
+
+    // records without the tag are ordered last, like NULLS LAST in SQL
+    fn sort_by_tag<T: PartialOrd>(
+        &self,
+        rows: &mut Vec<(i64, &MemoryItem)>,
+        name: &[u8],
+        parse: impl Fn(&[u8]) -> Option<T>,
+    ) {
+        let descending = self.descending;
+        let mut keyed = rows
+            .drain(..)
+            .map(|(item_id, item)| {
+                let values = item
+                    .tags
+                    .iter()
+                    .filter(|tag| tag.plaintext && tag.name == name)
+                    .filter_map(|tag| parse(&tag.value));
+                // a record may have several values for the same tag name
+                let value = values.reduce(|a, b| if (b > a) == descending { b } else { a });
+                (value, item_id, item)
+            })
+            .collect::<Vec<_>>();
+        keyed.sort_by(|(a_val, a_id, _), (b_val, b_id, _)| {
+            let ord = match (a_val, b_val) {
+                (Some(a), Some(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+                (Some(_), None) => return Ordering::Less,
+                (None, Some(_)) => return Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+            .then(a_id.cmp(b_id));
+            if descending {
+                ord.reverse()
+            } else {
+                ord
+            }
+        });
+        rows.extend(keyed.into_iter().map(|(_, item_id, item)| (item_id, item)));
+    }
+}
+
+/// The complete contents of an in-memory store
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MemoryTables {
+    pub default_profile: String,
+    pub store_key_ref: String,
+    pub profiles: BTreeMap<String, MemoryProfile>,
+    items: BTreeMap<i64, MemoryItem>,
+    index: HashMap<ItemKey, i64>,
+    last_profile_id: ProfileId,
+    last_item_id: i64,
+}
+
+impl MemoryTables {
+    pub fn new(default_profile: String, store_key_ref: String) -> Self {
+        Self {
+            default_profile,
+            store_key_ref,
+            ..Default::default()
+        }
+    }
+
+    pub fn insert_profile(&mut self, name: String, profile_key: Vec<u8>) -> Option<ProfileId> {
+        if self.profiles.contains_key(&name) {
+            return None;
+        }
+        self.last_profile_id += 1;
+        let id = self.last_profile_id;
+        self.profiles
+            .insert(name, MemoryProfile { id, profile_key });
+        Some(id)
+    }
+
+    pub fn remove_profile(&mut self, name: &str) -> bool {
+        if let Some(profile) = self.profiles.remove(name) {
+            let index = &mut self.index;
+            self.items.retain(|_, item| {
+                if item.profile_id == profile.id {
+                    index.remove(&item.key());
+                    false
+                } else {
+                    true
+                }
+            });
+            true
+        } else {
+            false
+        }
+    }
+
+    fn check_profile_key(&self, name: &str, enc_key: &[u8]) -> Result<(), Error> {
+        match self.profiles.get(name) {
+            Some(profile) if profile.profile_key == enc_key => Ok(()),
+            Some(_) => Err(err_msg!(
+                Conflict,
+                "Profile key was replaced after the session was started"
+            )),
+            None => Err(err_msg!(NotFound, "Profile not found")),
+        }
+    }
+
+    fn rename_profile(&mut self, from_name: &str, to_name: String) -> Result<bool, Error> {
+        if self.profiles.contains_key(&to_name) {
+            return Err(err_msg!(Duplicate, "Duplicate profile name"));
+        }
+        if let Some(profile) = self.profiles.remove(from_name) {
+            self.profiles.insert(to_name, profile);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn copy_profile(&mut self, from_name: &str, to_name: String) -> Result<bool, Error> {
+        let (from_id, profile_key) = match self.profiles.get(from_name) {
+            Some(profile) => (profile.id, profile.profile_key.clone()),
+            None => return Ok(false),
+        };
+        let to_id = self
+            .insert_profile(to_name.clone(), profile_key)
+            .ok_or_else(|| err_msg!(Duplicate, "Duplicate profile name"))?;
+        let now = chrono::Utc::now();
+        let copied = self
+            .items
+            .values()
+            .filter(|item| item.profile_id == from_id && !item.is_expired(&now))
+            .map(|item| MemoryItem {
+                profile_id: to_id,
+                ..item.clone()
+            })
+            .collect::<Vec<_>>();
+        if let Err(err) = copied
+            .into_iter()
+            .try_for_each(|item| self.insert_item(item))
+        {
+            // remove the new profile along with any records already copied
+            self.remove_profile(&to_name);
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    fn reencrypt_items(
+        &mut self,
+        profile_id: ProfileId,
+        key: &ProfileKey,
+        new_key: &ProfileKey,
+    ) -> Result<i64, Error> {
+        // all records are re-encrypted before any are replaced
+        let mut updated = Vec::new();
+        for (item_id, item) in self.items.iter() {
+            if item.profile_id == profile_id {
+                let enc_entry = key.reencrypt_entry(
+                    new_key,
+                    EncEntry {
+                        category: item.category.clone(),
+                        name: item.name.clone(),
+                        value: item.value.clone(),
+                        tags: item.tags.clone(),
+                    },
+                )?;
+                updated.push((*item_id, enc_entry));
+            }
+        }
+        let count = updated.len() as i64;
+        for (item_id, enc_entry) in updated {
+            if let Some(item) = self.items.get_mut(&item_id) {
+                self.index.remove(&item.key());
+                item.category = enc_entry.category;
+                item.name = enc_entry.name;
+                item.value = enc_entry.value;
+                item.tags = enc_entry.tags;
+                self.index.insert(item.key(), item_id);
+            }
+        }
+        Ok(count)
+    }
+
+    /// Collect the encrypted records of a profile for verification
+    fn profile_entries(&self, profile_id: ProfileId, kind: EntryKind) -> Vec<(i64, EncEntry)> {
+        self.items
+            .iter()
+            .filter(|(_, item)| item.profile_id == profile_id && item.kind == kind)
+            .map(|(item_id, item)| {
+                (
+                    *item_id,
+                    EncEntry {
+                        category: item.category.clone(),
+                        name: item.name.clone(),
+                        value: item.value.clone(),
+                        tags: item.tags.clone(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn orphan_items(&self) -> Vec<i64> {
+        self.items
+            .iter()
+            .filter(|(_, item)| !self.has_profile_id(item.profile_id))
+            .map(|(item_id, _)| *item_id)
+            .collect()
+    }
+
+    /// Count the records and tags belonging to a profile
+    fn profile_rows(&self, profile_id: ProfileId) -> u64 {
+        self.items
+            .values()
+            .filter(|item| item.profile_id == profile_id)
+            .map(|item| 1 + item.tags.len() as u64)
+            .sum()
+    }
+
+    /// Remove a record, returning the number of rows removed including its tags
+    fn remove_item_id(&mut self, item_id: i64) -> u64 {
+        if let Some(item) = self.items.remove(&item_id) {
+            self.index.remove(&item.key());
+            1 + item.tags.len() as u64
+        } else {
+            0
+        }
+    }
+
+    fn remove_item_tag(&mut self, item_id: i64, tag: &EncEntryTag) -> u64 {
+        if let Some(item) = self.items.get_mut(&item_id) {
+            let count = item.tags.len();
+            item.tags.retain(|t| t != tag);
+            (count - item.tags.len()) as u64
+        } else {
+            0
+        }
+    }
+
+    fn has_items(&self, profile_id: ProfileId) -> bool {
+        self.items
+            .values()
+            .any(|item| item.profile_id == profile_id)
+    }
+
+    fn has_profile_id(&self, profile_id: ProfileId) -> bool {
+        self.profiles.values().any(|p| p.id == profile_id)
+    }
+
+    fn insert_item(&mut self, item: MemoryItem) -> Result<(), Error> {
+        if !self.has_profile_id(item.profile_id) {
+            return Err(err_msg!(NotFound, "Profile not found"));
+        }
+        let key = item.key();
+        if self.index.contains_key(&key) {
+            return Err(err_msg!(Duplicate, "Duplicate row"));
+        }
+        self.last_item_id += 1;
+        self.index.insert(key, self.last_item_id);
+        self.items.insert(self.last_item_id, item);
+        Ok(())
+    }
+
+    fn remove_item(&mut self, key: &ItemKey) -> bool {
+        self.take_item(key).is_some()
+    }
+
+    fn take_item(&mut self, key: &ItemKey) -> Option<(i64, MemoryItem)> {
+        let item_id = self.index.remove(key)?;
+        self.items.remove(&item_id).map(|item| (item_id, item))
+    }
+
+    /// Apply a sequence of updates, reverting all of them if one fails
+    fn apply_batch(
+        &mut self,
+        updates: Vec<(EntryOperation, Option<i64>, MemoryItem)>,
+    ) -> Result<(), Error> {
+        let mut undo = Vec::new();
+        let mut result = Ok(());
+        for (op, expected_version, mut item) in updates {
+            let key = item.key();
+            if op != EntryOperation::Insert {
+                match self.take_item(&key) {
+                    Some((item_id, prev)) => {
+                        item.version = prev.version + 1;
+                        item.created = prev.created;
+                        let conflict = matches!(expected_version, Some(v) if v != prev.version);
+                        undo.push(BatchUndo::Removed(item_id, prev));
+                        if conflict {
+                            result = Err(err_msg!(Conflict, "Entry version mismatch"));
+                            break;
+                        }
+                    }
+                    None => {
+                        result = Err(err_msg!(NotFound, "Entry not found"));
+                        break;
+                    }
+                }
+                if op == EntryOperation::Remove {
+                    continue;
+                }
+            }
+            if let Err(err) = self.insert_item(item) {
+                result = Err(err);
+                break;
+            }
+            undo.push(BatchUndo::Inserted(key));
+        }
+        if result.is_err() {
+            for step in undo.into_iter().rev() {
+                match step {
+                    BatchUndo::Inserted(key) => {
+                        self.remove_item(&key);
+                    }
+                    BatchUndo::Removed(item_id, item) => {
+                        self.index.insert(item.key(), item_id);
+                        self.items.insert(item_id, item);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn update_tags(
+        &mut self,
+        key: &ItemKey,
+        add: Vec<EncEntryTag>,
+        remove: Vec<(Vec<u8>, bool)>,
+    ) -> Result<(), Error> {
+        let now = chrono::Utc::now();
+        let item = self
+            .index
+            .get(key)
+            .copied()
+            .and_then(|item_id| self.items.get_mut(&item_id))
+            .filter(|item| !item.is_expired(&now))
+            .ok_or_else(|| err_msg!(NotFound, "Entry not found"))?;
+        item.tags.retain(|tag| {
+            !remove
+                .iter()
+                .any(|(name, plaintext)| &tag.name == name && tag.plaintext == *plaintext)
+                && !add.contains(tag)
+        });
+        item.tags.extend(add);
+        item.version += 1;
+        item.updated = now;
+        Ok(())
+    }
+
+    fn get_item(&self, key: &ItemKey) -> Option<&MemoryItem> {
+        let now = chrono::Utc::now();
+        self.index
+            .get(key)
+            .and_then(|item_id| self.items.get(item_id))
+            .filter(|item| !item.is_expired(&now))
+    }
+
+    fn select<'s>(
+        &'s self,
+        profile_id: ProfileId,
+        kind: EntryKind,
+        category: &'s [u8],
+        tag_filter: Option<&'s TagMatch>,
+        include_expired: bool,
+    ) -> impl Iterator<Item = (i64, &'s MemoryItem)> + 's {
+        let now = chrono::Utc::now();
+        self.items
+            .iter()
+            .filter(move |(_, item)| {
+                item.profile_id == profile_id
+                    && item.kind == kind
+                    && item.category.as_slice() == category
+                    && (include_expired || !item.is_expired(&now))
+                    && tag_filter.map(|f| f.matches(&item.tags)).unwrap_or(true)
+            })
+            .map(|(item_id, item)| (*item_id, item))
+    }
+
+    fn categories(&self, profile_id: ProfileId, kind: EntryKind) -> Vec<Vec<u8>> {
+        let now = chrono::Utc::now();
+        self.items
+            .values()
+            .filter(|item| {
+                item.profile_id == profile_id && item.kind == kind && !item.is_expired(&now)
+            })
+            .map(|item| item.category.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn category_stats(
+        &self,
+        profile_id: ProfileId,
+        kind: EntryKind,
+    ) -> Vec<(Vec<u8>, CategoryStats)> {
+        let now = chrono::Utc::now();
+        let mut stats = BTreeMap::<Vec<u8>, CategoryStats>::new();
+        for item in self.items.values() {
+            if item.profile_id == profile_id && item.kind == kind && !item.is_expired(&now) {
+                let size = item.name.len()
+                    + item.value.len()
+                    + item
+                        .tags
+                        .iter()
+                        .map(|tag| tag.name.len() + tag.value.len())
+                        .sum::<usize>();
+                stats
+                    .entry(item.category.clone())
+                    .or_default()
+                    .add(&CategoryStats {
+                        count: 1,
+                        size: size as i64,
+                    });
+            }
+        }
+        stats.into_iter().collect()
+    }
+
+    fn tag_names(
+        &self,
+        profile_id: ProfileId,
+        kind: EntryKind,
+        category: &[u8],
+    ) -> Vec<(Vec<u8>, bool, i64)> {
+        let now = chrono::Utc::now();
+        let mut names = BTreeMap::<(Vec<u8>, bool), i64>::new();
+        for item in self.items.values() {
+            if item.profile_id == profile_id
+                && item.kind == kind
+                && item.category == category
+                && !item.is_expired(&now)
+            {
+                let item_names = item
+                    .tags
+                    .iter()
+                    .map(|tag| (tag.name.clone(), tag.plaintext))
+                    .collect::<BTreeSet<_>>();
+                for name in item_names {
+                    *names.entry(name).or_default() += 1;
+                }
+            }
+        }
+        names
+            .into_iter()
+            .map(|((name, plaintext), count)| (name, plaintext, count))
+            .collect()
+    }
+
+    fn remove_expired(&mut self) -> BTreeMap<String, i64> {
+        let now = chrono::Utc::now();
+        let mut removed = BTreeMap::<ProfileId, i64>::new();
+        let index = &mut self.index;
+        self.items.retain(|_, item| {
+            if item.is_expired(&now) {
+                index.remove(&item.key());
+                *removed.entry(item.profile_id).or_default() += 1;
+                false
+            } else {
+                true
+            }
+        });
+        self.profiles
+            .iter()
+            .filter_map(|(name, profile)| removed.get(&profile.id).map(|c| (name.clone(), *c)))
+            .collect()
+    }
+
+    fn remove_selected(
+        &mut self,
+        profile_id: ProfileId,
+        kind: EntryKind,
+        category: &[u8],
+        tag_filter: Option<&TagMatch>,
+    ) -> i64 {
+        let item_ids = self
+            .select(profile_id, kind, category, tag_filter, true)
+            .map(|(item_id, _)| item_id)
+            .collect::<Vec<_>>();
+        for item_id in item_ids.iter() {
+            if let Some(item) = self.items.remove(item_id) {
+                self.index.remove(&item.key());
+            }
+        }
+        item_ids.len() as i64
+    }
+
+    fn set_expiry(&mut self, key: &ItemKey, expiry: Option<Expiry>) -> Result<(), Error> {
+        let now = chrono::Utc::now();
+        let item = self
+            .index
+            .get(key)
+            .copied()
+            .and_then(|item_id| self.items.get_mut(&item_id))
+            .filter(|item| !item.is_expired(&now))
+            .ok_or_else(|| err_msg!(NotFound, "Entry not found"))?;
+        item.expiry = expiry;
+        item.updated = now;
+        Ok(())
+    }
+
+    fn set_expiry_selected(
+        &mut self,
+        profile_id: ProfileId,
+        kind: EntryKind,
+        category: &[u8],
+        tag_filter: Option<&TagMatch>,
+        expiry: Option<Expiry>,
+    ) -> i64 {
+        let now = chrono::Utc::now();
+        let item_ids = self
+            .select(profile_id, kind, category, tag_filter, false)
+            .map(|(item_id, _)| item_id)
+            .collect::<Vec<_>>();
+        for item_id in item_ids.iter() {
+            if let Some(item) = self.items.get_mut(item_id) {
+                item.expiry = expiry;
+                item.updated = now;
+            }
+        }
+        item_ids.len() as i64
+    }
+}
+
+/// An in-memory store
+pub struct MemoryStore {
+    db: MemoryDb,
+    default_profile: String,
+    key_cache: Arc<KeyCache>,
+    name: Option<String>,
+    read_only: bool,
+    query_limits: TagQueryLimits,
+}
+
+impl MemoryStore {
+    pub(crate) fn new(
+        db: MemoryDb,
+        default_profile: String,
+        key_cache: KeyCache,
+        name: Option<String>,
+        read_only: bool,
+        query_limits: TagQueryLimits,
+    ) -> Self {
+        Self {
+            db,
+            default_profile,
+            key_cache: Arc::new(key_cache),
+            name,
+            read_only,
+            query_limits,
+        }
+    }
+
+    async fn start_scan(
+        &self,
+        cursor: ScanCursor,
+        tag_filter: Option<TagFilter>,
+        token: Option<String>,
+        offset: Option<i64>,
+        limit: Option<i64>,
+    ) -> Result<Scan<'static, Entry>, Error> {
+        let mut session = self.session(Some(cursor.profile.clone()), false)?;
+        let (profile_id, key) = session.acquire_key().await?;
+        let (enc_category, tag_filter) =
+            encode_category_filter(&key, &cursor.category, tag_filter).await?;
+        let order = MemoryOrder::new(cursor.order_by.as_ref(), &key)?;
+        let after_id = cursor.after_id();
+        let enc_rows = session
+            .with_tables(|tables| {
+                let mut rows = tables
+                    .select(
+                        profile_id,
+                        cursor.kind,
+                        &enc_category,
+                        tag_filter.as_ref(),
+                        false,
+                    )
+                    .filter(|(item_id, _)| *item_id > after_id)
+                    .collect::<Vec<_>>();
+                order.sort(&mut rows);
+                let rows = rows
+                    .into_iter()
+                    .skip(offset.unwrap_or(0).max(0) as usize)
+                    .map(|(item_id, item)| (item_id, item.clone()));
+                match limit {
+                    Some(limit) if limit >= 0 => rows.take(limit as usize).collect(),
+                    _ => rows.collect::<Vec<_>>(),
+                }
+            })
+            .await;
+        let mut batches = Vec::with_capacity(enc_rows.len() / PAGE_SIZE + 1);
+        let mut enc_rows = enc_rows.into_iter().peekable();
+        while enc_rows.peek().is_some() {
+            batches.push(enc_rows.by_ref().take(PAGE_SIZE).collect::<Vec<_>>());
+        }
+        let store_key = self.key_cache.store_key.clone();
+        let mut cursor = cursor;
+        let stream = stream::iter(batches).then(move |enc_rows| {
+            let cursor = cursor.advance(enc_rows.len());
+            let key = key.clone();
+            let store_key = store_key.clone();
+            unblock(move || {
+                let last_id = enc_rows.last().map(|(item_id, _)| *item_id);
+                let token = cursor.token_after(last_id.unwrap_or(cursor.last_id), &store_key)?;
+                let items = enc_rows.into_iter().map(|(_, item)| item).collect();
+                Ok((decrypt_entries(cursor.category, items, &key)?, token))
+            })
+        });
+        Ok(Scan::new(stream, PAGE_SIZE, token))
+    }
+}
+
+impl Debug for MemoryStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryStore")
+            .field("default_profile", &self.default_profile)
+            .field("name", &self.name)
+            .field("read_only", &self.read_only)
+            .finish()
+    }
+}
+
+impl Backend for MemoryStore {
+    type Session = MemorySession;
+
+    fn create_profile(&self, name: Option<String>) -> BoxFuture<'_, Result<String, Error>> {
+        let name = name.unwrap_or_else(random_profile_name);
+        Box::pin(async move {
+            let key = ProfileKey::new()?;
+            let enc_key = encode_profile_key(&key, &self.key_cache).await?;
+            let pid = self
+                .db
+                .lock()
+                .await
+                .insert_profile(name.clone(), enc_key)
+                .ok_or_else(|| err_msg!(Duplicate, "Duplicate profile name"))?;
+            self.key_cache
+                .add_profile(name.clone(), pid, Arc::new(key))
+                .await;
+            Ok(name)
+        })
+    }
+
+    fn get_profile_name(&self) -> &str {
+        self.default_profile.as_str()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn query_limits(&self) -> TagQueryLimits {
+        self.query_limits
+    }
+
+    fn remove_profile(&self, name: String) -> BoxFuture<'_, Result<bool, Error>> {
+        Box::pin(async move {
+            let removed = self.db.lock().await.remove_profile(&name);
+            if removed {
+                self.key_cache.remove_profile(&name).await;
+            }
+            Ok(removed)
+        })
+    }
+
+    fn rename_profile(
+        &self,
+        from_name: String,
+        to_name: String,
+    ) -> BoxFuture<'_, Result<bool, Error>> {
+        Box::pin(async move {
+            if from_name == self.default_profile {
+                return Err(err_msg!(Input, "Cannot rename the default profile"));
+            }
+            let renamed = self.db.lock().await.rename_profile(&from_name, to_name)?;
+            if renamed {
+                self.key_cache.remove_profile(&from_name).await;
+            }
+            Ok(renamed)
+        })
+    }
+
+    fn copy_profile(
+        &self,
+        from_name: String,
+        to_name: String,
+    ) -> BoxFuture<'_, Result<bool, Error>> {
+        Box::pin(async move { self.db.lock().await.copy_profile(&from_name, to_name) })
+    }
+
+    fn rotate_profile_key(&self, name: String) -> BoxFuture<'_, Result<i64, Error>> {
+        Box::pin(async move {
+            let new_key = ProfileKey::new()?;
+            let enc_key = encode_profile_key(&new_key, &self.key_cache).await?;
+            let mut tables = self.db.lock().await;
+            let (pid, key) = match tables.profiles.get(&name) {
+                Some(profile) => (
+                    profile.id,
+                    self.key_cache.load_key(profile.profile_key.clone()).await?,
+                ),
+                None => return Err(err_msg!(NotFound, "Profile not found")),
+            };
+            let updated = tables.reencrypt_items(pid, &key, &new_key)?;
+            if let Some(profile) = tables.profiles.get_mut(&name) {
+                profile.profile_key = enc_key;
+            }
+            drop(tables);
+            self.key_cache
+                .add_profile(name, pid, Arc::new(new_key))
+                .await;
+            Ok(updated)
+        })
+    }
+
+    fn load_profile_key(&self, name: String) -> BoxFuture<'_, Result<SecretBytes, Error>> {
+        Box::pin(async move {
+            let mut session = self.session(Some(name), false)?;
+            let (_, key) = session.acquire_key().await?;
+            key.to_bytes()
+        })
+    }
+
+    fn import_profile(&self, name: String, key: SecretBytes) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            let key = Arc::new(ProfileKey::from_slice(&key)?);
+            let enc_key = encode_profile_key(&key, &self.key_cache).await?;
+            let mut tables = self.db.lock().await;
+            let pid = match tables.profiles.get(&name).map(|p| p.id) {
+                Some(pid) if tables.has_items(pid) => {
+                    let found_key = tables.profiles[&name].profile_key.clone();
+                    if self.key_cache.load_key(found_key).await? != *key {
+                        return Err(err_msg!(
+                            Duplicate,
+                            "Profile exists with a different profile key"
+                        ));
+                    }
+                    pid
+                }
+                Some(pid) => {
+                    tables.profiles.get_mut(&name).unwrap().profile_key = enc_key;
+                    pid
+                }
+                None => tables.insert_profile(name.clone(), enc_key).unwrap(),
+            };
+            self.key_cache.add_profile(name, pid, key).await;
+            Ok(())
+        })
+    }
+
+    fn list_profiles(&self) -> BoxFuture<'_, Result<Vec<String>, Error>> {
+        Box::pin(async move {
+            let tables = self.db.lock().await;
+            let mut profiles = tables.profiles.iter().collect::<Vec<_>>();
+            profiles.sort_by_key(|(_, p)| p.id);
+            Ok(profiles.into_iter().map(|(name, _)| name.clone()).collect())
+        })
+    }
+
+    fn list_categories(
+        &self,
+        profile: Option<String>,
+        kind: EntryKind,
+    ) -> BoxFuture<'_, Result<Vec<String>, Error>> {
+        Box::pin(async move {
+            let mut session = self.session(profile, false)?;
+            let (profile_id, key) = session.acquire_key().await?;
+            let enc_categories = session
+                .with_tables(|tables| tables.categories(profile_id, kind))
+                .await;
+            unblock(move || {
+                enc_categories
+                    .into_iter()
+                    .map(|enc_category| key.decrypt_entry_category(enc_category))
+                    .collect()
+            })
+            .await
+        })
+    }
+
+    fn purge_expired(&self) -> BoxFuture<'_, Result<BTreeMap<String, i64>, Error>> {
+        Box::pin(async move { Ok(self.db.lock().await.remove_expired()) })
+    }
+
+    fn verify(
+        &self,
+        profile: Option<String>,
+        repair: bool,
+    ) -> BoxFuture<'_, Result<VerifyReport, Error>> {
+        Box::pin(async move {
+            let mut report = VerifyReport::default();
+            let mut tables = self.db.lock().await;
+            let profiles: Vec<(String, MemoryProfile)> = if let Some(name) = profile.as_ref() {
+                let found = tables
+                    .profiles
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+                vec![(name.clone(), found)]
+            } else {
+                tables
+                    .profiles
+                    .iter()
+                    .map(|(name, profile)| (name.clone(), profile.clone()))
+                    .collect()
+            };
+
+            for (name, profile) in profiles {
+                report.profiles_checked += 1;
+                // records are always checked against the stored profile key
+                let key = match self.key_cache.load_key(profile.profile_key).await {
+                    Ok(key) => Arc::new(key),
+                    Err(_) => {
+                        report.issues.push(VerifyIssue::ProfileKey {
+                            profile: name.clone(),
+                        });
+                        // the default profile is never removed
+                        if repair && name != self.default_profile {
+                            let removed = tables.profile_rows(profile.id);
+                            if tables.remove_profile(&name) {
+                                self.key_cache.remove_profile(&name).await;
+                                report.rows_removed += 1 + removed;
+                            }
+                        }
+                        continue;
+                    }
+                };
+                // a profile key held by this instance which does not match the
+                // stored key prevents any repairs to the profile
+                let mismatch = matches!(
+                    self.key_cache.get_profile(&name).await,
+                    Some((_, cached)) if cached != key
+                );
+                if mismatch {
+                    report.issues.push(VerifyIssue::ProfileKeyMismatch {
+                        profile: name.clone(),
+                    });
+                }
+                let repair = repair && !mismatch;
+                for kind in [EntryKind::Kms, EntryKind::Item].iter().copied() {
+                    let rows = tables.profile_entries(profile.id, kind);
+                    let batch = report.merge(verify_entries(&key, &name, kind, rows));
+                    if repair {
+                        for item_id in batch.invalid_entries {
+                            report.rows_removed += tables.remove_item_id(item_id);
+                        }
+                        for (item_id, tag) in batch.invalid_tags {
+                            report.rows_removed += tables.remove_item_tag(item_id, &tag);
+                        }
+                    }
+                }
+            }
+
+            // records which do not belong to any profile are only found when
+            // checking the whole store
+            if profile.is_none() {
+                for item_id in tables.orphan_items() {
+                    report.issues.push(VerifyIssue::OrphanEntry { id: item_id });
+                    if repair {
+                        report.rows_removed += tables.remove_item_id(item_id);
+                    }
+                }
+            }
+            Ok(report)
+        })
+    }
+
+    fn rekey_backend(
+        &mut self,
+        method: StoreKeyMethod,
+        pass_key: PassKey<'_>,
+    ) -> BoxFuture<'_, Result<(), Error>> {
+        let pass_key = pass_key.into_owned();
+        Box::pin(async move {
+            let (store_key, store_key_ref) = unblock(move || method.resolve(pass_key)).await?;
+            let key_cache = Arc::new(KeyCache::new(store_key));
+            let mut tables = self.db.lock().await;
+            let mut upd_keys = BTreeMap::<String, Vec<u8>>::new();
+            for (name, profile) in tables.profiles.iter() {
+                let profile_key = self.key_cache.load_key(profile.profile_key.clone()).await?;
+                upd_keys.insert(
+                    name.clone(),
+                    encode_profile_key(&profile_key, &key_cache).await?,
+                );
+            }
+            for (name, key) in upd_keys {
+                if let Some(profile) = tables.profiles.get_mut(&name) {
+                    profile.profile_key = key;
+                }
+            }
+            tables.store_key_ref = store_key_ref.into_uri();
+            self.key_cache = key_cache;
+            Ok(())
+        })
+    }
+
+    fn scan(
+        &self,
+        profile: Option<String>,
+        kind: EntryKind,
+        category: String,
+        tag_filter: Option<TagFilter>,
+        order_by: Option<OrderBy>,
+        offset: Option<i64>,
+        limit: Option<i64>,
+    ) -> BoxFuture<'_, Result<Scan<'static, Entry>, Error>> {
+        Box::pin(async move {
+            let profile = profile.unwrap_or_else(|| self.default_profile.clone());
+            let cursor = ScanCursor::new(
+                profile,
+                kind,
+                category,
+                tag_filter.as_ref(),
+                order_by.as_ref(),
+                offset,
+            )?;
+            self.start_scan(cursor, tag_filter, None, offset, limit)
+                .await
+        })
+    }
+
+    fn resume_scan(
+        &self,
+        token: String,
+        limit: Option<i64>,
+    ) -> BoxFuture<'_, Result<Scan<'static, Entry>, Error>> {
+        Box::pin(async move {
+            let cursor = ScanCursor::from_token(&token, &self.key_cache.store_key)?;
+            let tag_filter = cursor.tag_filter()?;
+            let offset = cursor.resume_offset();
+            self.start_scan(cursor, tag_filter, Some(token), offset, limit)
+                .await
+        })
+    }
+
+    fn session(&self, profile: Option<String>, transaction: bool) -> Result<Self::Session, Error> {
+        Ok(MemorySession::new(
+            self.db.clone(),
+            self.key_cache.clone(),
+            profile.unwrap_or_else(|| self.default_profile.clone()),
+            transaction,
+        ))
+    }
+
+    fn snapshot_session(&self, profile: Option<String>) -> Result<Self::Session, Error> {
+        // a transaction holds the lock on the tables until it is closed
+        self.session(profile, true)
+    }
+
+    fn publishes_changes(&self) -> bool {
+        false
+    }
+
+    fn listen_changes(&self, _registry: Weak<WatchRegistry>) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async {
+            Err(err_msg!(
+                Unsupported,
+                "Change notifications are not supported by this backend"
+            ))
+        })
+    }
+
+    fn close(&self) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+struct MemoryTxn {
+    tables: MutexGuardArc<MemoryTables>,
+    backup: MemoryTables,
+}
+
+impl MemoryTxn {
+    fn rollback(self) {
+        let MemoryTxn { mut tables, backup } = self;
+        *tables = backup;
+    }
+}
+
+/// A session against an in-memory store
+///
+/// A transaction holds an exclusive lock on the store contents until it is closed
+pub struct MemorySession {
+    db: MemoryDb,
+    cache: Arc<KeyCache>,
+    profile: String,
+    profile_key: Option<(ProfileId, Arc<ProfileKey>)>,
+    checked_key: Option<Vec<u8>>,
+    txn: Option<MemoryTxn>,
+    transaction: bool,
+}
+
+impl MemorySession {
+    pub(crate) fn new(
+        db: MemoryDb,
+        cache: Arc<KeyCache>,
+        profile: String,
+        transaction: bool,
+    ) -> Self {
+        Self {
+            db,
+            cache,
+            profile,
+            profile_key: None,
+            checked_key: None,
+            txn: None,
+            transaction,
+        }
+    }
+
+    async fn with_tables<R>(&mut self, f: impl FnOnce(&mut MemoryTables) -> R) -> R {
+        if self.transaction {
+            if self.txn.is_none() {
+                info!("Start transaction");
+                let tables = self.db.lock_arc().await;
+                let backup = tables.clone();
+                self.txn.replace(MemoryTxn { tables, backup });
+            }
+            f(&mut self.txn.as_mut().unwrap().tables)
+        } else {
+            f(&mut *self.db.lock().await)
+        }
+    }
+
+    async fn acquire_key(&mut self) -> Result<(ProfileId, Arc<ProfileKey>), Error> {
+        if let Some((pid, key)) = self.profile_key.as_ref() {
+            return Ok((*pid, key.clone()));
+        }
+        let (pid, key) = if let Some(ret) = self.cache.get_profile(&self.profile).await {
+            ret
+        } else {
+            let profile = self.profile.clone();
+            let (pid, enc_key) = self
+                .with_tables(|tables| {
+                    tables
+                        .profiles
+                        .get(&profile)
+                        .map(|p| (p.id, p.profile_key.clone()))
+                })
+                .await
+                .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+            let key = Arc::new(self.cache.load_key(enc_key).await?);
+            self.cache.add_profile(profile, pid, key.clone()).await;
+            (pid, key)
+        };
+        self.profile_key.replace((pid, key.clone()));
+        Ok((pid, key))
+    }
+
+    /// Apply an update to the store contents, first checking that the
+    /// profile key used by this session has not been replaced
+    async fn with_checked_tables<R>(
+        &mut self,
+        f: impl FnOnce(&mut MemoryTables) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let (_, key) = self.acquire_key().await?;
+        let profile = self.profile.clone();
+        let enc_key = self
+            .with_tables(|tables| tables.profiles.get(&profile).map(|p| p.profile_key.clone()))
+            .await
+            .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+        if self.checked_key.as_ref() != Some(&enc_key) {
+            if self.cache.load_key(enc_key.clone()).await? != *key {
+                return Err(err_msg!(
+                    Conflict,
+                    "Profile key was replaced after the session was started"
+                ));
+            }
+            self.checked_key.replace(enc_key.clone());
+        }
+        // the key may be rotated before the table lock is acquired again
+        self.with_tables(move |tables| {
+            tables.check_profile_key(&profile, &enc_key)?;
+            f(tables)
+        })
+        .await
+    }
+}
+
+impl Debug for MemorySession {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemorySession")
+            .field("profile", &self.profile)
+            .field("transaction", &self.transaction)
+            .finish()
+    }
+}
+
+impl Drop for MemorySession {
+    fn drop(&mut self) {
+        if let Some(txn) = self.txn.take() {
+            info!("Dropped transaction: roll-back");
+            txn.rollback();
+        }
+    }
+}
+
+impl QueryBackend for MemorySession {
+    fn count<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        tag_filter: Option<TagFilter>,
+    ) -> BoxFuture<'q, Result<i64, Error>> {
+        Box::pin(async move {
+            let (profile_id, key) = self.acquire_key().await?;
+            let (enc_category, tag_filter) =
+                encode_category_filter(&key, category, tag_filter).await?;
+            let count = self
+                .with_tables(|tables| {
+                    tables
+                        .select(profile_id, kind, &enc_category, tag_filter.as_ref(), false)
+                        .count()
+                })
+                .await;
+            Ok(count as i64)
+        })
+    }
+
+    fn category_stats(
+        &mut self,
+        kind: EntryKind,
+    ) -> BoxFuture<'_, Result<BTreeMap<String, CategoryStats>, Error>> {
+        Box::pin(async move {
+            let (profile_id, key) = self.acquire_key().await?;
+            let rows = self
+                .with_tables(|tables| tables.category_stats(profile_id, kind))
+                .await;
+            unblock(move || {
+                rows.into_iter()
+                    .map(|(enc_category, stats)| {
+                        Ok((key.decrypt_entry_category(enc_category)?, stats))
+                    })
+                    .collect()
+            })
+            .await
+        })
+    }
+
+    fn tag_names<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+    ) -> BoxFuture<'q, Result<Vec<TagNameStats>, Error>> {
+        Box::pin(async move {
+            let (profile_id, key) = self.acquire_key().await?;
+            let (enc_category, _) = encode_category_filter(&key, category, None).await?;
+            let rows = self
+                .with_tables(|tables| tables.tag_names(profile_id, kind, &enc_category))
+                .await;
+            unblock(move || {
+                let mut names = rows
+                    .into_iter()
+                    .map(|(enc_name, plaintext, count)| {
+                        Ok(TagNameStats {
+                            name: key.decrypt_entry_tag_name(enc_name)?,
+                            plaintext,
+                            count,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                names.sort();
+                Ok(names)
+            })
+            .await
+        })
+    }
+
+    fn fetch<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+        _for_update: bool,
+    ) -> BoxFuture<'q, Result<Option<Entry>, Error>> {
+        let category = category.to_string();
+        let name = name.to_string();
+
+        Box::pin(async move {
+            let (profile_id, key) = self.acquire_key().await?;
+            let (enc_category, enc_name) = encode_category_name(&key, &category, &name).await?;
+            let item_key = (profile_id, kind, enc_category, enc_name);
+            if let Some(item) = self
+                .with_tables(|tables| tables.get_item(&item_key).cloned())
+                .await
+            {
+                let entry = unblock(move || decrypt_entry(category, item, &key)).await?;
+                Ok(Some(entry))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    fn fetch_all<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        tag_filter: Option<TagFilter>,
+        order_by: Option<OrderBy>,
+        limit: Option<i64>,
+        _for_update: bool,
+    ) -> BoxFuture<'q, Result<Vec<Entry>, Error>> {
+        let category = category.to_string();
+
+        Box::pin(async move {
+            let (profile_id, key) = self.acquire_key().await?;
+            let (enc_category, tag_filter) =
+                encode_category_filter(&key, &category, tag_filter).await?;
+            let order = MemoryOrder::new(order_by.as_ref(), &key)?;
+            let enc_rows = self
+                .with_tables(|tables| {
+                    let mut rows = tables
+                        .select(profile_id, kind, &enc_category, tag_filter.as_ref(), false)
+                        .collect::<Vec<_>>();
+                    order.sort(&mut rows);
+                    let rows = rows.into_iter().map(|(_, item)| item.clone());
+                    match limit {
+                        Some(limit) if limit >= 0 => rows.take(limit as usize).collect(),
+                        _ => rows.collect::<Vec<_>>(),
+                    }
+                })
+                .await;
+            unblock(move || decrypt_entries(category, enc_rows, &key)).await
+        })
+    }
+
+    fn remove_all<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        tag_filter: Option<TagFilter>,
+    ) -> BoxFuture<'q, Result<i64, Error>> {
+        Box::pin(async move {
+            let (profile_id, key) = self.acquire_key().await?;
+            let (enc_category, tag_filter) =
+                encode_category_filter(&key, category, tag_filter).await?;
+            self.with_checked_tables(|tables| {
+                Ok(tables.remove_selected(profile_id, kind, &enc_category, tag_filter.as_ref()))
+            })
+            .await
+        })
+    }
+
+    fn update<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        operation: EntryOperation,
+        category: &'q str,
+        name: &'q str,
+        value: Option<&'q [u8]>,
+        tags: Option<&'q [EntryTag]>,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        let category = ProfileKey::prepare_input(category.as_bytes());
+        let name = ProfileKey::prepare_input(name.as_bytes());
+
+        match operation {
+            op @ EntryOperation::Insert | op @ EntryOperation::Replace => {
+                let value = ProfileKey::prepare_input(value.unwrap());
+                let tags = tags.map(|t| t.to_vec());
+                Box::pin(async move {
+                    let (profile_id, key) = self.acquire_key().await?;
+                    let expiry = expiry_ms.map(expiry_timestamp).transpose()?;
+                    let now = chrono::Utc::now();
+                    let (enc_category, enc_name, enc_value, enc_tags) = unblock(move || {
+                        let enc_value =
+                            key.encrypt_entry_value(category.as_ref(), name.as_ref(), value)?;
+                        Result::<_, Error>::Ok((
+                            key.encrypt_entry_category(category)?,
+                            key.encrypt_entry_name(name)?,
+                            enc_value,
+                            tags.map(|t| key.encrypt_entry_tags(t)).transpose()?,
+                        ))
+                    })
+                    .await?;
+                    let mut item = MemoryItem {
+                        profile_id,
+                        kind,
+                        category: enc_category,
+                        name: enc_name,
+                        value: enc_value,
+                        tags: enc_tags.unwrap_or_default(),
+                        expiry,
+                        version: 1,
+                        created: now,
+                        updated: now,
+                    };
+                    self.with_checked_tables(|tables| {
+                        if op == EntryOperation::Replace {
+                            let (_, prev) = tables
+                                .take_item(&item.key())
+                                .ok_or_else(|| err_msg!(NotFound, "Entry not found"))?;
+                            item.version = prev.version + 1;
+                            item.created = prev.created;
+                        }
+                        tables.insert_item(item)
+                    })
+                    .await
+                })
+            }
+
+            EntryOperation::Remove => Box::pin(async move {
+                let (profile_id, key) = self.acquire_key().await?;
+                let (enc_category, enc_name) = unblock(move || {
+                    Result::<_, Error>::Ok((
+                        key.encrypt_entry_category(category)?,
+                        key.encrypt_entry_name(name)?,
+                    ))
+                })
+                .await?;
+                let item_key = (profile_id, kind, enc_category, enc_name);
+                self.with_checked_tables(|tables| {
+                    if tables.remove_item(&item_key) {
+                        Ok(())
+                    } else {
+                        Err(err_msg!(NotFound, "Entry not found"))
+                    }
+                })
+                .await
+            }),
+        }
+    }
+
+    fn update_tags<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+        add: &'q [EntryTag],
+        remove: &'q [&'q str],
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        let category = ProfileKey::prepare_input(category.as_bytes());
+        let name = ProfileKey::prepare_input(name.as_bytes());
+        let add = add.to_vec();
+        let remove = remove.iter().map(|name| name.to_string()).collect();
+
+        Box::pin(async move {
+            let (profile_id, key) = self.acquire_key().await?;
+            let (enc_category, enc_name, (enc_add, enc_remove)) = unblock(move || {
+                Result::<_, Error>::Ok((
+                    key.encrypt_entry_category(category)?,
+                    key.encrypt_entry_name(name)?,
+                    encrypt_tag_update(&key, add, remove)?,
+                ))
+            })
+            .await?;
+            let item_key = (profile_id, kind, enc_category, enc_name);
+            self.with_checked_tables(|tables| tables.update_tags(&item_key, enc_add, enc_remove))
+                .await
+        })
+    }
+
+    fn set_expiry<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        Box::pin(async move {
+            let (profile_id, key) = self.acquire_key().await?;
+            let expiry = expiry_ms.map(expiry_timestamp).transpose()?;
+            let (enc_category, enc_name) = encode_category_name(&key, category, name).await?;
+            let item_key = (profile_id, kind, enc_category, enc_name);
+            self.with_checked_tables(|tables| tables.set_expiry(&item_key, expiry))
+                .await
+        })
+    }
+
+    fn set_expiry_all<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        tag_filter: Option<TagFilter>,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<i64, Error>> {
+        Box::pin(async move {
+            let (profile_id, key) = self.acquire_key().await?;
+            let expiry = expiry_ms.map(expiry_timestamp).transpose()?;
+            let (enc_category, tag_filter) =
+                encode_category_filter(&key, category, tag_filter).await?;
+            self.with_checked_tables(|tables| {
+                Ok(tables.set_expiry_selected(
+                    profile_id,
+                    kind,
+                    &enc_category,
+                    tag_filter.as_ref(),
+                    expiry,
+                ))
+            })
+            .await
+        })
+    }
+
+    fn apply_batch<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        updates: Vec<EntryUpdate>,
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        Box::pin(async move {
+            let (profile_id, key) = self.acquire_key().await?;
+            let now = chrono::Utc::now();
+            let updates = encrypt_entry_updates(key, updates)
+                .await?
+                .into_iter()
+                .map(|update| {
+                    Ok((
+                        update.operation,
+                        update.expected_version,
+                        MemoryItem {
+                            profile_id,
+                            kind,
+                            category: update.category,
+                            name: update.name,
+                            value: update.value,
+                            tags: update.tags.unwrap_or_default(),
+                            expiry: update.expiry_ms.map(expiry_timestamp).transpose()?,
+                            version: update.version,
+                            created: now,
+                            updated: now,
+                        },
+                    ))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            self.with_checked_tables(|tables| tables.apply_batch(updates))
+                .await
+        })
+    }
+
+    fn publish_changes<'q>(
+        &'q mut self,
+        _changes: &'q [EntryChange],
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        Box::pin(async {
+            Err(err_msg!(
+                Unsupported,
+                "Change notifications are not supported by this backend"
+            ))
+        })
+    }
+
+    fn close(mut self, commit: bool) -> BoxFuture<'static, Result<(), Error>> {
+        if let Some(txn) = self.txn.take() {
+            if commit {
+                info!("Commit transaction on close");
+            } else {
+                info!("Roll-back transaction on close");
+                txn.rollback();
+            }
+        }
+        Box::pin(async { Ok(()) })
+    }
+}
+
+async fn encode_profile_key(key: &ProfileKey, cache: &KeyCache) -> Result<Vec<u8>, Error> {
+    let key_bytes = key.to_bytes()?;
+    let store_key = cache.store_key.clone();
+    unblock(move || store_key.wrap_data(key_bytes)).await
+}
+
+async fn encode_category_name(
+    key: &Arc<ProfileKey>,
+    category: &str,
+    name: &str,
+) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let key = key.clone();
+    let category = ProfileKey::prepare_input(category.as_bytes());
+    let name = ProfileKey::prepare_input(name.as_bytes());
+    unblock(move || {
+        Ok((
+            key.encrypt_entry_category(category)?,
+            key.encrypt_entry_name(name)?,
+        ))
+    })
+    .await
+}
+
+async fn encode_category_filter(
+    key: &Arc<ProfileKey>,
+    category: &str,
+    tag_filter: Option<TagFilter>,
+) -> Result<(Vec<u8>, Option<TagMatch>), Error> {
+    let key = key.clone();
+    let category = ProfileKey::prepare_input(category.as_bytes());
+    unblock(move || {
+        let tag_filter = if let Some(tag_filter) = tag_filter {
+            let tag_query = tag_query(tag_filter.query)?;
+            let mut enc = TagMatchEncoder::new(
+                |name| key.encrypt_tag_name(ProfileKey::prepare_input(name.as_bytes())),
+                |value| key.encrypt_tag_value(ProfileKey::prepare_input(value.as_bytes())),
+            );
+            enc.encode_query(&tag_query)?
+        } else {
+            None
+        };
+        Ok((key.encrypt_entry_category(category)?, tag_filter))
+    })
+    .await
+}
+
+fn decrypt_entry(category: String, item: MemoryItem, key: &ProfileKey) -> Result<Entry, Error> {
+    let name = key.decrypt_entry_name(item.name)?;
+    let value = key.decrypt_entry_value(category.as_bytes(), name.as_bytes(), item.value)?;
+    let tags = key.decrypt_entry_tags(item.tags)?;
+    Ok(Entry::new(category, name, value, tags)
+        .with_expiry(item.expiry.map(|e| e.timestamp_millis()))
+        .with_version(item.version)
+        .with_timestamps(
+            Some(item.created.timestamp_millis()),
+            Some(item.updated.timestamp_millis()),
+        ))
+}
+
+fn decrypt_entries(
+    category: String,
+    items: Vec<MemoryItem>,
+    key: &ProfileKey,
+) -> Result<Vec<Entry>, Error> {
+    let mut batch = Vec::with_capacity(items.len());
+    for item in items {
+        batch.push(decrypt_entry(category.clone(), item, key)?);
+    }
+    Ok(batch)
+}
+
+fn expiry_timestamp(expire_ms: i64) -> Result<Expiry, Error> {
+    chrono::Utc::now()
+        .checked_add_signed(chrono::Duration::milliseconds(expire_ms))
+        .ok_or_else(|| err_msg!(Unexpected, "Invalid expiry timestamp"))
+}
+
+#[inline]
+fn random_profile_name() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::future::block_on;
+    use crate::protect::{generate_raw_store_key, StoreKeyMethod};
+    use crate::storage::VerifyIssue;
+
+    #[test]
+    fn memory_verify_keeps_default_profile() {
+        block_on(async {
+            let key = generate_raw_store_key(None)?;
+            let db = MemoryStoreOptions::default()
+                .provision(StoreKeyMethod::RawKey, key, None, false)
+                .await?;
+            let profile = db.get_profile_name().to_string();
+            let other = db.create_profile(None).await?;
+            {
+                let mut tables = db.inner().db.lock().await;
+                for name in [&profile, &other] {
+                    tables.profiles.get_mut(name).unwrap().profile_key = vec![0];
+                }
+            }
+
+            let report = db.verify(None, true).await?;
+            assert_eq!(report.issues.len(), 2);
+            for name in [&profile, &other] {
+                assert!(report.issues.contains(&VerifyIssue::ProfileKey {
+                    profile: name.clone()
+                }));
+            }
+            // only the other profile is removed
+            assert_eq!(report.rows_removed, 1);
+            assert_eq!(db.list_profiles().await?, vec![profile]);
+            Result::<_, Error>::Ok(())
+        })
+        .unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as SyncMutex};
+
+use async_mutex::Mutex;
+use once_cell::sync::Lazy;
+
+use super::{random_profile_name, MemoryDb, MemoryStore, MemoryTables};
+use crate::{
+    backend::types::{ManageBackend, SchemaMigration},
+    error::Error,
+    future::{unblock, BoxFuture},
+    protect::{KeyCache, PassKey, ProfileKey, StoreKeyMethod, StoreKeyReference},
+    storage::{IntoOptions, Store, TagQueryLimits},
+};
+
+// named stores are retained until they are removed, so that they may be reopened
+static NAMED_STORES: Lazy<SyncMutex<HashMap<String, MemoryDb>>> =
+    Lazy::new(|| SyncMutex::new(HashMap::new()));
+
+/// Configuration options for in-memory stores
+///
+/// A store provisioned with a name (as in `memory://name`) may be reopened
+/// within the same process until it is removed. An unnamed store (`memory://`)
+/// is discarded once all references to it are closed. The `mode` parameter
+/// may be set to `ro` to open a named store in read-only mode, and the
+/// `max_query_depth`, `max_query_clauses` and `max_query_args` parameters
+/// set the limits applied to tag filters.
+#[derive(Debug, Default)]
+pub struct MemoryStoreOptions {
+    pub(crate) name: Option<String>,
+    pub(crate) read_only: bool,
+    pub(crate) query_limits: TagQueryLimits,
+}
+
+impl MemoryStoreOptions {
+    /// Initialize `MemoryStoreOptions` from a generic set of options
+    pub fn new<'a>(options: impl IntoOptions<'a>) -> Result<Self, Error> {
+        let mut opts = options.into_options()?;
+        let read_only = match opts.query.remove("mode").as_deref() {
+            None | Some("rw") => false,
+            Some("ro") => true,
+            Some(_) => return Err(err_msg!(Input, "Error parsing 'mode' parameter")),
+        };
+        let query_limits = opts.take_query_limits()?;
+        let mut name = opts.host.to_string();
+        name.push_str(&opts.path);
+        Ok(Self {
+            name: if name.is_empty() { None } else { Some(name) },
+            read_only,
+            query_limits,
+        })
+    }
+
+    /// Provision a new in-memory store from these configuration options
+    pub async fn provision(
+        self,
+        method: StoreKeyMethod,
+        pass_key: PassKey<'_>,
+        profile: Option<&'_ str>,
+        recreate: bool,
+    ) -> Result<Store<MemoryStore>, Error> {
+        if self.read_only {
+            return Err(err_msg!(
+                Input,
+                "Cannot provision a store in read-only mode"
+            ));
+        }
+        if !recreate {
+            if let Some(db) = self.find_db() {
+                return open_db(
+                    db,
+                    Some(method),
+                    pass_key,
+                    profile,
+                    self.name,
+                    false,
+                    self.query_limits,
+                )
+                .await;
+            }
+        }
+
+        let default_profile = profile
+            .map(str::to_string)
+            .unwrap_or_else(random_profile_name);
+        let (db, key_cache) = init_db(&default_profile, method, pass_key).await?;
+        if let Some(name) = self.name.as_ref() {
+            NAMED_STORES
+                .lock()
+                .unwrap()
+                .insert(name.clone(), db.clone());
+        }
+
+        Ok(Store::new(MemoryStore::new(
+            db,
+            default_profile,
+            key_cache,
+            self.name,
+            false,
+            self.query_limits,
+        )))
+    }
+
+    /// Open an existing in-memory store from this set of configuration options
+    pub async fn open(
+        self,
+        method: Option<StoreKeyMethod>,
+        pass_key: PassKey<'_>,
+        profile: Option<&'_ str>,
+    ) -> Result<Store<MemoryStore>, Error> {
+        let db = self
+            .find_db()
+            .ok_or_else(|| err_msg!(NotFound, "The requested store was not found"))?;
+        open_db(
+            db,
+            method,
+            pass_key,
+            profile,
+            self.name,
+            self.read_only,
+            self.query_limits,
+        )
+        .await
+    }
+
+    /// Remove the in-memory store defined by these configuration options
+    pub async fn remove(self) -> Result<bool, Error> {
+        if let Some(name) = self.name.as_ref() {
+            Ok(NAMED_STORES.lock().unwrap().remove(name).is_some())
+        } else {
+            Ok(true)
+        }
+    }
+
+    fn find_db(&self) -> Option<MemoryDb> {
+        self.name
+            .as_ref()
+            .and_then(|name| NAMED_STORES.lock().unwrap().get(name).cloned())
+    }
+}
+
+impl<'a> ManageBackend<'a> for MemoryStoreOptions {
+    type Store = Store<MemoryStore>;
+
+    fn open_backend(
+        self,
+        method: Option<StoreKeyMethod>,
+        pass_key: PassKey<'a>,
+        profile: Option<&'a str>,
+        read_only: bool,
+    ) -> BoxFuture<'a, Result<Store<MemoryStore>, Error>> {
+        let mut opts = self;
+        opts.read_only |= read_only;
+        Box::pin(opts.open(method, pass_key, profile))
+    }
+
+    fn provision_backend(
+        self,
+        method: StoreKeyMethod,
+        pass_key: PassKey<'a>,
+        profile: Option<&'a str>,
+        recreate: bool,
+    ) -> BoxFuture<'a, Result<Store<MemoryStore>, Error>> {
+        Box::pin(self.provision(method, pass_key, profile, recreate))
+    }
+
+    fn remove_backend(self) -> BoxFuture<'a, Result<bool, Error>> {
+        Box::pin(self.remove())
+    }
+
+    fn pending_migrations_backend(self) -> BoxFuture<'a, Result<Vec<SchemaMigration>, Error>> {
+        // in-memory stores are always created with the current layout
+        Box::pin(async move { Ok(Vec::new()) })
+    }
+}
+
+async fn init_db(
+    profile_name: &str,
+    method: StoreKeyMethod,
+    pass_key: PassKey<'_>,
+) -> Result<(MemoryDb, KeyCache), Error> {
+    let (profile_key, enc_profile_key, store_key, store_key_ref) = unblock({
+        let pass_key = pass_key.into_owned();
+        move || {
+            let (store_key, store_key_ref) = method.resolve(pass_key)?;
+            let profile_key = ProfileKey::new()?;
+            let enc_profile_key = store_key.wrap_data(profile_key.to_bytes()?)?;
+            Result::<_, Error>::Ok((
+                profile_key,
+                enc_profile_key,
+                store_key,
+                store_key_ref.into_uri(),
+            ))
+        }
+    })
+    .await?;
+
+    let mut tables = MemoryTables::new(profile_name.to_string(), store_key_ref);
+    let profile_id = tables
+        .insert_profile(profile_name.to_string(), enc_profile_key)
+        .unwrap();
+
+    let mut key_cache = KeyCache::new(store_key);
+    key_cache.add_profile_mut(profile_name.to_string(), profile_id, profile_key);
+
+    Ok((Arc::new(Mutex::new(tables)), key_cache))
+}
+
+async fn open_db(
+    db: MemoryDb,
+    method: Option<StoreKeyMethod>,
+    pass_key: PassKey<'_>,
+    profile: Option<&str>,
+    name: Option<String>,
+    read_only: bool,
+    query_limits: TagQueryLimits,
+) -> Result<Store<MemoryStore>, Error> {
+    let (default_profile, store_key_ref, profiles) = {
+        let tables = db.lock().await;
+        (
+            tables.default_profile.clone(),
+            tables.store_key_ref.clone(),
+            tables.profiles.clone(),
+        )
+    };
+    let profile = profile.map(str::to_string).unwrap_or(default_profile);
+    let wrap_ref = StoreKeyReference::parse_uri(&store_key_ref)?;
+    if let Some(method) = method {
+        if !wrap_ref.compare_method(&method) {
+            return Err(err_msg!(Input, "Store key method mismatch"));
+        }
+    }
+    let store_key = unblock({
+        let pass_key = pass_key.into_owned();
+        move || wrap_ref.resolve(pass_key)
+    })
+    .await?;
+    let mut key_cache = KeyCache::new(store_key);
+
+    let profile_info = profiles
+        .get(&profile)
+        .ok_or_else(|| err_msg!(NotFound, "Profile not found"))?;
+    let profile_key = key_cache.load_key(profile_info.profile_key.clone()).await?;
+    key_cache.add_profile_mut(profile.clone(), profile_info.id, profile_key);
+
+    Ok(Store::new(MemoryStore::new(
+        db,
+        profile,
+        key_cache,
+        name,
+        read_only,
+        query_limits,
+    )))
+}
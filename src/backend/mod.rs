@@ -8,6 +8,11 @@ pub mod any;
 #[cfg(any(feature = "postgres", feature = "sqlite"))]
 pub(crate) mod db_utils;
 
+#[cfg(feature = "memory")]
+#[cfg_attr(docsrs, doc(cfg(feature = "memory")))]
+/// In-memory store support
+pub mod memory;
+
 #[cfg(feature = "postgres")]
 #[cfg_attr(docsrs, doc(cfg(feature = "postgres")))]
 /// Postgres database support
@@ -19,4 +24,4 @@ pub mod postgres;
 pub mod sqlite;
 
 mod types;
-pub use self::types::{Backend, ManageBackend, QueryBackend};
+pub use self::types::{Backend, ManageBackend, QueryBackend, SchemaMigration};
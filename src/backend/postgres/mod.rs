@@ -1,69 +1,204 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{self, Debug, Formatter};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 
 use async_stream::try_stream;
 
 use futures_lite::{
-    pin,
+    future, pin,
     stream::{Stream, StreamExt},
 };
 
 use sqlx::{
     pool::PoolConnection,
-    postgres::{PgPool, Postgres},
-    Row,
+    postgres::{PgListener, PgPool, PgRow, Postgres},
+    Database, Error as SqlxError, Row, TransactionManager,
 };
 
 use crate::{
     backend::{
         db_utils::{
-            decode_tags, decrypt_scan_batch, encode_profile_key, encode_tag_filter,
-            expiry_timestamp, extend_query, prepare_tags, random_profile_name,
-            replace_arg_placeholders, DbSession, DbSessionActive, DbSessionRef, EncScanEntry,
-            ExtDatabase, QueryParams, QueryPrepare, PAGE_SIZE,
+            check_profile_key, decode_tags, decrypt_scan_batch, decrypt_scan_page, encode_order_by,
+            encode_profile_key, encode_tag_filter, expiry_timestamp, extend_query,
+            millis_timestamp, multi_row_values, order_by_query, prepare_tags, random_profile_name,
+            replace_arg_placeholders, rotate_profile_key, verify_store, DbSession, DbSessionActive,
+            DbSessionRef, EncScanEntry, Expiry, ExtDatabase, QueryParams, QueryPrepare,
+            RotateQueries, VerifyQueries, BATCH_ROWS, PAGE_SIZE,
         },
         types::{Backend, QueryBackend},
     },
+    crypto::buffer::SecretBytes,
     error::Error,
-    future::{unblock, BoxFuture},
-    protect::{EntryEncryptor, KeyCache, PassKey, ProfileId, ProfileKey, StoreKeyMethod},
-    storage::{EncEntryTag, Entry, EntryKind, EntryOperation, EntryTag, Scan, TagFilter},
+    future::{spawn_ok, unblock, BoxFuture},
+    protect::{
+        encrypt_entry_updates, encrypt_tag_update, EntryEncryptor, KeyCache, PassKey, ProfileId,
+        ProfileKey, StoreKeyMethod,
+    },
+    storage::{
+        CategoryStats, EncEntry, EncEntryTag, EncEntryUpdate, Entry, EntryChange, EntryKind,
+        EntryOperation, EntryTag, EntryUpdate, OrderBy, Scan, ScanCursor, TagFilter, TagNameStats,
+        TagQueryLimits, VerifyReport, WatchRegistry,
+    },
 };
 
+// the channel name is qualified by the current schema, so that stores sharing
+// a database do not receive each other's changes
+const CHANGES_CHANNEL: &'static str = "askar_changes_";
+const CHANGES_CHANNEL_QUERY: &'static str = "SELECT $1 || md5(current_schema())";
+const CHANGES_NOTIFY_QUERY: &'static str = "SELECT pg_notify($1 || md5(current_schema()), $2)";
+// the maximum length of a notification payload is 8000 bytes
+const CHANGES_PAYLOAD_MAX: usize = 7999;
+
 const COUNT_QUERY: &'static str = "SELECT COUNT(*) FROM items i
     WHERE profile_id = $1 AND kind = $2 AND category = $3
     AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)";
+const IMPORT_PROFILE_QUERY: &'static str = "INSERT INTO profiles (name, profile_key)
+    VALUES ($1, $2) ON CONFLICT (name) DO UPDATE SET profile_key = excluded.profile_key
+    WHERE NOT EXISTS (SELECT 1 FROM items WHERE items.profile_id = profiles.id)";
+const CATEGORIES_QUERY: &'static str = "SELECT DISTINCT category FROM items
+    WHERE profile_id = $1 AND kind = $2
+    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)";
+const CATEGORY_STATS_QUERY: &'static str = "SELECT category, COUNT(*),
+    CAST(SUM(LENGTH(name) + LENGTH(value) + COALESCE((SELECT SUM(LENGTH(it.name) + LENGTH(it.value))
+        FROM items_tags it WHERE it.item_id = i.id), 0)) AS BIGINT)
+    FROM items i WHERE profile_id = $1 AND kind = $2
+    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)
+    GROUP BY category";
+const COPY_ITEMS_QUERY: &'static str =
+    "INSERT INTO items (profile_id, kind, category, name, value, expiry, version,
+        created, updated)
+    SELECT $2, kind, category, name, value, expiry, version, created, updated FROM items
+    WHERE profile_id = $1 AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)";
+const COPY_TAGS_QUERY: &'static str = "INSERT INTO items_tags
+    (item_id, name, value, plaintext)
+    SELECT n.id, it.name, it.value, it.plaintext FROM items_tags it
+    JOIN items i ON i.id = it.item_id
+    JOIN items n ON n.profile_id = $2 AND n.kind = i.kind
+        AND n.category = i.category AND n.name = i.name
+    WHERE i.profile_id = $1";
 const DELETE_QUERY: &'static str = "DELETE FROM items
     WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4";
+const DELETE_ID_QUERY: &'static str = "DELETE FROM items WHERE id = $1";
 const FETCH_QUERY: &'static str = "SELECT id, value,
     (SELECT ARRAY_TO_STRING(ARRAY_AGG(it.plaintext || ':'
         || ENCODE(it.name, 'hex') || ':' || ENCODE(it.value, 'hex')), ',')
-        FROM items_tags it WHERE it.item_id = i.id) tags
+        FROM items_tags it WHERE it.item_id = i.id) tags,
+    CAST(EXTRACT(EPOCH FROM i.expiry) * 1000 AS BIGINT) expiry, version,
+    CAST(EXTRACT(EPOCH FROM i.created) * 1000 AS BIGINT) created,
+    CAST(EXTRACT(EPOCH FROM i.updated) * 1000 AS BIGINT) updated
     FROM items i
     WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4
     AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)";
 const FETCH_QUERY_UPDATE: &'static str = "SELECT id, value,
     (SELECT ARRAY_TO_STRING(ARRAY_AGG(it.plaintext || ':'
         || ENCODE(it.name, 'hex') || ':' || ENCODE(it.value, 'hex')), ',')
-        FROM items_tags it WHERE it.item_id = i.id) tags
+        FROM items_tags it WHERE it.item_id = i.id) tags,
+    CAST(EXTRACT(EPOCH FROM i.expiry) * 1000 AS BIGINT) expiry, version,
+    CAST(EXTRACT(EPOCH FROM i.created) * 1000 AS BIGINT) created,
+    CAST(EXTRACT(EPOCH FROM i.updated) * 1000 AS BIGINT) updated
     FROM items i
     WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4
     AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP) FOR UPDATE";
 const INSERT_QUERY: &'static str =
-    "INSERT INTO items (profile_id, kind, category, name, value, expiry)
-    VALUES ($1, $2, $3, $4, $5, $6)
+    "INSERT INTO items (profile_id, kind, category, name, value, expiry, version,
+        created, updated)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
     ON CONFLICT DO NOTHING RETURNING id";
-const SCAN_QUERY: &'static str = "SELECT id, name, value,
+// the row lock makes a key rotation wait for the writes already started
+const PROFILE_KEY_CHECK_QUERY: &'static str =
+    "SELECT profile_key, rotate_key IS NOT NULL FROM profiles WHERE id = $1 FOR SHARE";
+const PURGE_QUERY: &'static str = "WITH removed AS (
+        DELETE FROM items WHERE expiry <= CURRENT_TIMESTAMP RETURNING profile_id
+    )
+    SELECT p.name, COUNT(*) FROM removed r
+    JOIN profiles p ON p.id = r.profile_id
+    GROUP BY p.name";
+const ROTATE_FETCH_QUERY: &'static str = "SELECT id, category, name, value,
     (SELECT ARRAY_TO_STRING(ARRAY_AGG(it.plaintext || ':'
         || ENCODE(it.name, 'hex') || ':' || ENCODE(it.value, 'hex')), ',')
         FROM items_tags it WHERE it.item_id = i.id) tags
-    FROM items i WHERE profile_id = $1 AND kind = $2 AND category = $3
+    FROM items i WHERE profile_id = $1 AND id > $2
+    ORDER BY id LIMIT $3";
+const REMOVE_VERSION_QUERY: &'static str = "SELECT id, version,
+    CAST(EXTRACT(EPOCH FROM created) * 1000 AS BIGINT) created FROM items
+    WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4 FOR UPDATE";
+const SET_EXPIRY_QUERY: &'static str = "UPDATE items SET expiry = $5, updated = $6
+    WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4
+    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)";
+const SET_EXPIRY_ALL_QUERY: &'static str = "UPDATE items i SET expiry = $4, updated = $5
+    WHERE i.profile_id = $1 AND i.kind = $2 AND i.category = $3
+    AND (i.expiry IS NULL OR i.expiry > CURRENT_TIMESTAMP)";
+const TAG_NAMES_QUERY: &'static str = "SELECT it.name, it.plaintext, COUNT(DISTINCT i.id)
+    FROM items_tags it JOIN items i ON i.id = it.item_id
+    WHERE i.profile_id = $1 AND i.kind = $2 AND i.category = $3
+    AND (i.expiry IS NULL OR i.expiry > CURRENT_TIMESTAMP)
+    GROUP BY it.name, it.plaintext";
+const TAG_UPDATE_ITEM_QUERY: &'static str = "UPDATE items
+    SET version = version + 1, updated = $5
+    WHERE profile_id = $1 AND kind = $2 AND category = $3 AND name = $4
+    AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP) RETURNING id";
+const ROTATE_UPDATE_QUERY: &'static str = "UPDATE items
+    SET category = $1, name = $2, value = $3 WHERE id = $4";
+const SCAN_QUERY: &'static str = "SELECT id, name, value,
+    (SELECT ARRAY_TO_STRING(ARRAY_AGG(it.plaintext || ':'
+        || ENCODE(it.name, 'hex') || ':' || ENCODE(it.value, 'hex')), ',')
+        FROM items_tags it WHERE it.item_id = i.id) tags,
+    CAST(EXTRACT(EPOCH FROM i.expiry) * 1000 AS BIGINT) expiry, version,
+    CAST(EXTRACT(EPOCH FROM i.created) * 1000 AS BIGINT) created,
+    CAST(EXTRACT(EPOCH FROM i.updated) * 1000 AS BIGINT) updated
+    FROM items i WHERE profile_id = $1 AND kind = $2 AND category = $3 AND id > $4
     AND (expiry IS NULL OR expiry > CURRENT_TIMESTAMP)";
 const DELETE_ALL_QUERY: &'static str = "DELETE FROM items i
     WHERE i.profile_id = $1 AND i.kind = $2 AND i.category = $3";
+const TAG_DELETE_QUERY: &'static str = "DELETE FROM items_tags
+    WHERE item_id = $1 AND name = $2 AND plaintext = $3";
+const TAG_DELETE_VALUE_QUERY: &'static str = "DELETE FROM items_tags
+    WHERE item_id = $1 AND name = $2 AND value = $3 AND plaintext = $4";
 const TAG_INSERT_QUERY: &'static str = "INSERT INTO items_tags
     (item_id, name, value, plaintext) VALUES ($1, $2, $3, $4)";
+const INSERT_BATCH_QUERY: &'static str =
+    "INSERT INTO items (profile_id, kind, category, name, value, expiry, version,
+        created, updated) VALUES ";
+const INSERT_BATCH_SUFFIX: &'static str = " ON CONFLICT DO NOTHING RETURNING id, category, name";
+const TAG_INSERT_BATCH_QUERY: &'static str = "INSERT INTO items_tags
+    (item_id, name, value, plaintext) VALUES ";
+const VERIFY_FETCH_QUERY: &'static str = "SELECT i.id, i.category, i.name, i.value,
+    (SELECT ARRAY_TO_STRING(ARRAY_AGG(it.plaintext || ':'
+        || ENCODE(it.name, 'hex') || ':' || ENCODE(it.value, 'hex')), ',')
+        FROM items_tags it WHERE it.item_id = i.id) tags
+    FROM items i WHERE i.profile_id = $1 AND i.kind = $2 AND i.id > $3
+    ORDER BY i.id LIMIT $4";
+const VERIFY_ORPHAN_ITEMS_QUERY: &'static str = "SELECT id FROM items
+    WHERE profile_id NOT IN (SELECT id FROM profiles)";
+const VERIFY_ORPHAN_TAGS_QUERY: &'static str = "SELECT id FROM items_tags
+    WHERE item_id NOT IN (SELECT id FROM items)";
+const VERIFY_QUERIES: VerifyQueries = VerifyQueries {
+    profile: "SELECT id, name, profile_key, rotate_key IS NOT NULL FROM profiles WHERE name = $1",
+    profiles: "SELECT id, name, profile_key, rotate_key IS NOT NULL FROM profiles",
+    profile_rows: "SELECT (SELECT COUNT(*) FROM items WHERE profile_id = $1)
+        + (SELECT COUNT(*) FROM items_tags it JOIN items i ON i.id = it.item_id
+        WHERE i.profile_id = $1)",
+    delete_profile: "DELETE FROM profiles WHERE id = $1",
+    fetch: VERIFY_FETCH_QUERY,
+    item_tags: "SELECT COUNT(*) FROM items_tags WHERE item_id = $1",
+    delete_item: DELETE_ID_QUERY,
+    delete_tag_value: TAG_DELETE_VALUE_QUERY,
+    orphan_items: VERIFY_ORPHAN_ITEMS_QUERY,
+    orphan_tags: VERIFY_ORPHAN_TAGS_QUERY,
+    delete_tag: "DELETE FROM items_tags WHERE id = $1",
+};
+const ROTATE_QUERIES: RotateQueries = RotateQueries {
+    profile: "SELECT id, profile_key, rotate_key, rotate_pos FROM profiles WHERE name = $1",
+    start: "UPDATE profiles SET rotate_key = $1, rotate_pos = 0
+        WHERE id = $2 AND rotate_key IS NULL",
+    fetch: ROTATE_FETCH_QUERY,
+    update_item: ROTATE_UPDATE_QUERY,
+    delete_tags: "DELETE FROM items_tags WHERE item_id = $1",
+    insert_tag: TAG_INSERT_QUERY,
+    update_pos: "UPDATE profiles SET rotate_pos = $1 WHERE id = $2",
+    finish: "UPDATE profiles SET profile_key = rotate_key, rotate_key = NULL, rotate_pos = NULL
+        WHERE id = $1",
+};
 
 mod provision;
 pub use provision::PostgresStoreOptions;
@@ -78,6 +213,10 @@ pub struct PostgresStore {
     key_cache: Arc<KeyCache>,
     host: String,
     name: String,
+    schema: Option<String>,
+    notify: bool,
+    read_only: bool,
+    query_limits: TagQueryLimits,
 }
 
 impl PostgresStore {
@@ -87,6 +226,10 @@ impl PostgresStore {
         key_cache: KeyCache,
         host: String,
         name: String,
+        schema: Option<String>,
+        notify: bool,
+        read_only: bool,
+        query_limits: TagQueryLimits,
     ) -> Self {
         Self {
             conn_pool,
@@ -94,8 +237,47 @@ impl PostgresStore {
             key_cache: Arc::new(key_cache),
             host,
             name,
+            schema,
+            notify,
+            read_only,
+            query_limits,
         }
     }
+
+    async fn start_scan(
+        &self,
+        cursor: ScanCursor,
+        tag_filter: Option<TagFilter>,
+        token: Option<String>,
+        offset: Option<i64>,
+        limit: Option<i64>,
+    ) -> Result<Scan<'static, Entry>, Error> {
+        let session = self.session(Some(cursor.profile.clone()), false)?;
+        let mut active = session.owned_ref();
+        let (profile_id, key) = acquire_key(&mut *active).await?;
+        let scan = perform_scan(
+            active,
+            profile_id,
+            key.clone(),
+            cursor.kind,
+            cursor.category.clone(),
+            tag_filter,
+            cursor.order_by.clone(),
+            cursor.after_id(),
+            offset,
+            limit,
+            false,
+        );
+        let store_key = self.key_cache.store_key.clone();
+        let mut cursor = cursor;
+        let stream = scan.then(move |enc_rows| {
+            let cursor = cursor.advance(enc_rows.as_ref().map(Vec::len).unwrap_or(0));
+            let key = key.clone();
+            let store_key = store_key.clone();
+            unblock(move || decrypt_scan_page(&cursor, enc_rows?, &key, &store_key))
+        });
+        Ok(Scan::new(stream, PAGE_SIZE, token))
+    }
 }
 
 impl Backend for PostgresStore {
@@ -104,15 +286,20 @@ impl Backend for PostgresStore {
     fn create_profile(&self, name: Option<String>) -> BoxFuture<'_, Result<String, Error>> {
         let name = name.unwrap_or_else(random_profile_name);
         Box::pin(async move {
-            let key = ProfileKey::new()?;
-            let enc_key = key.to_bytes()?;
+            let store_key = self.key_cache.store_key.clone();
+            let (key, enc_key) = unblock(move || {
+                let key = ProfileKey::new()?;
+                let enc_key = encode_profile_key(&key, &store_key)?;
+                Result::<_, Error>::Ok((key, enc_key))
+            })
+            .await?;
             let mut conn = self.conn_pool.acquire().await?;
             if let Some(pid) = sqlx::query_scalar(
                 "INSERT INTO profiles (name, profile_key) VALUES ($1, $2) 
                 ON CONFLICT DO NOTHING RETURNING id",
             )
             .bind(&name)
-            .bind(enc_key.as_ref())
+            .bind(&enc_key)
             .fetch_optional(&mut conn)
             .await?
             {
@@ -130,18 +317,218 @@ impl Backend for PostgresStore {
         self.default_profile.as_str()
     }
 
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn query_limits(&self) -> TagQueryLimits {
+        self.query_limits
+    }
+
     fn remove_profile(&self, name: String) -> BoxFuture<'_, Result<bool, Error>> {
         Box::pin(async move {
             let mut conn = self.conn_pool.acquire().await?;
-            Ok(sqlx::query("DELETE FROM profiles WHERE name=$1")
+            let removed = sqlx::query("DELETE FROM profiles WHERE name=$1")
                 .bind(&name)
                 .execute(&mut conn)
                 .await?
                 .rows_affected()
-                != 0)
+                != 0;
+            if removed {
+                self.key_cache.remove_profile(&name).await;
+            }
+            Ok(removed)
+        })
+    }
+
+    fn rename_profile(
+        &self,
+        from_name: String,
+        to_name: String,
+    ) -> BoxFuture<'_, Result<bool, Error>> {
+        Box::pin(async move {
+            if from_name == self.default_profile {
+                return Err(err_msg!(Input, "Cannot rename the default profile"));
+            }
+            let mut txn = self.conn_pool.begin().await?;
+            if sqlx::query("SELECT 1 FROM profiles WHERE name=$1")
+                .bind(&to_name)
+                .fetch_optional(&mut txn)
+                .await?
+                .is_some()
+            {
+                return Err(err_msg!(Duplicate, "Duplicate profile name"));
+            }
+            let renamed = sqlx::query("UPDATE profiles SET name=$2 WHERE name=$1")
+                .bind(&from_name)
+                .bind(&to_name)
+                .execute(&mut txn)
+                .await?
+                .rows_affected()
+                != 0;
+            txn.commit().await?;
+            if renamed {
+                self.key_cache.remove_profile(&from_name).await;
+            }
+            Ok(renamed)
+        })
+    }
+
+    fn copy_profile(
+        &self,
+        from_name: String,
+        to_name: String,
+    ) -> BoxFuture<'_, Result<bool, Error>> {
+        Box::pin(async move {
+            let mut txn = self.conn_pool.begin().await?;
+            let row = match sqlx::query(
+                "SELECT id, profile_key, rotate_key IS NOT NULL FROM profiles WHERE name=$1",
+            )
+            .bind(&from_name)
+            .fetch_optional(&mut txn)
+            .await?
+            {
+                Some(row) => row,
+                None => return Ok(false),
+            };
+            if row.try_get(2)? {
+                return Err(err_msg!(Busy, "Profile key rotation in progress"));
+            }
+            let from_id: ProfileId = row.try_get(0)?;
+            let profile_key: Vec<u8> = row.try_get(1)?;
+            let to_id: ProfileId = sqlx::query_scalar(
+                "INSERT INTO profiles (name, profile_key) VALUES ($1, $2)
+                ON CONFLICT DO NOTHING RETURNING id",
+            )
+            .bind(&to_name)
+            .bind(&profile_key)
+            .fetch_optional(&mut txn)
+            .await?
+            .ok_or_else(|| err_msg!(Duplicate, "Duplicate profile name"))?;
+            sqlx::query(COPY_ITEMS_QUERY)
+                .bind(from_id)
+                .bind(to_id)
+                .execute(&mut txn)
+                .await?;
+            sqlx::query(COPY_TAGS_QUERY)
+                .bind(from_id)
+                .bind(to_id)
+                .execute(&mut txn)
+                .await?;
+            txn.commit().await?;
+            Ok(true)
+        })
+    }
+
+    fn rotate_profile_key(&self, name: String) -> BoxFuture<'_, Result<i64, Error>> {
+        Box::pin(rotate_profile_key(
+            &self.conn_pool,
+            &self.key_cache,
+            &ROTATE_QUERIES,
+            decode_enc_row,
+            name,
+        ))
+    }
+
+    fn load_profile_key(&self, name: String) -> BoxFuture<'_, Result<SecretBytes, Error>> {
+        Box::pin(async move {
+            let mut session = self.session(Some(name), false)?;
+            let (_, key) = acquire_key(&mut session).await?;
+            key.to_bytes()
+        })
+    }
+
+    fn import_profile(&self, name: String, key: SecretBytes) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            let key = Arc::new(ProfileKey::from_slice(&key)?);
+            let enc_key = unblock({
+                let key = key.clone();
+                let store_key = self.key_cache.store_key.clone();
+                move || encode_profile_key(&key, &store_key)
+            })
+            .await?;
+            let mut conn = self.conn_pool.acquire().await?;
+            sqlx::query(IMPORT_PROFILE_QUERY)
+                .bind(&name)
+                .bind(&enc_key)
+                .execute(&mut conn)
+                .await?;
+            let row = sqlx::query("SELECT id, profile_key FROM profiles WHERE name=$1")
+                .bind(&name)
+                .fetch_one(&mut conn)
+                .await?;
+            let pid = row.try_get(0)?;
+            let found_key: Vec<u8> = row.try_get(1)?;
+            // the existing profile key was retained if the profile has records
+            if found_key != enc_key && self.key_cache.load_key(found_key).await? != *key {
+                return Err(err_msg!(
+                    Duplicate,
+                    "Profile exists with a different profile key"
+                ));
+            }
+            self.key_cache.add_profile(name, pid, key).await;
+            Ok(())
+        })
+    }
+
+    fn list_profiles(&self) -> BoxFuture<'_, Result<Vec<String>, Error>> {
+        Box::pin(async move {
+            let mut conn = self.conn_pool.acquire().await?;
+            Ok(sqlx::query_scalar("SELECT name FROM profiles ORDER BY id")
+                .fetch_all(&mut conn)
+                .await?)
+        })
+    }
+
+    fn list_categories(
+        &self,
+        profile: Option<String>,
+        kind: EntryKind,
+    ) -> BoxFuture<'_, Result<Vec<String>, Error>> {
+        Box::pin(async move {
+            let mut session = self.session(profile, false)?;
+            let (profile_id, key) = acquire_key(&mut session).await?;
+            let mut active = acquire_session(&mut session).await?;
+            let enc_categories: Vec<Vec<u8>> = sqlx::query_scalar(CATEGORIES_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .fetch_all(active.connection_mut())
+                .await?;
+            unblock(move || {
+                enc_categories
+                    .into_iter()
+                    .map(|enc_category| key.decrypt_entry_category(enc_category))
+                    .collect()
+            })
+            .await
+        })
+    }
+
+    fn purge_expired(&self) -> BoxFuture<'_, Result<BTreeMap<String, i64>, Error>> {
+        Box::pin(async move {
+            let mut conn = self.conn_pool.acquire().await?;
+            let removed: Vec<(String, i64)> =
+                sqlx::query_as(PURGE_QUERY).fetch_all(&mut conn).await?;
+            Ok(removed.into_iter().collect())
         })
     }
 
+    fn verify(
+        &self,
+        profile: Option<String>,
+        repair: bool,
+    ) -> BoxFuture<'_, Result<VerifyReport, Error>> {
+        Box::pin(verify_store(
+            &self.conn_pool,
+            &self.key_cache,
+            &self.default_profile,
+            &VERIFY_QUERIES,
+            decode_enc_row,
+            profile,
+            repair,
+        ))
+    }
+
     fn rekey_backend(
         &mut self,
         method: StoreKeyMethod,
@@ -152,24 +539,38 @@ impl Backend for PostgresStore {
             let (store_key, store_key_ref) = unblock(move || method.resolve(pass_key)).await?;
             let store_key = Arc::new(store_key);
             let mut txn = self.conn_pool.begin().await?;
-            let mut rows = sqlx::query("SELECT id, profile_key FROM profiles").fetch(&mut txn);
-            let mut upd_keys = BTreeMap::<ProfileId, Vec<u8>>::new();
+            let mut rows =
+                sqlx::query("SELECT id, profile_key, rotate_key FROM profiles").fetch(&mut txn);
+            let mut upd_keys = BTreeMap::<ProfileId, (Vec<u8>, Option<Vec<u8>>)>::new();
             while let Some(row) = rows.next().await {
                 let row = row?;
                 let pid = row.try_get(0)?;
                 let enc_key = row.try_get(1)?;
                 let profile_key = self.key_cache.load_key(enc_key).await?;
-                let upd_key = unblock({
+                // the pending key of an interrupted rotation is also re-wrapped
+                let rotate_key = match row.try_get::<Option<Vec<u8>>, _>(2)? {
+                    Some(enc_key) => Some(self.key_cache.load_key(enc_key).await?),
+                    None => None,
+                };
+                let upd_keys_row = unblock({
                     let store_key = store_key.clone();
-                    move || encode_profile_key(&profile_key, &store_key)
+                    move || {
+                        Result::<_, Error>::Ok((
+                            encode_profile_key(&profile_key, &store_key)?,
+                            rotate_key
+                                .map(|key| encode_profile_key(&key, &store_key))
+                                .transpose()?,
+                        ))
+                    }
                 })
                 .await?;
-                upd_keys.insert(pid, upd_key);
+                upd_keys.insert(pid, upd_keys_row);
             }
             drop(rows);
-            for (pid, key) in upd_keys {
-                if sqlx::query("UPDATE profiles SET profile_key=$1 WHERE id=$2")
+            for (pid, (key, rotate_key)) in upd_keys {
+                if sqlx::query("UPDATE profiles SET profile_key=$1, rotate_key=$2 WHERE id=$3")
                     .bind(key)
+                    .bind(rotate_key)
                     .bind(pid)
                     .execute(&mut txn)
                     .await?
@@ -200,30 +601,36 @@ impl Backend for PostgresStore {
         kind: EntryKind,
         category: String,
         tag_filter: Option<TagFilter>,
+        order_by: Option<OrderBy>,
         offset: Option<i64>,
         limit: Option<i64>,
     ) -> BoxFuture<'_, Result<Scan<'static, Entry>, Error>> {
         Box::pin(async move {
-            let session = self.session(profile, false)?;
-            let mut active = session.owned_ref();
-            let (profile_id, key) = acquire_key(&mut *active).await?;
-            let scan = perform_scan(
-                active,
-                profile_id,
-                key.clone(),
+            let profile = profile.unwrap_or_else(|| self.default_profile.clone());
+            let cursor = ScanCursor::new(
+                profile,
                 kind,
-                category.clone(),
-                tag_filter,
+                category,
+                tag_filter.as_ref(),
+                order_by.as_ref(),
                 offset,
-                limit,
-                false,
-            );
-            let stream = scan.then(move |enc_rows| {
-                let category = category.clone();
-                let key = key.clone();
-                unblock(move || decrypt_scan_batch(category, enc_rows?, &key))
-            });
-            Ok(Scan::new(stream, PAGE_SIZE))
+            )?;
+            self.start_scan(cursor, tag_filter, None, offset, limit)
+                .await
+        })
+    }
+
+    fn resume_scan(
+        &self,
+        token: String,
+        limit: Option<i64>,
+    ) -> BoxFuture<'_, Result<Scan<'static, Entry>, Error>> {
+        Box::pin(async move {
+            let cursor = ScanCursor::from_token(&token, &self.key_cache.store_key)?;
+            let tag_filter = cursor.tag_filter()?;
+            let offset = cursor.resume_offset();
+            self.start_scan(cursor, tag_filter, Some(token), offset, limit)
+                .await
         })
     }
 
@@ -236,6 +643,73 @@ impl Backend for PostgresStore {
         ))
     }
 
+    fn snapshot_session(&self, profile: Option<String>) -> Result<Self::Session, Error> {
+        Ok(DbSession::snapshot(
+            self.conn_pool.clone(),
+            self.key_cache.clone(),
+            profile.unwrap_or_else(|| self.default_profile.clone()),
+        ))
+    }
+
+    fn publishes_changes(&self) -> bool {
+        self.notify
+    }
+
+    fn listen_changes(&self, registry: Weak<WatchRegistry>) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            if !self.notify {
+                return Err(err_msg!(
+                    Unsupported,
+                    "Change notifications are not enabled for this store"
+                ));
+            }
+            let closed = match registry.upgrade() {
+                Some(registry) => registry.closed(),
+                None => return Ok(()),
+            };
+            let channel: String = sqlx::query_scalar(CHANGES_CHANNEL_QUERY)
+                .bind(CHANGES_CHANNEL)
+                .fetch_one(&self.conn_pool)
+                .await?;
+            let mut listener = PgListener::connect_with(&self.conn_pool).await?;
+            listener.listen(&channel).await?;
+            let conn_pool = self.conn_pool.clone();
+            let key_cache = self.key_cache.clone();
+            spawn_ok(async move {
+                let mut profiles = HashMap::new();
+                loop {
+                    let notification =
+                        future::or(async { listener.recv().await.map(Some) }, async {
+                            closed.notified().await;
+                            Ok(None)
+                        })
+                        .await;
+                    let payload = match notification {
+                        Ok(Some(notification)) => notification.payload().to_string(),
+                        Ok(None) | Err(SqlxError::PoolClosed) => break,
+                        Err(err) => {
+                            warn!("Error receiving change notification: {}", err);
+                            break;
+                        }
+                    };
+                    let registry = match registry.upgrade() {
+                        Some(registry) => registry,
+                        None => break,
+                    };
+                    match decode_change(&conn_pool, &key_cache, &mut profiles, &payload).await {
+                        Ok(Some((profile, change))) => registry.publish(&profile, &[change]),
+                        Ok(None) => (),
+                        Err(err) => warn!("Error decoding change notification: {}", err),
+                    }
+                }
+                if let Some(registry) = registry.upgrade() {
+                    registry.stop_listening();
+                }
+            });
+            Ok(())
+        })
+    }
+
     fn close(&self) -> BoxFuture<'_, Result<(), Error>> {
         Box::pin(async move {
             self.conn_pool.close().await;
@@ -250,6 +724,8 @@ impl Debug for PostgresStore {
             .field("default_profile", &self.default_profile)
             .field("host", &self.host)
             .field("name", &self.name)
+            .field("schema", &self.schema)
+            .field("read_only", &self.read_only)
             .finish()
     }
 }
@@ -289,6 +765,71 @@ impl QueryBackend for DbSession<Postgres> {
         })
     }
 
+    fn category_stats(
+        &mut self,
+        kind: EntryKind,
+    ) -> BoxFuture<'_, Result<BTreeMap<String, CategoryStats>, Error>> {
+        Box::pin(async move {
+            let (profile_id, key) = acquire_key(&mut *self).await?;
+            let mut active = acquire_session(&mut *self).await?;
+            let rows: Vec<(Vec<u8>, i64, i64)> = sqlx::query_as(CATEGORY_STATS_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .fetch_all(active.connection_mut())
+                .await?;
+            unblock(move || {
+                rows.into_iter()
+                    .map(|(enc_category, count, size)| {
+                        Ok((
+                            key.decrypt_entry_category(enc_category)?,
+                            CategoryStats { count, size },
+                        ))
+                    })
+                    .collect()
+            })
+            .await
+        })
+    }
+
+    fn tag_names<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+    ) -> BoxFuture<'q, Result<Vec<TagNameStats>, Error>> {
+        let category = ProfileKey::prepare_input(category.as_bytes());
+
+        Box::pin(async move {
+            let (profile_id, key) = acquire_key(&mut *self).await?;
+            let enc_category = unblock({
+                let key = key.clone();
+                move || key.encrypt_entry_category(category)
+            })
+            .await?;
+            let mut active = acquire_session(&mut *self).await?;
+            let rows: Vec<(Vec<u8>, i16, i64)> = sqlx::query_as(TAG_NAMES_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .bind(enc_category)
+                .fetch_all(active.connection_mut())
+                .await?;
+            unblock(move || {
+                let mut names = rows
+                    .into_iter()
+                    .map(|(enc_name, plaintext, count)| {
+                        Ok(TagNameStats {
+                            name: key.decrypt_entry_tag_name(enc_name)?,
+                            plaintext: plaintext != 0,
+                            count,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                names.sort();
+                Ok(names)
+            })
+            .await
+        })
+    }
+
     fn fetch(
         &mut self,
         kind: EntryKind,
@@ -328,6 +869,10 @@ impl QueryBackend for DbSession<Postgres> {
             {
                 let value = row.try_get(1)?;
                 let tags = row.try_get::<Option<String>, _>(2)?.map(String::into_bytes);
+                let expiry = row.try_get(3)?;
+                let version = row.try_get(4)?;
+                let created = row.try_get(5)?;
+                let updated = row.try_get(6)?;
                 let (category, name, value, tags) = unblock(move || {
                     let value = key.decrypt_entry_value(category.as_ref(), name.as_ref(), value)?;
                     let tags = if let Some(enc_tags) = tags {
@@ -341,7 +886,12 @@ impl QueryBackend for DbSession<Postgres> {
                     Result::<_, Error>::Ok((category, name, value, tags))
                 })
                 .await?;
-                Ok(Some(Entry::new(category, name, value, tags)))
+                Ok(Some(
+                    Entry::new(category, name, value, tags)
+                        .with_expiry(expiry)
+                        .with_version(version)
+                        .with_timestamps(created, updated),
+                ))
             } else {
                 Ok(None)
             }
@@ -353,6 +903,7 @@ impl QueryBackend for DbSession<Postgres> {
         kind: EntryKind,
         category: &'q str,
         tag_filter: Option<TagFilter>,
+        order_by: Option<OrderBy>,
         limit: Option<i64>,
         for_update: bool,
     ) -> BoxFuture<'q, Result<Vec<Entry>, Error>> {
@@ -368,6 +919,8 @@ impl QueryBackend for DbSession<Postgres> {
                 kind,
                 category.clone(),
                 tag_filter,
+                order_by,
+                0,
                 None,
                 limit,
                 for_update,
@@ -418,11 +971,26 @@ impl QueryBackend for DbSession<Postgres> {
             )?;
 
             let mut active = acquire_session(&mut *self).await?;
-            let removed = sqlx::query_with(query.as_str(), params)
-                .execute(active.connection_mut())
-                .await?
-                .rows_affected();
-            Ok(removed as i64)
+            let mut txn = active.as_transaction().await?;
+            let result = async {
+                check_profile_key(&mut txn, PROFILE_KEY_CHECK_QUERY).await?;
+                let removed = sqlx::query_with(query.as_str(), params)
+                    .execute(txn.connection_mut())
+                    .await?
+                    .rows_affected();
+                Result::<_, Error>::Ok(removed as i64)
+            }
+            .await;
+            match result {
+                Ok(removed) => {
+                    txn.commit().await?;
+                    Ok(removed)
+                }
+                Err(err) => {
+                    txn.rollback().await?;
+                    Err(err)
+                }
+            }
         })
     }
 
@@ -460,18 +1028,31 @@ impl QueryBackend for DbSession<Postgres> {
                     .await?;
                     let mut active = acquire_session(&mut *self).await?;
                     let mut txn = active.as_transaction().await?;
-                    perform_insert(
-                        &mut txn,
-                        kind,
-                        &enc_category,
-                        &enc_name,
-                        &enc_value,
-                        enc_tags,
-                        expiry_ms,
-                    )
-                    .await?;
-                    txn.commit().await?;
-                    Ok(())
+                    let result = async {
+                        check_profile_key(&mut txn, PROFILE_KEY_CHECK_QUERY).await?;
+                        let now = chrono::Utc::now();
+                        perform_insert(
+                            &mut txn,
+                            kind,
+                            &enc_category,
+                            &enc_name,
+                            &enc_value,
+                            enc_tags,
+                            expiry_ms,
+                            1,
+                            Some(now),
+                            now,
+                        )
+                        .await
+                    }
+                    .await;
+                    match result {
+                        Ok(()) => txn.commit().await,
+                        Err(err) => {
+                            txn.rollback().await?;
+                            Err(err)
+                        }
+                    }
                 })
             }
             EntryOperation::Replace => {
@@ -495,19 +1076,33 @@ impl QueryBackend for DbSession<Postgres> {
 
                     let mut active = acquire_session(&mut *self).await?;
                     let mut txn = active.as_transaction().await?;
-                    perform_remove(&mut txn, kind, &enc_category, &enc_name, false).await?;
-                    perform_insert(
-                        &mut txn,
-                        kind,
-                        &enc_category,
-                        &enc_name,
-                        &enc_value,
-                        enc_tags,
-                        expiry_ms,
-                    )
-                    .await?;
-                    txn.commit().await?;
-                    Ok(())
+                    let result = async {
+                        check_profile_key(&mut txn, PROFILE_KEY_CHECK_QUERY).await?;
+                        let (version, created) =
+                            perform_remove_version(&mut txn, kind, &enc_category, &enc_name, None)
+                                .await?;
+                        perform_insert(
+                            &mut txn,
+                            kind,
+                            &enc_category,
+                            &enc_name,
+                            &enc_value,
+                            enc_tags,
+                            expiry_ms,
+                            version + 1,
+                            created.map(millis_timestamp).transpose()?,
+                            chrono::Utc::now(),
+                        )
+                        .await
+                    }
+                    .await;
+                    match result {
+                        Ok(()) => txn.commit().await,
+                        Err(err) => {
+                            txn.rollback().await?;
+                            Err(err)
+                        }
+                    }
                 })
             }
 
@@ -521,17 +1116,258 @@ impl QueryBackend for DbSession<Postgres> {
                 })
                 .await?;
                 let mut active = acquire_session(&mut *self).await?;
-                Ok(perform_remove(&mut active, kind, &enc_category, &enc_name, false).await?)
+                let mut txn = active.as_transaction().await?;
+                let result = async {
+                    check_profile_key(&mut txn, PROFILE_KEY_CHECK_QUERY).await?;
+                    perform_remove(&mut txn, kind, &enc_category, &enc_name, false).await
+                }
+                .await;
+                match result {
+                    Ok(()) => txn.commit().await,
+                    Err(err) => {
+                        txn.rollback().await?;
+                        Err(err)
+                    }
+                }
             }),
         }
     }
 
+    fn update_tags<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+        add: &'q [EntryTag],
+        remove: &'q [&'q str],
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        let category = ProfileKey::prepare_input(category.as_bytes());
+        let name = ProfileKey::prepare_input(name.as_bytes());
+        let add = prepare_tags(add);
+        let remove = remove.iter().map(|name| name.to_string()).collect();
+
+        Box::pin(async move {
+            let (_, key) = acquire_key(&mut *self).await?;
+            let (enc_category, enc_name, (enc_add, enc_remove)) = unblock(move || {
+                Result::<_, Error>::Ok((
+                    key.encrypt_entry_category(category)?,
+                    key.encrypt_entry_name(name)?,
+                    encrypt_tag_update(&key, add?, remove)?,
+                ))
+            })
+            .await?;
+            let mut active = acquire_session(&mut *self).await?;
+            // use a savepoint within an existing transaction so that
+            // failed tag changes can be rolled back on their own
+            let mut txn = if active.is_transaction() {
+                active.transaction().await?
+            } else {
+                active.as_transaction().await?
+            };
+            match perform_update_tags(
+                &mut txn,
+                kind,
+                &enc_category,
+                &enc_name,
+                enc_add,
+                enc_remove,
+            )
+            .await
+            {
+                Ok(()) => txn.commit().await,
+                Err(err) => {
+                    txn.rollback().await?;
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    fn set_expiry<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        let category = ProfileKey::prepare_input(category.as_bytes());
+        let name = ProfileKey::prepare_input(name.as_bytes());
+
+        Box::pin(async move {
+            let (_, key) = acquire_key(&mut *self).await?;
+            let (enc_category, enc_name) = unblock(move || {
+                Result::<_, Error>::Ok((
+                    key.encrypt_entry_category(category)?,
+                    key.encrypt_entry_name(name)?,
+                ))
+            })
+            .await?;
+            let expiry = expiry_ms.map(expiry_timestamp).transpose()?;
+            let mut active = acquire_session(&mut *self).await?;
+            let mut txn = active.as_transaction().await?;
+            let result = async {
+                check_profile_key(&mut txn, PROFILE_KEY_CHECK_QUERY).await?;
+                trace!("Update entry expiry");
+                let done = sqlx::query(SET_EXPIRY_QUERY)
+                    .bind(txn.profile_id)
+                    .bind(kind as i16)
+                    .bind(enc_category)
+                    .bind(enc_name)
+                    .bind(expiry)
+                    .bind(chrono::Utc::now())
+                    .execute(txn.connection_mut())
+                    .await?;
+                if done.rows_affected() == 0 {
+                    Err(err_msg!(NotFound, "Entry not found"))
+                } else {
+                    Ok(())
+                }
+            }
+            .await;
+            match result {
+                Ok(()) => txn.commit().await,
+                Err(err) => {
+                    txn.rollback().await?;
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    fn set_expiry_all<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        tag_filter: Option<TagFilter>,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<i64, Error>> {
+        let category = ProfileKey::prepare_input(category.as_bytes());
+
+        Box::pin(async move {
+            let (profile_id, key) = acquire_key(&mut *self).await?;
+            let mut params = QueryParams::new();
+            params.push(profile_id);
+            params.push(kind as i16);
+            let (enc_category, tag_filter) = unblock({
+                let params_len = params.len() + 3; // plus category, expiry and updated
+                move || {
+                    Result::<_, Error>::Ok((
+                        key.encrypt_entry_category(category)?,
+                        encode_tag_filter::<PostgresStore>(tag_filter, &key, params_len)?,
+                    ))
+                }
+            })
+            .await?;
+            params.push(enc_category);
+            params.push(expiry_ms.map(expiry_timestamp).transpose()?);
+            params.push(chrono::Utc::now());
+            let query = extend_query::<PostgresStore>(
+                SET_EXPIRY_ALL_QUERY,
+                &mut params,
+                tag_filter,
+                None,
+                None,
+            )?;
+
+            let mut active = acquire_session(&mut *self).await?;
+            let mut txn = active.as_transaction().await?;
+            let result = async {
+                check_profile_key(&mut txn, PROFILE_KEY_CHECK_QUERY).await?;
+                let updated = sqlx::query_with(query.as_str(), params)
+                    .execute(txn.connection_mut())
+                    .await?
+                    .rows_affected();
+                Result::<_, Error>::Ok(updated as i64)
+            }
+            .await;
+            match result {
+                Ok(updated) => {
+                    txn.commit().await?;
+                    Ok(updated)
+                }
+                Err(err) => {
+                    txn.rollback().await?;
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    fn apply_batch<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        updates: Vec<EntryUpdate>,
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        Box::pin(async move {
+            let (_, key) = acquire_key(&mut *self).await?;
+            let updates = encrypt_entry_updates(key, updates).await?;
+            let mut active = acquire_session(&mut *self).await?;
+            // use a savepoint within an existing transaction so that
+            // a failed batch can be rolled back on its own
+            let mut txn = if active.is_transaction() {
+                active.transaction().await?
+            } else {
+                active.as_transaction().await?
+            };
+            match perform_batch(&mut txn, kind, updates).await {
+                Ok(()) => txn.commit().await,
+                Err(err) => {
+                    txn.rollback().await?;
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    fn publish_changes<'q>(
+        &'q mut self,
+        changes: &'q [EntryChange],
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        Box::pin(async move {
+            let (profile_id, key) = acquire_key(&mut *self).await?;
+            let payloads = unblock({
+                let changes = changes.to_vec();
+                move || {
+                    changes
+                        .into_iter()
+                        .map(|change| encode_change(profile_id, &key, change))
+                        .collect::<Result<Vec<_>, Error>>()
+                }
+            })
+            .await?;
+            let mut active = acquire_session(&mut *self).await?;
+            for payload in payloads {
+                sqlx::query(CHANGES_NOTIFY_QUERY)
+                    .bind(CHANGES_CHANNEL)
+                    .bind(payload)
+                    .execute(active.connection_mut())
+                    .await?;
+            }
+            Ok(())
+        })
+    }
+
     fn close(self, commit: bool) -> BoxFuture<'static, Result<(), Error>> {
         Box::pin(DbSession::close(self, commit))
     }
 }
 
-impl ExtDatabase for Postgres {}
+impl ExtDatabase for Postgres {
+    fn start_snapshot(conn: &mut PoolConnection<Self>) -> BoxFuture<'_, Result<(), SqlxError>> {
+        // each statement in a read-committed transaction sees a new snapshot
+        Box::pin(async move {
+            <Postgres as Database>::TransactionManager::begin(&mut *conn).await?;
+            sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+                .execute(conn)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn rows_affected(result: &<Self as Database>::QueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
 
 impl QueryPrepare for PostgresStore {
     type DB = Postgres;
@@ -540,6 +1376,25 @@ impl QueryPrepare for PostgresStore {
         format!("${}", index)
     }
 
+    fn numeric_value(expr: &str) -> String {
+        // tag values are stored as bytea
+        format!("CAST(convert_from({}, 'UTF8') AS NUMERIC)", expr)
+    }
+
+    fn is_numeric(expr: &str) -> String {
+        format!(
+            "encode({}, 'escape') ~ '^[+-]?([0-9]+([.][0-9]*)?|[.][0-9]+)([eE][+-]?[0-9]+)?$'",
+            expr
+        )
+    }
+
+    fn is_date(expr: &str) -> String {
+        format!(
+            "encode({}, 'escape') ~ '^[0-9]{{4}}-[0-1][0-9]-[0-3][0-9]T[0-2][0-9]:[0-5][0-9]:[0-6][0-9]Z$'",
+            expr
+        )
+    }
+
     fn limit_query<'q>(
         mut query: String,
         args: &mut QueryParams<'q, Self::DB>,
@@ -560,6 +1415,24 @@ impl QueryPrepare for PostgresStore {
     }
 }
 
+/// Decode the id, category, name, value and tags of an encrypted record
+fn decode_enc_row(row: &PgRow) -> Result<(i64, EncEntry), Error> {
+    let tags = row
+        .try_get::<Option<String>, _>(4)?
+        .map(String::into_bytes)
+        .unwrap_or_default();
+    let tags = decode_tags(tags).map_err(|_| err_msg!(Unexpected, "Error decoding entry tags"))?;
+    Ok((
+        row.try_get(0)?,
+        EncEntry {
+            category: row.try_get(1)?,
+            name: row.try_get(2)?,
+            value: row.try_get(3)?,
+            tags,
+        },
+    ))
+}
+
 async fn acquire_key(
     session: &mut DbSession<Postgres>,
 ) -> Result<(ProfileId, Arc<ProfileKey>), Error> {
@@ -585,11 +1458,16 @@ async fn resolve_profile_key(
     if let Some((pid, key)) = cache.get_profile(profile.as_str()).await {
         Ok((pid, key))
     } else {
-        if let Some(row) = sqlx::query("SELECT id, profile_key FROM profiles WHERE name=?1")
-            .bind(profile.as_str())
-            .fetch_optional(conn)
-            .await?
+        if let Some(row) = sqlx::query(
+            "SELECT id, profile_key, rotate_key IS NOT NULL FROM profiles WHERE name=$1",
+        )
+        .bind(profile.as_str())
+        .fetch_optional(conn)
+        .await?
         {
+            if row.try_get(2)? {
+                return Err(err_msg!(Busy, "Profile key rotation in progress"));
+            }
             let pid = row.try_get(0)?;
             let key = Arc::new(cache.load_key(row.try_get(1)?).await?);
             cache.add_profile(profile, pid, key.clone()).await;
@@ -600,6 +1478,123 @@ async fn resolve_profile_key(
     }
 }
 
+fn encode_change(
+    profile_id: ProfileId,
+    key: &ProfileKey,
+    change: EntryChange,
+) -> Result<String, Error> {
+    let op = match change.operation {
+        EntryOperation::Insert => 0,
+        EntryOperation::Replace => 1,
+        EntryOperation::Remove => 2,
+    };
+    let enc_category = key.encrypt_entry_category(change.category.into())?;
+    let enc_name = key.encrypt_entry_name(change.name.into())?;
+    let mut payload = format!(
+        "{}:{}:{}:{}",
+        profile_id,
+        op,
+        hex::encode(&enc_category),
+        hex::encode(&enc_name)
+    );
+    if let Some(tags) = change.tags {
+        let enc_tags = key
+            .encrypt_entry_tags(tags)?
+            .into_iter()
+            .map(|tag| {
+                format!(
+                    "{}:{}:{}",
+                    if tag.plaintext { 1 } else { 0 },
+                    hex::encode(&tag.name),
+                    hex::encode(&tag.value)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        // tags are left out when the payload would exceed the size limit,
+        // in which case the change is reported to all watchers of the category
+        if payload.len() + enc_tags.len() < CHANGES_PAYLOAD_MAX {
+            payload.push(':');
+            payload.push_str(&enc_tags);
+        }
+    }
+    Ok(payload)
+}
+
+async fn decode_change(
+    conn_pool: &PgPool,
+    key_cache: &KeyCache,
+    profiles: &mut HashMap<ProfileId, (String, Arc<ProfileKey>)>,
+    payload: &str,
+) -> Result<Option<(String, EntryChange)>, Error> {
+    let mut parts = payload.splitn(5, ':');
+    let profile_id: ProfileId = parts
+        .next()
+        .and_then(|pid| pid.parse().ok())
+        .ok_or_else(|| err_msg!(Unexpected, "Invalid change notification"))?;
+    let operation = match parts.next() {
+        Some("0") => EntryOperation::Insert,
+        Some("1") => EntryOperation::Replace,
+        Some("2") => EntryOperation::Remove,
+        _ => return Err(err_msg!(Unexpected, "Invalid change notification")),
+    };
+    let mut decode_hex = || {
+        parts
+            .next()
+            .and_then(|part| hex::decode(part).ok())
+            .ok_or_else(|| err_msg!(Unexpected, "Invalid change notification"))
+    };
+    let enc_category = decode_hex()?;
+    let enc_name = decode_hex()?;
+    let enc_tags = parts
+        .next()
+        .map(|tags| {
+            decode_tags(tags.as_bytes().to_vec())
+                .map_err(|_| err_msg!(Unexpected, "Invalid change notification"))
+        })
+        .transpose()?;
+
+    // the cached profile key is reloaded once, in case the key has been rotated
+    let mut refreshed = false;
+    loop {
+        let (profile, key) = if let Some(cached) = profiles.get(&profile_id) {
+            cached.clone()
+        } else {
+            refreshed = true;
+            if let Some(row) = sqlx::query("SELECT name, profile_key FROM profiles WHERE id = $1")
+                .bind(profile_id)
+                .fetch_optional(conn_pool)
+                .await?
+            {
+                let name: String = row.try_get(0)?;
+                let key = Arc::new(key_cache.load_key(row.try_get(1)?).await?);
+                profiles.insert(profile_id, (name.clone(), key.clone()));
+                (name, key)
+            } else {
+                // the profile has been removed
+                return Ok(None);
+            }
+        };
+        let decrypted = key
+            .decrypt_entry_category(enc_category.clone())
+            .and_then(|category| Ok((category, key.decrypt_entry_name(enc_name.clone())?)))
+            .and_then(|(category, name)| {
+                let tags = enc_tags
+                    .clone()
+                    .map(|tags| key.decrypt_entry_tags(tags))
+                    .transpose()?;
+                Ok(EntryChange::new(&category, &name, operation, tags))
+            });
+        match decrypted {
+            Ok(change) => return Ok(Some((profile, change))),
+            Err(err) if refreshed => return Err(err),
+            Err(_) => {
+                profiles.remove(&profile_id);
+            }
+        }
+    }
+}
+
 async fn perform_insert<'q>(
     active: &mut DbSessionActive<'q, Postgres>,
     kind: EntryKind,
@@ -608,6 +1603,9 @@ async fn perform_insert<'q>(
     enc_value: &[u8],
     enc_tags: Option<Vec<EncEntryTag>>,
     expiry_ms: Option<i64>,
+    version: i64,
+    created: Option<Expiry>,
+    updated: Expiry,
 ) -> Result<(), Error> {
     trace!("Insert entry");
     let row_id: i64 = sqlx::query_scalar(INSERT_QUERY)
@@ -617,23 +1615,142 @@ async fn perform_insert<'q>(
         .bind(enc_name)
         .bind(enc_value)
         .bind(expiry_ms.map(expiry_timestamp).transpose()?)
+        .bind(version)
+        .bind(created)
+        .bind(updated)
         .fetch_optional(active.connection_mut())
         .await?
         .ok_or_else(|| err_msg!(Duplicate, "Duplicate row"))?;
     if let Some(tags) = enc_tags {
-        for tag in tags {
-            sqlx::query(TAG_INSERT_QUERY)
-                .bind(row_id)
-                .bind(&tag.name)
-                .bind(&tag.value)
-                .bind(tag.plaintext as i16)
-                .execute(active.connection_mut())
-                .await?;
+        perform_insert_tags(active, tags.into_iter().map(|tag| (row_id, tag)).collect()).await?;
+    }
+    Ok(())
+}
+
+async fn perform_insert_batch<'q>(
+    active: &mut DbSessionActive<'q, Postgres>,
+    kind: EntryKind,
+    updates: Vec<(EncEntryUpdate, Option<Expiry>)>,
+    updated: Expiry,
+) -> Result<(), Error> {
+    trace!("Insert entry batch");
+    let mut updates = updates.into_iter().peekable();
+    while updates.peek().is_some() {
+        let mut params = QueryParams::new();
+        let mut rows = 0;
+        let mut row_tags = HashMap::new();
+        for (update, created) in updates.by_ref().take(BATCH_ROWS) {
+            params.push(active.profile_id);
+            params.push(kind as i16);
+            params.push(update.category.clone());
+            params.push(update.name.clone());
+            params.push(update.value);
+            params.push(update.expiry_ms.map(expiry_timestamp).transpose()?);
+            params.push(update.version);
+            params.push(created);
+            params.push(updated);
+            if let Some(tags) = update.tags {
+                row_tags.insert((update.category, update.name), tags);
+            }
+            rows += 1;
+        }
+        let query = format!(
+            "{}{}{}",
+            INSERT_BATCH_QUERY,
+            multi_row_values::<PostgresStore>(rows, 9),
+            INSERT_BATCH_SUFFIX
+        );
+        let inserted = sqlx::query_with(query.as_str(), params)
+            .fetch_all(active.connection_mut())
+            .await?;
+        if inserted.len() != rows {
+            return Err(err_msg!(Duplicate, "Duplicate row"));
+        }
+        let mut tags = Vec::new();
+        for row in inserted {
+            let key: (Vec<u8>, Vec<u8>) = (row.try_get(1)?, row.try_get(2)?);
+            if let Some(enc_tags) = row_tags.remove(&key) {
+                let row_id: i64 = row.try_get(0)?;
+                tags.extend(enc_tags.into_iter().map(|tag| (row_id, tag)));
+            }
+        }
+        perform_insert_tags(active, tags).await?;
+    }
+    Ok(())
+}
+
+async fn perform_insert_tags<'q>(
+    active: &mut DbSessionActive<'q, Postgres>,
+    tags: Vec<(i64, EncEntryTag)>,
+) -> Result<(), Error> {
+    let mut tags = tags.into_iter().peekable();
+    while tags.peek().is_some() {
+        let mut params = QueryParams::new();
+        let mut rows = 0;
+        for (row_id, tag) in tags.by_ref().take(BATCH_ROWS) {
+            params.push(row_id);
+            params.push(tag.name);
+            params.push(tag.value);
+            params.push(tag.plaintext as i16);
+            rows += 1;
         }
+        let query = format!(
+            "{}{}",
+            TAG_INSERT_BATCH_QUERY,
+            multi_row_values::<PostgresStore>(rows, 4)
+        );
+        sqlx::query_with(query.as_str(), params)
+            .execute(active.connection_mut())
+            .await?;
     }
     Ok(())
 }
 
+async fn perform_batch<'q>(
+    active: &mut DbSessionActive<'q, Postgres>,
+    kind: EntryKind,
+    updates: Vec<EncEntryUpdate>,
+) -> Result<(), Error> {
+    trace!("Apply batch");
+    check_profile_key(active, PROFILE_KEY_CHECK_QUERY).await?;
+    // inserts are deferred in order to combine them, and must be written
+    // before the corresponding record is removed again
+    let mut pending = Vec::new();
+    let mut pending_keys = HashSet::new();
+    let now = chrono::Utc::now();
+    for mut update in updates {
+        let mut created = Some(now);
+        if update.operation != EntryOperation::Insert {
+            let key = (update.category.clone(), update.name.clone());
+            if pending_keys.contains(&key) {
+                perform_insert_batch(active, kind, pending.split_off(0), now).await?;
+                pending_keys.clear();
+            }
+            if update.operation == EntryOperation::Remove && update.expected_version.is_none() {
+                perform_remove(active, kind, &update.category, &update.name, false).await?;
+                continue;
+            }
+            let (version, prev_created) = perform_remove_version(
+                active,
+                kind,
+                &update.category,
+                &update.name,
+                update.expected_version,
+            )
+            .await?;
+            if update.operation == EntryOperation::Remove {
+                continue;
+            }
+            update.operation = EntryOperation::Insert;
+            update.version = version + 1;
+            created = prev_created.map(millis_timestamp).transpose()?;
+        }
+        pending_keys.insert((update.category.clone(), update.name.clone()));
+        pending.push((update, created));
+    }
+    perform_insert_batch(active, kind, pending, now).await
+}
+
 async fn perform_remove<'q>(
     active: &mut DbSessionActive<'q, Postgres>,
     kind: EntryKind,
@@ -656,6 +1773,77 @@ async fn perform_remove<'q>(
     }
 }
 
+async fn perform_update_tags<'q>(
+    active: &mut DbSessionActive<'q, Postgres>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: &[u8],
+    enc_add: Vec<EncEntryTag>,
+    enc_remove: Vec<(Vec<u8>, bool)>,
+) -> Result<(), Error> {
+    trace!("Update entry tags");
+    check_profile_key(active, PROFILE_KEY_CHECK_QUERY).await?;
+    let row_id: i64 = sqlx::query_scalar(TAG_UPDATE_ITEM_QUERY)
+        .bind(active.profile_id)
+        .bind(kind as i16)
+        .bind(enc_category)
+        .bind(enc_name)
+        .bind(chrono::Utc::now())
+        .fetch_optional(active.connection_mut())
+        .await?
+        .ok_or_else(|| err_msg!(NotFound, "Entry not found"))?;
+    for (tag_name, plaintext) in enc_remove {
+        sqlx::query(TAG_DELETE_QUERY)
+            .bind(row_id)
+            .bind(tag_name)
+            .bind(plaintext as i16)
+            .execute(active.connection_mut())
+            .await?;
+    }
+    // avoid storing duplicate values for the added tags
+    for tag in enc_add.iter() {
+        sqlx::query(TAG_DELETE_VALUE_QUERY)
+            .bind(row_id)
+            .bind(&tag.name)
+            .bind(&tag.value)
+            .bind(tag.plaintext as i16)
+            .execute(active.connection_mut())
+            .await?;
+    }
+    perform_insert_tags(
+        active,
+        enc_add.into_iter().map(|tag| (row_id, tag)).collect(),
+    )
+    .await
+}
+
+/// Remove an entry, checking and returning its current version and creation time
+async fn perform_remove_version<'q>(
+    active: &mut DbSessionActive<'q, Postgres>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: &[u8],
+    expected_version: Option<i64>,
+) -> Result<(i64, Option<i64>), Error> {
+    trace!("Remove entry version");
+    let (row_id, version, created): (i64, i64, Option<i64>) = sqlx::query_as(REMOVE_VERSION_QUERY)
+        .bind(active.profile_id)
+        .bind(kind as i16)
+        .bind(enc_category)
+        .bind(enc_name)
+        .fetch_optional(active.connection_mut())
+        .await?
+        .ok_or_else(|| err_msg!(NotFound, "Entry not found"))?;
+    if matches!(expected_version, Some(expected) if expected != version) {
+        return Err(err_msg!(Conflict, "Entry version mismatch"));
+    }
+    sqlx::query(DELETE_ID_QUERY)
+        .bind(row_id)
+        .execute(active.connection_mut())
+        .await?;
+    Ok((version, created))
+}
+
 fn perform_scan<'q>(
     mut active: DbSessionRef<'q, Postgres>,
     profile_id: ProfileId,
@@ -663,6 +1851,8 @@ fn perform_scan<'q>(
     kind: EntryKind,
     category: String,
     tag_filter: Option<TagFilter>,
+    order_by: Option<OrderBy>,
+    after_id: i64,
     offset: Option<i64>,
     limit: Option<i64>,
     for_update: bool,
@@ -671,19 +1861,25 @@ fn perform_scan<'q>(
         let mut params = QueryParams::new();
         params.push(profile_id);
         params.push(kind as i16);
-        let (enc_category, tag_filter) = unblock({
+        let (enc_category, tag_filter, order_by) = unblock({
             let key = key.clone();
             let category = ProfileKey::prepare_input(category.as_bytes());
-            let params_len = params.len() + 1; // plus category
+            let params_len = params.len() + 2; // plus category and last row ID
             move || {
                 Result::<_, Error>::Ok((
                     key.encrypt_entry_category(category)?,
-                    encode_tag_filter::<PostgresStore>(tag_filter, &key, params_len)?
+                    encode_tag_filter::<PostgresStore>(tag_filter, &key, params_len)?,
+                    encode_order_by(order_by.as_ref(), &key)?,
                 ))
             }
         }).await?;
         params.push(enc_category);
-        let mut query = extend_query::<PostgresStore>(SCAN_QUERY, &mut params, tag_filter, offset, limit)?;
+        params.push(after_id);
+        let mut query = extend_query::<PostgresStore>(SCAN_QUERY, &mut params, tag_filter, None, None)?;
+        query = order_by_query::<PostgresStore>(query, &mut params, order_by);
+        if offset.is_some() || limit.is_some() {
+            query = PostgresStore::limit_query(query, &mut params, offset, limit);
+        }
         if for_update {
             query.push_str(" FOR UPDATE");
         }
@@ -694,7 +1890,9 @@ fn perform_scan<'q>(
         while let Some(row) = rows.try_next().await? {
             let tags = row.try_get::<Option<String>, _>(3)?.map(String::into_bytes).unwrap_or_default();
             batch.push(EncScanEntry {
-                name: row.try_get(1)?, value: row.try_get(2)?, tags
+                id: row.try_get(0)?, name: row.try_get(1)?, value: row.try_get(2)?, tags,
+                expiry: row.try_get(4)?, version: row.try_get(5)?,
+                created: row.try_get(6)?, updated: row.try_get(7)?,
             });
             if batch.len() == PAGE_SIZE {
                 yield batch.split_off(0);
@@ -12,7 +12,7 @@ use crate::{
     backend::db_utils::{init_keys, random_profile_name},
     error::Error,
     future::{block_on, sleep, timeout, unblock},
-    protect::{generate_raw_store_key, KeyCache, StoreKeyMethod},
+    protect::{generate_raw_store_key, KeyCache, PassKey, StoreKeyMethod},
     storage::Store,
 };
 
@@ -21,6 +21,8 @@ use crate::{
 pub struct TestDB {
     inst: Option<Store<PostgresStore>>,
     lock_txn: Option<PgConnection>,
+    path: String,
+    key: PassKey<'static>,
 }
 
 impl TestDB {
@@ -33,8 +35,11 @@ impl TestDB {
         };
 
         let key = generate_raw_store_key(None)?;
-        let (profile_key, enc_profile_key, store_key, store_key_ref) =
-            unblock(|| init_keys(StoreKeyMethod::RawKey, key)).await?;
+        let (profile_key, enc_profile_key, store_key, store_key_ref) = unblock({
+            let key = key.clone();
+            || init_keys(StoreKeyMethod::RawKey, key)
+        })
+        .await?;
         let default_profile = random_profile_name();
 
         let opts = PostgresStoreOptions::new(path.as_str())?;
@@ -74,13 +79,28 @@ impl TestDB {
             key_cache,
             opts.host,
             opts.name,
+            opts.schema,
+            opts.notify,
+            false,
+            opts.query_limits,
         ));
 
         Ok(TestDB {
             inst: Some(inst),
             lock_txn: Some(lock_txn),
+            path,
+            key,
         })
     }
+
+    /// Open another instance of the test database which publishes record
+    /// changes through the database.
+    pub async fn open_notify(&self) -> Result<Store<PostgresStore>, Error> {
+        let mut opts = PostgresStoreOptions::new(self.path.as_str())?;
+        opts.notify = true;
+        opts.open(Some(StoreKeyMethod::RawKey), self.key.as_ref(), None)
+            .await
+    }
 }
 
 impl std::ops::Deref for TestDB {
@@ -93,13 +113,24 @@ impl std::ops::Deref for TestDB {
 
 impl Drop for TestDB {
     fn drop(&mut self) {
-        if let Some(lock_txn) = self.lock_txn.take() {
-            block_on(lock_txn.close()).expect("Error closing database connection");
-        }
-        if let Some(inst) = self.inst.take() {
-            block_on(timeout(Duration::from_secs(30), inst.close()))
-                .expect("Timed out waiting for the pool connection to close")
-                .expect("Error closing connection pool");
+        let inst = self.inst.take();
+        let lock_txn = self.lock_txn.take();
+        // the instance is usually dropped within `block_on`, which cannot be
+        // nested, so the connections are closed from a separate thread
+        let closed = std::thread::spawn(move || {
+            if let Some(inst) = inst {
+                block_on(timeout(Duration::from_secs(30), inst.close()))
+                    .expect("Timed out waiting for the pool connection to close")
+                    .expect("Error closing connection pool");
+            }
+            // release the lock once the store has been closed
+            if let Some(lock_txn) = lock_txn {
+                block_on(lock_txn.close()).expect("Error closing database connection");
+            }
+        })
+        .join();
+        if closed.is_err() && !std::thread::panicking() {
+            panic!("Error closing test database");
         }
     }
 }
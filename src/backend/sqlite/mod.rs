@@ -1,6 +1,6 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::{self, Debug, Formatter};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 
 use async_stream::try_stream;
 use futures_lite::{
@@ -10,24 +10,34 @@ use futures_lite::{
 
 use sqlx::{
     pool::PoolConnection,
-    sqlite::{Sqlite, SqlitePool},
+    sqlite::{Sqlite, SqlitePool, SqliteRow},
     Database, Error as SqlxError, Row, TransactionManager,
 };
 
 use crate::{
     backend::{
         db_utils::{
-            decode_tags, decrypt_scan_batch, encode_profile_key, encode_tag_filter,
-            expiry_timestamp, extend_query, prepare_tags, random_profile_name, DbSession,
-            DbSessionActive, DbSessionRef, EncScanEntry, ExtDatabase, QueryParams, QueryPrepare,
-            PAGE_SIZE,
+            check_profile_key, decode_tags, decrypt_scan_batch, decrypt_scan_page, encode_order_by,
+            encode_profile_key, encode_tag_filter, expiry_timestamp, extend_query,
+            millis_timestamp, multi_row_values, order_by_query, prepare_tags, random_profile_name,
+            rotate_profile_key, verify_store, DbSession, DbSessionActive, DbSessionRef,
+            EncScanEntry, Expiry, ExtDatabase, QueryParams, QueryPrepare, RotateQueries,
+            VerifyQueries, BATCH_ROWS, PAGE_SIZE,
         },
         types::{Backend, QueryBackend},
     },
+    crypto::buffer::SecretBytes,
     error::Error,
     future::{unblock, BoxFuture},
-    protect::{EntryEncryptor, KeyCache, PassKey, ProfileId, ProfileKey, StoreKeyMethod},
-    storage::{EncEntryTag, Entry, EntryKind, EntryOperation, EntryTag, Scan, TagFilter},
+    protect::{
+        encrypt_entry_updates, encrypt_tag_update, EntryEncryptor, KeyCache, PassKey, ProfileId,
+        ProfileKey, StoreKeyMethod,
+    },
+    storage::{
+        CategoryStats, EncEntry, EncEntryTag, EncEntryUpdate, Entry, EntryChange, EntryKind,
+        EntryOperation, EntryTag, EntryUpdate, OrderBy, Scan, ScanCursor, Store, TagFilter,
+        TagNameStats, TagQueryLimits, VerifyReport, WatchRegistry,
+    },
 };
 
 mod provision;
@@ -35,27 +45,138 @@ pub use provision::SqliteStoreOptions;
 
 const COUNT_QUERY: &'static str = "SELECT COUNT(*) FROM items i
     WHERE profile_id = ?1 AND kind = ?2 AND category = ?3
-    AND (expiry IS NULL OR expiry > DATETIME('now'))";
+    AND (expiry IS NULL OR JULIANDAY(expiry) > JULIANDAY('now'))";
+const IMPORT_PROFILE_QUERY: &'static str = "INSERT INTO profiles (name, profile_key)
+    VALUES (?1, ?2) ON CONFLICT (name) DO UPDATE SET profile_key = excluded.profile_key
+    WHERE NOT EXISTS (SELECT 1 FROM items WHERE items.profile_id = profiles.id)";
+const CATEGORIES_QUERY: &'static str = "SELECT DISTINCT category FROM items
+    WHERE profile_id = ?1 AND kind = ?2
+    AND (expiry IS NULL OR JULIANDAY(expiry) > JULIANDAY('now'))";
+const CATEGORY_STATS_QUERY: &'static str = "SELECT category, COUNT(*),
+    CAST(SUM(LENGTH(name) + LENGTH(value) + COALESCE((SELECT SUM(LENGTH(it.name) + LENGTH(it.value))
+        FROM items_tags it WHERE it.item_id = i.id), 0)) AS BIGINT)
+    FROM items i WHERE profile_id = ?1 AND kind = ?2
+    AND (expiry IS NULL OR JULIANDAY(expiry) > JULIANDAY('now'))
+    GROUP BY category";
+const COPY_ITEMS_QUERY: &'static str =
+    "INSERT INTO items (profile_id, kind, category, name, value, expiry, version,
+        created, updated)
+    SELECT ?2, kind, category, name, value, expiry, version, created, updated FROM items
+    WHERE profile_id = ?1 AND (expiry IS NULL OR JULIANDAY(expiry) > JULIANDAY('now'))";
+const COPY_TAGS_QUERY: &'static str = "INSERT INTO items_tags
+    (item_id, name, value, plaintext)
+    SELECT n.id, it.name, it.value, it.plaintext FROM items_tags it
+    JOIN items i ON i.id = it.item_id
+    JOIN items n ON n.profile_id = ?2 AND n.kind = i.kind
+        AND n.category = i.category AND n.name = i.name
+    WHERE i.profile_id = ?1";
 const DELETE_QUERY: &'static str = "DELETE FROM items
     WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4";
+const DELETE_ID_QUERY: &'static str = "DELETE FROM items WHERE id = ?1";
 const FETCH_QUERY: &'static str = "SELECT i.id, i.value,
     (SELECT GROUP_CONCAT(it.plaintext || ':' || HEX(it.name) || ':' || HEX(it.value))
-        FROM items_tags it WHERE it.item_id = i.id) AS tags
+        FROM items_tags it WHERE it.item_id = i.id) AS tags,
+    CAST(ROUND((JULIANDAY(i.expiry) - 2440587.5) * 86400000.0) AS INTEGER) AS expiry,
+    i.version,
+    CAST(ROUND((JULIANDAY(i.created) - 2440587.5) * 86400000.0) AS INTEGER) AS created,
+    CAST(ROUND((JULIANDAY(i.updated) - 2440587.5) * 86400000.0) AS INTEGER) AS updated
     FROM items i WHERE i.profile_id = ?1 AND i.kind = ?2
     AND i.category = ?3 AND i.name = ?4
-    AND (i.expiry IS NULL OR i.expiry > DATETIME('now'))";
+    AND (i.expiry IS NULL OR JULIANDAY(i.expiry) > JULIANDAY('now'))";
 const INSERT_QUERY: &'static str =
-    "INSERT OR IGNORE INTO items (profile_id, kind, category, name, value, expiry)
-    VALUES (?1, ?2, ?3, ?4, ?5, ?6)";
-const SCAN_QUERY: &'static str = "SELECT i.id, i.name, i.value,
+    "INSERT OR IGNORE INTO items (profile_id, kind, category, name, value, expiry, version,
+        created, updated)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)";
+const PROFILE_KEY_CHECK_QUERY: &'static str =
+    "SELECT profile_key, rotate_key IS NOT NULL FROM profiles WHERE id = ?1";
+const PURGE_COUNT_QUERY: &'static str = "SELECT p.name, COUNT(*) FROM items i
+    JOIN profiles p ON p.id = i.profile_id
+    WHERE i.expiry IS NOT NULL AND JULIANDAY(i.expiry) <= JULIANDAY(?1)
+    GROUP BY p.name";
+const PURGE_QUERY: &'static str = "DELETE FROM items
+    WHERE expiry IS NOT NULL AND JULIANDAY(expiry) <= JULIANDAY(?1)";
+const ROTATE_FETCH_QUERY: &'static str = "SELECT i.id, i.category, i.name, i.value,
     (SELECT GROUP_CONCAT(it.plaintext || ':' || HEX(it.name) || ':' || HEX(it.value))
         FROM items_tags it WHERE it.item_id = i.id) AS tags
-    FROM items i WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3
-    AND (i.expiry IS NULL OR i.expiry > DATETIME('now'))";
+    FROM items i WHERE i.profile_id = ?1 AND i.id > ?2
+    ORDER BY i.id LIMIT ?3";
+const REMOVE_VERSION_QUERY: &'static str = "SELECT id, version,
+    CAST(ROUND((JULIANDAY(created) - 2440587.5) * 86400000.0) AS INTEGER) AS created
+    FROM items
+    WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4";
+const SET_EXPIRY_QUERY: &'static str = "UPDATE items SET expiry = ?5, updated = ?6
+    WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4
+    AND (expiry IS NULL OR JULIANDAY(expiry) > JULIANDAY('now'))";
+const SET_EXPIRY_ALL_QUERY: &'static str = "UPDATE items AS i SET expiry = ?4, updated = ?5
+    WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3
+    AND (i.expiry IS NULL OR JULIANDAY(i.expiry) > JULIANDAY('now'))";
+const TAG_NAMES_QUERY: &'static str = "SELECT it.name, it.plaintext, COUNT(DISTINCT i.id)
+    FROM items_tags it JOIN items i ON i.id = it.item_id
+    WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3
+    AND (i.expiry IS NULL OR JULIANDAY(i.expiry) > JULIANDAY('now'))
+    GROUP BY it.name, it.plaintext";
+const TAG_UPDATE_ITEM_QUERY: &'static str = "SELECT id FROM items
+    WHERE profile_id = ?1 AND kind = ?2 AND category = ?3 AND name = ?4
+    AND (expiry IS NULL OR JULIANDAY(expiry) > JULIANDAY('now'))";
+const TAG_UPDATE_VERSION_QUERY: &'static str =
+    "UPDATE items SET version = version + 1, updated = ?2 WHERE id = ?1";
+const ROTATE_UPDATE_QUERY: &'static str = "UPDATE items
+    SET category = ?1, name = ?2, value = ?3 WHERE id = ?4";
+const SCAN_QUERY: &'static str = "SELECT i.id, i.name, i.value,
+    (SELECT GROUP_CONCAT(it.plaintext || ':' || HEX(it.name) || ':' || HEX(it.value))
+        FROM items_tags it WHERE it.item_id = i.id) AS tags,
+    CAST(ROUND((JULIANDAY(i.expiry) - 2440587.5) * 86400000.0) AS INTEGER) AS expiry,
+    i.version,
+    CAST(ROUND((JULIANDAY(i.created) - 2440587.5) * 86400000.0) AS INTEGER) AS created,
+    CAST(ROUND((JULIANDAY(i.updated) - 2440587.5) * 86400000.0) AS INTEGER) AS updated
+    FROM items i WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3 AND i.id > ?4
+    AND (i.expiry IS NULL OR JULIANDAY(i.expiry) > JULIANDAY('now'))";
 const DELETE_ALL_QUERY: &'static str = "DELETE FROM items AS i
     WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.category = ?3";
+const TAG_DELETE_QUERY: &'static str = "DELETE FROM items_tags
+    WHERE item_id = ?1 AND name = ?2 AND plaintext = ?3";
+const TAG_DELETE_VALUE_QUERY: &'static str = "DELETE FROM items_tags
+    WHERE item_id = ?1 AND name = ?2 AND value = ?3 AND plaintext = ?4";
 const TAG_INSERT_QUERY: &'static str = "INSERT INTO items_tags
     (item_id, name, value, plaintext) VALUES (?1, ?2, ?3, ?4)";
+const TAG_INSERT_BATCH_QUERY: &'static str = "INSERT INTO items_tags
+    (item_id, name, value, plaintext) VALUES ";
+const VERIFY_FETCH_QUERY: &'static str = "SELECT i.id, i.category, i.name, i.value,
+    (SELECT GROUP_CONCAT(it.plaintext || ':' || HEX(it.name) || ':' || HEX(it.value))
+        FROM items_tags it WHERE it.item_id = i.id) AS tags
+    FROM items i WHERE i.profile_id = ?1 AND i.kind = ?2 AND i.id > ?3
+    ORDER BY i.id LIMIT ?4";
+const VERIFY_ORPHAN_ITEMS_QUERY: &'static str = "SELECT id FROM items
+    WHERE profile_id NOT IN (SELECT id FROM profiles)";
+const VERIFY_ORPHAN_TAGS_QUERY: &'static str = "SELECT id FROM items_tags
+    WHERE item_id NOT IN (SELECT id FROM items)";
+const VERIFY_QUERIES: VerifyQueries = VerifyQueries {
+    profile: "SELECT id, name, profile_key, rotate_key IS NOT NULL FROM profiles WHERE name = ?1",
+    profiles: "SELECT id, name, profile_key, rotate_key IS NOT NULL FROM profiles",
+    profile_rows: "SELECT (SELECT COUNT(*) FROM items WHERE profile_id = ?1)
+        + (SELECT COUNT(*) FROM items_tags it JOIN items i ON i.id = it.item_id
+        WHERE i.profile_id = ?1)",
+    delete_profile: "DELETE FROM profiles WHERE id = ?1",
+    fetch: VERIFY_FETCH_QUERY,
+    item_tags: "SELECT COUNT(*) FROM items_tags WHERE item_id = ?1",
+    delete_item: DELETE_ID_QUERY,
+    delete_tag_value: TAG_DELETE_VALUE_QUERY,
+    orphan_items: VERIFY_ORPHAN_ITEMS_QUERY,
+    orphan_tags: VERIFY_ORPHAN_TAGS_QUERY,
+    delete_tag: "DELETE FROM items_tags WHERE id = ?1",
+};
+const ROTATE_QUERIES: RotateQueries = RotateQueries {
+    profile: "SELECT id, profile_key, rotate_key, rotate_pos FROM profiles WHERE name = ?1",
+    start: "UPDATE profiles SET rotate_key = ?1, rotate_pos = 0
+        WHERE id = ?2 AND rotate_key IS NULL",
+    fetch: ROTATE_FETCH_QUERY,
+    update_item: ROTATE_UPDATE_QUERY,
+    delete_tags: "DELETE FROM items_tags WHERE item_id = ?1",
+    insert_tag: TAG_INSERT_QUERY,
+    update_pos: "UPDATE profiles SET rotate_pos = ?1 WHERE id = ?2",
+    finish: "UPDATE profiles SET profile_key = rotate_key, rotate_key = NULL, rotate_pos = NULL
+        WHERE id = ?1",
+};
 
 /// A Sqlite database store
 pub struct SqliteStore {
@@ -63,6 +184,8 @@ pub struct SqliteStore {
     default_profile: String,
     key_cache: Arc<KeyCache>,
     path: String,
+    read_only: bool,
+    query_limits: TagQueryLimits,
 }
 
 impl SqliteStore {
@@ -71,13 +194,101 @@ impl SqliteStore {
         default_profile: String,
         key_cache: KeyCache,
         path: String,
+        read_only: bool,
+        query_limits: TagQueryLimits,
     ) -> Self {
         Self {
             conn_pool,
             default_profile,
             key_cache: Arc::new(key_cache),
             path,
+            read_only,
+            query_limits,
+        }
+    }
+
+    async fn start_scan(
+        &self,
+        cursor: ScanCursor,
+        tag_filter: Option<TagFilter>,
+        token: Option<String>,
+        offset: Option<i64>,
+        limit: Option<i64>,
+    ) -> Result<Scan<'static, Entry>, Error> {
+        let session = self.session(Some(cursor.profile.clone()), false)?;
+        let mut active = session.owned_ref();
+        let (profile_id, key) = acquire_key(&mut *active).await?;
+        let scan = perform_scan(
+            active,
+            profile_id,
+            key.clone(),
+            cursor.kind,
+            cursor.category.clone(),
+            tag_filter,
+            cursor.order_by.clone(),
+            cursor.after_id(),
+            offset,
+            limit,
+        );
+        let store_key = self.key_cache.store_key.clone();
+        let mut cursor = cursor;
+        let stream = scan.then(move |enc_rows| {
+            let cursor = cursor.advance(enc_rows.as_ref().map(Vec::len).unwrap_or(0));
+            let key = key.clone();
+            let store_key = store_key.clone();
+            unblock(move || decrypt_scan_page(&cursor, enc_rows?, &key, &store_key))
+        });
+        Ok(Scan::new(stream, PAGE_SIZE, token))
+    }
+
+    /// Write a consistent copy of the database to a new file
+    ///
+    /// The copy may be opened as a Sqlite store using the same pass key.
+    /// Writes to the store are not blocked while the copy is being made
+    pub async fn backup_to(&self, path: &str) -> Result<(), Error> {
+        if self.path == ":memory:" {
+            return Err(err_msg!(
+                Unsupported,
+                "Backup is not supported for in-memory databases"
+            ));
+        }
+        if path.is_empty() || path == ":memory:" {
+            return Err(err_msg!(Input, "Invalid backup path"));
+        }
+        let check_path = path.to_string();
+        if unblock(move || std::path::Path::new(&check_path).exists()).await {
+            return Err(err_msg!(Duplicate, "Backup destination already exists"));
         }
+        let mut conn = self.conn_pool.acquire().await?;
+        sqlx::query("VACUUM INTO ?1")
+            .bind(path)
+            .execute(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Rebuild the database file to reclaim unused space
+    ///
+    /// This may be used after removing a large number of records or profiles
+    pub async fn compact(&self) -> Result<(), Error> {
+        let mut conn = self.conn_pool.acquire().await?;
+        sqlx::query("VACUUM").execute(&mut conn).await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+impl Store<SqliteStore> {
+    /// Write a consistent copy of the store to a new database file
+    pub async fn backup_to(&self, path: &str) -> Result<(), Error> {
+        self.inner().backup_to(path).await
+    }
+
+    /// Rebuild the database file to reclaim unused space
+    pub async fn compact(&self) -> Result<(), Error> {
+        self.inner().compact().await
     }
 }
 
@@ -86,6 +297,7 @@ impl Debug for SqliteStore {
         f.debug_struct("SqliteStore")
             .field("default_profile", &self.default_profile)
             .field("path", &self.path)
+            .field("read_only", &self.read_only)
             .finish()
     }
 }
@@ -100,13 +312,18 @@ impl Backend for SqliteStore {
     fn create_profile(&self, name: Option<String>) -> BoxFuture<'_, Result<String, Error>> {
         let name = name.unwrap_or_else(random_profile_name);
         Box::pin(async move {
-            let key = ProfileKey::new()?;
-            let enc_key = key.to_bytes()?;
+            let store_key = self.key_cache.store_key.clone();
+            let (key, enc_key) = unblock(move || {
+                let key = ProfileKey::new()?;
+                let enc_key = encode_profile_key(&key, &store_key)?;
+                Result::<_, Error>::Ok((key, enc_key))
+            })
+            .await?;
             let mut conn = self.conn_pool.acquire().await?;
             let done =
                 sqlx::query("INSERT OR IGNORE INTO profiles (name, profile_key) VALUES (?1, ?2)")
                     .bind(&name)
-                    .bind(enc_key.as_ref())
+                    .bind(&enc_key)
                     .execute(&mut conn)
                     .await?;
             if done.rows_affected() == 0 {
@@ -123,18 +340,224 @@ impl Backend for SqliteStore {
         self.default_profile.as_str()
     }
 
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn query_limits(&self) -> TagQueryLimits {
+        self.query_limits
+    }
+
     fn remove_profile(&self, name: String) -> BoxFuture<'_, Result<bool, Error>> {
         Box::pin(async move {
             let mut conn = self.conn_pool.acquire().await?;
-            Ok(sqlx::query("DELETE FROM profiles WHERE name=?")
+            let removed = sqlx::query("DELETE FROM profiles WHERE name=?")
                 .bind(&name)
                 .execute(&mut conn)
                 .await?
                 .rows_affected()
-                != 0)
+                != 0;
+            if removed {
+                self.key_cache.remove_profile(&name).await;
+            }
+            Ok(removed)
+        })
+    }
+
+    fn rename_profile(
+        &self,
+        from_name: String,
+        to_name: String,
+    ) -> BoxFuture<'_, Result<bool, Error>> {
+        Box::pin(async move {
+            if from_name == self.default_profile {
+                return Err(err_msg!(Input, "Cannot rename the default profile"));
+            }
+            let mut txn = self.conn_pool.begin().await?;
+            if sqlx::query("SELECT 1 FROM profiles WHERE name=?1")
+                .bind(&to_name)
+                .fetch_optional(&mut txn)
+                .await?
+                .is_some()
+            {
+                return Err(err_msg!(Duplicate, "Duplicate profile name"));
+            }
+            let renamed = sqlx::query("UPDATE profiles SET name=?2 WHERE name=?1")
+                .bind(&from_name)
+                .bind(&to_name)
+                .execute(&mut txn)
+                .await?
+                .rows_affected()
+                != 0;
+            txn.commit().await?;
+            if renamed {
+                self.key_cache.remove_profile(&from_name).await;
+            }
+            Ok(renamed)
+        })
+    }
+
+    fn copy_profile(
+        &self,
+        from_name: String,
+        to_name: String,
+    ) -> BoxFuture<'_, Result<bool, Error>> {
+        Box::pin(async move {
+            let mut txn = self.conn_pool.begin().await?;
+            let row = match sqlx::query(
+                "SELECT id, profile_key, rotate_key IS NOT NULL FROM profiles WHERE name=?1",
+            )
+            .bind(&from_name)
+            .fetch_optional(&mut txn)
+            .await?
+            {
+                Some(row) => row,
+                None => return Ok(false),
+            };
+            if row.try_get(2)? {
+                return Err(err_msg!(Busy, "Profile key rotation in progress"));
+            }
+            let from_id: ProfileId = row.try_get(0)?;
+            let profile_key: Vec<u8> = row.try_get(1)?;
+            let done =
+                sqlx::query("INSERT OR IGNORE INTO profiles (name, profile_key) VALUES (?1, ?2)")
+                    .bind(&to_name)
+                    .bind(&profile_key)
+                    .execute(&mut txn)
+                    .await?;
+            if done.rows_affected() == 0 {
+                return Err(err_msg!(Duplicate, "Duplicate profile name"));
+            }
+            let to_id = done.last_insert_rowid();
+            sqlx::query(COPY_ITEMS_QUERY)
+                .bind(from_id)
+                .bind(to_id)
+                .execute(&mut txn)
+                .await?;
+            sqlx::query(COPY_TAGS_QUERY)
+                .bind(from_id)
+                .bind(to_id)
+                .execute(&mut txn)
+                .await?;
+            txn.commit().await?;
+            Ok(true)
+        })
+    }
+
+    fn rotate_profile_key(&self, name: String) -> BoxFuture<'_, Result<i64, Error>> {
+        Box::pin(rotate_profile_key(
+            &self.conn_pool,
+            &self.key_cache,
+            &ROTATE_QUERIES,
+            decode_enc_row,
+            name,
+        ))
+    }
+
+    fn load_profile_key(&self, name: String) -> BoxFuture<'_, Result<SecretBytes, Error>> {
+        Box::pin(async move {
+            let mut session = self.session(Some(name), false)?;
+            let (_, key) = acquire_key(&mut session).await?;
+            key.to_bytes()
+        })
+    }
+
+    fn import_profile(&self, name: String, key: SecretBytes) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            let key = Arc::new(ProfileKey::from_slice(&key)?);
+            let enc_key = unblock({
+                let key = key.clone();
+                let store_key = self.key_cache.store_key.clone();
+                move || encode_profile_key(&key, &store_key)
+            })
+            .await?;
+            let mut conn = self.conn_pool.acquire().await?;
+            sqlx::query(IMPORT_PROFILE_QUERY)
+                .bind(&name)
+                .bind(&enc_key)
+                .execute(&mut conn)
+                .await?;
+            let row = sqlx::query("SELECT id, profile_key FROM profiles WHERE name=?1")
+                .bind(&name)
+                .fetch_one(&mut conn)
+                .await?;
+            let pid = row.try_get(0)?;
+            let found_key: Vec<u8> = row.try_get(1)?;
+            // the existing profile key was retained if the profile has records
+            if found_key != enc_key && self.key_cache.load_key(found_key).await? != *key {
+                return Err(err_msg!(
+                    Duplicate,
+                    "Profile exists with a different profile key"
+                ));
+            }
+            self.key_cache.add_profile(name, pid, key).await;
+            Ok(())
+        })
+    }
+
+    fn list_profiles(&self) -> BoxFuture<'_, Result<Vec<String>, Error>> {
+        Box::pin(async move {
+            let mut conn = self.conn_pool.acquire().await?;
+            Ok(sqlx::query_scalar("SELECT name FROM profiles ORDER BY id")
+                .fetch_all(&mut conn)
+                .await?)
+        })
+    }
+
+    fn list_categories(
+        &self,
+        profile: Option<String>,
+        kind: EntryKind,
+    ) -> BoxFuture<'_, Result<Vec<String>, Error>> {
+        Box::pin(async move {
+            let mut session = self.session(profile, false)?;
+            let (profile_id, key) = acquire_key(&mut session).await?;
+            let mut active = acquire_session(&mut session).await?;
+            let enc_categories: Vec<Vec<u8>> = sqlx::query_scalar(CATEGORIES_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .fetch_all(active.connection_mut())
+                .await?;
+            unblock(move || {
+                enc_categories
+                    .into_iter()
+                    .map(|enc_category| key.decrypt_entry_category(enc_category))
+                    .collect()
+            })
+            .await
+        })
+    }
+
+    fn purge_expired(&self) -> BoxFuture<'_, Result<BTreeMap<String, i64>, Error>> {
+        Box::pin(async move {
+            let now = chrono::Utc::now();
+            let mut txn = self.conn_pool.begin().await?;
+            let removed: Vec<(String, i64)> = sqlx::query_as(PURGE_COUNT_QUERY)
+                .bind(now)
+                .fetch_all(&mut txn)
+                .await?;
+            sqlx::query(PURGE_QUERY).bind(now).execute(&mut txn).await?;
+            txn.commit().await?;
+            Ok(removed.into_iter().collect())
         })
     }
 
+    fn verify(
+        &self,
+        profile: Option<String>,
+        repair: bool,
+    ) -> BoxFuture<'_, Result<VerifyReport, Error>> {
+        Box::pin(verify_store(
+            &self.conn_pool,
+            &self.key_cache,
+            &self.default_profile,
+            &VERIFY_QUERIES,
+            decode_enc_row,
+            profile,
+            repair,
+        ))
+    }
+
     fn rekey_backend(
         &mut self,
         method: StoreKeyMethod,
@@ -145,24 +568,38 @@ impl Backend for SqliteStore {
             let (store_key, store_key_ref) = unblock(move || method.resolve(pass_key)).await?;
             let store_key = Arc::new(store_key);
             let mut txn = self.conn_pool.begin().await?;
-            let mut rows = sqlx::query("SELECT id, profile_key FROM profiles").fetch(&mut txn);
-            let mut upd_keys = BTreeMap::<ProfileId, Vec<u8>>::new();
+            let mut rows =
+                sqlx::query("SELECT id, profile_key, rotate_key FROM profiles").fetch(&mut txn);
+            let mut upd_keys = BTreeMap::<ProfileId, (Vec<u8>, Option<Vec<u8>>)>::new();
             while let Some(row) = rows.next().await {
                 let row = row?;
                 let pid = row.try_get(0)?;
                 let enc_key = row.try_get(1)?;
                 let profile_key = self.key_cache.load_key(enc_key).await?;
-                let upd_key = unblock({
+                // the pending key of an interrupted rotation is also re-wrapped
+                let rotate_key = match row.try_get::<Option<Vec<u8>>, _>(2)? {
+                    Some(enc_key) => Some(self.key_cache.load_key(enc_key).await?),
+                    None => None,
+                };
+                let upd_keys_row = unblock({
                     let store_key = store_key.clone();
-                    move || encode_profile_key(&profile_key, &store_key)
+                    move || {
+                        Result::<_, Error>::Ok((
+                            encode_profile_key(&profile_key, &store_key)?,
+                            rotate_key
+                                .map(|key| encode_profile_key(&key, &store_key))
+                                .transpose()?,
+                        ))
+                    }
                 })
                 .await?;
-                upd_keys.insert(pid, upd_key);
+                upd_keys.insert(pid, upd_keys_row);
             }
             drop(rows);
-            for (pid, key) in upd_keys {
-                if sqlx::query("UPDATE profiles SET profile_key=?1 WHERE id=?2")
+            for (pid, (key, rotate_key)) in upd_keys {
+                if sqlx::query("UPDATE profiles SET profile_key=?1, rotate_key=?2 WHERE id=?3")
                     .bind(key)
+                    .bind(rotate_key)
                     .bind(pid)
                     .execute(&mut txn)
                     .await?
@@ -193,29 +630,36 @@ impl Backend for SqliteStore {
         kind: EntryKind,
         category: String,
         tag_filter: Option<TagFilter>,
+        order_by: Option<OrderBy>,
         offset: Option<i64>,
         limit: Option<i64>,
     ) -> BoxFuture<'_, Result<Scan<'static, Entry>, Error>> {
         Box::pin(async move {
-            let session = self.session(profile, false)?;
-            let mut active = session.owned_ref();
-            let (profile_id, key) = acquire_key(&mut *active).await?;
-            let scan = perform_scan(
-                active,
-                profile_id,
-                key.clone(),
+            let profile = profile.unwrap_or_else(|| self.default_profile.clone());
+            let cursor = ScanCursor::new(
+                profile,
                 kind,
-                category.clone(),
-                tag_filter,
+                category,
+                tag_filter.as_ref(),
+                order_by.as_ref(),
                 offset,
-                limit,
-            );
-            let stream = scan.then(move |enc_rows| {
-                let category = category.clone();
-                let key = key.clone();
-                unblock(move || decrypt_scan_batch(category, enc_rows?, &key))
-            });
-            Ok(Scan::new(stream, PAGE_SIZE))
+            )?;
+            self.start_scan(cursor, tag_filter, None, offset, limit)
+                .await
+        })
+    }
+
+    fn resume_scan(
+        &self,
+        token: String,
+        limit: Option<i64>,
+    ) -> BoxFuture<'_, Result<Scan<'static, Entry>, Error>> {
+        Box::pin(async move {
+            let cursor = ScanCursor::from_token(&token, &self.key_cache.store_key)?;
+            let tag_filter = cursor.tag_filter()?;
+            let offset = cursor.resume_offset();
+            self.start_scan(cursor, tag_filter, Some(token), offset, limit)
+                .await
         })
     }
 
@@ -228,6 +672,27 @@ impl Backend for SqliteStore {
         ))
     }
 
+    fn snapshot_session(&self, profile: Option<String>) -> Result<Self::Session, Error> {
+        Ok(DbSession::snapshot(
+            self.conn_pool.clone(),
+            self.key_cache.clone(),
+            profile.unwrap_or_else(|| self.default_profile.clone()),
+        ))
+    }
+
+    fn publishes_changes(&self) -> bool {
+        false
+    }
+
+    fn listen_changes(&self, _registry: Weak<WatchRegistry>) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async {
+            Err(err_msg!(
+                Unsupported,
+                "Change notifications are not supported by this backend"
+            ))
+        })
+    }
+
     fn close(&self) -> BoxFuture<'_, Result<(), Error>> {
         Box::pin(async move {
             self.conn_pool.close().await;
@@ -271,6 +736,71 @@ impl QueryBackend for DbSession<Sqlite> {
         })
     }
 
+    fn category_stats(
+        &mut self,
+        kind: EntryKind,
+    ) -> BoxFuture<'_, Result<BTreeMap<String, CategoryStats>, Error>> {
+        Box::pin(async move {
+            let (profile_id, key) = acquire_key(&mut *self).await?;
+            let mut active = acquire_session(&mut *self).await?;
+            let rows: Vec<(Vec<u8>, i64, i64)> = sqlx::query_as(CATEGORY_STATS_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .fetch_all(active.connection_mut())
+                .await?;
+            unblock(move || {
+                rows.into_iter()
+                    .map(|(enc_category, count, size)| {
+                        Ok((
+                            key.decrypt_entry_category(enc_category)?,
+                            CategoryStats { count, size },
+                        ))
+                    })
+                    .collect()
+            })
+            .await
+        })
+    }
+
+    fn tag_names<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+    ) -> BoxFuture<'q, Result<Vec<TagNameStats>, Error>> {
+        let category = ProfileKey::prepare_input(category.as_bytes());
+
+        Box::pin(async move {
+            let (profile_id, key) = acquire_key(&mut *self).await?;
+            let enc_category = unblock({
+                let key = key.clone();
+                move || key.encrypt_entry_category(category)
+            })
+            .await?;
+            let mut active = acquire_session(&mut *self).await?;
+            let rows: Vec<(Vec<u8>, bool, i64)> = sqlx::query_as(TAG_NAMES_QUERY)
+                .bind(profile_id)
+                .bind(kind as i16)
+                .bind(enc_category)
+                .fetch_all(active.connection_mut())
+                .await?;
+            unblock(move || {
+                let mut names = rows
+                    .into_iter()
+                    .map(|(enc_name, plaintext, count)| {
+                        Ok(TagNameStats {
+                            name: key.decrypt_entry_tag_name(enc_name)?,
+                            plaintext,
+                            count,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                names.sort();
+                Ok(names)
+            })
+            .await
+        })
+    }
+
     fn fetch(
         &mut self,
         kind: EntryKind,
@@ -306,6 +836,10 @@ impl QueryBackend for DbSession<Sqlite> {
             {
                 let value = row.try_get(1)?;
                 let tags = row.try_get(2)?;
+                let expiry = row.try_get(3)?;
+                let version = row.try_get(4)?;
+                let created = row.try_get(5)?;
+                let updated = row.try_get(6)?;
                 let (category, name, value, tags) = unblock(move || {
                     let value = key.decrypt_entry_value(category.as_ref(), name.as_ref(), value)?;
                     let enc_tags = decode_tags(tags)
@@ -314,7 +848,12 @@ impl QueryBackend for DbSession<Sqlite> {
                     Result::<_, Error>::Ok((category, name, value, tags))
                 })
                 .await?;
-                Ok(Some(Entry::new(category, name, value, tags)))
+                Ok(Some(
+                    Entry::new(category, name, value, tags)
+                        .with_expiry(expiry)
+                        .with_version(version)
+                        .with_timestamps(created, updated),
+                ))
             } else {
                 Ok(None)
             }
@@ -326,6 +865,7 @@ impl QueryBackend for DbSession<Sqlite> {
         kind: EntryKind,
         category: &'q str,
         tag_filter: Option<TagFilter>,
+        order_by: Option<OrderBy>,
         limit: Option<i64>,
         _for_update: bool,
     ) -> BoxFuture<'q, Result<Vec<Entry>, Error>> {
@@ -340,6 +880,8 @@ impl QueryBackend for DbSession<Sqlite> {
                 kind,
                 category.clone(),
                 tag_filter,
+                order_by,
+                0,
                 None,
                 limit,
             );
@@ -384,11 +926,26 @@ impl QueryBackend for DbSession<Sqlite> {
                 extend_query::<SqliteStore>(DELETE_ALL_QUERY, &mut params, tag_filter, None, None)?;
 
             let mut active = acquire_session(&mut *self).await?;
-            let removed = sqlx::query_with(query.as_str(), params)
-                .execute(active.connection_mut())
-                .await?
-                .rows_affected();
-            Ok(removed as i64)
+            let mut txn = active.as_transaction().await?;
+            let result = async {
+                check_profile_key(&mut txn, PROFILE_KEY_CHECK_QUERY).await?;
+                let removed = sqlx::query_with(query.as_str(), params)
+                    .execute(txn.connection_mut())
+                    .await?
+                    .rows_affected();
+                Result::<_, Error>::Ok(removed as i64)
+            }
+            .await;
+            match result {
+                Ok(removed) => {
+                    txn.commit().await?;
+                    Ok(removed)
+                }
+                Err(err) => {
+                    txn.rollback().await?;
+                    Err(err)
+                }
+            }
         })
     }
 
@@ -426,21 +983,44 @@ impl QueryBackend for DbSession<Sqlite> {
                     .await?;
                     let mut active = acquire_session(&mut *self).await?;
                     let mut txn = active.as_transaction().await?;
-                    if op == EntryOperation::Replace {
-                        perform_remove(&mut txn, kind, &enc_category, &enc_name, false).await?;
+                    let result = async {
+                        check_profile_key(&mut txn, PROFILE_KEY_CHECK_QUERY).await?;
+                        let now = chrono::Utc::now();
+                        let (version, created) = if op == EntryOperation::Replace {
+                            let (version, created) = perform_remove_version(
+                                &mut txn,
+                                kind,
+                                &enc_category,
+                                &enc_name,
+                                None,
+                            )
+                            .await?;
+                            (version + 1, created.map(millis_timestamp).transpose()?)
+                        } else {
+                            (1, Some(now))
+                        };
+                        perform_insert(
+                            &mut txn,
+                            kind,
+                            &enc_category,
+                            &enc_name,
+                            &enc_value,
+                            enc_tags,
+                            expiry_ms,
+                            version,
+                            created,
+                            now,
+                        )
+                        .await
+                    }
+                    .await;
+                    match result {
+                        Ok(()) => txn.commit().await,
+                        Err(err) => {
+                            txn.rollback().await?;
+                            Err(err)
+                        }
                     }
-                    perform_insert(
-                        &mut txn,
-                        kind,
-                        &enc_category,
-                        &enc_name,
-                        &enc_value,
-                        enc_tags,
-                        expiry_ms,
-                    )
-                    .await?;
-                    txn.commit().await?;
-                    Ok(())
                 })
             }
 
@@ -454,42 +1034,271 @@ impl QueryBackend for DbSession<Sqlite> {
                 })
                 .await?;
                 let mut active = acquire_session(&mut *self).await?;
-                Ok(perform_remove(&mut active, kind, &enc_category, &enc_name, false).await?)
+                let mut txn = active.as_transaction().await?;
+                let result = async {
+                    check_profile_key(&mut txn, PROFILE_KEY_CHECK_QUERY).await?;
+                    perform_remove(&mut txn, kind, &enc_category, &enc_name, false).await
+                }
+                .await;
+                match result {
+                    Ok(()) => txn.commit().await,
+                    Err(err) => {
+                        txn.rollback().await?;
+                        Err(err)
+                    }
+                }
             }),
         }
     }
 
-    fn close(self, commit: bool) -> BoxFuture<'static, Result<(), Error>> {
-        Box::pin(DbSession::close(self, commit))
-    }
-}
+    fn update_tags<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+        add: &'q [EntryTag],
+        remove: &'q [&'q str],
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        let category = ProfileKey::prepare_input(category.as_bytes());
+        let name = ProfileKey::prepare_input(name.as_bytes());
+        let add = prepare_tags(add);
+        let remove = remove.iter().map(|name| name.to_string()).collect();
 
-impl ExtDatabase for Sqlite {
-    fn start_transaction(
-        conn: &mut PoolConnection<Self>,
-        nested: bool,
-    ) -> BoxFuture<'_, std::result::Result<(), SqlxError>> {
-        // FIXME - this is a horrible workaround because there is currently
-        // no good way to start an immediate transaction with sqlx. Without this
-        // adjustment, updates will run into 'database is locked' errors.
         Box::pin(async move {
-            <Sqlite as Database>::TransactionManager::begin(&mut *conn).await?;
-            if !nested {
-                sqlx::query("ROLLBACK").execute(&mut *conn).await?;
-                sqlx::query("BEGIN IMMEDIATE").execute(conn).await?;
+            let (_, key) = acquire_key(&mut *self).await?;
+            let (enc_category, enc_name, (enc_add, enc_remove)) = unblock(move || {
+                Result::<_, Error>::Ok((
+                    key.encrypt_entry_category(category)?,
+                    key.encrypt_entry_name(name)?,
+                    encrypt_tag_update(&key, add?, remove)?,
+                ))
+            })
+            .await?;
+            let mut active = acquire_session(&mut *self).await?;
+            // use a savepoint within an existing transaction so that
+            // failed tag changes can be rolled back on their own
+            let mut txn = if active.is_transaction() {
+                active.transaction().await?
+            } else {
+                active.as_transaction().await?
+            };
+            match perform_update_tags(
+                &mut txn,
+                kind,
+                &enc_category,
+                &enc_name,
+                enc_add,
+                enc_remove,
+            )
+            .await
+            {
+                Ok(()) => txn.commit().await,
+                Err(err) => {
+                    txn.rollback().await?;
+                    Err(err)
+                }
             }
-            Ok(())
         })
     }
-}
 
-async fn acquire_key(
-    session: &mut DbSession<Sqlite>,
-) -> Result<(ProfileId, Arc<ProfileKey>), Error> {
-    if let Some(ret) = session.profile_and_key() {
-        Ok(ret)
-    } else {
-        session.make_active(&resolve_profile_key).await?;
+    fn set_expiry<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        let category = ProfileKey::prepare_input(category.as_bytes());
+        let name = ProfileKey::prepare_input(name.as_bytes());
+
+        Box::pin(async move {
+            let (_, key) = acquire_key(&mut *self).await?;
+            let (enc_category, enc_name) = unblock(move || {
+                Result::<_, Error>::Ok((
+                    key.encrypt_entry_category(category)?,
+                    key.encrypt_entry_name(name)?,
+                ))
+            })
+            .await?;
+            let expiry = expiry_ms.map(expiry_timestamp).transpose()?;
+            let mut active = acquire_session(&mut *self).await?;
+            let mut txn = active.as_transaction().await?;
+            let result = async {
+                check_profile_key(&mut txn, PROFILE_KEY_CHECK_QUERY).await?;
+                trace!("Update entry expiry");
+                let done = sqlx::query(SET_EXPIRY_QUERY)
+                    .bind(txn.profile_id)
+                    .bind(kind as i16)
+                    .bind(enc_category)
+                    .bind(enc_name)
+                    .bind(expiry)
+                    .bind(chrono::Utc::now())
+                    .execute(txn.connection_mut())
+                    .await?;
+                if done.rows_affected() == 0 {
+                    Err(err_msg!(NotFound, "Entry not found"))
+                } else {
+                    Ok(())
+                }
+            }
+            .await;
+            match result {
+                Ok(()) => txn.commit().await,
+                Err(err) => {
+                    txn.rollback().await?;
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    fn set_expiry_all<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        tag_filter: Option<TagFilter>,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<i64, Error>> {
+        let category = ProfileKey::prepare_input(category.as_bytes());
+
+        Box::pin(async move {
+            let (profile_id, key) = acquire_key(&mut *self).await?;
+            let mut params = QueryParams::new();
+            params.push(profile_id);
+            params.push(kind as i16);
+            let (enc_category, tag_filter) = unblock({
+                let params_len = params.len() + 3; // plus category, expiry and updated
+                move || {
+                    Result::<_, Error>::Ok((
+                        key.encrypt_entry_category(category)?,
+                        encode_tag_filter::<SqliteStore>(tag_filter, &key, params_len)?,
+                    ))
+                }
+            })
+            .await?;
+            params.push(enc_category);
+            params.push(expiry_ms.map(expiry_timestamp).transpose()?);
+            params.push(chrono::Utc::now());
+            let query = extend_query::<SqliteStore>(
+                SET_EXPIRY_ALL_QUERY,
+                &mut params,
+                tag_filter,
+                None,
+                None,
+            )?;
+
+            let mut active = acquire_session(&mut *self).await?;
+            let mut txn = active.as_transaction().await?;
+            let result = async {
+                check_profile_key(&mut txn, PROFILE_KEY_CHECK_QUERY).await?;
+                let updated = sqlx::query_with(query.as_str(), params)
+                    .execute(txn.connection_mut())
+                    .await?
+                    .rows_affected();
+                Result::<_, Error>::Ok(updated as i64)
+            }
+            .await;
+            match result {
+                Ok(updated) => {
+                    txn.commit().await?;
+                    Ok(updated)
+                }
+                Err(err) => {
+                    txn.rollback().await?;
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    fn apply_batch<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        updates: Vec<EntryUpdate>,
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        Box::pin(async move {
+            let (_, key) = acquire_key(&mut *self).await?;
+            let updates = encrypt_entry_updates(key, updates).await?;
+            let mut active = acquire_session(&mut *self).await?;
+            // use a savepoint within an existing transaction so that
+            // a failed batch can be rolled back on its own
+            let mut txn = if active.is_transaction() {
+                active.transaction().await?
+            } else {
+                active.as_transaction().await?
+            };
+            match perform_batch(&mut txn, kind, updates).await {
+                Ok(()) => txn.commit().await,
+                Err(err) => {
+                    txn.rollback().await?;
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    fn publish_changes<'q>(
+        &'q mut self,
+        _changes: &'q [EntryChange],
+    ) -> BoxFuture<'q, Result<(), Error>> {
+        Box::pin(async {
+            Err(err_msg!(
+                Unsupported,
+                "Change notifications are not supported by this backend"
+            ))
+        })
+    }
+
+    fn close(self, commit: bool) -> BoxFuture<'static, Result<(), Error>> {
+        Box::pin(DbSession::close(self, commit))
+    }
+}
+
+impl ExtDatabase for Sqlite {
+    fn start_transaction(
+        conn: &mut PoolConnection<Self>,
+        nested: bool,
+    ) -> BoxFuture<'_, std::result::Result<(), SqlxError>> {
+        // FIXME - this is a horrible workaround because there is currently
+        // no good way to start an immediate transaction with sqlx. Without this
+        // adjustment, updates will run into 'database is locked' errors.
+        Box::pin(async move {
+            <Sqlite as Database>::TransactionManager::begin(&mut *conn).await?;
+            if !nested {
+                sqlx::query("ROLLBACK").execute(&mut *conn).await?;
+                sqlx::query("BEGIN IMMEDIATE").execute(conn).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn rows_affected(result: &<Self as Database>::QueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
+/// Decode the id, category, name, value and tags of an encrypted record
+fn decode_enc_row(row: &SqliteRow) -> Result<(i64, EncEntry), Error> {
+    let tags = decode_tags(row.try_get(4)?)
+        .map_err(|_| err_msg!(Unexpected, "Error decoding entry tags"))?;
+    Ok((
+        row.try_get(0)?,
+        EncEntry {
+            category: row.try_get(1)?,
+            name: row.try_get(2)?,
+            value: row.try_get(3)?,
+            tags,
+        },
+    ))
+}
+
+async fn acquire_key(
+    session: &mut DbSession<Sqlite>,
+) -> Result<(ProfileId, Arc<ProfileKey>), Error> {
+    if let Some(ret) = session.profile_and_key() {
+        Ok(ret)
+    } else {
+        session.make_active(&resolve_profile_key).await?;
         Ok(session.profile_and_key().unwrap())
     }
 }
@@ -508,11 +1317,16 @@ async fn resolve_profile_key(
     if let Some((pid, key)) = cache.get_profile(profile.as_str()).await {
         Ok((pid, key))
     } else {
-        if let Some(row) = sqlx::query("SELECT id, profile_key FROM profiles WHERE name=?1")
-            .bind(profile.as_str())
-            .fetch_optional(conn)
-            .await?
+        if let Some(row) = sqlx::query(
+            "SELECT id, profile_key, rotate_key IS NOT NULL FROM profiles WHERE name=?1",
+        )
+        .bind(profile.as_str())
+        .fetch_optional(conn)
+        .await?
         {
+            if row.try_get(2)? {
+                return Err(err_msg!(Busy, "Profile key rotation in progress"));
+            }
             let pid = row.try_get(0)?;
             let key = Arc::new(cache.load_key(row.try_get(1)?).await?);
             cache.add_profile(profile, pid, key.clone()).await;
@@ -531,7 +1345,39 @@ async fn perform_insert<'q>(
     enc_value: &[u8],
     enc_tags: Option<Vec<EncEntryTag>>,
     expiry_ms: Option<i64>,
+    version: i64,
+    created: Option<Expiry>,
+    updated: Expiry,
 ) -> Result<(), Error> {
+    let row_id = perform_insert_item(
+        active,
+        kind,
+        enc_category,
+        enc_name,
+        enc_value,
+        expiry_ms,
+        version,
+        created,
+        updated,
+    )
+    .await?;
+    if let Some(tags) = enc_tags {
+        perform_insert_tags(active, tags.into_iter().map(|tag| (row_id, tag)).collect()).await?;
+    }
+    Ok(())
+}
+
+async fn perform_insert_item<'q>(
+    active: &mut DbSessionActive<'q, Sqlite>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: &[u8],
+    enc_value: &[u8],
+    expiry_ms: Option<i64>,
+    version: i64,
+    created: Option<Expiry>,
+    updated: Expiry,
+) -> Result<i64, Error> {
     trace!("Insert entry");
     let done = sqlx::query(INSERT_QUERY)
         .bind(active.profile_id)
@@ -540,26 +1386,102 @@ async fn perform_insert<'q>(
         .bind(enc_name)
         .bind(enc_value)
         .bind(expiry_ms.map(expiry_timestamp).transpose()?)
+        .bind(version)
+        .bind(created)
+        .bind(updated)
         .execute(active.connection_mut())
         .await?;
     if done.rows_affected() == 0 {
         return Err(err_msg!(Duplicate, "Duplicate row"));
     }
-    let row_id = done.last_insert_rowid();
-    if let Some(tags) = enc_tags {
-        for tag in tags {
-            sqlx::query(TAG_INSERT_QUERY)
-                .bind(row_id)
-                .bind(&tag.name)
-                .bind(&tag.value)
-                .bind(tag.plaintext as i16)
-                .execute(active.connection_mut())
-                .await?;
+    Ok(done.last_insert_rowid())
+}
+
+async fn perform_insert_tags<'q>(
+    active: &mut DbSessionActive<'q, Sqlite>,
+    tags: Vec<(i64, EncEntryTag)>,
+) -> Result<(), Error> {
+    let mut tags = tags.into_iter().peekable();
+    while tags.peek().is_some() {
+        let mut params = QueryParams::new();
+        let mut rows = 0;
+        for (row_id, tag) in tags.by_ref().take(BATCH_ROWS) {
+            params.push(row_id);
+            params.push(tag.name);
+            params.push(tag.value);
+            params.push(tag.plaintext as i16);
+            rows += 1;
         }
+        let query = format!(
+            "{}{}",
+            TAG_INSERT_BATCH_QUERY,
+            multi_row_values::<SqliteStore>(rows, 4)
+        );
+        sqlx::query_with(query.as_str(), params)
+            .execute(active.connection_mut())
+            .await?;
     }
     Ok(())
 }
 
+async fn perform_batch<'q>(
+    active: &mut DbSessionActive<'q, Sqlite>,
+    kind: EntryKind,
+    updates: Vec<EncEntryUpdate>,
+) -> Result<(), Error> {
+    trace!("Apply batch");
+    check_profile_key(active, PROFILE_KEY_CHECK_QUERY).await?;
+    // tag inserts are deferred, and must be written before the
+    // corresponding record is removed again
+    let mut pending_tags = Vec::new();
+    let mut pending_keys = HashSet::new();
+    let now = chrono::Utc::now();
+    for mut update in updates {
+        let mut created = Some(now);
+        if update.operation != EntryOperation::Insert {
+            let key = (update.category.clone(), update.name.clone());
+            if pending_keys.contains(&key) {
+                perform_insert_tags(active, pending_tags.split_off(0)).await?;
+                pending_keys.clear();
+            }
+            if update.operation == EntryOperation::Remove && update.expected_version.is_none() {
+                perform_remove(active, kind, &update.category, &update.name, false).await?;
+                continue;
+            }
+            let (version, prev_created) = perform_remove_version(
+                active,
+                kind,
+                &update.category,
+                &update.name,
+                update.expected_version,
+            )
+            .await?;
+            if update.operation == EntryOperation::Remove {
+                continue;
+            }
+            update.version = version + 1;
+            created = prev_created.map(millis_timestamp).transpose()?;
+        }
+        let row_id = perform_insert_item(
+            active,
+            kind,
+            &update.category,
+            &update.name,
+            &update.value,
+            update.expiry_ms,
+            update.version,
+            created,
+            now,
+        )
+        .await?;
+        if let Some(tags) = update.tags {
+            pending_tags.extend(tags.into_iter().map(|tag| (row_id, tag)));
+            pending_keys.insert((update.category, update.name));
+        }
+    }
+    perform_insert_tags(active, pending_tags).await
+}
+
 async fn perform_remove<'q>(
     active: &mut DbSessionActive<'q, Sqlite>,
     kind: EntryKind,
@@ -582,6 +1504,81 @@ async fn perform_remove<'q>(
     }
 }
 
+async fn perform_update_tags<'q>(
+    active: &mut DbSessionActive<'q, Sqlite>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: &[u8],
+    enc_add: Vec<EncEntryTag>,
+    enc_remove: Vec<(Vec<u8>, bool)>,
+) -> Result<(), Error> {
+    trace!("Update entry tags");
+    check_profile_key(active, PROFILE_KEY_CHECK_QUERY).await?;
+    let row_id: i64 = sqlx::query_scalar(TAG_UPDATE_ITEM_QUERY)
+        .bind(active.profile_id)
+        .bind(kind as i16)
+        .bind(enc_category)
+        .bind(enc_name)
+        .fetch_optional(active.connection_mut())
+        .await?
+        .ok_or_else(|| err_msg!(NotFound, "Entry not found"))?;
+    sqlx::query(TAG_UPDATE_VERSION_QUERY)
+        .bind(row_id)
+        .bind(chrono::Utc::now())
+        .execute(active.connection_mut())
+        .await?;
+    for (tag_name, plaintext) in enc_remove {
+        sqlx::query(TAG_DELETE_QUERY)
+            .bind(row_id)
+            .bind(tag_name)
+            .bind(plaintext as i16)
+            .execute(active.connection_mut())
+            .await?;
+    }
+    // avoid storing duplicate values for the added tags
+    for tag in enc_add.iter() {
+        sqlx::query(TAG_DELETE_VALUE_QUERY)
+            .bind(row_id)
+            .bind(&tag.name)
+            .bind(&tag.value)
+            .bind(tag.plaintext as i16)
+            .execute(active.connection_mut())
+            .await?;
+    }
+    perform_insert_tags(
+        active,
+        enc_add.into_iter().map(|tag| (row_id, tag)).collect(),
+    )
+    .await
+}
+
+/// Remove an entry, checking and returning its current version and creation time
+async fn perform_remove_version<'q>(
+    active: &mut DbSessionActive<'q, Sqlite>,
+    kind: EntryKind,
+    enc_category: &[u8],
+    enc_name: &[u8],
+    expected_version: Option<i64>,
+) -> Result<(i64, Option<i64>), Error> {
+    trace!("Remove entry version");
+    let (row_id, version, created): (i64, i64, Option<i64>) = sqlx::query_as(REMOVE_VERSION_QUERY)
+        .bind(active.profile_id)
+        .bind(kind as i16)
+        .bind(enc_category)
+        .bind(enc_name)
+        .fetch_optional(active.connection_mut())
+        .await?
+        .ok_or_else(|| err_msg!(NotFound, "Entry not found"))?;
+    if matches!(expected_version, Some(expected) if expected != version) {
+        return Err(err_msg!(Conflict, "Entry version mismatch"));
+    }
+    sqlx::query(DELETE_ID_QUERY)
+        .bind(row_id)
+        .execute(active.connection_mut())
+        .await?;
+    Ok((version, created))
+}
+
 fn perform_scan<'q>(
     mut active: DbSessionRef<'q, Sqlite>,
     profile_id: ProfileId,
@@ -589,6 +1586,8 @@ fn perform_scan<'q>(
     kind: EntryKind,
     category: String,
     tag_filter: Option<TagFilter>,
+    order_by: Option<OrderBy>,
+    after_id: i64,
     offset: Option<i64>,
     limit: Option<i64>,
 ) -> impl Stream<Item = Result<Vec<EncScanEntry>, Error>> + 'q {
@@ -596,19 +1595,25 @@ fn perform_scan<'q>(
         let mut params = QueryParams::new();
         params.push(profile_id);
         params.push(kind as i16);
-        let (enc_category, tag_filter) = unblock({
+        let (enc_category, tag_filter, order_by) = unblock({
             let key = key.clone();
             let category = ProfileKey::prepare_input(category.as_bytes());
-            let params_len = params.len() + 1; // plus category
+            let params_len = params.len() + 2; // plus category and last row ID
             move || {
                 Result::<_, Error>::Ok((
                     key.encrypt_entry_category(category)?,
-                    encode_tag_filter::<SqliteStore>(tag_filter, &key, params_len)?
+                    encode_tag_filter::<SqliteStore>(tag_filter, &key, params_len)?,
+                    encode_order_by(order_by.as_ref(), &key)?,
                 ))
             }
         }).await?;
         params.push(enc_category);
-        let query = extend_query::<SqliteStore>(SCAN_QUERY, &mut params, tag_filter, offset, limit)?;
+        params.push(after_id);
+        let mut query = extend_query::<SqliteStore>(SCAN_QUERY, &mut params, tag_filter, None, None)?;
+        query = order_by_query::<SqliteStore>(query, &mut params, order_by);
+        if offset.is_some() || limit.is_some() {
+            query = SqliteStore::limit_query(query, &mut params, offset, limit);
+        }
 
         let mut batch = Vec::with_capacity(PAGE_SIZE);
 
@@ -616,7 +1621,9 @@ fn perform_scan<'q>(
         let mut rows = sqlx::query_with(query.as_str(), params).fetch(acquired.connection_mut());
         while let Some(row) = rows.try_next().await? {
             batch.push(EncScanEntry {
-                name: row.try_get(1)?, value: row.try_get(2)?, tags: row.try_get(3)?
+                id: row.try_get(0)?, name: row.try_get(1)?, value: row.try_get(2)?, tags: row.try_get(3)?,
+                expiry: row.try_get(4)?, version: row.try_get(5)?,
+                created: row.try_get(6)?, updated: row.try_get(7)?,
             });
             if batch.len() == PAGE_SIZE {
                 yield batch.split_off(0);
@@ -634,10 +1641,15 @@ fn perform_scan<'q>(
 
 #[cfg(test)]
 mod tests {
+    use super::provision::{migrate_db, MIGRATIONS};
     use super::*;
-    use crate::backend::db_utils::replace_arg_placeholders;
+    use crate::backend::{
+        db_utils::{latest_schema_version, replace_arg_placeholders, select_migrations},
+        types::SchemaMigration,
+    };
     use crate::future::block_on;
     use crate::protect::{generate_raw_store_key, StoreKeyMethod};
+    use crate::storage::VerifyIssue;
 
     #[test]
     fn sqlite_check_expiry_timestamp() {
@@ -647,16 +1659,551 @@ mod tests {
                 .provision(StoreKeyMethod::RawKey, key, None, false)
                 .await?;
             let ts = expiry_timestamp(1000).unwrap();
-            let check = sqlx::query("SELECT datetime('now'), ?1, ?1 > datetime('now')")
-                .bind(ts)
-                .fetch_one(&db.inner().conn_pool)
-                .await?;
+            let past_ts = expiry_timestamp(-1000).unwrap();
+            let check = sqlx::query(
+                "SELECT datetime('now'), ?1, JULIANDAY(?1) > JULIANDAY('now'),
+                JULIANDAY(?2) > JULIANDAY('now')",
+            )
+            .bind(ts)
+            .bind(past_ts)
+            .fetch_one(&db.inner().conn_pool)
+            .await?;
             let now: String = check.try_get(0)?;
             let cmp_ts: String = check.try_get(1)?;
             let cmp: bool = check.try_get(2)?;
             if !cmp {
                 panic!("now ({}) > expiry timestamp ({})", now, cmp_ts);
             }
+            let cmp_past: bool = check.try_get(3)?;
+            assert!(!cmp_past, "expected past timestamp to be expired");
+            Result::<_, Error>::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn sqlite_migrate_schema() {
+        const TEST_MIGRATIONS: &[SchemaMigration] = &[SchemaMigration {
+            version: 2,
+            description: "Add test table",
+            sql: "CREATE TABLE migration_test (id INTEGER NOT NULL)",
+        }];
+
+        assert_eq!(select_migrations(TEST_MIGRATIONS, "1").unwrap().len(), 1);
+        assert!(select_migrations(TEST_MIGRATIONS, "2").unwrap().is_empty());
+        assert!(select_migrations(TEST_MIGRATIONS, "3").is_err());
+        assert!(select_migrations(TEST_MIGRATIONS, "0").is_err());
+        assert!(select_migrations(TEST_MIGRATIONS, "invalid").is_err());
+        let latest = latest_schema_version(MIGRATIONS).to_string();
+        assert!(select_migrations(MIGRATIONS, &latest).unwrap().is_empty());
+
+        block_on(async {
+            let key = generate_raw_store_key(None)?;
+            let db = SqliteStoreOptions::in_memory()
+                .provision(StoreKeyMethod::RawKey, key, None, false)
+                .await?;
+            let mut conn = db.inner().conn_pool.acquire().await?;
+            sqlx::query("UPDATE config SET value = '1' WHERE name = 'version'")
+                .execute(&mut conn)
+                .await?;
+            for _ in 0..2 {
+                migrate_db(&mut conn, TEST_MIGRATIONS).await?;
+                let version: String =
+                    sqlx::query_scalar("SELECT value FROM config WHERE name = 'version'")
+                        .fetch_one(&mut conn)
+                        .await?;
+                assert_eq!(version, "2");
+            }
+            let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM migration_test")
+                .fetch_one(&mut conn)
+                .await?;
+            assert_eq!(count, 0);
+            Result::<_, Error>::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn sqlite_migrate_rotation_state() {
+        block_on(async {
+            let key = generate_raw_store_key(None)?;
+            let db = SqliteStoreOptions::in_memory()
+                .provision(StoreKeyMethod::RawKey, key, None, false)
+                .await?;
+            let mut conn = db.inner().conn_pool.acquire().await?;
+            // restore the original schema of the profiles and items tables
+            sqlx::query(
+                "ALTER TABLE profiles DROP COLUMN rotate_key;
+                ALTER TABLE profiles DROP COLUMN rotate_pos;
+                ALTER TABLE items DROP COLUMN version;
+                ALTER TABLE items DROP COLUMN created;
+                ALTER TABLE items DROP COLUMN updated;
+                UPDATE config SET value = '1' WHERE name = 'version';",
+            )
+            .execute(&mut conn)
+            .await?;
+            migrate_db(&mut conn, MIGRATIONS).await?;
+            let pending: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM profiles WHERE rotate_key IS NOT NULL OR rotate_pos IS NOT NULL",
+            )
+            .fetch_one(&mut conn)
+            .await?;
+            assert_eq!(pending, 0);
+            Result::<_, Error>::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn sqlite_resume_profile_key_rotation() {
+        block_on(async {
+            let key = generate_raw_store_key(None)?;
+            let db = SqliteStoreOptions::in_memory()
+                .provision(StoreKeyMethod::RawKey, key, None, false)
+                .await?;
+            let profile = db.get_profile_name().to_string();
+            let mut session = db.session(None).await?;
+            for idx in 0..(PAGE_SIZE + 5) {
+                session
+                    .insert(
+                        "category",
+                        &format!("name-{}", idx),
+                        b"value",
+                        Some(&[EntryTag::Encrypted("tag".into(), idx.to_string())]),
+                        None,
+                    )
+                    .await?;
+            }
+            drop(session);
+
+            // simulate a rotation interrupted after the first batch of records
+            let inner = db.inner();
+            let old_key = inner.load_profile_key(profile.clone()).await?;
+            let new_key = ProfileKey::new()?;
+            let enc_key = encode_profile_key(&new_key, &inner.key_cache.store_key)?;
+            sqlx::query("UPDATE profiles SET rotate_key = ?1, rotate_pos = 0 WHERE name = ?2")
+                .bind(&enc_key)
+                .bind(&profile)
+                .execute(&inner.conn_pool)
+                .await?;
+            inner.key_cache.remove_profile(&profile).await;
+            let err = db
+                .session(None)
+                .await?
+                .count("category", None)
+                .await
+                .expect_err("Expected error for rotation in progress");
+            assert_eq!(err.kind(), crate::ErrorKind::Busy);
+
+            assert_eq!(
+                db.rotate_profile_key(profile.clone()).await?,
+                PAGE_SIZE as i64 + 5
+            );
+            let rotated_key = inner.load_profile_key(profile.clone()).await?;
+            assert_ne!(rotated_key, old_key);
+            assert_eq!(rotated_key, new_key.to_bytes()?);
+
+            let mut session = db.session(None).await?;
+            let found = session
+                .fetch_all("category", Some(TagFilter::is_eq("tag", "3")), None, false)
+                .await?;
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].name, "name-3");
+            assert_eq!(session.count("category", None).await?, PAGE_SIZE as i64 + 5);
+            Result::<_, Error>::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn sqlite_profile_key_rotation_concurrent_writer() {
+        let fname = format!("sqlite-test-{}.db", uuid::Uuid::new_v4());
+        block_on(async {
+            let key = generate_raw_store_key(None)?;
+            let db =
+                SqliteStoreOptions::new(format!("sqlite://{}?max_connections=2", fname).as_str())?
+                    .provision(StoreKeyMethod::RawKey, key, None, false)
+                    .await?;
+            let profile = db.get_profile_name().to_string();
+            // the writer keeps its connection and the old profile key
+            let mut writer = db.session(None).await?;
+            writer.insert("category", "a", b"value", None, None).await?;
+
+            // simulate a rotation started by another instance of the store
+            let inner = db.inner();
+            let enc_key = encode_profile_key(&ProfileKey::new()?, &inner.key_cache.store_key)?;
+            sqlx::query("UPDATE profiles SET rotate_key = ?1, rotate_pos = 0 WHERE name = ?2")
+                .bind(&enc_key)
+                .bind(&profile)
+                .execute(&inner.conn_pool)
+                .await?;
+            let err = writer
+                .insert("category", "b", b"value", None, None)
+                .await
+                .expect_err("Expected error for rotation in progress");
+            assert_eq!(err.kind(), crate::ErrorKind::Busy);
+            let err = writer
+                .update_tags(
+                    "category",
+                    "a",
+                    &[EntryTag::Encrypted("tag".into(), "value".into())],
+                    &[],
+                )
+                .await
+                .expect_err("Expected error for rotation in progress");
+            assert_eq!(err.kind(), crate::ErrorKind::Busy);
+
+            assert_eq!(db.rotate_profile_key(profile.clone()).await?, 1);
+            // the key held by the writer has been replaced
+            let err = writer
+                .insert("category", "b", b"value", None, None)
+                .await
+                .expect_err("Expected error for replaced profile key");
+            assert_eq!(err.kind(), crate::ErrorKind::Conflict);
+            let err = writer
+                .remove("category", "a")
+                .await
+                .expect_err("Expected error for replaced profile key");
+            assert_eq!(err.kind(), crate::ErrorKind::Conflict);
+            let err = writer
+                .set_expiry("category", "a", Some(1000))
+                .await
+                .expect_err("Expected error for replaced profile key");
+            assert_eq!(err.kind(), crate::ErrorKind::Conflict);
+            let err = writer
+                .set_expiry_all("category", None, Some(1000))
+                .await
+                .expect_err("Expected error for replaced profile key");
+            assert_eq!(err.kind(), crate::ErrorKind::Conflict);
+            let err = writer
+                .remove_all("category", None)
+                .await
+                .expect_err("Expected error for replaced profile key");
+            assert_eq!(err.kind(), crate::ErrorKind::Conflict);
+            drop(writer);
+
+            let mut session = db.session(None).await?;
+            session
+                .insert("category", "b", b"value", None, None)
+                .await?;
+            let found = session.fetch_all("category", None, None, false).await?;
+            assert_eq!(found.len(), 2);
+            assert!(found.iter().all(|entry| entry.tags.is_empty()));
+            drop(session);
+            db.close().await?;
+            SqliteStoreOptions::new(fname.as_str())?.remove().await?;
+            Result::<_, Error>::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn sqlite_verify_repair() {
+        block_on(async {
+            let key = generate_raw_store_key(None)?;
+            let db = SqliteStoreOptions::in_memory()
+                .provision(StoreKeyMethod::RawKey, key, None, false)
+                .await?;
+            let profile = db.get_profile_name().to_string();
+            let mut session = db.session(None).await?;
+            for name in ["a", "b"].iter() {
+                session
+                    .insert(
+                        "category",
+                        name,
+                        b"value",
+                        Some(&[EntryTag::Encrypted("tag".into(), name.to_string())]),
+                        None,
+                    )
+                    .await?;
+            }
+            drop(session);
+            let broken = db.create_profile(None).await?;
+            db.session(Some(broken.clone()))
+                .await?
+                .insert(
+                    "category",
+                    "c",
+                    b"value",
+                    Some(&[EntryTag::Plaintext("tag".into(), "c".into())]),
+                    None,
+                )
+                .await?;
+
+            // corrupt a record value, a tag value and a profile key, and add an orphaned tag
+            let mut conn = db.inner().conn_pool.acquire().await?;
+            sqlx::query(
+                "UPDATE items SET value = X'00' WHERE id = 1;
+                UPDATE items_tags SET value = X'0000' WHERE item_id = 2;",
+            )
+            .execute(&mut conn)
+            .await?;
+            sqlx::query("UPDATE profiles SET profile_key = X'00' WHERE name = ?1")
+                .bind(&broken)
+                .execute(&mut conn)
+                .await?;
+            sqlx::query(
+                "PRAGMA foreign_keys = OFF;
+                INSERT INTO items_tags (item_id, name, value, plaintext) VALUES (99, X'00', X'00', 1);
+                PRAGMA foreign_keys = ON;",
+            )
+            .execute(&mut conn)
+            .await?;
+            drop(conn);
+
+            let report = db.verify(None, false).await?;
+            assert_eq!(report.profiles_checked, 2);
+            assert_eq!(report.entries_checked, 2);
+            assert_eq!(report.tags_checked, 1);
+            assert_eq!(
+                report.issues,
+                vec![
+                    VerifyIssue::Entry {
+                        profile: profile.clone(),
+                        kind: EntryKind::Item,
+                        id: 1
+                    },
+                    VerifyIssue::Tag {
+                        profile: profile.clone(),
+                        kind: EntryKind::Item,
+                        category: "category".to_string(),
+                        name: "b".to_string()
+                    },
+                    VerifyIssue::ProfileKey {
+                        profile: broken.clone()
+                    },
+                    VerifyIssue::OrphanTag { id: 4 },
+                ]
+            );
+            assert_eq!(report.rows_removed, 0);
+
+            // removed records and profiles are counted along with their tags and records
+            let report = db.verify(None, true).await?;
+            assert_eq!(report.issues.len(), 4);
+            assert_eq!(report.rows_removed, 7);
+
+            let report = db.verify(None, false).await?;
+            assert!(report.is_valid());
+            assert_eq!(report.profiles_checked, 1);
+            let mut session = db.session(None).await?;
+            let found = session.fetch("category", "b", false).await?.unwrap();
+            assert!(found.tags.is_empty());
+            assert!(session.fetch("category", "a", false).await?.is_none());
+            drop(session);
+
+            // a cached profile key which differs from the stored key prevents repairs
+            let (pid, _) = db.inner().key_cache.get_profile(&profile).await.unwrap();
+            db.inner()
+                .key_cache
+                .add_profile(profile.clone(), pid, Arc::new(ProfileKey::new()?))
+                .await;
+            sqlx::query("UPDATE items SET value = X'00' WHERE id = 2")
+                .execute(&db.inner().conn_pool)
+                .await?;
+            let report = db.verify(None, true).await?;
+            assert_eq!(
+                report.issues,
+                vec![
+                    VerifyIssue::ProfileKeyMismatch {
+                        profile: profile.clone()
+                    },
+                    VerifyIssue::Entry {
+                        profile: profile.clone(),
+                        kind: EntryKind::Item,
+                        id: 2
+                    },
+                ]
+            );
+            assert_eq!(report.rows_removed, 0);
+            Result::<_, Error>::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn sqlite_verify_keeps_default_profile() {
+        block_on(async {
+            let key = generate_raw_store_key(None)?;
+            let db = SqliteStoreOptions::in_memory()
+                .provision(StoreKeyMethod::RawKey, key, None, false)
+                .await?;
+            let profile = db.get_profile_name().to_string();
+            let rotating = db.create_profile(None).await?;
+            db.session(Some(rotating.clone()))
+                .await?
+                .insert("category", "a", b"value", None, None)
+                .await?;
+
+            // a pending rotation is reported without checking the profile
+            let inner = db.inner();
+            let enc_key = encode_profile_key(&ProfileKey::new()?, &inner.key_cache.store_key)?;
+            sqlx::query("UPDATE profiles SET rotate_key = ?1, rotate_pos = 0 WHERE name = ?2")
+                .bind(&enc_key)
+                .bind(&rotating)
+                .execute(&inner.conn_pool)
+                .await?;
+            let report = db.verify(None, false).await?;
+            assert_eq!(report.profiles_checked, 1);
+            assert_eq!(
+                report.issues,
+                vec![VerifyIssue::ProfileKeyRotation {
+                    profile: rotating.clone()
+                }]
+            );
+
+            // the default profile is not removed when its key cannot be unwrapped
+            sqlx::query("UPDATE profiles SET profile_key = X'00' WHERE name = ?1")
+                .bind(&profile)
+                .execute(&inner.conn_pool)
+                .await?;
+            let report = db.verify(None, true).await?;
+            assert_eq!(
+                report.issues,
+                vec![
+                    VerifyIssue::ProfileKey {
+                        profile: profile.clone()
+                    },
+                    VerifyIssue::ProfileKeyRotation {
+                        profile: rotating.clone()
+                    },
+                ]
+            );
+            assert_eq!(report.rows_removed, 0);
+            let mut profiles = db.list_profiles().await?;
+            profiles.sort();
+            let mut expected = vec![profile, rotating];
+            expected.sort();
+            assert_eq!(profiles, expected);
+            Result::<_, Error>::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn sqlite_parse_migrate_option() {
+        assert!(
+            SqliteStoreOptions::new("sqlite://:memory:")
+                .unwrap()
+                .migrate
+        );
+        assert!(
+            !SqliteStoreOptions::new("sqlite://:memory:?migrate=false")
+                .unwrap()
+                .migrate
+        );
+        assert!(SqliteStoreOptions::new("sqlite://:memory:?migrate=maybe").is_err());
+    }
+
+    #[test]
+    fn sqlite_snapshot_session() {
+        let fname = format!("sqlite-test-{}.db", uuid::Uuid::new_v4());
+        block_on(async {
+            let key = generate_raw_store_key(None)?;
+            let db =
+                SqliteStoreOptions::new(format!("sqlite://{}?max_connections=2", fname).as_str())?
+                    .provision(StoreKeyMethod::RawKey, key, None, false)
+                    .await?;
+            let backend = db.inner();
+            let insert = |name| async move {
+                let mut session = backend.session(None, false)?;
+                session
+                    .update(
+                        EntryKind::Item,
+                        EntryOperation::Insert,
+                        "category",
+                        name,
+                        Some(b"value"),
+                        None,
+                        None,
+                    )
+                    .await
+            };
+            insert("a").await?;
+
+            let mut snapshot = backend.snapshot_session(None)?;
+            assert_eq!(snapshot.count(EntryKind::Item, "category", None).await?, 1);
+            // writers are not blocked, and their changes are not visible to the snapshot
+            insert("b").await?;
+            assert_eq!(snapshot.count(EntryKind::Item, "category", None).await?, 1);
+            snapshot.close(false).await?;
+
+            let mut session = backend.session(None, false)?;
+            assert_eq!(session.count(EntryKind::Item, "category", None).await?, 2);
+            drop(session);
+            db.close().await?;
+            SqliteStoreOptions::new(fname.as_str())?.remove().await?;
+            Result::<_, Error>::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn sqlite_read_only_error() {
+        assert!(
+            SqliteStoreOptions::new("sqlite://:memory:?mode=ro")
+                .unwrap()
+                .read_only
+        );
+        assert!(SqliteStoreOptions::new("sqlite://:memory:?mode=readonly").is_err());
+
+        let fname = format!("sqlite-test-{}.db", uuid::Uuid::new_v4());
+        block_on(async {
+            let key = generate_raw_store_key(None)?;
+            SqliteStoreOptions::new(fname.as_str())?
+                .provision(StoreKeyMethod::RawKey, key.as_ref(), None, false)
+                .await?
+                .close()
+                .await?;
+            let db = SqliteStoreOptions::new(format!("sqlite://{}?mode=ro", fname).as_str())?
+                .open(Some(StoreKeyMethod::RawKey), key.as_ref(), None)
+                .await?;
+            let err = sqlx::query("INSERT INTO config (name, value) VALUES ('test', 'test')")
+                .execute(&db.inner().conn_pool)
+                .await
+                .map_err(Error::from)
+                .expect_err("Expected error for read-only insert");
+            assert_eq!(err.kind(), crate::ErrorKind::ReadOnly);
+            db.close().await?;
+            SqliteStoreOptions::new(fname.as_str())?.remove().await?;
+            Result::<_, Error>::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn sqlite_parse_connection_options() {
+        let opts = SqliteStoreOptions::new(
+            "sqlite://:memory:?journal_mode=delete&synchronous=normal&busy_timeout=250\
+            &cache_size=-4000&secure_delete=true&locking_mode=exclusive",
+        )
+        .unwrap();
+        assert_eq!(opts.busy_timeout, std::time::Duration::from_millis(250));
+        assert_eq!(opts.cache_size, Some(-4000));
+        assert_eq!(opts.secure_delete, Some(true));
+        assert_eq!(opts.max_connections, 1);
+        assert!(SqliteStoreOptions::new("sqlite://:memory:?journal_mode=fast").is_err());
+        assert!(SqliteStoreOptions::new("sqlite://:memory:?synchronous=always").is_err());
+        assert!(SqliteStoreOptions::new("sqlite://:memory:?busy_timeout=-1").is_err());
+        assert!(SqliteStoreOptions::new("sqlite://:memory:?locking_mode=shared").is_err());
+        assert!(SqliteStoreOptions::new(
+            "sqlite://:memory:?locking_mode=exclusive&max_connections=2"
+        )
+        .is_err());
+
+        block_on(async {
+            let key = generate_raw_store_key(None)?;
+            let db = opts
+                .provision(StoreKeyMethod::RawKey, key, None, false)
+                .await?;
+            let row = sqlx::query(
+                "SELECT synchronous, cache_size, secure_delete
+                FROM pragma_synchronous, pragma_cache_size, pragma_secure_delete",
+            )
+            .fetch_one(&db.inner().conn_pool)
+            .await?;
+            assert_eq!(row.try_get::<i64, _>(0)?, 1);
+            assert_eq!(row.try_get::<i64, _>(1)?, -4000);
+            assert_eq!(row.try_get::<i64, _>(2)?, 1);
             Result::<_, Error>::Ok(())
         })
         .unwrap();
@@ -2,56 +2,211 @@ use std::borrow::Cow;
 use std::fs::remove_file;
 use std::io::ErrorKind as IoErrorKind;
 use std::str::FromStr;
+use std::time::Duration;
 
 use sqlx::{
-    sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions},
-    ConnectOptions, Error as SqlxError, Row,
+    sqlite::{
+        SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqliteLockingMode, SqlitePool,
+        SqlitePoolOptions, SqliteSynchronous,
+    },
+    ConnectOptions, Connection, Error as SqlxError, Executor, Row,
 };
 
 use super::SqliteStore;
 use crate::{
     backend::{
-        db_utils::{init_keys, random_profile_name},
-        types::ManageBackend,
+        db_utils::{init_keys, latest_schema_version, random_profile_name, select_migrations},
+        types::{ManageBackend, SchemaMigration},
     },
     error::Error,
     future::{unblock, BoxFuture},
     protect::{KeyCache, PassKey, StoreKeyMethod, StoreKeyReference},
-    storage::{IntoOptions, Options, Store},
+    storage::{IntoOptions, Options, Store, TagQueryLimits},
 };
 
+const DEFAULT_BUSY_TIMEOUT: u64 = 5000;
+const DEFAULT_JOURNAL_MODE: SqliteJournalMode = SqliteJournalMode::Wal;
+const DEFAULT_LOCKING_MODE: SqliteLockingMode = SqliteLockingMode::Normal;
+const DEFAULT_SYNCHRONOUS: SqliteSynchronous = SqliteSynchronous::Full;
+
+/// Schema migrations for Sqlite stores, in order of increasing version
+///
+/// New stores are created with the latest schema, so each change must be
+/// reflected in `init_db` as well.
+pub(crate) const MIGRATIONS: &[SchemaMigration] = &[
+    SchemaMigration {
+        version: 2,
+        description: "Add profile key rotation state",
+        sql: "ALTER TABLE profiles ADD COLUMN rotate_key BLOB NULL;
+            ALTER TABLE profiles ADD COLUMN rotate_pos INTEGER NULL;",
+    },
+    SchemaMigration {
+        version: 3,
+        description: "Add record versions",
+        sql: "ALTER TABLE items ADD COLUMN version INTEGER NOT NULL DEFAULT 1;",
+    },
+    SchemaMigration {
+        version: 4,
+        description: "Add record timestamps",
+        sql: "ALTER TABLE items ADD COLUMN created DATETIME NULL;
+            ALTER TABLE items ADD COLUMN updated DATETIME NULL;",
+    },
+];
+
 /// Configuration options for Sqlite stores
 #[derive(Debug)]
 pub struct SqliteStoreOptions {
     pub(crate) in_memory: bool,
     pub(crate) path: String,
     pub(crate) max_connections: u32,
+    pub(crate) migrate: bool,
+    pub(crate) read_only: bool,
+    pub(crate) query_limits: TagQueryLimits,
+    pub(crate) busy_timeout: Duration,
+    pub(crate) cache_size: Option<i64>,
+    pub(crate) journal_mode: SqliteJournalMode,
+    pub(crate) locking_mode: SqliteLockingMode,
+    pub(crate) secure_delete: Option<bool>,
+    pub(crate) synchronous: SqliteSynchronous,
 }
 
 impl SqliteStoreOptions {
     /// Initialize `SqliteStoreOptions` from a generic set of options
+    ///
+    /// The following query parameters are supported, and are applied to
+    /// each connection in the pool:
+    /// - `max_connections`: the maximum size of the connection pool,
+    ///   defaulting to the number of CPUs
+    /// - `migrate`: whether to upgrade an outdated schema when opening
+    ///   the store, defaulting to `true`
+    /// - `mode`: either `rw` (the default) or `ro` to open the store using
+    ///   read-only connections
+    /// - `journal_mode`: one of `delete`, `truncate`, `persist`, `memory`,
+    ///   `wal` or `off`, defaulting to `wal`
+    /// - `synchronous`: one of `off`, `normal`, `full` or `extra`,
+    ///   defaulting to `full`
+    /// - `busy_timeout`: the time in milliseconds to wait for a locked
+    ///   database, defaulting to 5000
+    /// - `cache_size`: the page cache size, as a number of pages or a
+    ///   negative number of KiB, defaulting to the SQLite default of -2000
+    /// - `secure_delete`: whether to overwrite deleted content, defaulting
+    ///   to the SQLite default of `false`
+    /// - `locking_mode`: either `normal` or `exclusive`, defaulting to `normal`.
+    ///   The exclusive lock is held by a single connection, so this mode limits
+    ///   the pool to one connection and cannot be combined with a larger
+    ///   `max_connections`
+    /// - `max_query_depth`, `max_query_clauses` and `max_query_args`: the
+    ///   limits applied to tag filters, see [`TagQueryLimits`]
     pub fn new<'a>(options: impl IntoOptions<'a>) -> Result<Self, Error> {
         let mut opts = options.into_options()?;
         let max_connections = if let Some(max_conn) = opts.query.remove("max_connections") {
-            max_conn
+            Some(
+                max_conn
+                    .parse()
+                    .map_err(err_map!(Input, "Error parsing 'max_connections' parameter"))?,
+            )
+        } else {
+            None
+        };
+        let migrate = if let Some(migrate) = opts.query.remove("migrate") {
+            migrate
                 .parse()
-                .map_err(err_map!(Input, "Error parsing 'max_connections' parameter"))?
+                .map_err(err_map!(Input, "Error parsing 'migrate' parameter"))?
         } else {
-            num_cpus::get() as u32
+            true
         };
+        let read_only = match opts.query.remove("mode").as_deref() {
+            None | Some("rw") => false,
+            Some("ro") => true,
+            Some(_) => return Err(err_msg!(Input, "Error parsing 'mode' parameter")),
+        };
+        let busy_timeout = if let Some(timeout) = opts.query.remove("busy_timeout") {
+            timeout
+                .parse()
+                .map_err(err_map!(Input, "Error parsing 'busy_timeout' parameter"))?
+        } else {
+            DEFAULT_BUSY_TIMEOUT
+        };
+        let cache_size = if let Some(cache_size) = opts.query.remove("cache_size") {
+            Some(
+                cache_size
+                    .parse()
+                    .map_err(err_map!(Input, "Error parsing 'cache_size' parameter"))?,
+            )
+        } else {
+            None
+        };
+        let journal_mode = if let Some(mode) = opts.query.remove("journal_mode") {
+            SqliteJournalMode::from_str(&mode)
+                .map_err(err_map!(Input, "Error parsing 'journal_mode' parameter"))?
+        } else {
+            DEFAULT_JOURNAL_MODE
+        };
+        let locking_mode = if let Some(mode) = opts.query.remove("locking_mode") {
+            SqliteLockingMode::from_str(&mode)
+                .map_err(err_map!(Input, "Error parsing 'locking_mode' parameter"))?
+        } else {
+            DEFAULT_LOCKING_MODE
+        };
+        let max_connections = match (locking_mode, max_connections) {
+            // an exclusive lock is held by one connection and blocks any others
+            (SqliteLockingMode::Exclusive, None | Some(1)) => 1,
+            (SqliteLockingMode::Exclusive, Some(_)) => {
+                return Err(err_msg!(
+                    Input,
+                    "The 'exclusive' locking mode requires 'max_connections=1'"
+                ));
+            }
+            (_, max_conn) => max_conn.unwrap_or_else(|| num_cpus::get() as u32),
+        };
+        let secure_delete = if let Some(secure_delete) = opts.query.remove("secure_delete") {
+            Some(
+                secure_delete
+                    .parse()
+                    .map_err(err_map!(Input, "Error parsing 'secure_delete' parameter"))?,
+            )
+        } else {
+            None
+        };
+        let synchronous = if let Some(synchronous) = opts.query.remove("synchronous") {
+            SqliteSynchronous::from_str(&synchronous)
+                .map_err(err_map!(Input, "Error parsing 'synchronous' parameter"))?
+        } else {
+            DEFAULT_SYNCHRONOUS
+        };
+        let query_limits = opts.take_query_limits()?;
         let mut path = opts.host.to_string();
         path.push_str(&*opts.path);
         Ok(Self {
             in_memory: path == ":memory:",
             path,
             max_connections,
+            migrate,
+            read_only,
+            query_limits,
+            busy_timeout: Duration::from_millis(busy_timeout),
+            cache_size,
+            journal_mode,
+            locking_mode,
+            secure_delete,
+            synchronous,
         })
     }
 
     async fn pool(&self, auto_create: bool) -> std::result::Result<SqlitePool, SqlxError> {
-        #[allow(unused_mut)]
-        let mut conn_opts =
-            SqliteConnectOptions::from_str(self.path.as_ref())?.create_if_missing(auto_create);
+        let mut conn_opts = SqliteConnectOptions::from_str(self.path.as_ref())?
+            .create_if_missing(auto_create)
+            .read_only(self.read_only)
+            .busy_timeout(self.busy_timeout)
+            .journal_mode(self.journal_mode)
+            .locking_mode(self.locking_mode)
+            .synchronous(self.synchronous);
+        if let Some(cache_size) = self.cache_size {
+            conn_opts = conn_opts.pragma("cache_size", cache_size.to_string());
+        }
+        if let Some(secure_delete) = self.secure_delete {
+            conn_opts = conn_opts.pragma("secure_delete", secure_delete.to_string());
+        }
         #[cfg(feature = "log")]
         {
             conn_opts.log_statements(log::LevelFilter::Debug);
@@ -76,6 +231,12 @@ impl SqliteStoreOptions {
         profile: Option<&'_ str>,
         recreate: bool,
     ) -> Result<Store<SqliteStore>, Error> {
+        if self.read_only {
+            return Err(err_msg!(
+                Input,
+                "Cannot provision a store in read-only mode"
+            ));
+        }
         if recreate && !self.in_memory {
             try_remove_file(self.path.to_string()).await?;
         }
@@ -95,6 +256,9 @@ impl SqliteStoreOptions {
                     pass_key,
                     profile,
                     self.path.to_string(),
+                    self.migrate,
+                    false,
+                    self.query_limits,
                 )
                 .await;
             }
@@ -111,17 +275,13 @@ impl SqliteStoreOptions {
             default_profile,
             key_cache,
             self.path.to_string(),
+            false,
+            self.query_limits,
         )))
     }
 
-    /// Open an existing Sqlite store from this set of configuration options
-    pub async fn open(
-        self,
-        method: Option<StoreKeyMethod>,
-        pass_key: PassKey<'_>,
-        profile: Option<&'_ str>,
-    ) -> Result<Store<SqliteStore>, Error> {
-        let conn_pool = match self.pool(false).await {
+    async fn existing_pool(&self) -> Result<SqlitePool, Error> {
+        match self.pool(false).await {
             Ok(pool) => Ok(pool),
             Err(SqlxError::Database(db_err)) => {
                 if db_err.code().expect("Expected SQLite error code") == "14" {
@@ -135,8 +295,44 @@ impl SqliteStoreOptions {
                 }
             }
             Err(err) => Err(err.into()),
-        }?;
-        Ok(open_db(conn_pool, method, pass_key, profile, self.path.to_string()).await?)
+        }
+    }
+
+    /// Open an existing Sqlite store from this set of configuration options
+    ///
+    /// An outdated store schema is upgraded unless the `migrate` option is disabled,
+    /// in which case an error of kind `Migration` is returned.
+    pub async fn open(
+        self,
+        method: Option<StoreKeyMethod>,
+        pass_key: PassKey<'_>,
+        profile: Option<&'_ str>,
+    ) -> Result<Store<SqliteStore>, Error> {
+        let conn_pool = self.existing_pool().await?;
+        Ok(open_db(
+            conn_pool,
+            method,
+            pass_key,
+            profile,
+            self.path.to_string(),
+            self.migrate,
+            self.read_only,
+            self.query_limits,
+        )
+        .await?)
+    }
+
+    /// List the schema migrations which would be applied when opening the store,
+    /// without making any changes
+    pub async fn pending_migrations(self) -> Result<Vec<SchemaMigration>, Error> {
+        let conn_pool = self.existing_pool().await?;
+        let version: Option<String> =
+            sqlx::query_scalar("SELECT value FROM config WHERE name = 'version'")
+                .fetch_optional(&conn_pool)
+                .await?;
+        conn_pool.close().await;
+        let version = version.ok_or_else(|| err_msg!(Unsupported, "Store version not found"))?;
+        Ok(select_migrations(MIGRATIONS, &version)?.to_vec())
     }
 
     /// Remove the Sqlite store defined by these configuration options
@@ -144,7 +340,12 @@ impl SqliteStoreOptions {
         if self.in_memory {
             Ok(true)
         } else {
-            try_remove_file(self.path.to_string()).await
+            let removed = try_remove_file(self.path.to_string()).await?;
+            // left behind when the last connection was read-only
+            for suffix in ["-wal", "-shm"].iter() {
+                try_remove_file(format!("{}{}", self.path, suffix)).await?;
+            }
+            Ok(removed)
         }
     }
 
@@ -171,8 +372,11 @@ impl<'a> ManageBackend<'a> for SqliteStoreOptions {
         method: Option<StoreKeyMethod>,
         pass_key: PassKey<'a>,
         profile: Option<&'a str>,
+        read_only: bool,
     ) -> BoxFuture<'a, Result<Store<SqliteStore>, Error>> {
-        Box::pin(self.open(method, pass_key, profile))
+        let mut opts = self;
+        opts.read_only |= read_only;
+        Box::pin(opts.open(method, pass_key, profile))
     }
 
     fn provision_backend(
@@ -188,6 +392,10 @@ impl<'a> ManageBackend<'a> for SqliteStoreOptions {
     fn remove_backend(self) -> BoxFuture<'a, Result<bool, Error>> {
         Box::pin(self.remove())
     }
+
+    fn pending_migrations_backend(self) -> BoxFuture<'a, Result<Vec<SchemaMigration>, Error>> {
+        Box::pin(self.pending_migrations())
+    }
 }
 
 async fn init_db(
@@ -216,13 +424,15 @@ async fn init_db(
         INSERT INTO config (name, value) VALUES
             ("default_profile", ?1),
             ("key", ?2),
-            ("version", "1");
+            ("version", ?4);
 
         CREATE TABLE profiles (
             id INTEGER NOT NULL,
             name TEXT NOT NULL,
             reference TEXT NULL,
             profile_key BLOB NULL,
+            rotate_key BLOB NULL,
+            rotate_pos INTEGER NULL,
             PRIMARY KEY(id)
         );
         CREATE UNIQUE INDEX ix_profile_name ON profiles (name);
@@ -235,6 +445,9 @@ async fn init_db(
             name BLOB NOT NULL,
             value BLOB NOT NULL,
             expiry DATETIME NULL,
+            version INTEGER NOT NULL DEFAULT 1,
+            created DATETIME NULL,
+            updated DATETIME NULL,
             PRIMARY KEY (id),
             FOREIGN KEY (profile_id) REFERENCES profiles (id)
                 ON DELETE CASCADE ON UPDATE CASCADE
@@ -264,6 +477,7 @@ async fn init_db(
     .bind(profile_name)
     .bind(store_key_ref)
     .bind(enc_profile_key)
+    .bind(latest_schema_version(MIGRATIONS).to_string())
     .execute(&mut conn)
     .await?;
 
@@ -285,9 +499,12 @@ async fn open_db(
     pass_key: PassKey<'_>,
     profile: Option<&str>,
     path: String,
+    migrate: bool,
+    read_only: bool,
+    query_limits: TagQueryLimits,
 ) -> Result<Store<SqliteStore>, Error> {
     let mut conn = conn_pool.acquire().await?;
-    let mut ver_ok = false;
+    let mut version: Option<String> = None;
     let mut default_profile: Option<String> = None;
     let mut store_key_ref: Option<String> = None;
 
@@ -306,16 +523,21 @@ async fn open_db(
                 store_key_ref.replace(row.try_get(1)?);
             }
             "version" => {
-                if row.try_get::<&str, _>(1)? != "1" {
-                    return Err(err_msg!(Unsupported, "Unsupported store version"));
-                }
-                ver_ok = true;
+                version.replace(row.try_get(1)?);
             }
             _ => (),
         }
     }
-    if !ver_ok {
-        return Err(err_msg!(Unsupported, "Store version not found"));
+    let version = version.ok_or_else(|| err_msg!(Unsupported, "Store version not found"))?;
+    if !select_migrations(MIGRATIONS, &version)?.is_empty() {
+        if !migrate || read_only {
+            return Err(err_msg!(
+                Migration,
+                "Store schema version {} must be migrated",
+                version
+            ));
+        }
+        migrate_db(&mut conn, MIGRATIONS).await?;
     }
     let profile = profile
         .map(str::to_string)
@@ -347,10 +569,43 @@ async fn open_db(
     key_cache.add_profile_mut(profile.clone(), profile_id, profile_key);
 
     Ok(Store::new(SqliteStore::new(
-        conn_pool, profile, key_cache, path,
+        conn_pool,
+        profile,
+        key_cache,
+        path,
+        read_only,
+        query_limits,
     )))
 }
 
+pub(crate) async fn migrate_db(
+    conn: &mut SqliteConnection,
+    migrations: &[SchemaMigration],
+) -> Result<(), Error> {
+    let mut txn = conn.begin().await?;
+    // take the write lock before checking the version, so that concurrent
+    // migrations of the same store are serialized
+    sqlx::query("UPDATE config SET value = value WHERE name = 'version'")
+        .execute(&mut txn)
+        .await?;
+    let version: String = sqlx::query_scalar("SELECT value FROM config WHERE name = 'version'")
+        .fetch_one(&mut txn)
+        .await?;
+    for migration in select_migrations(migrations, &version)? {
+        info!(
+            "Migrating store schema to version {}: {}",
+            migration.version, migration.description
+        );
+        txn.execute(migration.sql).await?;
+        sqlx::query("UPDATE config SET value = ?1 WHERE name = 'version'")
+            .bind(migration.version.to_string())
+            .execute(&mut txn)
+            .await?;
+    }
+    txn.commit().await?;
+    Ok(())
+}
+
 async fn try_remove_file(path: String) -> Result<bool, Error> {
     unblock(|| match remove_file(path) {
         Ok(()) => Ok(true),
@@ -1,8 +1,15 @@
+use std::collections::BTreeMap;
+use std::sync::Weak;
+
 use crate::{
+    crypto::buffer::SecretBytes,
     error::Error,
     future::BoxFuture,
     protect::{PassKey, StoreKeyMethod},
-    storage::{Entry, EntryKind, EntryOperation, EntryTag, Scan, TagFilter},
+    storage::{
+        CategoryStats, Entry, EntryChange, EntryKind, EntryOperation, EntryTag, EntryUpdate,
+        OrderBy, Scan, TagFilter, TagNameStats, TagQueryLimits, VerifyReport, WatchRegistry,
+    },
 };
 
 /// Represents a generic backend implementation
@@ -16,23 +23,104 @@ pub trait Backend: Send + Sync {
     /// Get the name of the active profile
     fn get_profile_name(&self) -> &str;
 
+    /// Determine whether the store was opened in read-only mode
+    fn is_read_only(&self) -> bool;
+
+    /// Get the limits applied to the tag filters of queries against the store
+    fn query_limits(&self) -> TagQueryLimits;
+
     /// Remove an existing profile
     fn remove_profile(&self, name: String) -> BoxFuture<'_, Result<bool, Error>>;
 
+    /// Rename an existing profile
+    ///
+    /// Returns `false` if the profile was not found
+    fn rename_profile(
+        &self,
+        from_name: String,
+        to_name: String,
+    ) -> BoxFuture<'_, Result<bool, Error>>;
+
+    /// Copy an existing profile and all of its unexpired records to a new profile
+    ///
+    /// The new profile is assigned the same profile key as the original.
+    /// Returns `false` if the source profile was not found
+    fn copy_profile(
+        &self,
+        from_name: String,
+        to_name: String,
+    ) -> BoxFuture<'_, Result<bool, Error>>;
+
+    /// Replace the profile key of an existing profile, re-encrypting its records
+    ///
+    /// Records are updated in batches, and an interrupted rotation is resumed
+    /// when this method is called again. Returns the number of updated records
+    fn rotate_profile_key(&self, name: String) -> BoxFuture<'_, Result<i64, Error>>;
+
+    /// Load the serialized profile key for an existing profile
+    fn load_profile_key(&self, name: String) -> BoxFuture<'_, Result<SecretBytes, Error>>;
+
+    /// Create a profile using an existing serialized profile key
+    ///
+    /// A profile with the same name which does not contain any records is
+    /// assigned the new key. Otherwise, the existing profile key must match.
+    fn import_profile(&self, name: String, key: SecretBytes) -> BoxFuture<'_, Result<(), Error>>;
+
+    /// Get the names of all existing profiles
+    fn list_profiles(&self) -> BoxFuture<'_, Result<Vec<String>, Error>>;
+
+    /// Get the distinct categories of the records of a given kind in a profile
+    fn list_categories(
+        &self,
+        profile: Option<String>,
+        kind: EntryKind,
+    ) -> BoxFuture<'_, Result<Vec<String>, Error>>;
+
     /// Create a [`Scan`] against the store
+    ///
+    /// Records are returned in insertion order unless `order_by` is given
     fn scan(
         &self,
         profile: Option<String>,
         kind: EntryKind,
         category: String,
         tag_filter: Option<TagFilter>,
+        order_by: Option<OrderBy>,
         offset: Option<i64>,
         limit: Option<i64>,
     ) -> BoxFuture<'_, Result<Scan<'static, Entry>, Error>>;
 
+    /// Resume a [`Scan`] following the position recorded in a continuation token
+    ///
+    /// The profile, category and tag filter of the original scan are restored from the token
+    fn resume_scan(
+        &self,
+        token: String,
+        limit: Option<i64>,
+    ) -> BoxFuture<'_, Result<Scan<'static, Entry>, Error>>;
+
     /// Create a new session against the store
     fn session(&self, profile: Option<String>, transaction: bool) -> Result<Self::Session, Error>;
 
+    /// Create a transaction session which reads a consistent snapshot of the store
+    ///
+    /// The session is intended for reading only, and is closed without committing
+    fn snapshot_session(&self, profile: Option<String>) -> Result<Self::Session, Error>;
+
+    /// Remove all expired records from the store
+    ///
+    /// Returns the number of records removed from each profile
+    fn purge_expired(&self) -> BoxFuture<'_, Result<BTreeMap<String, i64>, Error>>;
+
+    /// Check the integrity of the stored profiles, records and tags
+    ///
+    /// When `repair` is set, rows which cannot be recovered are removed
+    fn verify(
+        &self,
+        profile: Option<String>,
+        repair: bool,
+    ) -> BoxFuture<'_, Result<VerifyReport, Error>>;
+
     /// Replace the wrapping key of the store
     fn rekey_backend(
         &mut self,
@@ -40,21 +128,44 @@ pub trait Backend: Send + Sync {
         key: PassKey<'_>,
     ) -> BoxFuture<'_, Result<(), Error>>;
 
+    /// Determine whether committed record changes are published through the
+    /// backend, making them visible to other store instances
+    fn publishes_changes(&self) -> bool;
+
+    /// Start forwarding the record changes published through the backend to
+    /// the watchers of this store instance
+    fn listen_changes(&self, registry: Weak<WatchRegistry>) -> BoxFuture<'_, Result<(), Error>>;
+
     /// Close the store instance
     fn close(&self) -> BoxFuture<'_, Result<(), Error>>;
 }
 
+/// A schema migration for a database-backed store
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SchemaMigration {
+    /// The schema version produced by the migration
+    pub version: u32,
+    /// A short summary of the changes applied
+    pub description: &'static str,
+    pub(crate) sql: &'static str,
+}
+
 /// Create, open, or remove a generic backend implementation
 pub trait ManageBackend<'a> {
     /// The type of store being managed
     type Store;
 
     /// Open an existing store
+    ///
+    /// A store opened in read-only mode rejects all modifications with an
+    /// error of kind `ReadOnly`. Read-only mode may also be selected by
+    /// the `mode=ro` parameter of the store URI.
     fn open_backend(
         self,
         method: Option<StoreKeyMethod>,
         pass_key: PassKey<'a>,
         profile: Option<&'a str>,
+        read_only: bool,
     ) -> BoxFuture<'a, Result<Self::Store, Error>>;
 
     /// Provision a new store
@@ -68,6 +179,9 @@ pub trait ManageBackend<'a> {
 
     /// Remove an existing store
     fn remove_backend(self) -> BoxFuture<'a, Result<bool, Error>>;
+
+    /// List the schema migrations which would be applied when opening an existing store
+    fn pending_migrations_backend(self) -> BoxFuture<'a, Result<Vec<SchemaMigration>, Error>>;
 }
 
 /// Query from a generic backend implementation
@@ -80,6 +194,21 @@ pub trait QueryBackend: Send {
         tag_filter: Option<TagFilter>,
     ) -> BoxFuture<'q, Result<i64, Error>>;
 
+    /// Collect usage statistics for the unexpired records of a given kind,
+    /// grouped by category
+    fn category_stats(
+        &mut self,
+        kind: EntryKind,
+    ) -> BoxFuture<'_, Result<BTreeMap<String, CategoryStats>, Error>>;
+
+    /// Collect the tag names used by the unexpired records of a category,
+    /// along with the number of records using each tag
+    fn tag_names<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+    ) -> BoxFuture<'q, Result<Vec<TagNameStats>, Error>>;
+
     /// Fetch a single record from the store by category and name
     fn fetch<'q>(
         &'q mut self,
@@ -90,11 +219,14 @@ pub trait QueryBackend: Send {
     ) -> BoxFuture<'q, Result<Option<Entry>, Error>>;
 
     /// Fetch all matching records from the store
+    ///
+    /// Records are returned in insertion order unless `order_by` is given
     fn fetch_all<'q>(
         &'q mut self,
         kind: EntryKind,
         category: &'q str,
         tag_filter: Option<TagFilter>,
+        order_by: Option<OrderBy>,
         limit: Option<i64>,
         for_update: bool,
     ) -> BoxFuture<'q, Result<Vec<Entry>, Error>>;
@@ -119,6 +251,53 @@ pub trait QueryBackend: Send {
         expiry_ms: Option<i64>,
     ) -> BoxFuture<'q, Result<(), Error>>;
 
+    /// Add and remove tags of an existing record, leaving its value unchanged
+    ///
+    /// Tags are removed by name, before the new tags are added
+    fn update_tags<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+        add: &'q [EntryTag],
+        remove: &'q [&'q str],
+    ) -> BoxFuture<'q, Result<(), Error>>;
+
+    /// Update the expiry time of an existing record, leaving its value unchanged
+    fn set_expiry<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        name: &'q str,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<(), Error>>;
+
+    /// Update the expiry time of all matching records, returning the number updated
+    fn set_expiry_all<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        category: &'q str,
+        tag_filter: Option<TagFilter>,
+        expiry_ms: Option<i64>,
+    ) -> BoxFuture<'q, Result<i64, Error>>;
+
+    /// Apply a sequence of record updates in order
+    ///
+    /// Either all of the updates are applied, or none of them are
+    fn apply_batch<'q>(
+        &'q mut self,
+        kind: EntryKind,
+        updates: Vec<EntryUpdate>,
+    ) -> BoxFuture<'q, Result<(), Error>>;
+
+    /// Publish record changes through the backend
+    ///
+    /// Within a transaction, the changes are delivered when it is committed
+    fn publish_changes<'q>(
+        &'q mut self,
+        changes: &'q [EntryChange],
+    ) -> BoxFuture<'q, Result<(), Error>>;
+
     /// Close the current store session
     fn close(self, commit: bool) -> BoxFuture<'static, Result<(), Error>>;
 }
@@ -12,6 +12,9 @@ pub enum ErrorKind {
     /// The store backend was too busy to handle the request
     Busy,
 
+    /// An update failed because the record was modified by another update
+    Conflict,
+
     /// An insert operation failed due to a unique key conflict
     Duplicate,
 
@@ -21,9 +24,15 @@ pub enum ErrorKind {
     /// The input parameters to the method were incorrect
     Input,
 
+    /// The store schema is outdated and must be migrated before use
+    Migration,
+
     /// The requested record was not found
     NotFound,
 
+    /// A modification was requested on a store opened in read-only mode
+    ReadOnly,
+
     /// An unexpected error occurred
     Unexpected,
 
@@ -37,10 +46,13 @@ impl ErrorKind {
         match self {
             Self::Backend => "Backend error",
             Self::Busy => "Busy",
+            Self::Conflict => "Conflict",
             Self::Duplicate => "Duplicate",
             Self::Encryption => "Encryption error",
             Self::Input => "Input error",
+            Self::Migration => "Migration required",
             Self::NotFound => "Not found",
+            Self::ReadOnly => "Read-only",
             Self::Unexpected => "Unexpected error",
             Self::Unsupported => "Unsupported",
         }
@@ -129,7 +141,37 @@ impl From<ErrorKind> for Error {
 #[cfg(any(feature = "indy_compat", feature = "postgres", feature = "sqlite"))]
 impl From<sqlx::Error> for Error {
     fn from(err: sqlx::Error) -> Self {
-        Error::from(ErrorKind::Backend).with_cause(err)
+        // determine whether a database error was caused by a write to a read-only
+        // connection or transaction
+        #[allow(unused_variables)]
+        fn is_read_only_error(err: &dyn sqlx::error::DatabaseError) -> bool {
+            #[cfg(feature = "postgres")]
+            if let Some(err) = err.try_downcast_ref::<sqlx::postgres::PgDatabaseError>() {
+                // read_only_sql_transaction
+                return err.code() == "25006";
+            }
+            #[cfg(feature = "sqlite")]
+            if err
+                .try_downcast_ref::<sqlx::sqlite::SqliteError>()
+                .is_some()
+            {
+                // SQLITE_READONLY, including its extended result codes
+                return err
+                    .code()
+                    .and_then(|code| code.parse::<i32>().ok())
+                    .map(|code| code & 0xff == 8)
+                    .unwrap_or(false);
+            }
+            false
+        }
+
+        let kind = match &err {
+            sqlx::Error::Database(db_err) if is_read_only_error(db_err.as_ref()) => {
+                ErrorKind::ReadOnly
+            }
+            _ => ErrorKind::Backend,
+        };
+        Error::from(kind).with_cause(err)
     }
 }
 
@@ -0,0 +1,104 @@
+use std::sync::Mutex;
+
+use ffi_support::{ByteBuffer, FfiStr};
+
+use super::{handle::ArcHandle, ErrorCode};
+use crate::storage::{EntryOperation, EntryTagSet, EntryUpdate};
+
+pub type EntryBatchHandle = ArcHandle<FfiEntryBatch>;
+
+/// A sequence of record updates collected for `askar_session_apply_batch`
+#[derive(Default)]
+pub struct FfiEntryBatch(Mutex<Vec<EntryUpdate>>);
+
+impl FfiEntryBatch {
+    /// Remove the collected updates, leaving the batch empty
+    pub fn take(&self) -> Vec<EntryUpdate> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_entry_batch_new(out: *mut EntryBatchHandle) -> ErrorCode {
+    catch_err! {
+        trace!("Create entry batch");
+        check_useful_c_ptr!(out);
+        unsafe { *out = EntryBatchHandle::create(FfiEntryBatch::default()) };
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_entry_batch_push(
+    handle: EntryBatchHandle,
+    operation: i8,
+    category: FfiStr<'_>,
+    name: FfiStr<'_>,
+    value: ByteBuffer,
+    tags: FfiStr<'_>,
+    expiry_ms: i64,
+    expected_version: i64,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Add entry batch update");
+        let batch = handle.load()?;
+        let operation = match operation {
+            0 => EntryOperation::Insert,
+            1 => EntryOperation::Replace,
+            2 => EntryOperation::Remove,
+            _ => return Err(err_msg!("Invalid update operation"))
+        };
+        let category = category.into_opt_string().ok_or_else(|| err_msg!("Entry category not provided"))?;
+        let name = name.into_opt_string().ok_or_else(|| err_msg!("Entry name not provided"))?;
+        let value = if operation == EntryOperation::Remove {
+            None
+        } else {
+            Some(value.as_slice().to_vec().into())
+        };
+        let tags = if let Some(tags) = tags.as_opt_str() {
+            Some(
+                serde_json::from_str::<EntryTagSet<'static>>(tags)
+                    .map_err(err_map!("Error decoding tags"))?
+                    .into_vec(),
+            )
+        } else {
+            None
+        };
+        let expiry_ms = if expiry_ms < 0 {
+            None
+        } else {
+            Some(expiry_ms)
+        };
+        let expected_version = if expected_version < 0 {
+            None
+        } else {
+            Some(expected_version)
+        };
+        batch.0.lock().unwrap().push(EntryUpdate {
+            operation,
+            category,
+            name,
+            value,
+            tags,
+            expiry_ms,
+            expected_version,
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_entry_batch_count(handle: EntryBatchHandle, count: *mut i32) -> ErrorCode {
+    catch_err! {
+        trace!("Count entry batch");
+        check_useful_c_ptr!(count);
+        let batch = handle.load()?;
+        unsafe { *count = batch.0.lock().unwrap().len() as i32 };
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_entry_batch_free(handle: EntryBatchHandle) {
+    handle.remove();
+}
@@ -21,6 +21,9 @@ pub enum ErrorCode {
     NotFound = 6,
     Unexpected = 7,
     Unsupported = 8,
+    Migration = 9,
+    Conflict = 10,
+    ReadOnly = 11,
 }
 
 impl From<ErrorKind> for ErrorCode {
@@ -28,10 +31,13 @@ impl From<ErrorKind> for ErrorCode {
         match kind {
             ErrorKind::Backend => ErrorCode::Backend,
             ErrorKind::Busy => ErrorCode::Busy,
+            ErrorKind::Conflict => ErrorCode::Conflict,
             ErrorKind::Duplicate => ErrorCode::Duplicate,
             ErrorKind::Encryption => ErrorCode::Encryption,
             ErrorKind::Input => ErrorCode::Input,
+            ErrorKind::Migration => ErrorCode::Migration,
             ErrorKind::NotFound => ErrorCode::NotFound,
+            ErrorKind::ReadOnly => ErrorCode::ReadOnly,
             ErrorKind::Unexpected => ErrorCode::Unexpected,
             ErrorKind::Unsupported => ErrorCode::Unsupported,
         }
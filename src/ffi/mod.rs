@@ -15,6 +15,7 @@ mod handle;
 #[macro_use]
 mod macros;
 
+mod batch;
 mod error;
 mod key;
 mod log;
@@ -130,11 +130,105 @@ pub extern "C" fn askar_entry_list_get_tags(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_entry_list_get_version(
+    handle: EntryListHandle,
+    index: i32,
+    version: *mut i64,
+) -> ErrorCode {
+    catch_err! {
+        check_useful_c_ptr!(version);
+        let results = handle.load()?;
+        let entry = results.get_row(index)?;
+        unsafe { *version = entry.version };
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_entry_list_get_expiry(
+    handle: EntryListHandle,
+    index: i32,
+    expiry: *mut i64,
+) -> ErrorCode {
+    catch_err! {
+        check_useful_c_ptr!(expiry);
+        let results = handle.load()?;
+        let entry = results.get_row(index)?;
+        unsafe { *expiry = entry.expiry.unwrap_or(-1) };
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_entry_list_get_created(
+    handle: EntryListHandle,
+    index: i32,
+    created: *mut i64,
+) -> ErrorCode {
+    catch_err! {
+        check_useful_c_ptr!(created);
+        let results = handle.load()?;
+        let entry = results.get_row(index)?;
+        unsafe { *created = entry.created.unwrap_or(-1) };
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_entry_list_get_updated(
+    handle: EntryListHandle,
+    index: i32,
+    updated: *mut i64,
+) -> ErrorCode {
+    catch_err! {
+        check_useful_c_ptr!(updated);
+        let results = handle.load()?;
+        let entry = results.get_row(index)?;
+        unsafe { *updated = entry.updated.unwrap_or(-1) };
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_entry_list_free(handle: EntryListHandle) {
     handle.remove();
 }
 
+pub type StringListHandle = ArcHandle<FfiStringList>;
+
+pub type FfiStringList = FfiResultList<String>;
+
+#[no_mangle]
+pub extern "C" fn askar_string_list_count(handle: StringListHandle, count: *mut i32) -> ErrorCode {
+    catch_err! {
+        check_useful_c_ptr!(count);
+        let results = handle.load()?;
+        unsafe { *count = results.len() };
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_string_list_get_item(
+    handle: StringListHandle,
+    index: i32,
+    item: *mut *const c_char,
+) -> ErrorCode {
+    catch_err! {
+        check_useful_c_ptr!(item);
+        let results = handle.load()?;
+        let row = results.get_row(index)?;
+        unsafe { *item = CString::new(row.as_str()).unwrap().into_raw() };
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_string_list_free(handle: StringListHandle) {
+    handle.remove();
+}
+
 pub type KeyEntryListHandle = ArcHandle<FfiKeyEntryList>;
 
 pub type FfiKeyEntryList = FfiResultList<KeyEntry>;
@@ -230,6 +324,51 @@ pub extern "C" fn askar_key_entry_list_get_tags(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_key_entry_list_get_expiry(
+    handle: KeyEntryListHandle,
+    index: i32,
+    expiry: *mut i64,
+) -> ErrorCode {
+    catch_err! {
+        check_useful_c_ptr!(expiry);
+        let results = handle.load()?;
+        let entry = results.get_row(index)?;
+        unsafe { *expiry = entry.expiry().unwrap_or(-1) };
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_key_entry_list_get_created(
+    handle: KeyEntryListHandle,
+    index: i32,
+    created: *mut i64,
+) -> ErrorCode {
+    catch_err! {
+        check_useful_c_ptr!(created);
+        let results = handle.load()?;
+        let entry = results.get_row(index)?;
+        unsafe { *created = entry.created().unwrap_or(-1) };
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_key_entry_list_get_updated(
+    handle: KeyEntryListHandle,
+    index: i32,
+    updated: *mut i64,
+) -> ErrorCode {
+    catch_err! {
+        check_useful_c_ptr!(updated);
+        let results = handle.load()?;
+        let entry = results.get_row(index)?;
+        unsafe { *updated = entry.updated().unwrap_or(-1) };
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_key_entry_list_load_local(
     handle: KeyEntryListHandle,
@@ -5,9 +5,13 @@ use ffi_support::{rust_string_to_c, ByteBuffer, FfiStr};
 use once_cell::sync::Lazy;
 
 use super::{
+    batch::EntryBatchHandle,
     error::set_last_error,
     key::LocalKeyHandle,
-    result_list::{EntryListHandle, FfiEntryList, FfiKeyEntryList, KeyEntryListHandle},
+    result_list::{
+        EntryListHandle, FfiEntryList, FfiKeyEntryList, FfiStringList, KeyEntryListHandle,
+        StringListHandle,
+    },
     CallbackId, EnsureCallback, ErrorCode,
 };
 use crate::{
@@ -18,7 +22,7 @@ use crate::{
     error::Error,
     future::spawn_ok,
     protect::{generate_raw_store_key, PassKey, StoreKeyMethod},
-    storage::{Entry, EntryOperation, EntryTagSet, Scan, TagFilter},
+    storage::{Entry, EntryOperation, EntryTagSet, OrderBy, Scan, TagFilter},
 };
 
 new_sequence_handle!(StoreHandle, FFI_STORE_COUNTER);
@@ -223,7 +227,8 @@ pub extern "C" fn askar_store_open(
                 let store = spec_uri.open_backend(
                     key_method,
                     pass_key,
-                    profile.as_ref().map(String::as_str)
+                    profile.as_ref().map(String::as_str),
+                    false,
                 ).await?;
                 Ok(StoreHandle::create(store).await)
             }.await;
@@ -343,6 +348,177 @@ pub extern "C" fn askar_store_remove_profile(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_store_list_profiles(
+    handle: StoreHandle,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, results: StringListHandle)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("List profiles");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(rows) => {
+                    let results = StringListHandle::create(FfiStringList::from(rows));
+                    cb(cb_id, ErrorCode::Success, results)
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), StringListHandle::invalid()),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let store = handle.load().await?;
+                store.list_profiles().await
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_store_rename_profile(
+    handle: StoreHandle,
+    from_profile: FfiStr<'_>,
+    to_profile: FfiStr<'_>,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, renamed: i8)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Rename profile");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let from_profile = from_profile.into_opt_string().ok_or_else(|| err_msg!("Profile name not provided"))?;
+        let to_profile = to_profile.into_opt_string().ok_or_else(|| err_msg!("New profile name not provided"))?;
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(renamed) => cb(cb_id, ErrorCode::Success, renamed as i8),
+                Err(err) => cb(cb_id, set_last_error(Some(err)), 0),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let store = handle.load().await?;
+                Ok(store.rename_profile(from_profile, to_profile).await?)
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_store_copy_profile(
+    handle: StoreHandle,
+    from_profile: FfiStr<'_>,
+    to_profile: FfiStr<'_>,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, copied: i8)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Copy profile");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let from_profile = from_profile.into_opt_string().ok_or_else(|| err_msg!("Profile name not provided"))?;
+        let to_profile = to_profile.into_opt_string().ok_or_else(|| err_msg!("New profile name not provided"))?;
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(copied) => cb(cb_id, ErrorCode::Success, copied as i8),
+                Err(err) => cb(cb_id, set_last_error(Some(err)), 0),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let store = handle.load().await?;
+                Ok(store.copy_profile(from_profile, to_profile).await?)
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_store_rotate_profile_key(
+    handle: StoreHandle,
+    profile: FfiStr<'_>,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, updated: i64)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Rotate profile key");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let profile = profile.into_opt_string().ok_or_else(|| err_msg!("Profile name not provided"))?;
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(updated) => cb(cb_id, ErrorCode::Success, updated),
+                Err(err) => cb(cb_id, set_last_error(Some(err)), 0),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let store = handle.load().await?;
+                Ok(store.rotate_profile_key(profile).await?)
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_store_backup(
+    handle: StoreHandle,
+    path: FfiStr<'_>,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Backup store");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let path = path.into_opt_string().ok_or_else(|| err_msg!("Backup path not provided"))?;
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(_) => cb(cb_id, ErrorCode::Success),
+                Err(err) => cb(cb_id, set_last_error(Some(err))),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let store = handle.load().await?;
+                store.backup_to(&path).await
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_store_compact(
+    handle: StoreHandle,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Compact store");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(_) => cb(cb_id, ErrorCode::Success),
+                Err(err) => cb(cb_id, set_last_error(Some(err))),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let store = handle.load().await?;
+                store.compact().await
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_store_rekey(
     handle: StoreHandle,
@@ -430,6 +606,32 @@ pub extern "C" fn askar_scan_start(
     limit: i64,
     cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, handle: ScanHandle)>,
     cb_id: CallbackId,
+) -> ErrorCode {
+    askar_scan_start_ordered(
+        handle,
+        profile,
+        category,
+        tag_filter,
+        // a null string selects the default order
+        unsafe { FfiStr::from_raw(ptr::null()) },
+        offset,
+        limit,
+        cb,
+        cb_id,
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn askar_scan_start_ordered(
+    handle: StoreHandle,
+    profile: FfiStr<'_>,
+    category: FfiStr<'_>,
+    tag_filter: FfiStr<'_>,
+    order_by: FfiStr<'_>,
+    offset: i64,
+    limit: i64,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, handle: ScanHandle)>,
+    cb_id: CallbackId,
 ) -> ErrorCode {
     catch_err! {
         trace!("Scan store start");
@@ -437,6 +639,7 @@ pub extern "C" fn askar_scan_start(
         let profile = profile.into_opt_string();
         let category = category.into_opt_string().ok_or_else(|| err_msg!("Category not provided"))?;
         let tag_filter = tag_filter.as_opt_str().map(TagFilter::from_str).transpose()?;
+        let order_by = order_by.as_opt_str().map(OrderBy::from_str).transpose()?.unwrap_or_default();
         let cb = EnsureCallback::new(move |result: Result<ScanHandle,Error>|
             match result {
                 Ok(scan_handle) => {
@@ -449,7 +652,7 @@ pub extern "C" fn askar_scan_start(
         spawn_ok(async move {
             let result = async {
                 let store = handle.load().await?;
-                let scan = store.scan(profile, category, tag_filter, Some(offset), if limit < 0 { None }else {Some(limit)}).await?;
+                let scan = store.scan_ordered(profile, category, tag_filter, order_by, Some(offset), if limit < 0 { None }else {Some(limit)}).await?;
                 Ok(ScanHandle::create(scan).await)
             }.await;
             cb.resolve(result);
@@ -490,6 +693,68 @@ pub extern "C" fn askar_scan_next(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_scan_resume(
+    handle: StoreHandle,
+    token: FfiStr<'_>,
+    limit: i64,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, handle: ScanHandle)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Scan store resume");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let token = token.into_opt_string().ok_or_else(|| err_msg!("Continuation token not provided"))?;
+        let cb = EnsureCallback::new(move |result: Result<ScanHandle,Error>|
+            match result {
+                Ok(scan_handle) => {
+                    info!("Resumed scan {} on store {}", scan_handle, handle);
+                    cb(cb_id, ErrorCode::Success, scan_handle)
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ScanHandle::invalid()),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let store = handle.load().await?;
+                let scan = store.resume_scan(token, if limit < 0 { None }else {Some(limit)}).await?;
+                Ok(ScanHandle::create(scan).await)
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_scan_continuation_token(
+    handle: ScanHandle,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, token: *const c_char)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Scan continuation token");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let cb = EnsureCallback::new(move |result: Result<Option<String>,Error>|
+            match result {
+                Ok(Some(token)) => cb(cb_id, ErrorCode::Success, rust_string_to_c(token)),
+                Ok(None) => cb(cb_id, ErrorCode::Success, ptr::null()),
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ptr::null()),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let scan = handle.borrow().await?;
+                let token = scan.continuation_token().map(str::to_string);
+                handle.release(scan).await?;
+                Ok(token)
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_scan_free(handle: ScanHandle) -> ErrorCode {
     catch_err! {
@@ -569,6 +834,34 @@ pub extern "C" fn askar_session_count(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_session_stats(
+    handle: SessionHandle,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, stats: *const c_char)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Collect session statistics");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(stats) => cb(cb_id, ErrorCode::Success, rust_string_to_c(stats)),
+                Err(err) => cb(cb_id, set_last_error(Some(err)), ptr::null_mut()),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let stats = session.stats().await?;
+                serde_json::to_string(&stats)
+                    .map_err(err_map!(Unexpected, "Error serializing statistics"))
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_session_fetch(
     handle: SessionHandle,
@@ -728,6 +1021,207 @@ pub extern "C" fn askar_session_update(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn askar_session_replace_if_version(
+    handle: SessionHandle,
+    category: FfiStr<'_>,
+    name: FfiStr<'_>,
+    expected_version: i64,
+    value: ByteBuffer,
+    tags: FfiStr<'_>,
+    expiry_ms: i64,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Replace entry version");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let category = category.into_opt_string().ok_or_else(|| err_msg!("Entry category not provided"))?;
+        let name = name.into_opt_string().ok_or_else(|| err_msg!("Entry name not provided"))?;
+        let value = value.as_slice().to_vec();
+        let tags = if let Some(tags) = tags.as_opt_str() {
+            Some(
+                serde_json::from_str::<EntryTagSet<'static>>(tags)
+                    .map_err(err_map!("Error decoding tags"))?
+                    .into_vec(),
+            )
+        } else {
+            None
+        };
+        let expiry_ms = if expiry_ms < 0 {
+            None
+        } else {
+            Some(expiry_ms)
+        };
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(_) => cb(cb_id, ErrorCode::Success),
+                Err(err) => cb(cb_id, set_last_error(Some(err))),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                session.replace_if_version(&category, &name, expected_version, value.as_slice(), tags.as_ref().map(Vec::as_slice), expiry_ms).await?;
+                Ok(())
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_update_tags(
+    handle: SessionHandle,
+    category: FfiStr<'_>,
+    name: FfiStr<'_>,
+    add: FfiStr<'_>,
+    remove: FfiStr<'_>,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Update entry tags");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let category = category.into_opt_string().ok_or_else(|| err_msg!("Entry category not provided"))?;
+        let name = name.into_opt_string().ok_or_else(|| err_msg!("Entry name not provided"))?;
+        let add = if let Some(add) = add.as_opt_str() {
+            serde_json::from_str::<EntryTagSet<'static>>(add)
+                .map_err(err_map!("Error decoding tags"))?
+                .into_vec()
+        } else {
+            Vec::new()
+        };
+        let remove = if let Some(remove) = remove.as_opt_str() {
+            serde_json::from_str::<Vec<String>>(remove)
+                .map_err(err_map!("Error decoding tag names"))?
+        } else {
+            Vec::new()
+        };
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(_) => cb(cb_id, ErrorCode::Success),
+                Err(err) => cb(cb_id, set_last_error(Some(err))),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                let remove = remove.iter().map(String::as_str).collect::<Vec<_>>();
+                session.update_tags(&category, &name, &add, &remove).await?;
+                Ok(())
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_set_expiry(
+    handle: SessionHandle,
+    category: FfiStr<'_>,
+    name: FfiStr<'_>,
+    expiry_ms: i64,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Update entry expiry");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let category = category.into_opt_string().ok_or_else(|| err_msg!("Entry category not provided"))?;
+        let name = name.into_opt_string().ok_or_else(|| err_msg!("Entry name not provided"))?;
+        let expiry_ms = if expiry_ms < 0 {
+            None
+        } else {
+            Some(expiry_ms)
+        };
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(_) => cb(cb_id, ErrorCode::Success),
+                Err(err) => cb(cb_id, set_last_error(Some(err))),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                session.set_expiry(&category, &name, expiry_ms).await?;
+                Ok(())
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_set_expiry_all(
+    handle: SessionHandle,
+    category: FfiStr<'_>,
+    tag_filter: FfiStr<'_>,
+    expiry_ms: i64,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode, updated: i64)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Update expiry of matching entries");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let category = category.into_opt_string().ok_or_else(|| err_msg!("Category not provided"))?;
+        let tag_filter = tag_filter.as_opt_str().map(TagFilter::from_str).transpose()?;
+        let expiry_ms = if expiry_ms < 0 {
+            None
+        } else {
+            Some(expiry_ms)
+        };
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(updated) => {
+                    cb(cb_id, ErrorCode::Success, updated)
+                }
+                Err(err) => cb(cb_id, set_last_error(Some(err)), 0),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                session.set_expiry_all(&category, tag_filter, expiry_ms).await
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn askar_session_apply_batch(
+    handle: SessionHandle,
+    batch: EntryBatchHandle,
+    cb: Option<extern "C" fn(cb_id: CallbackId, err: ErrorCode)>,
+    cb_id: CallbackId,
+) -> ErrorCode {
+    catch_err! {
+        trace!("Apply batch");
+        let cb = cb.ok_or_else(|| err_msg!("No callback provided"))?;
+        let updates = batch.load()?.take();
+        let cb = EnsureCallback::new(move |result|
+            match result {
+                Ok(_) => cb(cb_id, ErrorCode::Success),
+                Err(err) => cb(cb_id, set_last_error(Some(err))),
+            }
+        );
+        spawn_ok(async move {
+            let result = async {
+                let mut session = handle.load().await?;
+                session.apply_batch(updates).await?;
+                Ok(())
+            }.await;
+            cb.resolve(result);
+        });
+        Ok(ErrorCode::Success)
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn askar_session_insert_key(
     handle: SessionHandle,
@@ -1,7 +1,7 @@
 use std::{future::Future, pin::Pin, time::Duration};
 
 use once_cell::sync::Lazy;
-use tokio::runtime::Runtime;
+use tokio::{runtime::Runtime, task::JoinHandle};
 
 pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
@@ -23,17 +23,47 @@ where
         .expect("Error running blocking task")
 }
 
+/// Run a set of blocking tasks concurrently, returning the results in order
+pub async fn unblock_all<F, T>(tasks: impl IntoIterator<Item = F>) -> Vec<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let handles = tasks
+        .into_iter()
+        .map(|f| RUNTIME.spawn_blocking(f))
+        .collect::<Vec<_>>();
+    let mut results = Vec::with_capacity(handles.len());
+    for handle in handles {
+        results.push(handle.await.expect("Error running blocking task"));
+    }
+    results
+}
+
 #[inline]
 pub fn spawn_ok(fut: impl Future<Output = ()> + Send + 'static) {
     RUNTIME.spawn(fut);
 }
 
+#[inline]
+pub fn spawn<T: Send + 'static>(fut: impl Future<Output = T> + Send + 'static) -> JoinHandle<T> {
+    RUNTIME.spawn(fut)
+}
+
 pub async fn sleep(dur: Duration) {
-    let _rt = RUNTIME.enter();
-    tokio::time::sleep(dur).await
+    // the timer is bound to the runtime when created, so the guard is not held
+    // across the await point
+    let sleep = {
+        let _rt = RUNTIME.enter();
+        tokio::time::sleep(dur)
+    };
+    sleep.await
 }
 
 pub async fn timeout<R>(dur: Duration, f: impl Future<Output = R>) -> Option<R> {
-    let _rt = RUNTIME.enter();
-    tokio::time::timeout(dur, f).await.ok()
+    let timeout = {
+        let _rt = RUNTIME.enter();
+        tokio::time::timeout(dur, f)
+    };
+    timeout.await.ok()
 }
@@ -48,6 +48,12 @@ pub struct KeyEntry {
     pub(crate) thumbprints: Vec<String>,
     /// Thumbprints for the key
     pub(crate) tags: Vec<EntryTag>,
+    /// The expiry time of the key entry in milliseconds since the Unix epoch
+    pub(crate) expiry: Option<i64>,
+    /// The creation time of the key entry in milliseconds since the Unix epoch
+    pub(crate) created: Option<i64>,
+    /// The last modification time of the key entry in milliseconds since the Unix epoch
+    pub(crate) updated: Option<i64>,
 }
 
 impl KeyEntry {
@@ -66,6 +72,21 @@ impl KeyEntry {
         self.name.as_str()
     }
 
+    /// Accessor for the expiry time of the key entry, if any
+    pub fn expiry(&self) -> Option<i64> {
+        self.expiry
+    }
+
+    /// Accessor for the creation time of the key entry, if recorded
+    pub fn created(&self) -> Option<i64> {
+        self.created
+    }
+
+    /// Accessor for the last modification time of the key entry, if recorded
+    pub fn updated(&self) -> Option<i64> {
+        self.updated
+    }
+
     /// Determine if a key entry refers to a local or external key
     pub fn is_local(&self) -> bool {
         self.params.reference.is_none()
@@ -101,6 +122,9 @@ impl KeyEntry {
             alg,
             thumbprints,
             tags,
+            expiry: entry.expiry,
+            created: entry.created,
+            updated: entry.updated,
         })
     }
 
@@ -51,4 +51,8 @@ mod protect;
 pub use protect::{generate_raw_store_key, PassKey, StoreKeyMethod};
 
 mod storage;
-pub use storage::{Entry, EntryTag, Scan, Store, TagFilter};
+pub use storage::{
+    CategoryStats, Entry, EntryChange, EntryOperation, EntryTag, EntryUpdate, ExpirySweeper,
+    MigrationProgress, OrderBy, ProfileStats, Scan, Store, TagFilter, TagNameStats, TagQueryLimits,
+    VerifyIssue, VerifyReport, Watch,
+};
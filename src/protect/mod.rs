@@ -19,8 +19,8 @@ pub use self::store_key::{generate_raw_store_key, StoreKey, StoreKeyMethod, Stor
 use crate::{
     crypto::buffer::SecretBytes,
     error::Error,
-    future::unblock,
-    storage::{EncEntryTag, EntryTag},
+    future::{unblock, unblock_all},
+    storage::{EncEntry, EncEntryTag, EncEntryUpdate, EntryOperation, EntryTag, EntryUpdate},
 };
 
 pub type ProfileId = i64;
@@ -65,6 +65,10 @@ impl KeyCache {
     pub async fn get_profile(&self, name: &str) -> Option<(ProfileId, Arc<ProfileKey>)> {
         self.profile_info.lock().await.get(name).cloned()
     }
+
+    pub async fn remove_profile(&self, name: &str) -> Option<(ProfileId, Arc<ProfileKey>)> {
+        self.profile_info.lock().await.remove(name)
+    }
 }
 
 pub(crate) trait EntryEncryptor {
@@ -91,6 +95,120 @@ pub(crate) trait EntryEncryptor {
         enc_value: Vec<u8>,
     ) -> Result<SecretBytes, Error>;
     fn decrypt_entry_tags(&self, enc_tags: Vec<EncEntryTag>) -> Result<Vec<EntryTag>, Error>;
+    fn decrypt_entry_tag_name(&self, enc_tag_name: Vec<u8>) -> Result<String, Error>;
+
+    /// Decrypt an entry and encrypt it again using another key
+    fn reencrypt_entry<E: EntryEncryptor>(
+        &self,
+        target: &E,
+        enc_entry: EncEntry,
+    ) -> Result<EncEntry, Error> {
+        let category = self.decrypt_entry_category(enc_entry.category)?;
+        let name = self.decrypt_entry_name(enc_entry.name)?;
+        let value =
+            self.decrypt_entry_value(category.as_bytes(), name.as_bytes(), enc_entry.value)?;
+        let tags = self.decrypt_entry_tags(enc_entry.tags)?;
+        Ok(EncEntry {
+            value: target.encrypt_entry_value(
+                category.as_bytes(),
+                name.as_bytes(),
+                E::prepare_input(&value),
+            )?,
+            category: target.encrypt_entry_category(E::prepare_input(category.as_bytes()))?,
+            name: target.encrypt_entry_name(E::prepare_input(name.as_bytes()))?,
+            tags: target.encrypt_entry_tags(tags)?,
+        })
+    }
+}
+
+/// Encrypt a sequence of record updates, distributing the work across
+/// multiple blocking tasks
+pub(crate) async fn encrypt_entry_updates(
+    key: Arc<ProfileKey>,
+    updates: Vec<EntryUpdate>,
+) -> Result<Vec<EncEntryUpdate>, Error> {
+    const CHUNK_SIZE: usize = 32;
+    let count = updates.len();
+    let mut chunks = Vec::with_capacity(count / CHUNK_SIZE + 1);
+    let mut updates = updates.into_iter().peekable();
+    while updates.peek().is_some() {
+        chunks.push(updates.by_ref().take(CHUNK_SIZE).collect::<Vec<_>>());
+    }
+    let results = unblock_all(chunks.into_iter().map(|chunk| {
+        let key = key.clone();
+        move || {
+            chunk
+                .into_iter()
+                .map(|update| encrypt_entry_update(&key, update))
+                .collect::<Result<Vec<_>, Error>>()
+        }
+    }))
+    .await;
+    let mut enc_updates = Vec::with_capacity(count);
+    for result in results {
+        enc_updates.extend(result?);
+    }
+    Ok(enc_updates)
+}
+
+fn encrypt_entry_update(key: &ProfileKey, update: EntryUpdate) -> Result<EncEntryUpdate, Error> {
+    let category = ProfileKey::prepare_input(update.category.as_bytes());
+    let name = ProfileKey::prepare_input(update.name.as_bytes());
+    let value = match (update.operation, update.value) {
+        (EntryOperation::Remove, _) => Vec::new(),
+        (_, Some(value)) => key.encrypt_entry_value(
+            category.as_ref(),
+            name.as_ref(),
+            ProfileKey::prepare_input(value.as_ref()),
+        )?,
+        (_, None) => return Err(err_msg!(Input, "Missing value for record update")),
+    };
+    if update.operation == EntryOperation::Insert && update.expected_version.is_some() {
+        return Err(err_msg!(
+            Input,
+            "Expected version is not supported for record insertion"
+        ));
+    }
+    Ok(EncEntryUpdate {
+        operation: update.operation,
+        category: key.encrypt_entry_category(category)?,
+        name: key.encrypt_entry_name(name)?,
+        value,
+        tags: update
+            .tags
+            .map(|tags| key.encrypt_entry_tags(tags))
+            .transpose()?,
+        expiry_ms: update.expiry_ms,
+        expected_version: update.expected_version,
+        version: 1,
+    })
+}
+
+/// The encrypted tags to be added to a record, and the encrypted names
+/// and plaintext flags of the tags to be removed
+pub(crate) type EncTagUpdate = (Vec<EncEntryTag>, Vec<(Vec<u8>, bool)>);
+
+/// Encrypt the tags to be added to a record, along with the names of the
+/// tags to be removed. Names with a `~` prefix refer to plaintext tags
+pub(crate) fn encrypt_tag_update(
+    key: &ProfileKey,
+    add: Vec<EntryTag>,
+    remove: Vec<String>,
+) -> Result<EncTagUpdate, Error> {
+    let remove = remove
+        .into_iter()
+        .map(|name| {
+            let (name, plaintext) = match name.strip_prefix('~') {
+                Some(name) => (name, true),
+                None => (name.as_str(), false),
+            };
+            Ok((
+                key.encrypt_tag_name(ProfileKey::prepare_input(name.as_bytes()))?,
+                plaintext,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok((key.encrypt_entry_tags(add)?, remove))
 }
 
 pub struct NullEncryptor;
@@ -142,6 +260,9 @@ impl EntryEncryptor for NullEncryptor {
     ) -> Result<SecretBytes, Error> {
         Ok(enc_value.into())
     }
+    fn decrypt_entry_tag_name(&self, enc_tag_name: Vec<u8>) -> Result<String, Error> {
+        Ok(String::from_utf8(enc_tag_name).map_err(err_map!(Encryption))?)
+    }
     fn decrypt_entry_tags(&self, enc_tags: Vec<EncEntryTag>) -> Result<Vec<EntryTag>, Error> {
         Ok(enc_tags.into_iter().try_fold(vec![], |mut acc, tag| {
             let name = String::from_utf8(tag.name).map_err(err_map!(Encryption))?;
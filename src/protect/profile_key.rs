@@ -227,9 +227,13 @@ where
             .collect()
     }
 
+    fn decrypt_entry_tag_name(&self, enc_tag_name: Vec<u8>) -> Result<String, Error> {
+        decode_utf8(self.decrypt_tag_name(enc_tag_name)?.into_vec())
+    }
+
     fn decrypt_entry_tags(&self, enc_tags: Vec<EncEntryTag>) -> Result<Vec<EntryTag>, Error> {
         enc_tags.into_iter().try_fold(vec![], |mut acc, tag| {
-            let name = decode_utf8(self.decrypt_tag_name(tag.name)?.into_vec())?;
+            let name = self.decrypt_entry_tag_name(tag.name)?;
             acc.push(if tag.plaintext {
                 let value = decode_utf8(tag.value)?;
                 EntryTag::Plaintext(name, value)
@@ -250,7 +254,7 @@ fn decode_utf8(value: Vec<u8>) -> Result<String, Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::storage::Entry;
+    use crate::storage::{EncEntry, Entry};
 
     #[test]
     fn encrypt_entry_round_trip() {
@@ -296,6 +300,40 @@ mod tests {
         assert_eq!(test_record, cmp_record);
     }
 
+    #[test]
+    fn reencrypt_entry_round_trip() {
+        let key = ProfileKey::new().unwrap();
+        let new_key = ProfileKey::new().unwrap();
+        let tags = vec![
+            EntryTag::Plaintext("plain".to_string(), "tag".to_string()),
+            EntryTag::Encrypted("enctag".to_string(), "envtagval".to_string()),
+        ];
+        let enc_entry = EncEntry {
+            category: key.encrypt_entry_category("category".into()).unwrap(),
+            name: key.encrypt_entry_name("name".into()).unwrap(),
+            value: key
+                .encrypt_entry_value(b"category", b"name", "value".into())
+                .unwrap(),
+            tags: key.encrypt_entry_tags(tags.clone()).unwrap(),
+        };
+        let upd_entry = key.reencrypt_entry(&new_key, enc_entry.clone()).unwrap();
+        assert_ne!(upd_entry.category, enc_entry.category);
+        assert!(key.decrypt_entry_name(upd_entry.name.clone()).is_err());
+
+        assert_eq!(
+            upd_entry.category,
+            new_key.encrypt_entry_category("category".into()).unwrap()
+        );
+        assert_eq!(new_key.decrypt_entry_name(upd_entry.name).unwrap(), "name");
+        assert_eq!(
+            new_key
+                .decrypt_entry_value(b"category", b"name", upd_entry.value)
+                .unwrap(),
+            &b"value"[..]
+        );
+        assert_eq!(new_key.decrypt_entry_tags(upd_entry.tags).unwrap(), tags);
+    }
+
     #[test]
     fn check_encrypt_searchable() {
         let input = SecretBytes::from(&b"hello"[..]);
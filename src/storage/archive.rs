@@ -0,0 +1,395 @@
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::entry::{EntryKind, EntryTagSet};
+use crate::{
+    crypto::buffer::SecretBytes,
+    error::Error,
+    future::unblock,
+    protect::{PassKey, StoreKey, StoreKeyMethod, StoreKeyReference},
+};
+
+const ARCHIVE_PREFIX: &[u8; 8] = b"askararc";
+const ARCHIVE_VERSION: u32 = 1;
+const MAX_HEADER_SIZE: usize = 4096;
+const MAX_CHUNK_SIZE: usize = 1 << 28;
+const CHUNK_RECORDS: usize = 32;
+
+/// The unencrypted archive header, describing how to recover the archive key
+#[derive(Debug, Deserialize, Serialize)]
+struct ArchiveHeader {
+    #[serde(rename = "ver")]
+    version: u32,
+    key: String,
+}
+
+/// An encrypted sequence of archive records
+#[derive(Debug, Deserialize, Serialize)]
+struct ArchiveChunk {
+    seq: u64,
+    records: Vec<ArchiveRecord>,
+}
+
+/// A single record within a store archive
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ArchiveRecord {
+    /// Start of the records for a profile
+    Profile { name: String },
+    /// An entry record belonging to the last listed profile
+    Entry {
+        kind: EntryKind,
+        category: String,
+        name: String,
+        value: SecretBytes,
+        tags: EntryTagSet<'static>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expiry: Option<i64>,
+    },
+    /// End of the archive
+    End { entries: u64 },
+}
+
+/// Write an encrypted archive as a series of length-prefixed chunks
+///
+/// Writes are performed on a blocking thread, so the writer is held in an
+/// `Option` while it is not in use
+pub(crate) struct ArchiveWriter<W> {
+    writer: Option<W>,
+    key: Arc<StoreKey>,
+    pending: Vec<ArchiveRecord>,
+    entries: u64,
+    seq: u64,
+}
+
+impl<W: Write + Send + 'static> ArchiveWriter<W> {
+    pub async fn create(
+        writer: W,
+        method: StoreKeyMethod,
+        pass_key: PassKey<'_>,
+    ) -> Result<Self, Error> {
+        let pass_key = pass_key.into_owned();
+        let (key, key_ref) = unblock(move || method.resolve(pass_key)).await?;
+        let header = serde_cbor::to_vec(&ArchiveHeader {
+            version: ARCHIVE_VERSION,
+            key: key_ref.into_uri(),
+        })
+        .map_err(err_map!(Unexpected, "Error serializing archive header"))?;
+        let writer = unblock(move || {
+            let mut writer = writer;
+            writer
+                .write_all(ARCHIVE_PREFIX)
+                .map_err(err_map!(Backend, "Error writing archive"))?;
+            write_block(&mut writer, &header)?;
+            Result::<_, Error>::Ok(writer)
+        })
+        .await?;
+        Ok(Self {
+            writer: Some(writer),
+            key: Arc::new(key),
+            pending: Vec::new(),
+            entries: 0,
+            seq: 0,
+        })
+    }
+
+    /// Add a record to the archive, writing a chunk when enough are pending
+    pub async fn push(&mut self, record: ArchiveRecord) -> Result<(), Error> {
+        if matches!(record, ArchiveRecord::Entry { .. }) {
+            self.entries += 1;
+        }
+        self.pending.push(record);
+        if self.pending.len() >= CHUNK_RECORDS {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let chunk = ArchiveChunk {
+            seq: self.seq,
+            records: std::mem::take(&mut self.pending),
+        };
+        let key = self.key.clone();
+        let enc_chunk = unblock(move || {
+            let data = serde_cbor::to_vec(&chunk)
+                .map(SecretBytes::from)
+                .map_err(err_map!(Unexpected, "Error serializing archive records"))?;
+            key.wrap_data(data)
+        })
+        .await?;
+        let mut writer = self.take_writer()?;
+        self.writer =
+            Some(unblock(move || write_block(&mut writer, &enc_chunk).map(|_| writer)).await?);
+        self.seq += 1;
+        Ok(())
+    }
+
+    /// Write the final chunk, returning the writer and the number of archived entries
+    pub async fn finish(mut self) -> Result<(W, u64), Error> {
+        let entries = self.entries;
+        self.pending.push(ArchiveRecord::End { entries });
+        self.flush().await?;
+        let mut writer = self.take_writer()?;
+        let writer = unblock(move || {
+            writer
+                .flush()
+                .map(|_| writer)
+                .map_err(err_map!(Backend, "Error writing archive"))
+        })
+        .await?;
+        Ok((writer, entries))
+    }
+
+    fn take_writer(&mut self) -> Result<W, Error> {
+        // the writer is not restored after a failed write
+        self.writer
+            .take()
+            .ok_or_else(|| err_msg!(Backend, "Archive writer is unavailable"))
+    }
+}
+
+/// Read and decrypt the chunks of an archive
+///
+/// Reads are performed on a blocking thread, so the reader is held in an
+/// `Option` while it is not in use
+pub(crate) struct ArchiveReader<R> {
+    reader: Option<R>,
+    key: Arc<StoreKey>,
+    entries: u64,
+    seq: u64,
+    done: bool,
+}
+
+impl<R: Read + Send + 'static> ArchiveReader<R> {
+    pub async fn open(reader: R, pass_key: PassKey<'_>) -> Result<Self, Error> {
+        let (reader, header) = unblock(move || {
+            let mut reader = reader;
+            let mut prefix = [0u8; ARCHIVE_PREFIX.len()];
+            reader
+                .read_exact(&mut prefix)
+                .map_err(err_map!(Input, "Error reading archive"))?;
+            if &prefix != ARCHIVE_PREFIX {
+                return Err(err_msg!(Input, "Invalid archive format"));
+            }
+            let header = read_block(&mut reader, MAX_HEADER_SIZE)?;
+            Ok((reader, header))
+        })
+        .await?;
+        let header = header.ok_or_else(|| err_msg!(Input, "Archive header not found"))?;
+        let header: ArchiveHeader =
+            serde_cbor::from_slice(&header).map_err(err_map!(Input, "Invalid archive header"))?;
+        if header.version != ARCHIVE_VERSION {
+            return Err(err_msg!(Unsupported, "Unsupported archive version"));
+        }
+        let key_ref = StoreKeyReference::parse_uri(&header.key)?;
+        let pass_key = pass_key.into_owned();
+        let key = unblock(move || key_ref.resolve(pass_key)).await?;
+        Ok(Self {
+            reader: Some(reader),
+            key: Arc::new(key),
+            entries: 0,
+            seq: 0,
+            done: false,
+        })
+    }
+
+    /// Fetch the next set of records, returning `None` after the end of the archive
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<ArchiveRecord>>, Error> {
+        if self.done {
+            return Ok(None);
+        }
+        let enc_chunk = self
+            .read_next(MAX_CHUNK_SIZE)
+            .await?
+            .ok_or_else(|| err_msg!(Input, "Unexpected end of archive"))?;
+        let key = self.key.clone();
+        let chunk: ArchiveChunk = unblock(move || {
+            let data = key
+                .unwrap_data(enc_chunk)
+                .map_err(err_map!(Encryption, "Error decrypting archive"))?;
+            serde_cbor::from_slice(&data).map_err(err_map!(Input, "Invalid archive records"))
+        })
+        .await?;
+        if chunk.seq != self.seq {
+            return Err(err_msg!(Input, "Archive chunks out of sequence"));
+        }
+        self.seq += 1;
+        for (idx, record) in chunk.records.iter().enumerate() {
+            match record {
+                ArchiveRecord::Entry { .. } => {
+                    self.entries += 1;
+                }
+                ArchiveRecord::End { entries } => {
+                    if idx != chunk.records.len() - 1 || *entries != self.entries {
+                        return Err(err_msg!(Input, "Archive entry count mismatch"));
+                    }
+                    if self.read_next(0).await?.is_some() {
+                        return Err(err_msg!(Input, "Unexpected data after end of archive"));
+                    }
+                    self.done = true;
+                }
+                _ => (),
+            }
+        }
+        Ok(Some(chunk.records))
+    }
+
+    async fn read_next(&mut self, max_len: usize) -> Result<Option<Vec<u8>>, Error> {
+        // the reader is not restored after a failed read
+        let mut reader = self
+            .reader
+            .take()
+            .ok_or_else(|| err_msg!(Input, "Archive reader is unavailable"))?;
+        let (reader, block) =
+            unblock(move || read_block(&mut reader, max_len).map(|block| (reader, block))).await?;
+        self.reader = Some(reader);
+        Ok(block)
+    }
+}
+
+fn write_block(writer: &mut impl Write, data: &[u8]) -> Result<(), Error> {
+    let len = u32::try_from(data.len())
+        .map_err(|_| err_msg!(Unexpected, "Archive chunk exceeds maximum length"))?;
+    writer
+        .write_all(&len.to_be_bytes())
+        .and_then(|_| writer.write_all(data))
+        .map_err(err_map!(Backend, "Error writing archive"))
+}
+
+fn read_block(reader: &mut impl Read, max_len: usize) -> Result<Option<Vec<u8>>, Error> {
+    let mut len_buf = [0u8; 4];
+    let mut read = 0;
+    while read < len_buf.len() {
+        match reader.read(&mut len_buf[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(err_msg!(Input, "Unexpected end of archive")),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(err_msg!(Input, "Error reading archive").with_cause(e)),
+        }
+    }
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len > max_len {
+        return Err(err_msg!(Input, "Archive chunk exceeds maximum length"));
+    }
+    let mut data = vec![0u8; len];
+    reader
+        .read_exact(&mut data)
+        .map_err(err_map!(Input, "Unexpected end of archive"))?;
+    Ok(Some(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::future::block_on;
+    use crate::protect::generate_raw_store_key;
+
+    fn write_archive(pass_key: PassKey<'_>) -> Vec<u8> {
+        block_on(async {
+            let mut writer = ArchiveWriter::create(Vec::new(), StoreKeyMethod::RawKey, pass_key)
+                .await
+                .unwrap();
+            writer
+                .push(ArchiveRecord::Profile {
+                    name: "profile".to_string(),
+                })
+                .await
+                .unwrap();
+            writer
+                .push(ArchiveRecord::Entry {
+                    kind: EntryKind::Item,
+                    category: "category".to_string(),
+                    name: "name".to_string(),
+                    value: SecretBytes::from_slice(b"value"),
+                    tags: Vec::new().into(),
+                    expiry: None,
+                })
+                .await
+                .unwrap();
+            writer.flush().await.unwrap();
+            let (buf, entries) = writer.finish().await.unwrap();
+            assert_eq!(entries, 1);
+            buf
+        })
+    }
+
+    fn read_archive(archive: &[u8], pass_key: PassKey<'_>) -> Result<Vec<ArchiveRecord>, Error> {
+        block_on(async {
+            let mut reader =
+                ArchiveReader::open(std::io::Cursor::new(archive.to_vec()), pass_key).await?;
+            let mut records = Vec::new();
+            while let Some(chunk) = reader.next_chunk().await? {
+                records.extend(chunk);
+            }
+            Ok(records)
+        })
+    }
+
+    #[test]
+    fn archive_round_trip() {
+        let pass_key = generate_raw_store_key(None).unwrap();
+        let archive = write_archive(pass_key.as_ref());
+        let records = read_archive(&archive, pass_key.as_ref()).unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(matches!(records[2], ArchiveRecord::End { entries: 1 }));
+
+        let mut trailing = archive.clone();
+        trailing.extend_from_slice(&[0, 0, 0, 1, 0]);
+        assert!(read_archive(&trailing, pass_key.as_ref()).is_err());
+
+        let truncated = &archive[..archive.len() - 1];
+        assert!(read_archive(truncated, pass_key.as_ref()).is_err());
+
+        let other_key = generate_raw_store_key(None).unwrap();
+        assert!(read_archive(&archive, other_key.as_ref()).is_err());
+    }
+
+    #[cfg(feature = "memory")]
+    #[test]
+    fn import_entry_without_profile() {
+        use crate::backend::memory::MemoryStoreOptions;
+
+        let pass_key = generate_raw_store_key(None).unwrap();
+        let archive = block_on(async {
+            let mut writer =
+                ArchiveWriter::create(Vec::new(), StoreKeyMethod::RawKey, pass_key.as_ref())
+                    .await
+                    .unwrap();
+            writer
+                .push(ArchiveRecord::Entry {
+                    kind: EntryKind::Item,
+                    category: "category".to_string(),
+                    name: "name".to_string(),
+                    value: SecretBytes::from_slice(b"value"),
+                    tags: Vec::new().into(),
+                    expiry: None,
+                })
+                .await
+                .unwrap();
+            writer.finish().await.unwrap().0
+        });
+        let err = block_on(async {
+            let store = MemoryStoreOptions::default()
+                .provision(
+                    StoreKeyMethod::RawKey,
+                    generate_raw_store_key(None)?,
+                    None,
+                    false,
+                )
+                .await?;
+            store
+                .import(std::io::Cursor::new(archive), pass_key.as_ref(), None)
+                .await
+        })
+        .expect_err("Expected error for entry without a profile");
+        assert_eq!(err.kind(), crate::error::ErrorKind::Input);
+    }
+}
@@ -14,7 +14,7 @@ use serde::{
 use zeroize::Zeroize;
 
 use super::wql;
-use crate::{crypto::buffer::SecretBytes, error::Error};
+use crate::{crypto::buffer::SecretBytes, error::Error, protect::StoreKey};
 
 pub(crate) fn sorted_tags(tags: &Vec<EntryTag>) -> Vec<&EntryTag> {
     if tags.len() > 0 {
@@ -40,6 +40,20 @@ pub struct Entry {
 
     /// Tags associated with the entry record
     pub tags: Vec<EntryTag>,
+
+    /// The expiry time of the entry record in milliseconds since the Unix epoch, if any
+    pub expiry: Option<i64>,
+
+    /// The version of the entry record, incremented each time it is updated
+    pub version: i64,
+
+    /// The creation time of the entry record in milliseconds since the Unix epoch,
+    /// if recorded
+    pub created: Option<i64>,
+
+    /// The last modification time of the entry record in milliseconds since the
+    /// Unix epoch, if recorded
+    pub updated: Option<i64>,
 }
 
 impl Entry {
@@ -56,9 +70,35 @@ impl Entry {
             name: name.into(),
             value: value.into(),
             tags,
+            expiry: None,
+            version: 1,
+            created: None,
+            updated: None,
         }
     }
 
+    /// Set the expiry time of the entry record
+    #[inline]
+    pub(crate) fn with_expiry(mut self, expiry: Option<i64>) -> Self {
+        self.expiry = expiry;
+        self
+    }
+
+    /// Set the version of the entry record
+    #[inline]
+    pub(crate) fn with_version(mut self, version: i64) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Set the creation and modification times of the entry record
+    #[inline]
+    pub(crate) fn with_timestamps(mut self, created: Option<i64>, updated: Option<i64>) -> Self {
+        self.created = created;
+        self.updated = updated;
+        self
+    }
+
     pub(crate) fn sorted_tags(&self) -> Vec<&EntryTag> {
         sorted_tags(&self.tags)
     }
@@ -73,7 +113,8 @@ impl PartialEq for Entry {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum EntryKind {
     Kms = 1,
     Item = 2,
@@ -90,12 +131,107 @@ pub enum EntryOperation {
     Remove,
 }
 
+/// A record update to be applied as part of a batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryUpdate {
+    /// The operation to perform
+    pub operation: EntryOperation,
+    /// The category of the record
+    pub category: String,
+    /// The name of the record
+    pub name: String,
+    /// The value of the record, required for inserts and replacements
+    pub value: Option<SecretBytes>,
+    /// The tags of the record
+    pub tags: Option<Vec<EntryTag>>,
+    /// The expiry time of the record, in milliseconds from the present
+    pub expiry_ms: Option<i64>,
+    /// The version of the existing record, which must match for the update to succeed
+    pub expected_version: Option<i64>,
+}
+
+impl EntryUpdate {
+    /// Create a new record insertion
+    pub fn insert(
+        category: impl Into<String>,
+        name: impl Into<String>,
+        value: impl Into<SecretBytes>,
+        tags: Option<Vec<EntryTag>>,
+        expiry_ms: Option<i64>,
+    ) -> Self {
+        Self {
+            operation: EntryOperation::Insert,
+            category: category.into(),
+            name: name.into(),
+            value: Some(value.into()),
+            tags,
+            expiry_ms,
+            expected_version: None,
+        }
+    }
+
+    /// Create a new record replacement
+    pub fn replace(
+        category: impl Into<String>,
+        name: impl Into<String>,
+        value: impl Into<SecretBytes>,
+        tags: Option<Vec<EntryTag>>,
+        expiry_ms: Option<i64>,
+    ) -> Self {
+        Self {
+            operation: EntryOperation::Replace,
+            ..Self::insert(category, name, value, tags, expiry_ms)
+        }
+    }
+
+    /// Create a new record removal
+    pub fn remove(category: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            operation: EntryOperation::Remove,
+            category: category.into(),
+            name: name.into(),
+            value: None,
+            tags: None,
+            expiry_ms: None,
+            expected_version: None,
+        }
+    }
+
+    /// Require the existing record to have a specific version
+    ///
+    /// Only applicable to replacements and removals. When the version of the
+    /// record does not match, the update fails with a `Conflict` error
+    pub fn with_expected_version(mut self, version: i64) -> Self {
+        self.expected_version = Some(version);
+        self
+    }
+}
+
+/// An encrypted record update
+#[derive(Clone, Debug)]
+pub(crate) struct EncEntryUpdate {
+    pub operation: EntryOperation,
+    pub category: Vec<u8>,
+    pub name: Vec<u8>,
+    pub value: Vec<u8>,
+    pub tags: Option<Vec<EncEntryTag>>,
+    pub expiry_ms: Option<i64>,
+    pub expected_version: Option<i64>,
+    pub version: i64,
+}
+
 /// A tag on an entry record in the store
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Zeroize)]
 pub enum EntryTag {
     /// An entry tag to be stored encrypted
     Encrypted(String, String),
     /// An entry tag to be stored in plaintext (for ordered comparison)
+    ///
+    /// Tags with a name beginning with `#` are compared numerically. Their
+    /// values which are not decimal numbers never match a numeric comparison.
+    /// Tags with a name beginning with `@` hold dates written as UTC timestamps
+    /// in the form `YYYY-MM-DDTHH:MM:SSZ`, and values in any other form never
+    /// match a comparison
     Plaintext(String, String),
 }
 
@@ -107,6 +243,11 @@ impl EntryTag {
         }
     }
 
+    /// Determine whether the tag is a plaintext tag which is compared numerically
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Self::Plaintext(name, _) if name.starts_with('#'))
+    }
+
     pub(crate) fn map_ref(&self, f: impl FnOnce(&str, &str) -> (String, String)) -> Self {
         match self {
             Self::Encrypted(name, val) => {
@@ -337,6 +478,14 @@ impl Serialize for EntryTagSet<'_> {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct EncEntry {
+    pub category: Vec<u8>,
+    pub name: Vec<u8>,
+    pub value: Vec<u8>,
+    pub tags: Vec<EncEntryTag>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) struct EncEntryTag {
     pub name: Vec<u8>,
@@ -469,20 +618,204 @@ impl FromStr for TagFilter {
     }
 }
 
+/// The order in which the records matching a query are returned
+///
+/// Records are ordered by insertion unless a plaintext tag is given, in which
+/// case records without the tag are returned last. Tags with a name beginning
+/// with `~#` are ordered numerically, and those beginning with `~@` are
+/// ordered by date.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OrderBy {
+    tag: Option<String>,
+    descending: bool,
+}
+
+impl OrderBy {
+    /// Order records by insertion
+    #[inline]
+    pub fn id() -> Self {
+        Self::default()
+    }
+
+    /// Order records by the value of a plaintext tag, such as `~created`
+    #[inline]
+    pub fn tag(name: impl Into<String>) -> Self {
+        Self {
+            tag: Some(name.into()),
+            descending: false,
+        }
+    }
+
+    /// Reverse the order of the records
+    #[inline]
+    pub fn desc(self) -> Self {
+        Self {
+            descending: true,
+            ..self
+        }
+    }
+
+    /// Get the name of the tag used to order the records, if any
+    pub fn tag_name(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    /// Determine whether the records are returned in descending order
+    pub fn is_descending(&self) -> bool {
+        self.descending
+    }
+
+    /// Determine whether this is the default order, by ascending insertion
+    pub(crate) fn is_default(&self) -> bool {
+        self.tag.is_none() && !self.descending
+    }
+
+    /// Get the name of the plaintext tag used to order the records, without
+    /// the `~` prefix
+    pub(crate) fn plaintext_tag(&self) -> Result<Option<&str>, Error> {
+        match self.tag.as_deref() {
+            None => Ok(None),
+            Some(name) => match name.strip_prefix('~') {
+                Some(name) if !name.is_empty() => Ok(Some(name)),
+                _ => Err(err_msg!(
+                    Input,
+                    "Records can only be ordered by a plaintext tag, found '{}'",
+                    name
+                )),
+            },
+        }
+    }
+}
+
+impl FromStr for OrderBy {
+    type Err = Error;
+
+    /// Parse an ordering in the form `id` or `~tag`, optionally prefixed
+    /// with `-` for descending order
+    fn from_str(order: &str) -> Result<Self, Error> {
+        let (order, descending) = match order.strip_prefix('-') {
+            Some(order) => (order, true),
+            None => (order, false),
+        };
+        let result = match order {
+            "id" => Self::id(),
+            _ => Self::tag(order),
+        };
+        result.plaintext_tag()?;
+        Ok(Self {
+            descending,
+            ..result
+        })
+    }
+}
+
+/// The position of a record scan, sealed by the store key to produce an
+/// opaque continuation token
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct ScanCursor {
+    pub profile: String,
+    pub kind: EntryKind,
+    pub category: String,
+    pub tag_filter: Option<String>,
+    pub last_id: i64,
+    #[serde(default)]
+    pub order_by: Option<OrderBy>,
+    #[serde(default)]
+    pub position: i64,
+}
+
+impl ScanCursor {
+    pub fn new(
+        profile: String,
+        kind: EntryKind,
+        category: String,
+        tag_filter: Option<&TagFilter>,
+        order_by: Option<&OrderBy>,
+        offset: Option<i64>,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            profile,
+            kind,
+            category,
+            tag_filter: tag_filter.map(TagFilter::to_string).transpose()?,
+            last_id: 0,
+            order_by: order_by.filter(|o| !o.is_default()).cloned(),
+            position: offset.unwrap_or(0).max(0),
+        })
+    }
+
+    /// The identifier of the record following which the scan continues
+    ///
+    /// Scans in the default order continue from the last record returned,
+    /// while other scans continue from their position in the results, so
+    /// records changed in the meantime may be skipped or repeated
+    pub fn after_id(&self) -> i64 {
+        if self.order_by.is_some() {
+            0
+        } else {
+            self.last_id
+        }
+    }
+
+    /// The offset at which a resumed scan continues
+    pub fn resume_offset(&self) -> Option<i64> {
+        self.order_by.as_ref().map(|_| self.position)
+    }
+
+    /// Record the number of rows returned in a page of results, returning
+    /// the cursor for the position following the page
+    pub fn advance(&mut self, count: usize) -> Self {
+        self.position += count as i64;
+        self.clone()
+    }
+
+    /// Decode a continuation token produced by `to_token`
+    pub fn from_token(token: &str, store_key: &StoreKey) -> Result<Self, Error> {
+        let enc_cursor = bs58::decode(token)
+            .into_vec()
+            .map_err(err_map!(Input, "Invalid scan continuation token"))?;
+        let cursor = store_key
+            .unwrap_data(enc_cursor)
+            .map_err(err_map!(Input, "Invalid scan continuation token"))?;
+        serde_cbor::from_slice(&cursor).map_err(err_map!(Input, "Invalid scan continuation token"))
+    }
+
+    /// Parse the tag filter applied to the scan
+    pub fn tag_filter(&self) -> Result<Option<TagFilter>, Error> {
+        self.tag_filter
+            .as_deref()
+            .map(TagFilter::from_str)
+            .transpose()
+    }
+
+    /// Encode a continuation token for the position following a given record
+    pub fn token_after(&self, last_id: i64, store_key: &StoreKey) -> Result<String, Error> {
+        let cursor = serde_cbor::to_vec(&Self {
+            last_id,
+            ..self.clone()
+        })
+        .map_err(err_map!(Unexpected, "Error encoding scan cursor"))?;
+        let enc_cursor = store_key.wrap_data(cursor.into())?;
+        Ok(bs58::encode(enc_cursor).into_string())
+    }
+}
+
 /// An active record scan of a store backend
 pub struct Scan<'s, T> {
-    stream: Option<Pin<Box<dyn Stream<Item = Result<Vec<T>, Error>> + Send + 's>>>,
+    stream: Option<Pin<Box<dyn Stream<Item = Result<(Vec<T>, String), Error>> + Send + 's>>>,
     page_size: usize,
+    token: Option<String>,
 }
 
 impl<'s, T> Scan<'s, T> {
-    pub(crate) fn new<S>(stream: S, page_size: usize) -> Self
+    pub(crate) fn new<S>(stream: S, page_size: usize, token: Option<String>) -> Self
     where
-        S: Stream<Item = Result<Vec<T>, Error>> + Send + 's,
+        S: Stream<Item = Result<(Vec<T>, String), Error>> + Send + 's,
     {
         Self {
             stream: Some(stream.boxed()),
             page_size,
+            token,
         }
     }
 
@@ -490,10 +823,11 @@ impl<'s, T> Scan<'s, T> {
     pub async fn fetch_next(&mut self) -> Result<Option<Vec<T>>, Error> {
         if let Some(mut s) = self.stream.take() {
             match s.try_next().await? {
-                Some(val) => {
+                Some((val, token)) => {
                     if val.len() == self.page_size {
                         self.stream.replace(s);
                     }
+                    self.token.replace(token);
                     Ok(Some(val))
                 }
                 None => Ok(None),
@@ -502,6 +836,15 @@ impl<'s, T> Scan<'s, T> {
             Ok(None)
         }
     }
+
+    /// Get an opaque token for the position following the last fetched set of rows
+    ///
+    /// The token may be passed to [`Store::resume_scan`](crate::Store::resume_scan)
+    /// in order to continue the scan at a later time, including from another process.
+    /// It is only valid for the store key which was active when it was produced.
+    pub fn continuation_token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
 }
 
 impl<S> Debug for Scan<'_, S> {
@@ -528,4 +871,17 @@ mod tests {
         let tags2 = serde_json::from_str(&ser).unwrap();
         assert_eq!(tags, tags2);
     }
+
+    #[test]
+    fn parse_order_by() {
+        assert_eq!(OrderBy::from_str("id").unwrap(), OrderBy::id());
+        assert_eq!(OrderBy::from_str("-id").unwrap(), OrderBy::id().desc());
+        assert_eq!(OrderBy::from_str("~#seq").unwrap(), OrderBy::tag("~#seq"));
+        assert_eq!(
+            OrderBy::from_str("-~name").unwrap(),
+            OrderBy::tag("~name").desc()
+        );
+        assert!(OrderBy::from_str("name").is_err());
+        assert!(OrderBy::from_str("-~").is_err());
+    }
 }
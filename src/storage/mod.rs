@@ -1,11 +1,26 @@
+mod archive;
+
 mod entry;
-pub(crate) use self::entry::{EncEntryTag, EntryTagSet};
-pub use self::entry::{Entry, EntryKind, EntryOperation, EntryTag, Scan, TagFilter};
+pub(crate) use self::entry::{EncEntry, EncEntryTag, EncEntryUpdate, EntryTagSet, ScanCursor};
+pub use self::entry::{
+    Entry, EntryKind, EntryOperation, EntryTag, EntryUpdate, OrderBy, Scan, TagFilter,
+};
 
 mod options;
 pub(crate) use self::options::{IntoOptions, Options};
 
+mod stats;
+pub use self::stats::{CategoryStats, ProfileStats, TagNameStats};
+
 mod store;
-pub use self::store::{Session, Store};
+pub use self::store::{ExpirySweeper, MigrationProgress, Session, Store};
+
+mod verify;
+pub(crate) use self::verify::verify_entries;
+pub use self::verify::{VerifyIssue, VerifyReport};
+
+mod watch;
+pub use self::watch::{EntryChange, Watch, WatchRegistry};
 
 pub(crate) mod wql;
+pub use self::wql::tags::TagQueryLimits;
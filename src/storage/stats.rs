@@ -0,0 +1,39 @@
+use std::collections::BTreeMap;
+
+/// Usage statistics for a set of records
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CategoryStats {
+    /// The number of unexpired records
+    pub count: i64,
+    /// The total size in bytes of the encrypted names, values and tags
+    pub size: i64,
+}
+
+impl CategoryStats {
+    pub(crate) fn add(&mut self, other: &CategoryStats) {
+        self.count += other.count;
+        self.size += other.size;
+    }
+}
+
+/// Usage statistics for a tag name within a category
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct TagNameStats {
+    /// The name of the tag
+    pub name: String,
+    /// Whether the tag values are stored in plaintext
+    pub plaintext: bool,
+    /// The number of unexpired records with the tag
+    pub count: i64,
+}
+
+/// Usage statistics for the records of a profile
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ProfileStats {
+    /// The name of the profile
+    pub profile: String,
+    /// The statistics for the records of each category
+    pub categories: BTreeMap<String, CategoryStats>,
+    /// The statistics for the stored keys
+    pub keys: CategoryStats,
+}
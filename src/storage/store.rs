@@ -1,24 +1,52 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
 use std::sync::Arc;
+use std::time::Duration;
 
-use super::entry::{Entry, EntryKind, EntryOperation, EntryTag, Scan, TagFilter};
+use tokio::task::JoinHandle;
+
+use super::archive::{ArchiveReader, ArchiveRecord, ArchiveWriter};
+use super::entry::{
+    Entry, EntryKind, EntryOperation, EntryTag, EntryUpdate, OrderBy, Scan, TagFilter,
+};
+use super::stats::{CategoryStats, ProfileStats, TagNameStats};
+use super::verify::VerifyReport;
+use super::watch::{EntryChange, Watch, WatchRegistry};
+use super::wql::tags::{check_query_limits, TagQueryLimits};
 use crate::{
     backend::{Backend, QueryBackend},
-    error::Error,
+    error::{Error, ErrorKind},
+    future::{sleep, spawn},
     kms::{KeyEntry, KeyParams, KmsCategory, LocalKey},
     protect::{PassKey, StoreKeyMethod},
 };
 
+/// The progress of a migration between stores
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MigrationProgress {
+    /// The name of the profile currently being migrated
+    pub profile: String,
+    /// The number of profiles which have been fully migrated
+    pub profiles_done: usize,
+    /// The total number of profiles in the source store
+    pub profiles_total: usize,
+    /// The number of records copied to the target store, including those
+    /// replaced because they changed since a previous migration
+    pub entries_copied: u64,
+    /// The number of records skipped, either because they were already present
+    /// and unchanged in the target store or have expired
+    pub entries_skipped: u64,
+}
+
 #[derive(Debug)]
 /// An instance of an opened store
-pub struct Store<B: Backend>(B);
+pub struct Store<B: Backend>(B, Arc<WatchRegistry>);
 
 impl<B: Backend> Store<B> {
     pub(crate) fn new(inner: B) -> Self {
-        Self(inner)
+        Self(inner, Arc::new(WatchRegistry::default()))
     }
 
-    #[cfg(test)]
-    #[allow(unused)]
     pub(crate) fn inner(&self) -> &B {
         &self.0
     }
@@ -26,6 +54,40 @@ impl<B: Backend> Store<B> {
     pub(crate) fn into_inner(self) -> B {
         self.0
     }
+
+    fn check_writable(&self) -> Result<(), Error> {
+        if self.0.is_read_only() {
+            Err(err_msg!(ReadOnly, "The store was opened in read-only mode"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_tag_filter(&self, tag_filter: Option<&TagFilter>) -> Result<(), Error> {
+        check_tag_filter(tag_filter, &self.0.query_limits())
+    }
+}
+
+fn check_tag_filter(tag_filter: Option<&TagFilter>, limits: &TagQueryLimits) -> Result<(), Error> {
+    if let Some(tag_filter) = tag_filter {
+        check_query_limits(&tag_filter.query, limits)
+    } else {
+        Ok(())
+    }
+}
+
+/// Determine whether a record copied by a previous migration still matches the source
+fn is_migrated(source: &Entry, target: &Entry) -> bool {
+    let expiry_matches = match (source.expiry, target.expiry) {
+        (Some(a), Some(b)) => (a - b).abs() < 1000,
+        (None, None) => true,
+        _ => false,
+    };
+    let mut source_tags = source.tags.clone();
+    let mut target_tags = target.tags.clone();
+    source_tags.sort();
+    target_tags.sort();
+    expiry_matches && source.value == target.value && source_tags == target_tags
 }
 
 impl<B: Backend> Store<B> {
@@ -34,28 +96,395 @@ impl<B: Backend> Store<B> {
         self.0.get_profile_name()
     }
 
+    /// Determine whether the store was opened in read-only mode
+    pub fn is_read_only(&self) -> bool {
+        self.0.is_read_only()
+    }
+
+    /// Get the limits applied to the tag filters of queries against the store
+    pub fn query_limits(&self) -> TagQueryLimits {
+        self.0.query_limits()
+    }
+
     /// Replace the wrapping key on a store
     pub async fn rekey(
         &mut self,
         method: StoreKeyMethod,
         pass_key: PassKey<'_>,
     ) -> Result<(), Error> {
+        self.check_writable()?;
         Ok(self.0.rekey_backend(method, pass_key).await?)
     }
 
     /// Create a new profile with the given profile name
     pub async fn create_profile(&self, name: Option<String>) -> Result<String, Error> {
+        self.check_writable()?;
         Ok(self.0.create_profile(name).await?)
     }
 
     /// Remove an existing profile with the given profile name
     pub async fn remove_profile(&self, name: String) -> Result<bool, Error> {
+        self.check_writable()?;
         Ok(self.0.remove_profile(name).await?)
     }
 
+    /// Get the names of all existing profiles
+    pub async fn list_profiles(&self) -> Result<Vec<String>, Error> {
+        Ok(self.0.list_profiles().await?)
+    }
+
+    /// Rename an existing profile, returning `false` if it was not found
+    ///
+    /// The default profile of the store cannot be renamed.
+    pub async fn rename_profile(&self, from_name: String, to_name: String) -> Result<bool, Error> {
+        self.check_writable()?;
+        Ok(self.0.rename_profile(from_name, to_name).await?)
+    }
+
+    /// Copy an existing profile and its records to a new profile
+    ///
+    /// Returns `false` if the source profile was not found. Expired records
+    /// are not copied.
+    pub async fn copy_profile(&self, from_name: String, to_name: String) -> Result<bool, Error> {
+        self.check_writable()?;
+        Ok(self.0.copy_profile(from_name, to_name).await?)
+    }
+
+    /// Replace the profile key of an existing profile and re-encrypt its records
+    ///
+    /// Records are updated in batches, so other profiles remain available.
+    /// New sessions and record updates for the profile itself are rejected
+    /// until the rotation completes, including those of other store instances.
+    /// Existing sessions should be closed beforehand, as their updates are
+    /// rejected once the key has been replaced. If the rotation is
+    /// interrupted, calling this method again resumes it. Returns the number
+    /// of re-encrypted records.
+    pub async fn rotate_profile_key(&self, name: String) -> Result<i64, Error> {
+        self.check_writable()?;
+        Ok(self.0.rotate_profile_key(name).await?)
+    }
+
+    /// Export the contents of the store to an encrypted archive
+    ///
+    /// The archive is protected by a key derived from `method` and `pass_key`,
+    /// independent of the store's own wrapping key. When `profiles` is not
+    /// provided, every profile in the store is exported. The records of each
+    /// profile are read from a consistent snapshot of the store, although
+    /// separate profiles may be read at different times. Returns the number of
+    /// exported entries.
+    pub async fn export(
+        &self,
+        writer: impl Write + Send + 'static,
+        method: StoreKeyMethod,
+        pass_key: PassKey<'_>,
+        profiles: Option<&[&str]>,
+    ) -> Result<u64, Error> {
+        let profiles = match profiles {
+            Some(names) => names.iter().map(|name| name.to_string()).collect(),
+            None => self.0.list_profiles().await?,
+        };
+        let mut archive = ArchiveWriter::create(writer, method, pass_key).await?;
+        for profile in profiles {
+            let mut snapshot = self.0.snapshot_session(Some(profile.clone()))?;
+            archive
+                .push(ArchiveRecord::Profile { name: profile })
+                .await?;
+            for kind in [EntryKind::Kms, EntryKind::Item].iter().copied() {
+                let categories = snapshot.category_stats(kind).await?;
+                for category in categories.keys() {
+                    let rows = snapshot
+                        .fetch_all(kind, category, None, None, None, false)
+                        .await?;
+                    for row in rows {
+                        archive
+                            .push(ArchiveRecord::Entry {
+                                kind,
+                                category: row.category,
+                                name: row.name,
+                                value: row.value,
+                                tags: row.tags.into(),
+                                expiry: row.expiry,
+                            })
+                            .await?;
+                    }
+                }
+            }
+            snapshot.close(false).await?;
+        }
+        let (_, entries) = archive.finish().await?;
+        Ok(entries)
+    }
+
+    /// Import the contents of an archive created by `Store::export`
+    ///
+    /// Missing profiles are created, and each profile is imported within a
+    /// single transaction. Entries already present in a profile are replaced by
+    /// the archived entries, while other existing entries are left in place.
+    /// Entries which have expired since the archive was created are skipped.
+    /// When `profiles` is provided, only the named profiles are imported.
+    /// Returns the number of imported entries.
+    pub async fn import(
+        &self,
+        reader: impl Read + Send + 'static,
+        pass_key: PassKey<'_>,
+        profiles: Option<&[&str]>,
+    ) -> Result<u64, Error> {
+        self.check_writable()?;
+        let mut archive = ArchiveReader::open(reader, pass_key).await?;
+        let mut txn: Option<B::Session> = None;
+        let mut skip_profile = false;
+        let mut count = 0;
+        let now = chrono::Utc::now().timestamp_millis();
+        while let Some(records) = archive.next_chunk().await? {
+            for record in records {
+                match record {
+                    ArchiveRecord::Profile { name } => {
+                        if let Some(txn) = txn.take() {
+                            txn.close(true).await?;
+                        }
+                        skip_profile = profiles.map(|p| !p.contains(&name.as_str())) == Some(true);
+                        if skip_profile {
+                            continue;
+                        }
+                        match self.0.create_profile(Some(name.clone())).await {
+                            Ok(_) => (),
+                            Err(err) if err.kind() == ErrorKind::Duplicate => (),
+                            Err(err) => return Err(err),
+                        }
+                        txn.replace(self.0.session(Some(name), true)?);
+                    }
+                    ArchiveRecord::Entry {
+                        kind,
+                        category,
+                        name,
+                        value,
+                        tags,
+                        expiry,
+                    } => {
+                        if skip_profile {
+                            continue;
+                        }
+                        let expiry_ms = match expiry {
+                            Some(expiry) if expiry <= now => continue,
+                            Some(expiry) => Some(expiry - now),
+                            None => None,
+                        };
+                        let tags = tags.into_vec();
+                        let txn = txn
+                            .as_mut()
+                            .ok_or_else(|| err_msg!(Input, "Archive entry without a profile"))?;
+                        let inserted = match txn
+                            .update(
+                                kind,
+                                EntryOperation::Insert,
+                                &category,
+                                &name,
+                                Some(value.as_ref()),
+                                Some(tags.as_slice()),
+                                expiry_ms,
+                            )
+                            .await
+                        {
+                            Ok(()) => true,
+                            Err(err) if err.kind() == ErrorKind::Duplicate => false,
+                            Err(err) => return Err(err),
+                        };
+                        if !inserted {
+                            txn.update(
+                                kind,
+                                EntryOperation::Replace,
+                                &category,
+                                &name,
+                                Some(value.as_ref()),
+                                Some(tags.as_slice()),
+                                expiry_ms,
+                            )
+                            .await?;
+                        }
+                        count += 1;
+                    }
+                    ArchiveRecord::End { .. } => (),
+                }
+            }
+        }
+        if let Some(txn) = txn.take() {
+            txn.close(true).await?;
+        }
+        Ok(count)
+    }
+
+    /// Copy every profile and record of this store into another store
+    ///
+    /// Each profile is recreated in the target store using the same profile key,
+    /// wrapped by the store key of the target. An interrupted migration may be
+    /// resumed by repeating the call: records which are already present in the
+    /// target are skipped when their value, tags and expiry match the source,
+    /// and replaced otherwise. Expiry times are compared to within one second,
+    /// as they are recomputed when copied. Records removed from the source
+    /// since the previous call, or only present in the target, are left in place.
+    /// The `progress` callback is invoked after each batch of records is
+    /// committed to the target.
+    pub async fn migrate_to<T: Backend>(
+        &self,
+        target: &Store<T>,
+        mut progress: impl FnMut(&MigrationProgress),
+    ) -> Result<MigrationProgress, Error> {
+        target.check_writable()?;
+        let profiles = self.0.list_profiles().await?;
+        let mut status = MigrationProgress {
+            profiles_total: profiles.len(),
+            ..Default::default()
+        };
+        for profile in profiles {
+            let key = self.0.load_profile_key(profile.clone()).await?;
+            target.0.import_profile(profile.clone(), key).await?;
+            status.profile = profile.clone();
+            for kind in [EntryKind::Kms, EntryKind::Item].iter().copied() {
+                let categories = self.0.list_categories(Some(profile.clone()), kind).await?;
+                for category in categories {
+                    let mut scan = self
+                        .0
+                        .scan(
+                            Some(profile.clone()),
+                            kind,
+                            category,
+                            None,
+                            None,
+                            None,
+                            None,
+                        )
+                        .await?;
+                    while let Some(rows) = scan.fetch_next().await? {
+                        let now = chrono::Utc::now().timestamp_millis();
+                        let mut txn = target.0.session(Some(profile.clone()), true)?;
+                        for row in rows {
+                            let expiry_ms = match row.expiry {
+                                Some(expiry) if expiry <= now => {
+                                    status.entries_skipped += 1;
+                                    continue;
+                                }
+                                Some(expiry) => Some(expiry - now),
+                                None => None,
+                            };
+                            let inserted = match txn
+                                .update(
+                                    kind,
+                                    EntryOperation::Insert,
+                                    &row.category,
+                                    &row.name,
+                                    Some(row.value.as_ref()),
+                                    Some(row.tags.as_slice()),
+                                    expiry_ms,
+                                )
+                                .await
+                            {
+                                Ok(()) => true,
+                                Err(err) if err.kind() == ErrorKind::Duplicate => false,
+                                Err(err) => return Err(err),
+                            };
+                            if inserted {
+                                status.entries_copied += 1;
+                                continue;
+                            }
+                            // copied by a previous call, replace it if it has changed since
+                            let found = txn.fetch(kind, &row.category, &row.name, true).await?;
+                            if matches!(found, Some(found) if is_migrated(&row, &found)) {
+                                status.entries_skipped += 1;
+                            } else {
+                                txn.update(
+                                    kind,
+                                    EntryOperation::Replace,
+                                    &row.category,
+                                    &row.name,
+                                    Some(row.value.as_ref()),
+                                    Some(row.tags.as_slice()),
+                                    expiry_ms,
+                                )
+                                .await?;
+                                status.entries_copied += 1;
+                            }
+                        }
+                        txn.close(true).await?;
+                        progress(&status);
+                    }
+                }
+            }
+            status.profiles_done += 1;
+            progress(&status);
+        }
+        Ok(status)
+    }
+
+    /// Remove all expired records from the store
+    ///
+    /// Returns the number of records removed from each profile
+    pub async fn purge_expired(&self) -> Result<BTreeMap<String, i64>, Error> {
+        self.check_writable()?;
+        Ok(self.0.purge_expired().await?)
+    }
+
+    /// Check the integrity of the store contents
+    ///
+    /// Every record and tag of the profile is decrypted using its stored profile
+    /// key, and the searchable encryption of record categories, names and tags is
+    /// checked for consistency. When `profile` is not provided, every profile is
+    /// checked along with any records and tags which do not belong to a profile.
+    ///
+    /// When `repair` is set, profiles whose key cannot be unwrapped (other than
+    /// the default profile), records and tags which cannot be decrypted, and
+    /// orphaned rows are removed. The repairs to each profile are applied
+    /// together, and are skipped when this instance holds a different key for
+    /// the profile. Profiles with a key rotation in progress are reported and
+    /// not checked. Other issues are only reported.
+    pub async fn verify(
+        &self,
+        profile: Option<String>,
+        repair: bool,
+    ) -> Result<VerifyReport, Error> {
+        if repair {
+            self.check_writable()?;
+        }
+        Ok(self.0.verify(profile, repair).await?)
+    }
+
+    /// Start a background task which periodically removes expired records
+    ///
+    /// The result of each purge is passed to `on_purge`. The task stops when the
+    /// returned handle is dropped, or once the store itself has been dropped.
+    /// Fails if the store is read-only or the interval is zero.
+    pub fn start_expiry_sweeper(
+        self: &Arc<Self>,
+        interval: Duration,
+        mut on_purge: impl FnMut(Result<BTreeMap<String, i64>, Error>) + Send + 'static,
+    ) -> Result<ExpirySweeper, Error>
+    where
+        B: 'static,
+    {
+        self.check_writable()?;
+        if interval == Duration::from_secs(0) {
+            return Err(err_msg!(
+                Input,
+                "The expiry sweeper interval must be non-zero"
+            ));
+        }
+        let store = Arc::downgrade(self);
+        let task = spawn(async move {
+            loop {
+                sleep(interval).await;
+                let result = match store.upgrade() {
+                    Some(store) => store.purge_expired().await,
+                    None => break,
+                };
+                on_purge(result);
+            }
+        });
+        Ok(ExpirySweeper { task: Some(task) })
+    }
+
     /// Create a new scan instance against the store
     ///
-    /// The result will keep an open connection to the backend until it is consumed
+    /// Records are returned in the order they were added to the store. The result
+    /// will keep an open connection to the backend until it is consumed
     pub async fn scan(
         &self,
         profile: Option<String>,
@@ -64,6 +493,34 @@ impl<B: Backend> Store<B> {
         offset: Option<i64>,
         limit: Option<i64>,
     ) -> Result<Scan<'static, Entry>, Error> {
+        self.scan_ordered(
+            profile,
+            category,
+            tag_filter,
+            OrderBy::default(),
+            offset,
+            limit,
+        )
+        .await
+    }
+
+    /// Create a new scan instance against the store, returning records in a
+    /// given order
+    ///
+    /// Combined with `limit`, this may be used to fetch the first records in
+    /// the order. The result will keep an open connection to the backend until
+    /// it is consumed
+    pub async fn scan_ordered(
+        &self,
+        profile: Option<String>,
+        category: String,
+        tag_filter: Option<TagFilter>,
+        order_by: OrderBy,
+        offset: Option<i64>,
+        limit: Option<i64>,
+    ) -> Result<Scan<'static, Entry>, Error> {
+        self.check_tag_filter(tag_filter.as_ref())?;
+        order_by.plaintext_tag()?;
         Ok(self
             .0
             .scan(
@@ -71,21 +528,92 @@ impl<B: Backend> Store<B> {
                 EntryKind::Item,
                 category,
                 tag_filter,
+                Some(order_by),
                 offset,
                 limit,
             )
             .await?)
     }
 
+    /// Resume a scan from the continuation token of a previous scan instance
+    ///
+    /// Records are returned in the order of the previous scan, starting after the
+    /// last record it fetched. In the default order, a record which was replaced
+    /// in the meantime is given a new position, so it may be returned again at the
+    /// end of the resumed scan. When a scan is not in the default order, it resumes
+    /// from its position in the results, so records changed in the meantime may be
+    /// skipped or repeated
+    pub async fn resume_scan(
+        &self,
+        token: String,
+        limit: Option<i64>,
+    ) -> Result<Scan<'static, Entry>, Error> {
+        Ok(self.0.resume_scan(token, limit).await?)
+    }
+
+    /// Watch for committed changes to the records of a category
+    ///
+    /// Insertions, replacements and removals of records are reported, including
+    /// those applied in batches, by `remove_all`, and tag and expiry updates,
+    /// which are reported as replacements. Records removed on expiry and key
+    /// operations are not reported. The tag filter is only applied when the tags
+    /// of the changed record are known, so removals, tag updates and expiry
+    /// updates of single records are reported to every watcher of the category.
+    ///
+    /// While a category is watched, `remove_all` and `set_expiry_all` first
+    /// fetch the matching records in order to report them. Outside of a
+    /// transaction, records added by another session in the meantime may be
+    /// updated without being reported.
+    ///
+    /// Unless the backend is configured to publish changes (see the `notify`
+    /// option of the PostgreSQL backend), only the changes committed through
+    /// this store instance are reported.
+    pub async fn watch(
+        &self,
+        profile: Option<String>,
+        category: &str,
+        tag_filter: Option<TagFilter>,
+    ) -> Result<Watch, Error> {
+        self.check_tag_filter(tag_filter.as_ref())?;
+        let profile = profile.unwrap_or_else(|| self.0.get_profile_name().to_string());
+        let watch = self.1.register(profile, category.to_string(), tag_filter)?;
+        if self.0.publishes_changes() && self.1.start_listening() {
+            if let Err(err) = self.0.listen_changes(Arc::downgrade(&self.1)).await {
+                self.1.stop_listening();
+                return Err(err);
+            }
+        }
+        Ok(watch)
+    }
+
     /// Create a new session against the store
     pub async fn session(&self, profile: Option<String>) -> Result<Session<B::Session>, Error> {
         // FIXME - add 'immediate' flag
-        Ok(Session::new(self.0.session(profile, false)?))
+        self.new_session(profile, false)
     }
 
     /// Create a new transaction session against the store
     pub async fn transaction(&self, profile: Option<String>) -> Result<Session<B::Session>, Error> {
-        Ok(Session::new(self.0.session(profile, true)?))
+        self.new_session(profile, true)
+    }
+
+    fn new_session(
+        &self,
+        profile: Option<String>,
+        transaction: bool,
+    ) -> Result<Session<B::Session>, Error> {
+        let changes = SessionChanges {
+            registry: self.1.clone(),
+            profile: profile
+                .clone()
+                .unwrap_or_else(|| self.0.get_profile_name().to_string()),
+            transaction,
+            read_only: self.0.is_read_only(),
+            query_limits: self.0.query_limits(),
+            notify: self.0.publishes_changes(),
+            pending: Vec::new(),
+        };
+        Ok(Session::new(self.0.session(profile, transaction)?, changes))
     }
 
     /// Close the store instance, waiting for any shutdown procedures to complete.
@@ -98,13 +626,126 @@ impl<B: Backend> Store<B> {
     }
 }
 
+/// A handle to a background task removing expired records from a store
+///
+/// The task is stopped when the handle is dropped.
+#[derive(Debug)]
+pub struct ExpirySweeper {
+    task: Option<JoinHandle<()>>,
+}
+
+impl ExpirySweeper {
+    /// Stop the background task, waiting for it to finish
+    ///
+    /// A purge in progress is abandoned and its changes are rolled back.
+    pub async fn stop(mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+            // the task is either cancelled or has already stopped
+            task.await.ok();
+        }
+    }
+}
+
+impl Drop for ExpirySweeper {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+/// The record changes made within a session, to be reported to watchers
+#[derive(Debug)]
+struct SessionChanges {
+    registry: Arc<WatchRegistry>,
+    profile: String,
+    transaction: bool,
+    read_only: bool,
+    query_limits: TagQueryLimits,
+    notify: bool,
+    pending: Vec<EntryChange>,
+}
+
 /// An active connection to the store backend
 #[derive(Debug)]
-pub struct Session<Q: QueryBackend>(Q);
+pub struct Session<Q: QueryBackend>(Q, SessionChanges);
 
 impl<Q: QueryBackend> Session<Q> {
-    pub(crate) fn new(inner: Q) -> Self {
-        Self(inner)
+    fn new(inner: Q, changes: SessionChanges) -> Self {
+        Self(inner, changes)
+    }
+
+    fn check_writable(&self) -> Result<(), Error> {
+        if self.1.read_only {
+            Err(err_msg!(ReadOnly, "The store was opened in read-only mode"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_tag_filter(&self, tag_filter: Option<&TagFilter>) -> Result<(), Error> {
+        check_tag_filter(tag_filter, &self.1.query_limits)
+    }
+
+    fn is_watched(&self) -> bool {
+        self.1.notify || !self.1.registry.is_empty()
+    }
+
+    /// Report the changes from a completed operation, or hold them until
+    /// the transaction is committed
+    async fn changed(&mut self, changes: Vec<EntryChange>) {
+        if self.1.transaction {
+            self.1.pending.extend(changes);
+        } else if self.1.notify {
+            // the operation has already been applied
+            if let Err(err) = self.0.publish_changes(&changes).await {
+                warn!("Error publishing record changes: {}", err);
+            }
+        } else {
+            self.1.registry.publish(&self.1.profile, &changes);
+        }
+    }
+
+    async fn record_update(
+        &mut self,
+        operation: EntryOperation,
+        category: &str,
+        name: &str,
+        tags: Option<&[EntryTag]>,
+    ) {
+        if self.is_watched() {
+            let tags = match operation {
+                EntryOperation::Remove => None,
+                _ => Some(tags.map(<[_]>::to_vec).unwrap_or_default()),
+            };
+            self.changed(vec![EntryChange::new(category, name, operation, tags)])
+                .await;
+        }
+    }
+
+    /// Fetch the records affected by an update to a whole category, so that
+    /// the changes can be reported to any watchers
+    async fn fetch_watched(
+        &mut self,
+        category: &str,
+        tag_filter: Option<&TagFilter>,
+    ) -> Result<Vec<Entry>, Error> {
+        if self.is_watched() {
+            Ok(self
+                .0
+                .fetch_all(
+                    EntryKind::Item,
+                    category,
+                    tag_filter.cloned(),
+                    None,
+                    None,
+                    true,
+                )
+                .await?)
+        } else {
+            Ok(Vec::new())
+        }
     }
 }
 
@@ -115,9 +756,35 @@ impl<Q: QueryBackend> Session<Q> {
         category: &str,
         tag_filter: Option<TagFilter>,
     ) -> Result<i64, Error> {
+        self.check_tag_filter(tag_filter.as_ref())?;
         Ok(self.0.count(EntryKind::Item, category, tag_filter).await?)
     }
 
+    /// Collect usage statistics for the unexpired records and keys of the profile
+    ///
+    /// Records are grouped by category, while keys are reported in total
+    pub async fn stats(&mut self) -> Result<ProfileStats, Error> {
+        let categories = self.0.category_stats(EntryKind::Item).await?;
+        let mut keys = CategoryStats::default();
+        for stats in self.0.category_stats(EntryKind::Kms).await?.values() {
+            keys.add(stats);
+        }
+        Ok(ProfileStats {
+            profile: self.1.profile.clone(),
+            categories,
+            keys,
+        })
+    }
+
+    /// Get the tag names used by the unexpired records of a category
+    ///
+    /// Each tag name is reported once for plaintext and once for encrypted
+    /// values where both are in use, with the number of records using it.
+    /// Plaintext tag names are given without the `~` prefix
+    pub async fn tag_names(&mut self, category: &str) -> Result<Vec<TagNameStats>, Error> {
+        Ok(self.0.tag_names(EntryKind::Item, category).await?)
+    }
+
     /// Retrieve the current record at `(category, name)`.
     ///
     /// Specify `for_update` when in a transaction to create an update lock on the
@@ -146,9 +813,35 @@ impl<Q: QueryBackend> Session<Q> {
         limit: Option<i64>,
         for_update: bool,
     ) -> Result<Vec<Entry>, Error> {
+        self.fetch_all_ordered(category, tag_filter, OrderBy::default(), limit, for_update)
+            .await
+    }
+
+    /// Retrieve all records matching the given `category` and `tag_filter`,
+    /// in a given order
+    ///
+    /// Combined with `limit`, this may be used to fetch the first records in
+    /// the order
+    pub async fn fetch_all_ordered(
+        &mut self,
+        category: &str,
+        tag_filter: Option<TagFilter>,
+        order_by: OrderBy,
+        limit: Option<i64>,
+        for_update: bool,
+    ) -> Result<Vec<Entry>, Error> {
+        self.check_tag_filter(tag_filter.as_ref())?;
+        order_by.plaintext_tag()?;
         Ok(self
             .0
-            .fetch_all(EntryKind::Item, category, tag_filter, limit, for_update)
+            .fetch_all(
+                EntryKind::Item,
+                category,
+                tag_filter,
+                Some(order_by),
+                limit,
+                for_update,
+            )
             .await?)
     }
 
@@ -161,8 +854,8 @@ impl<Q: QueryBackend> Session<Q> {
         tags: Option<&[EntryTag]>,
         expiry_ms: Option<i64>,
     ) -> Result<(), Error> {
-        Ok(self
-            .0
+        self.check_writable()?;
+        self.0
             .update(
                 EntryKind::Item,
                 EntryOperation::Insert,
@@ -172,13 +865,16 @@ impl<Q: QueryBackend> Session<Q> {
                 tags,
                 expiry_ms,
             )
-            .await?)
+            .await?;
+        self.record_update(EntryOperation::Insert, category, name, tags)
+            .await;
+        Ok(())
     }
 
     /// Remove a record from the store
     pub async fn remove(&mut self, category: &str, name: &str) -> Result<(), Error> {
-        Ok(self
-            .0
+        self.check_writable()?;
+        self.0
             .update(
                 EntryKind::Item,
                 EntryOperation::Remove,
@@ -188,7 +884,10 @@ impl<Q: QueryBackend> Session<Q> {
                 None,
                 None,
             )
-            .await?)
+            .await?;
+        self.record_update(EntryOperation::Remove, category, name, None)
+            .await;
+        Ok(())
     }
 
     /// Replace the value and tags of a record in the store
@@ -200,8 +899,8 @@ impl<Q: QueryBackend> Session<Q> {
         tags: Option<&[EntryTag]>,
         expiry_ms: Option<i64>,
     ) -> Result<(), Error> {
-        Ok(self
-            .0
+        self.check_writable()?;
+        self.0
             .update(
                 EntryKind::Item,
                 EntryOperation::Replace,
@@ -211,7 +910,147 @@ impl<Q: QueryBackend> Session<Q> {
                 tags,
                 expiry_ms,
             )
-            .await?)
+            .await?;
+        self.record_update(EntryOperation::Replace, category, name, tags)
+            .await;
+        Ok(())
+    }
+
+    /// Replace the value and tags of a record in the store, if the current
+    /// version of the record matches `expected_version`
+    ///
+    /// Fails with a `Conflict` error if the record has been replaced since
+    /// the expected version was fetched
+    pub async fn replace_if_version(
+        &mut self,
+        category: &str,
+        name: &str,
+        expected_version: i64,
+        value: &[u8],
+        tags: Option<&[EntryTag]>,
+        expiry_ms: Option<i64>,
+    ) -> Result<(), Error> {
+        self.check_writable()?;
+        let update =
+            EntryUpdate::replace(category, name, value, tags.map(<[_]>::to_vec), expiry_ms)
+                .with_expected_version(expected_version);
+        self.0.apply_batch(EntryKind::Item, vec![update]).await?;
+        self.record_update(EntryOperation::Replace, category, name, tags)
+            .await;
+        Ok(())
+    }
+
+    /// Add and remove tags of a record in the store, without replacing its value
+    ///
+    /// The tags named in `remove` are removed before the tags in `add` are
+    /// inserted. Plaintext tag names are given with a `~` prefix
+    pub async fn update_tags(
+        &mut self,
+        category: &str,
+        name: &str,
+        add: &[EntryTag],
+        remove: &[&str],
+    ) -> Result<(), Error> {
+        self.check_writable()?;
+        self.0
+            .update_tags(EntryKind::Item, category, name, add, remove)
+            .await?;
+        if self.is_watched() {
+            // the resulting set of tags is not known
+            self.changed(vec![EntryChange::new(
+                category,
+                name,
+                EntryOperation::Replace,
+                None,
+            )])
+            .await;
+        }
+        Ok(())
+    }
+
+    /// Update the expiry time of a record in the store, without replacing its value
+    ///
+    /// The expiry is given in milliseconds from the present, or `None` to
+    /// remove any existing expiry
+    pub async fn set_expiry(
+        &mut self,
+        category: &str,
+        name: &str,
+        expiry_ms: Option<i64>,
+    ) -> Result<(), Error> {
+        self.check_writable()?;
+        self.0
+            .set_expiry(EntryKind::Item, category, name, expiry_ms)
+            .await?;
+        if self.is_watched() {
+            self.changed(vec![EntryChange::new(
+                category,
+                name,
+                EntryOperation::Replace,
+                None,
+            )])
+            .await;
+        }
+        Ok(())
+    }
+
+    /// Update the expiry time of all records in the store matching a given
+    /// `category` and `tag_filter`, returning the number of records updated
+    pub async fn set_expiry_all(
+        &mut self,
+        category: &str,
+        tag_filter: Option<TagFilter>,
+        expiry_ms: Option<i64>,
+    ) -> Result<i64, Error> {
+        self.check_writable()?;
+        self.check_tag_filter(tag_filter.as_ref())?;
+        let updated = self.fetch_watched(category, tag_filter.as_ref()).await?;
+        let count = self
+            .0
+            .set_expiry_all(EntryKind::Item, category, tag_filter, expiry_ms)
+            .await?;
+        if !updated.is_empty() {
+            let changes = updated
+                .into_iter()
+                .map(|entry| {
+                    EntryChange::new(
+                        &entry.category,
+                        &entry.name,
+                        EntryOperation::Replace,
+                        Some(entry.tags),
+                    )
+                })
+                .collect();
+            self.changed(changes).await;
+        }
+        Ok(count)
+    }
+
+    /// Apply a sequence of record insertions, replacements and removals
+    ///
+    /// The updates are applied in order. If any update fails then the
+    /// store is left unchanged
+    pub async fn apply_batch(&mut self, updates: Vec<EntryUpdate>) -> Result<(), Error> {
+        self.check_writable()?;
+        let changes = if self.is_watched() {
+            updates
+                .iter()
+                .map(|update| {
+                    let tags = match update.operation {
+                        EntryOperation::Remove => None,
+                        _ => Some(update.tags.clone().unwrap_or_default()),
+                    };
+                    EntryChange::new(&update.category, &update.name, update.operation, tags)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self.0.apply_batch(EntryKind::Item, updates).await?;
+        if !changes.is_empty() {
+            self.changed(changes).await;
+        }
+        Ok(())
     }
 
     /// Remove all records in the store matching a given `category` and `tag_filter`
@@ -220,10 +1059,23 @@ impl<Q: QueryBackend> Session<Q> {
         category: &str,
         tag_filter: Option<TagFilter>,
     ) -> Result<i64, Error> {
-        Ok(self
+        self.check_writable()?;
+        self.check_tag_filter(tag_filter.as_ref())?;
+        let removed = self.fetch_watched(category, tag_filter.as_ref()).await?;
+        let count = self
             .0
             .remove_all(EntryKind::Item, category, tag_filter)
-            .await?)
+            .await?;
+        if !removed.is_empty() {
+            let changes = removed
+                .iter()
+                .map(|entry| {
+                    EntryChange::new(&entry.category, &entry.name, EntryOperation::Remove, None)
+                })
+                .collect();
+            self.changed(changes).await;
+        }
+        Ok(count)
     }
 
     /// Perform a record update
@@ -239,8 +1091,8 @@ impl<Q: QueryBackend> Session<Q> {
         tags: Option<&[EntryTag]>,
         expiry_ms: Option<i64>,
     ) -> Result<(), Error> {
-        Ok(self
-            .0
+        self.check_writable()?;
+        self.0
             .update(
                 EntryKind::Item,
                 operation,
@@ -250,7 +1102,9 @@ impl<Q: QueryBackend> Session<Q> {
                 tags,
                 expiry_ms,
             )
-            .await?)
+            .await?;
+        self.record_update(operation, category, name, tags).await;
+        Ok(())
     }
 
     /// Insert a local key instance into the store
@@ -262,6 +1116,7 @@ impl<Q: QueryBackend> Session<Q> {
         tags: Option<&[EntryTag]>,
         expiry_ms: Option<i64>,
     ) -> Result<(), Error> {
+        self.check_writable()?;
         let data = key.encode()?;
         let params = KeyParams {
             metadata: metadata.map(str::to_string),
@@ -333,6 +1188,7 @@ impl<Q: QueryBackend> Session<Q> {
         limit: Option<i64>,
         for_update: bool,
     ) -> Result<Vec<KeyEntry>, Error> {
+        self.check_tag_filter(tag_filter.as_ref())?;
         let mut query_parts = Vec::with_capacity(3);
         if let Some(query) = tag_filter.map(|f| f.query) {
             query_parts.push(TagFilter::from(
@@ -361,6 +1217,7 @@ impl<Q: QueryBackend> Session<Q> {
                 EntryKind::Kms,
                 KmsCategory::CryptoKey.as_str(),
                 tag_filter,
+                None,
                 limit,
                 for_update,
             )
@@ -374,6 +1231,7 @@ impl<Q: QueryBackend> Session<Q> {
 
     /// Remove an existing key from the store
     pub async fn remove_key(&mut self, name: &str) -> Result<(), Error> {
+        self.check_writable()?;
         self.0
             .update(
                 EntryKind::Kms,
@@ -395,6 +1253,7 @@ impl<Q: QueryBackend> Session<Q> {
         tags: Option<&[EntryTag]>,
         expiry_ms: Option<i64>,
     ) -> Result<(), Error> {
+        self.check_writable()?;
         let row = self
             .0
             .fetch(EntryKind::Kms, KmsCategory::CryptoKey.as_str(), name, true)
@@ -433,8 +1292,20 @@ impl<Q: QueryBackend> Session<Q> {
     }
 
     /// Commit the pending transaction
-    pub async fn commit(self) -> Result<(), Error> {
-        Ok(self.0.close(true).await?)
+    ///
+    /// The record changes made within the transaction are reported to
+    /// watchers once it has been committed
+    pub async fn commit(mut self) -> Result<(), Error> {
+        let pending = std::mem::take(&mut self.1.pending);
+        if self.1.notify && !pending.is_empty() {
+            // notifications are delivered by the backend when the transaction commits
+            self.0.publish_changes(&pending).await?;
+        }
+        self.0.close(true).await?;
+        if !self.1.notify && !pending.is_empty() {
+            self.1.registry.publish(&self.1.profile, &pending);
+        }
+        Ok(())
     }
 
     /// Roll back the pending transaction
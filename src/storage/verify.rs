@@ -0,0 +1,256 @@
+use super::entry::{EncEntry, EncEntryTag, EntryKind};
+use crate::protect::{EntryEncryptor, ProfileKey};
+
+/// A problem found by a store integrity check
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyIssue {
+    /// The profile key cannot be unwrapped using the store key
+    ProfileKey {
+        /// The name of the profile
+        profile: String,
+    },
+    /// The profile key in use by this store instance differs from the stored
+    /// profile key, so the records of the profile are not repaired
+    ProfileKeyMismatch {
+        /// The name of the profile
+        profile: String,
+    },
+    /// A rotation of the profile key is in progress, so the records of the
+    /// profile are not checked
+    ProfileKeyRotation {
+        /// The name of the profile
+        profile: String,
+    },
+    /// A record cannot be decrypted using the profile key
+    Entry {
+        /// The name of the profile
+        profile: String,
+        /// The kind of record
+        kind: EntryKind,
+        /// The backend identifier of the record
+        id: i64,
+    },
+    /// The encrypted category or name of a record is inconsistent with its
+    /// content, so the record cannot be looked up by name
+    EntrySearchable {
+        /// The name of the profile
+        profile: String,
+        /// The kind of record
+        kind: EntryKind,
+        /// The category of the record
+        category: String,
+        /// The name of the record
+        name: String,
+    },
+    /// A tag of a record cannot be decrypted using the profile key
+    Tag {
+        /// The name of the profile
+        profile: String,
+        /// The kind of record
+        kind: EntryKind,
+        /// The category of the record
+        category: String,
+        /// The name of the record
+        name: String,
+    },
+    /// The encrypted name or value of a tag is inconsistent with its content,
+    /// so the tag is not matched by tag filters
+    TagSearchable {
+        /// The name of the profile
+        profile: String,
+        /// The kind of record
+        kind: EntryKind,
+        /// The category of the record
+        category: String,
+        /// The name of the record
+        name: String,
+        /// The name of the tag
+        tag_name: String,
+    },
+    /// A record which does not belong to any profile
+    OrphanEntry {
+        /// The backend identifier of the record
+        id: i64,
+    },
+    /// A tag which does not belong to any record
+    OrphanTag {
+        /// The backend identifier of the tag
+        id: i64,
+    },
+}
+
+/// The result of a store integrity check
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// The number of profiles checked
+    pub profiles_checked: u64,
+    /// The number of records checked
+    pub entries_checked: u64,
+    /// The number of record tags checked
+    pub tags_checked: u64,
+    /// The problems found in the store
+    pub issues: Vec<VerifyIssue>,
+    /// The number of unrecoverable profiles, records and tags removed
+    pub rows_removed: u64,
+}
+
+impl VerifyReport {
+    /// Determine whether the store passed the integrity check
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Add the results of checking a batch of records, returning the batch
+    pub(crate) fn merge(&mut self, mut batch: VerifyBatch) -> VerifyBatch {
+        self.entries_checked += batch.entries_checked;
+        self.tags_checked += batch.tags_checked;
+        self.issues.append(&mut batch.issues);
+        batch
+    }
+}
+
+/// The results of checking a batch of records
+#[derive(Debug, Default)]
+pub(crate) struct VerifyBatch {
+    pub entries_checked: u64,
+    pub tags_checked: u64,
+    pub issues: Vec<VerifyIssue>,
+    /// The identifiers of the records which cannot be decrypted
+    pub invalid_entries: Vec<i64>,
+    /// The tags which cannot be decrypted, with the identifiers of their records
+    pub invalid_tags: Vec<(i64, EncEntryTag)>,
+}
+
+/// Check a batch of encrypted records belonging to a profile
+pub(crate) fn verify_entries(
+    key: &ProfileKey,
+    profile: &str,
+    kind: EntryKind,
+    rows: Vec<(i64, EncEntry)>,
+) -> VerifyBatch {
+    let mut batch = VerifyBatch::default();
+    for (id, entry) in rows {
+        batch.entries_checked += 1;
+        let (category, name) = match verify_entry(key, &entry.category, &entry.name, &entry.value) {
+            VerifyCheck::Decrypted(result, searchable) => {
+                if !searchable {
+                    batch.issues.push(VerifyIssue::EntrySearchable {
+                        profile: profile.to_string(),
+                        kind,
+                        category: result.0.clone(),
+                        name: result.1.clone(),
+                    });
+                }
+                result
+            }
+            VerifyCheck::Undecryptable => {
+                batch.issues.push(VerifyIssue::Entry {
+                    profile: profile.to_string(),
+                    kind,
+                    id,
+                });
+                batch.invalid_entries.push(id);
+                continue;
+            }
+        };
+        for tag in entry.tags {
+            batch.tags_checked += 1;
+            match verify_tag(key, &tag) {
+                VerifyCheck::Decrypted(_, true) => (),
+                VerifyCheck::Decrypted(tag_name, false) => {
+                    batch.issues.push(VerifyIssue::TagSearchable {
+                        profile: profile.to_string(),
+                        kind,
+                        category: category.clone(),
+                        name: name.clone(),
+                        tag_name,
+                    });
+                }
+                VerifyCheck::Undecryptable => {
+                    batch.issues.push(VerifyIssue::Tag {
+                        profile: profile.to_string(),
+                        kind,
+                        category: category.clone(),
+                        name: name.clone(),
+                    });
+                    batch.invalid_tags.push((id, tag));
+                }
+            }
+        }
+    }
+    batch
+}
+
+/// The outcome of decrypting a stored record or tag
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum VerifyCheck<T> {
+    /// The value could not be decrypted
+    Undecryptable,
+    /// The value was decrypted, and the searchable encryption was found to be
+    /// consistent or not
+    Decrypted(T, bool),
+}
+
+/// Check that an encrypted record can be decrypted, returning the category and name
+fn verify_entry(
+    key: &ProfileKey,
+    enc_category: &[u8],
+    enc_name: &[u8],
+    enc_value: &[u8],
+) -> VerifyCheck<(String, String)> {
+    let (category, name) = match key
+        .decrypt_entry_category(enc_category.to_vec())
+        .and_then(|category| Ok((category, key.decrypt_entry_name(enc_name.to_vec())?)))
+    {
+        Ok(result) => result,
+        Err(_) => return VerifyCheck::Undecryptable,
+    };
+    if key
+        .decrypt_entry_value(category.as_bytes(), name.as_bytes(), enc_value.to_vec())
+        .is_err()
+    {
+        return VerifyCheck::Undecryptable;
+    }
+    let searchable = key
+        .encrypt_entry_category(ProfileKey::prepare_input(category.as_bytes()))
+        .map(|enc| enc == enc_category)
+        .unwrap_or(false)
+        && key
+            .encrypt_entry_name(ProfileKey::prepare_input(name.as_bytes()))
+            .map(|enc| enc == enc_name)
+            .unwrap_or(false);
+    VerifyCheck::Decrypted((category, name), searchable)
+}
+
+/// Check that an encrypted tag can be decrypted, returning the tag name
+fn verify_tag(key: &ProfileKey, tag: &EncEntryTag) -> VerifyCheck<String> {
+    let name = match key
+        .decrypt_tag_name(tag.name.clone())
+        .ok()
+        .and_then(|name| String::from_utf8(name.into_vec()).ok())
+    {
+        Some(name) => name,
+        None => return VerifyCheck::Undecryptable,
+    };
+    let mut searchable = key
+        .encrypt_tag_name(ProfileKey::prepare_input(name.as_bytes()))
+        .map(|enc| enc == tag.name)
+        .unwrap_or(false);
+    if tag.plaintext {
+        if std::str::from_utf8(&tag.value).is_err() {
+            return VerifyCheck::Undecryptable;
+        }
+    } else {
+        match key.decrypt_tag_value(tag.value.clone()) {
+            Ok(value) if std::str::from_utf8(value.as_ref()).is_ok() => {
+                searchable = searchable
+                    && key
+                        .encrypt_tag_value(ProfileKey::prepare_input(value.as_ref()))
+                        .map(|enc| enc == tag.value)
+                        .unwrap_or(false);
+            }
+            _ => return VerifyCheck::Undecryptable,
+        }
+    }
+    VerifyCheck::Decrypted(name, searchable)
+}
@@ -0,0 +1,186 @@
+use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::task::{Context, Poll};
+
+use futures_lite::stream::Stream;
+use tokio::sync::{mpsc, Notify};
+
+use super::{
+    entry::{EncEntryTag, EntryOperation, EntryTag, TagFilter},
+    wql::{
+        filter::{TagMatch, TagMatchEncoder},
+        tags::{tag_query, TagQueryEncoder},
+    },
+};
+use crate::error::Error;
+
+/// A committed change to a record in the store
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryChange {
+    /// The category of the record
+    pub category: String,
+    /// The name of the record
+    pub name: String,
+    /// The operation performed on the record
+    pub operation: EntryOperation,
+    /// The tags of the inserted or replaced record, if known
+    pub tags: Option<Vec<EntryTag>>,
+}
+
+impl EntryChange {
+    pub(crate) fn new(
+        category: &str,
+        name: &str,
+        operation: EntryOperation,
+        tags: Option<Vec<EntryTag>>,
+    ) -> Self {
+        Self {
+            category: category.to_string(),
+            name: name.to_string(),
+            operation,
+            tags,
+        }
+    }
+}
+
+/// A stream of the changes committed to the records of a category
+///
+/// Changes are reported in the order they are committed. The stream ends
+/// when the store is closed
+pub struct Watch {
+    receiver: mpsc::UnboundedReceiver<EntryChange>,
+}
+
+impl Debug for Watch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watch").finish()
+    }
+}
+
+impl Stream for Watch {
+    type Item = EntryChange;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+struct Watcher {
+    profile: String,
+    category: String,
+    filter: Option<TagMatch>,
+    sender: mpsc::UnboundedSender<EntryChange>,
+}
+
+impl Watcher {
+    fn matches(&self, profile: &str, change: &EntryChange) -> bool {
+        if self.profile != profile || self.category != change.category {
+            return false;
+        }
+        match (self.filter.as_ref(), change.tags.as_ref()) {
+            (Some(filter), Some(tags)) => filter.matches(&plain_match_tags(tags)),
+            // changes without known tags are reported to all watchers of the category
+            _ => true,
+        }
+    }
+}
+
+/// The set of active watchers for a store instance
+#[derive(Default)]
+pub struct WatchRegistry {
+    watchers: Mutex<Vec<Watcher>>,
+    listening: AtomicBool,
+    closed: Arc<Notify>,
+}
+
+impl WatchRegistry {
+    /// Determine whether there are any active watchers
+    pub(crate) fn is_empty(&self) -> bool {
+        self.watchers.lock().unwrap().is_empty()
+    }
+
+    /// Register a new watcher for the records of a profile and category
+    pub(crate) fn register(
+        &self,
+        profile: String,
+        category: String,
+        tag_filter: Option<TagFilter>,
+    ) -> Result<Watch, Error> {
+        let filter = if let Some(tag_filter) = tag_filter {
+            let mut enc = TagMatchEncoder::new(
+                |name| Ok(name.as_bytes().to_vec()),
+                |value| Ok(value.as_bytes().to_vec()),
+            );
+            enc.encode_query(&tag_query(tag_filter.query)?)?
+        } else {
+            None
+        };
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.watchers.lock().unwrap().push(Watcher {
+            profile,
+            category,
+            filter,
+            sender,
+        });
+        Ok(Watch { receiver })
+    }
+
+    /// Report committed changes to the matching watchers
+    pub(crate) fn publish(&self, profile: &str, changes: &[EntryChange]) {
+        let mut watchers = self.watchers.lock().unwrap();
+        watchers.retain(|watcher| {
+            for change in changes {
+                if watcher.matches(profile, change) && watcher.sender.send(change.clone()).is_err()
+                {
+                    return false;
+                }
+            }
+            !watcher.sender.is_closed()
+        });
+    }
+
+    /// Mark the registry as receiving changes from the backend, returning
+    /// `false` if it was already marked
+    pub(crate) fn start_listening(&self) -> bool {
+        !self.listening.swap(true, Ordering::AcqRel)
+    }
+
+    /// Reset the listening state after the backend listener has stopped
+    pub(crate) fn stop_listening(&self) {
+        self.listening.store(false, Ordering::Release);
+    }
+
+    /// Obtain a handle which is notified when the registry is dropped
+    pub(crate) fn closed(&self) -> Arc<Notify> {
+        self.closed.clone()
+    }
+}
+
+impl Drop for WatchRegistry {
+    fn drop(&mut self) {
+        // stores a permit if the backend listener is not currently waiting
+        self.closed.notify_one();
+    }
+}
+
+impl Debug for WatchRegistry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchRegistry")
+            .field("watchers", &self.watchers.lock().unwrap().len())
+            .finish()
+    }
+}
+
+fn plain_match_tags(tags: &[EntryTag]) -> Vec<EncEntryTag> {
+    tags.iter()
+        .map(|tag| EncEntryTag {
+            name: tag.name().as_bytes().to_vec(),
+            value: tag.value().as_bytes().to_vec(),
+            plaintext: matches!(tag, EntryTag::Plaintext(..)),
+        })
+        .collect()
+}
@@ -0,0 +1,375 @@
+use crate::{
+    error::Error,
+    storage::{
+        wql::tags::{
+            is_date_value, is_numeric_value, CompareOp, ConjunctionOp, TagName, TagQueryEncoder,
+            TagType,
+        },
+        EncEntryTag,
+    },
+};
+
+/// A compiled tag filter, evaluated against the (possibly encrypted) tags of each entry
+#[derive(Debug)]
+pub enum TagMatch {
+    Compare {
+        op: CompareOp,
+        name: Vec<u8>,
+        value: Vec<u8>,
+        tag_type: TagType,
+    },
+    In {
+        name: Vec<u8>,
+        values: Vec<Vec<u8>>,
+        tag_type: TagType,
+        negate: bool,
+    },
+    Exist {
+        name: Vec<u8>,
+        plaintext: bool,
+        negate: bool,
+    },
+    Conj {
+        op: ConjunctionOp,
+        clauses: Vec<TagMatch>,
+    },
+}
+
+impl TagMatch {
+    // mirrors the semantics of the SQL encoder: each comparison selects the items
+    // having at least one tag with a matching name and a matching value
+    pub fn matches(&self, tags: &[EncEntryTag]) -> bool {
+        match self {
+            Self::Compare {
+                op,
+                name,
+                value,
+                tag_type,
+            } => tags.iter().any(|tag| {
+                tag.plaintext == tag_type.is_plaintext()
+                    && &tag.name == name
+                    && match tag_type {
+                        TagType::Numeric => compare_numeric(*op, &tag.value, value),
+                        TagType::Date => {
+                            is_date(&tag.value) && compare_value(*op, &tag.value, value)
+                        }
+                        _ => compare_value(*op, &tag.value, value),
+                    }
+            }),
+            Self::In {
+                name,
+                values,
+                tag_type,
+                negate,
+            } => tags.iter().any(|tag| {
+                tag.plaintext == tag_type.is_plaintext()
+                    && &tag.name == name
+                    && match tag_type {
+                        TagType::Numeric => matches!(
+                            parse_numeric(&tag.value),
+                            Some(tag_value)
+                                if values.iter().any(|v| parse_numeric(v) == Some(tag_value)) != *negate
+                        ),
+                        TagType::Date => {
+                            is_date(&tag.value) && values.contains(&tag.value) != *negate
+                        }
+                        _ => values.contains(&tag.value) != *negate,
+                    }
+            }),
+            Self::Exist {
+                name,
+                plaintext,
+                negate,
+            } => {
+                tags.iter()
+                    .any(|tag| tag.plaintext == *plaintext && &tag.name == name)
+                    != *negate
+            }
+            Self::Conj {
+                op: ConjunctionOp::And,
+                clauses,
+            } => clauses.iter().all(|c| c.matches(tags)),
+            Self::Conj {
+                op: ConjunctionOp::Or,
+                clauses,
+            } => clauses.iter().any(|c| c.matches(tags)),
+        }
+    }
+}
+
+fn compare_value(op: CompareOp, value: &[u8], target: &[u8]) -> bool {
+    match op {
+        CompareOp::Eq => value == target,
+        CompareOp::Neq => value != target,
+        CompareOp::Gt => value > target,
+        CompareOp::Gte => value >= target,
+        CompareOp::Lt => value < target,
+        CompareOp::Lte => value <= target,
+        CompareOp::Like => like_match(value, target),
+        CompareOp::NotLike => !like_match(value, target),
+    }
+}
+
+/// Parse a tag value as a number, if it is well-formed
+pub(crate) fn parse_numeric(value: &[u8]) -> Option<f64> {
+    let value = std::str::from_utf8(value).ok()?;
+    if is_numeric_value(value) {
+        value.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Determine whether a tag value is a well-formed date. Other values of date
+/// tags never match, like the NULL result in SQL
+pub(crate) fn is_date(value: &[u8]) -> bool {
+    matches!(std::str::from_utf8(value), Ok(value) if is_date_value(value))
+}
+
+// values which are not numeric never match, like the NULL result in SQL
+fn compare_numeric(op: CompareOp, value: &[u8], target: &[u8]) -> bool {
+    let (value, target) = match (parse_numeric(value), parse_numeric(target)) {
+        (Some(v), Some(t)) => (v, t),
+        _ => return false,
+    };
+    match op {
+        CompareOp::Eq => value == target,
+        CompareOp::Neq => value != target,
+        CompareOp::Gt => value > target,
+        CompareOp::Gte => value >= target,
+        CompareOp::Lt => value < target,
+        CompareOp::Lte => value <= target,
+        // rejected by query validation
+        CompareOp::Like | CompareOp::NotLike => false,
+    }
+}
+
+fn like_match(value: &[u8], pattern: &[u8]) -> bool {
+    let (value, pattern) = match (std::str::from_utf8(value), std::str::from_utf8(pattern)) {
+        (Ok(v), Ok(p)) => (v.chars().collect::<Vec<_>>(), p.chars().collect::<Vec<_>>()),
+        _ => return false,
+    };
+    let (mut v_idx, mut p_idx) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while v_idx < value.len() {
+        match pattern.get(p_idx) {
+            Some('%') => {
+                backtrack = Some((p_idx, v_idx));
+                p_idx += 1;
+            }
+            Some('_') => {
+                p_idx += 1;
+                v_idx += 1;
+            }
+            Some(c) if *c == value[v_idx] => {
+                p_idx += 1;
+                v_idx += 1;
+            }
+            _ => {
+                if let Some((bp, bv)) = backtrack {
+                    backtrack = Some((bp, bv + 1));
+                    p_idx = bp + 1;
+                    v_idx = bv + 1;
+                } else {
+                    return false;
+                }
+            }
+        }
+    }
+    pattern[p_idx..].iter().all(|c| *c == '%')
+}
+
+pub struct TagMatchEncoder<EN, EV> {
+    enc_name: EN,
+    enc_value: EV,
+}
+
+impl<EN, EV> TagMatchEncoder<EN, EV>
+where
+    EN: Fn(&str) -> Result<Vec<u8>, Error>,
+    EV: Fn(&str) -> Result<Vec<u8>, Error>,
+{
+    pub fn new(enc_name: EN, enc_value: EV) -> Self {
+        Self {
+            enc_name,
+            enc_value,
+        }
+    }
+}
+
+impl<EN, EV> TagQueryEncoder for TagMatchEncoder<EN, EV>
+where
+    EN: Fn(&str) -> Result<Vec<u8>, Error>,
+    EV: Fn(&str) -> Result<Vec<u8>, Error>,
+{
+    type Arg = Vec<u8>;
+    type Clause = TagMatch;
+
+    fn encode_name(&mut self, name: &TagName) -> Result<Self::Arg, Error> {
+        Ok(match name {
+            TagName::Encrypted(name) | TagName::Plaintext(name) => (self.enc_name)(name)?,
+        })
+    }
+
+    fn encode_value(&mut self, value: &String, tag_type: TagType) -> Result<Self::Arg, Error> {
+        Ok(if tag_type.is_plaintext() {
+            value.as_bytes().to_vec()
+        } else {
+            (self.enc_value)(value)?
+        })
+    }
+
+    fn encode_op_clause(
+        &mut self,
+        op: CompareOp,
+        enc_name: Self::Arg,
+        enc_value: Self::Arg,
+        tag_type: TagType,
+    ) -> Result<Option<Self::Clause>, Error> {
+        Ok(Some(TagMatch::Compare {
+            op,
+            name: enc_name,
+            value: enc_value,
+            tag_type,
+        }))
+    }
+
+    fn encode_in_clause(
+        &mut self,
+        enc_name: Self::Arg,
+        enc_values: Vec<Self::Arg>,
+        tag_type: TagType,
+        negate: bool,
+    ) -> Result<Option<Self::Clause>, Error> {
+        Ok(Some(TagMatch::In {
+            name: enc_name,
+            values: enc_values,
+            tag_type,
+            negate,
+        }))
+    }
+
+    fn encode_exist_clause(
+        &mut self,
+        enc_name: Self::Arg,
+        tag_type: TagType,
+        negate: bool,
+    ) -> Result<Option<Self::Clause>, Error> {
+        Ok(Some(TagMatch::Exist {
+            name: enc_name,
+            plaintext: tag_type.is_plaintext(),
+            negate,
+        }))
+    }
+
+    fn encode_conj_clause(
+        &mut self,
+        op: ConjunctionOp,
+        mut clauses: Vec<Self::Clause>,
+    ) -> Result<Option<Self::Clause>, Error> {
+        match clauses.len() {
+            // an empty disjunction matches nothing, an empty conjunction is no filter
+            0 if op == ConjunctionOp::And => Ok(None),
+            1 => Ok(clauses.pop()),
+            _ => Ok(Some(TagMatch::Conj { op, clauses })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::wql::tags::TagQuery;
+
+    fn tag(name: &str, value: &str, plaintext: bool) -> EncEntryTag {
+        EncEntryTag {
+            name: name.as_bytes().to_vec(),
+            value: value.as_bytes().to_vec(),
+            plaintext,
+        }
+    }
+
+    #[test]
+    fn tag_match_encode() {
+        let query = TagQuery::Or(vec![
+            TagQuery::And(vec![
+                TagQuery::Eq(TagName::Encrypted("enc".to_string()), "a".to_string()),
+                TagQuery::Gte(TagName::Plaintext("plain".to_string()), "5".to_string()),
+            ]),
+            TagQuery::Not(Box::new(TagQuery::Exist(vec![TagName::Encrypted(
+                "other".to_string(),
+            )]))),
+        ]);
+        let mut enc = TagMatchEncoder::new(
+            |name: &str| Ok(name.as_bytes().to_vec()),
+            |value: &str| Ok(value.to_uppercase().into_bytes()),
+        );
+        let filter = enc.encode_query(&query).unwrap().unwrap();
+
+        assert!(filter.matches(&[tag("enc", "A", false), tag("plain", "7", true)]));
+        assert!(!filter.matches(&[
+            tag("enc", "A", false),
+            tag("plain", "3", true),
+            tag("other", "x", false)
+        ]));
+        assert!(filter.matches(&[tag("plain", "3", true)]));
+        assert!(!filter.matches(&[tag("enc", "a", false), tag("other", "", false)]));
+    }
+
+    #[test]
+    fn tag_match_numeric() {
+        let query = TagQuery::And(vec![
+            TagQuery::Gt(TagName::Plaintext("#seq".to_string()), "9".to_string()),
+            TagQuery::Not(Box::new(TagQuery::In(
+                TagName::Plaintext("#seq".to_string()),
+                vec!["12".to_string()],
+            ))),
+        ]);
+        let mut enc = TagMatchEncoder::new(
+            |name: &str| Ok(name.as_bytes().to_vec()),
+            |value: &str| Ok(value.as_bytes().to_vec()),
+        );
+        let filter = enc.encode_query(&query).unwrap().unwrap();
+
+        assert!(filter.matches(&[tag("#seq", "10", true)]));
+        assert!(filter.matches(&[tag("#seq", "9.5e0", true)]));
+        assert!(!filter.matches(&[tag("#seq", "9", true)]));
+        assert!(!filter.matches(&[tag("#seq", "12.0", true)]));
+        assert!(!filter.matches(&[tag("#seq", "10", false)]));
+    }
+
+    #[test]
+    fn tag_match_date() {
+        let query = TagQuery::And(vec![
+            TagQuery::Lt(
+                TagName::Plaintext("@created".to_string()),
+                "2021-06-01T00:00:00Z".to_string(),
+            ),
+            TagQuery::Not(Box::new(TagQuery::In(
+                TagName::Plaintext("@created".to_string()),
+                vec!["2021-01-01T00:00:00Z".to_string()],
+            ))),
+        ]);
+        let mut enc = TagMatchEncoder::new(
+            |name: &str| Ok(name.as_bytes().to_vec()),
+            |value: &str| Ok(value.as_bytes().to_vec()),
+        );
+        let filter = enc.encode_query(&query).unwrap().unwrap();
+
+        assert!(filter.matches(&[tag("@created", "2021-05-31T23:59:59Z", true)]));
+        assert!(!filter.matches(&[tag("@created", "2021-06-01T00:00:00Z", true)]));
+        assert!(!filter.matches(&[tag("@created", "2021-01-01T00:00:00Z", true)]));
+        // not a well-formed date, although it sorts before the target
+        assert!(!filter.matches(&[tag("@created", "2021-05-31", true)]));
+    }
+
+    #[test]
+    fn tag_match_like() {
+        assert!(like_match(b"value", b"val%"));
+        assert!(like_match(b"value", b"%lu%"));
+        assert!(like_match(b"value", b"v_l_e"));
+        assert!(like_match(b"", b"%"));
+        assert!(!like_match(b"value", b"val"));
+        assert!(!like_match(b"value", b"%x%"));
+    }
+}
@@ -1,4 +1,5 @@
 pub use indy_wql::{AbstractQuery, Query};
 
+pub mod filter;
 pub mod sql;
 pub mod tags;
@@ -2,12 +2,15 @@ use std::marker::PhantomData;
 
 use itertools::Itertools;
 
-use super::tags::{CompareOp, ConjunctionOp, TagName, TagQueryEncoder};
+use super::tags::{CompareOp, ConjunctionOp, TagName, TagQueryEncoder, TagType};
 use crate::error::Error;
 
 pub struct TagSqlEncoder<'e, EN, EV> {
     pub enc_name: EN,
     pub enc_value: EV,
+    pub numeric_value: fn(&str) -> String,
+    pub is_numeric: fn(&str) -> String,
+    pub is_date: fn(&str) -> String,
     pub arguments: Vec<Vec<u8>>,
     _pd: PhantomData<&'e ()>,
 }
@@ -17,16 +20,53 @@ where
     EN: Fn(&str) -> Result<Vec<u8>, Error> + 'e,
     EV: Fn(&str) -> Result<Vec<u8>, Error> + 'e,
 {
-    pub fn new(enc_name: EN, enc_value: EV) -> Self {
+    /// Create a new encoder, where `numeric_value` formats the SQL expression
+    /// used to convert a tag value to a number, `is_numeric` formats the
+    /// condition that a stored tag value is a valid number, and `is_date`
+    /// the condition that it is a valid date
+    pub fn new(
+        enc_name: EN,
+        enc_value: EV,
+        numeric_value: fn(&str) -> String,
+        is_numeric: fn(&str) -> String,
+        is_date: fn(&str) -> String,
+    ) -> Self {
         Self {
             enc_name,
             enc_value,
+            numeric_value,
+            is_numeric,
+            is_date,
             arguments: vec![],
             _pd: PhantomData,
         }
     }
 }
 
+impl<'e, EN, EV> TagSqlEncoder<'e, EN, EV> {
+    // the conversion is only applied to valid numbers in tags having the
+    // expected name, as the database may evaluate the expression before the
+    // other conditions. other values are NULL and never match
+    fn numeric_tag_value(&self, name_idx: usize) -> String {
+        format!(
+            "CASE WHEN name = ${} AND plaintext = 1 AND {} THEN {} END",
+            name_idx,
+            (self.is_numeric)("value"),
+            (self.numeric_value)("value")
+        )
+    }
+
+    // date values are compared as byte strings, ignoring any stored values
+    // which are not well-formed dates
+    fn date_condition(&self, tag_type: TagType) -> String {
+        if tag_type == TagType::Date {
+            format!(" AND {}", (self.is_date)("value"))
+        } else {
+            String::new()
+        }
+    }
+}
+
 impl<'e, EN, EV> TagQueryEncoder for TagSqlEncoder<'e, EN, EV>
 where
     EN: Fn(&str) -> Result<Vec<u8>, Error> + 'e,
@@ -41,8 +81,8 @@ where
         })
     }
 
-    fn encode_value(&mut self, value: &String, is_plaintext: bool) -> Result<Self::Arg, Error> {
-        Ok(if is_plaintext {
+    fn encode_value(&mut self, value: &String, tag_type: TagType) -> Result<Self::Arg, Error> {
+        Ok(if tag_type.is_plaintext() {
             value.as_bytes().to_vec()
         } else {
             (&self.enc_value)(value)?
@@ -54,9 +94,23 @@ where
         op: CompareOp,
         enc_name: Self::Arg,
         enc_value: Self::Arg,
-        is_plaintext: bool,
+        tag_type: TagType,
     ) -> Result<Option<Self::Clause>, Error> {
         let idx = self.arguments.len();
+        if tag_type == TagType::Numeric {
+            let query = format!(
+                "i.id IN (SELECT item_id FROM items_tags WHERE name = ${} AND plaintext = 1 AND {} {} {})",
+                idx + 1,
+                self.numeric_tag_value(idx + 2),
+                op.as_sql_str(),
+                (self.numeric_value)(&format!("${}", idx + 3)),
+            );
+            self.arguments.push(enc_name.clone());
+            self.arguments.push(enc_name);
+            self.arguments.push(enc_value);
+            return Ok(Some(query));
+        }
+        let is_plaintext = tag_type.is_plaintext();
         let (op_prefix, match_prefix) = match (is_plaintext, op.as_sql_str_for_prefix()) {
             (false, Some(pfx_op)) if enc_value.len() > 12 => {
                 // the first 12 characters of an encrypted tag is the nonce, based
@@ -77,12 +131,13 @@ where
         }
 
         let query = format!(
-            "i.id IN (SELECT item_id FROM items_tags WHERE name = ${} AND value {} ${}{} AND plaintext = {})",
+            "i.id IN (SELECT item_id FROM items_tags WHERE name = ${} AND value {} ${}{} AND plaintext = {}{})",
             idx + 1,
             op.as_sql_str(),
             idx + 2,
             op_prefix.as_str(),
-            if is_plaintext { 1 } else { 0 }
+            if is_plaintext { 1 } else { 0 },
+            self.date_condition(tag_type)
         );
         Ok(Some(query))
     }
@@ -91,16 +146,36 @@ where
         &mut self,
         enc_name: Self::Arg,
         enc_values: Vec<Self::Arg>,
-        is_plaintext: bool,
+        tag_type: TagType,
         negate: bool,
     ) -> Result<Option<Self::Clause>, Error> {
+        if tag_type == TagType::Numeric {
+            let idx = self.arguments.len();
+            let args_in = (0..enc_values.len())
+                .map(|i| (self.numeric_value)(&format!("${}", idx + i + 3)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let query = format!(
+                "i.id IN (SELECT item_id FROM items_tags WHERE name = ${} AND plaintext = 1 AND {} {} ({}))",
+                idx + 1,
+                self.numeric_tag_value(idx + 2),
+                if negate { "NOT IN" } else { "IN" },
+                args_in,
+            );
+            self.arguments.push(enc_name.clone());
+            self.arguments.push(enc_name);
+            self.arguments.extend(enc_values);
+            return Ok(Some(query));
+        }
+        let is_plaintext = tag_type.is_plaintext();
         let args_in = Itertools::intersperse(std::iter::repeat("$$").take(enc_values.len()), ", ")
             .collect::<String>();
         let query = format!(
-            "i.id IN (SELECT item_id FROM items_tags WHERE name = $$ AND value {} ({}) AND plaintext = {})",
+            "i.id IN (SELECT item_id FROM items_tags WHERE name = $$ AND value {} ({}) AND plaintext = {}{})",
             if negate { "NOT IN" } else { "IN" },
             args_in,
-            if is_plaintext { 1 } else { 0 }
+            if is_plaintext { 1 } else { 0 },
+            self.date_condition(tag_type)
         );
         self.arguments.push(enc_name);
         self.arguments.extend(enc_values);
@@ -110,13 +185,13 @@ where
     fn encode_exist_clause(
         &mut self,
         enc_name: Self::Arg,
-        is_plaintext: bool,
+        tag_type: TagType,
         negate: bool,
     ) -> Result<Option<Self::Clause>, Error> {
         let query = format!(
             "i.id {} (SELECT item_id FROM items_tags WHERE name = $$ AND plaintext = {})",
             if negate { "NOT IN" } else { "IN" },
-            if is_plaintext { 1 } else { 0 }
+            if tag_type.is_plaintext() { 1 } else { 0 }
         );
         self.arguments.push(enc_name);
         Ok(Some(query))
@@ -183,6 +258,9 @@ mod tests {
         let mut enc = TagSqlEncoder::new(
             |name: &str| Ok(format!("--{}--", name).into_bytes()),
             |value: &str| Ok(value.to_uppercase().into_bytes()),
+            |expr| format!("NUM({})", expr),
+            |expr| format!("ISNUM({})", expr),
+            |expr| format!("ISDATE({})", expr),
         );
         let query_str = enc.encode_query(&query).unwrap().unwrap();
         assert_eq!(query_str, "((i.id IN (SELECT item_id FROM items_tags WHERE name = $1 AND value = $2 AND SUBSTR(value, 1, 12) = $3 AND plaintext = 0) AND i.id IN (SELECT item_id FROM items_tags WHERE name = $4 AND value = $5 AND plaintext = 1)) OR (i.id IN (SELECT item_id FROM items_tags WHERE name = $6 AND value = $7 AND SUBSTR(value, 1, 12) = $8 AND plaintext = 0) AND i.id IN (SELECT item_id FROM items_tags WHERE name = $9 AND value != $10 AND plaintext = 1)))");
@@ -203,4 +281,68 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn tag_query_encode_numeric() {
+        let query = TagQuery::And(vec![
+            TagQuery::Gt(TagName::Plaintext("#seq".to_string()), "9".to_string()),
+            TagQuery::Not(Box::new(TagQuery::In(
+                TagName::Plaintext("#seq".to_string()),
+                vec!["10".to_string(), "12.5".to_string()],
+            ))),
+        ]);
+        let mut enc = TagSqlEncoder::new(
+            |name: &str| Ok(format!("--{}--", name).into_bytes()),
+            |value: &str| Ok(value.to_uppercase().into_bytes()),
+            |expr| format!("NUM({})", expr),
+            |expr| format!("ISNUM({})", expr),
+            |expr| format!("ISDATE({})", expr),
+        );
+        let query_str = enc.encode_query(&query).unwrap().unwrap();
+        assert_eq!(query_str, "(i.id IN (SELECT item_id FROM items_tags WHERE name = $1 AND plaintext = 1 AND CASE WHEN name = $2 AND plaintext = 1 AND ISNUM(value) THEN NUM(value) END > NUM($3)) AND i.id IN (SELECT item_id FROM items_tags WHERE name = $4 AND plaintext = 1 AND CASE WHEN name = $5 AND plaintext = 1 AND ISNUM(value) THEN NUM(value) END NOT IN (NUM($6), NUM($7))))");
+        assert_eq!(
+            enc.arguments,
+            vec![
+                b"--#seq--".to_vec(),
+                b"--#seq--".to_vec(),
+                b"9".to_vec(),
+                b"--#seq--".to_vec(),
+                b"--#seq--".to_vec(),
+                b"10".to_vec(),
+                b"12.5".to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn tag_query_encode_date() {
+        let query = TagQuery::And(vec![
+            TagQuery::Gte(
+                TagName::Plaintext("@created".to_string()),
+                "2021-01-01T00:00:00Z".to_string(),
+            ),
+            TagQuery::In(
+                TagName::Plaintext("@created".to_string()),
+                vec!["2021-03-04T05:06:07Z".to_string()],
+            ),
+        ]);
+        let mut enc = TagSqlEncoder::new(
+            |name: &str| Ok(format!("--{}--", name).into_bytes()),
+            |value: &str| Ok(value.to_uppercase().into_bytes()),
+            |expr| format!("NUM({})", expr),
+            |expr| format!("ISNUM({})", expr),
+            |expr| format!("ISDATE({})", expr),
+        );
+        let query_str = enc.encode_query(&query).unwrap().unwrap();
+        assert_eq!(query_str, "(i.id IN (SELECT item_id FROM items_tags WHERE name = $1 AND value >= $2 AND plaintext = 1 AND ISDATE(value)) AND i.id IN (SELECT item_id FROM items_tags WHERE name = $$ AND value IN ($$) AND plaintext = 1 AND ISDATE(value)))");
+        assert_eq!(
+            enc.arguments,
+            vec![
+                b"--@created--".to_vec(),
+                b"2021-01-01T00:00:00Z".to_vec(),
+                b"--@created--".to_vec(),
+                b"2021-03-04T05:06:07Z".to_vec(),
+            ]
+        );
+    }
 }
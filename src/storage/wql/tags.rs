@@ -17,9 +17,253 @@ pub fn tag_query(query: Query) -> Result<TagQuery, Error> {
     Ok(result)
 }
 
-pub fn validate_tag_query(_query: &TagQuery) -> Result<(), Error> {
-    // FIXME only equality comparison supported for encrypted keys
-    Ok(())
+/// Check that the operators of a tag query are supported for the types of
+/// the tags being compared
+pub fn validate_tag_query(query: &TagQuery) -> Result<(), Error> {
+    validate_clause(query, "")
+}
+
+fn validate_clause(query: &TagQuery, prefix: &str) -> Result<(), Error> {
+    let path = clause_path(query, prefix);
+    match query {
+        TagQuery::Eq(name, value) | TagQuery::Neq(name, value) => {
+            validate_tag_value(name, value, &path)
+        }
+        TagQuery::Gt(name, value)
+        | TagQuery::Gte(name, value)
+        | TagQuery::Lt(name, value)
+        | TagQuery::Lte(name, value) => {
+            if name.tag_type() == TagType::Encrypted {
+                return Err(err_msg!(
+                    Input,
+                    "Range comparison is not supported for encrypted tags, at '{}'",
+                    path
+                ));
+            }
+            validate_tag_value(name, value, &path)
+        }
+        TagQuery::Like(name, _) => match name.tag_type() {
+            TagType::Plaintext | TagType::Date => Ok(()),
+            TagType::Encrypted => Err(err_msg!(
+                Input,
+                "Pattern matching is not supported for encrypted tags, at '{}'",
+                path
+            )),
+            TagType::Numeric => Err(err_msg!(
+                Input,
+                "Pattern matching is not supported for numeric tags, at '{}'",
+                path
+            )),
+        },
+        TagQuery::In(name, values) => values
+            .iter()
+            .try_for_each(|value| validate_tag_value(name, value, &path)),
+        TagQuery::Exist(_) => Ok(()),
+        TagQuery::And(subqueries) | TagQuery::Or(subqueries) => {
+            for (idx, subquery) in subqueries.iter().enumerate() {
+                validate_clause(subquery, &format!("{}[{}]", path, idx))?;
+            }
+            Ok(())
+        }
+        TagQuery::Not(subquery) => validate_clause(subquery, &path),
+    }
+}
+
+fn validate_tag_value(name: &TagName, value: &str, path: &str) -> Result<(), Error> {
+    match name.tag_type() {
+        TagType::Numeric if !is_numeric_value(value) => Err(err_msg!(
+            Input,
+            "Invalid value for numeric tag, at '{}'",
+            path
+        )),
+        TagType::Date if !is_date_value(value) => {
+            Err(err_msg!(Input, "Invalid value for date tag, at '{}'", path))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Limits on the complexity of a tag query, bounding the size of the
+/// resulting SQL statement and its arguments
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TagQueryLimits {
+    /// The maximum nesting depth of clauses, where a single comparison
+    /// has a depth of one
+    pub max_depth: usize,
+    /// The maximum total number of clauses
+    pub max_clauses: usize,
+    /// The maximum total number of tag names and values
+    pub max_arguments: usize,
+}
+
+impl Default for TagQueryLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 32,
+            max_clauses: 512,
+            max_arguments: 4096,
+        }
+    }
+}
+
+#[derive(Default)]
+struct QueryCounts {
+    clauses: usize,
+    arguments: usize,
+}
+
+/// Check that a tag query does not exceed the given limits
+pub fn check_query_limits<K: ToString, V>(
+    query: &AbstractQuery<K, V>,
+    limits: &TagQueryLimits,
+) -> Result<(), Error> {
+    check_clause_limits(query, limits, "", 1, &mut QueryCounts::default())
+}
+
+fn check_clause_limits<K: ToString, V>(
+    query: &AbstractQuery<K, V>,
+    limits: &TagQueryLimits,
+    prefix: &str,
+    depth: usize,
+    counts: &mut QueryCounts,
+) -> Result<(), Error> {
+    let path = clause_path(query, prefix);
+    if depth > limits.max_depth {
+        return Err(err_msg!(
+            Input,
+            "Tag query exceeds the maximum depth of {}, at '{}'",
+            limits.max_depth,
+            path
+        ));
+    }
+    counts.clauses += 1;
+    if counts.clauses > limits.max_clauses {
+        return Err(err_msg!(
+            Input,
+            "Tag query exceeds the maximum of {} clauses, at '{}'",
+            limits.max_clauses,
+            path
+        ));
+    }
+    counts.arguments += match query {
+        AbstractQuery::And(_) | AbstractQuery::Or(_) | AbstractQuery::Not(_) => 0,
+        AbstractQuery::In(_, values) => values.len() + 1,
+        AbstractQuery::Exist(names) => names.len(),
+        _ => 2,
+    };
+    if counts.arguments > limits.max_arguments {
+        return Err(err_msg!(
+            Input,
+            "Tag query exceeds the maximum of {} arguments, at '{}'",
+            limits.max_arguments,
+            path
+        ));
+    }
+    match query {
+        AbstractQuery::And(subqueries) | AbstractQuery::Or(subqueries) => {
+            for (idx, subquery) in subqueries.iter().enumerate() {
+                check_clause_limits(
+                    subquery,
+                    limits,
+                    &format!("{}[{}]", path, idx),
+                    depth + 1,
+                    counts,
+                )?;
+            }
+            Ok(())
+        }
+        AbstractQuery::Not(subquery) => {
+            check_clause_limits(subquery, limits, &path, depth + 1, counts)
+        }
+        _ => Ok(()),
+    }
+}
+
+// the path to a clause is formed from the operators and tag names leading to
+// it, for example `$or[1].$not.~score.$gt`
+fn clause_path<K: ToString, V>(query: &AbstractQuery<K, V>, prefix: &str) -> String {
+    let clause = match query {
+        AbstractQuery::And(_) => "$and".to_string(),
+        AbstractQuery::Or(_) => "$or".to_string(),
+        AbstractQuery::Not(_) => "$not".to_string(),
+        AbstractQuery::Eq(name, _) => format!("{}.$eq", name.to_string()),
+        AbstractQuery::Neq(name, _) => format!("{}.$neq", name.to_string()),
+        AbstractQuery::Gt(name, _) => format!("{}.$gt", name.to_string()),
+        AbstractQuery::Gte(name, _) => format!("{}.$gte", name.to_string()),
+        AbstractQuery::Lt(name, _) => format!("{}.$lt", name.to_string()),
+        AbstractQuery::Lte(name, _) => format!("{}.$lte", name.to_string()),
+        AbstractQuery::Like(name, _) => format!("{}.$like", name.to_string()),
+        AbstractQuery::In(name, _) => format!("{}.$in", name.to_string()),
+        AbstractQuery::Exist(_) => "$exist".to_string(),
+    };
+    if prefix.is_empty() {
+        clause
+    } else {
+        format!("{}.{}", prefix, clause)
+    }
+}
+
+/// Determine whether a tag value is a valid decimal number, with an optional
+/// sign, fractional part and exponent
+pub fn is_numeric_value(value: &str) -> bool {
+    fn digits(s: &[u8]) -> usize {
+        s.iter().take_while(|c| c.is_ascii_digit()).count()
+    }
+    let mut s = value.as_bytes();
+    if let Some(b'+') | Some(b'-') = s.first() {
+        s = &s[1..];
+    }
+    let int_len = digits(s);
+    s = &s[int_len..];
+    let mut frac_len = 0;
+    if let Some(b'.') = s.first() {
+        frac_len = digits(&s[1..]);
+        s = &s[(frac_len + 1)..];
+    }
+    if int_len + frac_len == 0 {
+        return false;
+    }
+    if let Some(b'e') | Some(b'E') = s.first() {
+        s = &s[1..];
+        if let Some(b'+') | Some(b'-') = s.first() {
+            s = &s[1..];
+        }
+        let exp_len = digits(s);
+        if exp_len == 0 {
+            return false;
+        }
+        s = &s[exp_len..];
+    }
+    s.is_empty()
+}
+
+/// Determine whether a tag value is a valid date, written as a UTC timestamp
+/// in the form `YYYY-MM-DDTHH:MM:SSZ`. Dates in this form sort chronologically
+/// when compared as byte strings
+pub fn is_date_value(value: &str) -> bool {
+    fn field(s: &[u8], start: usize, len: usize, min: u32, max: u32) -> bool {
+        let digits = &s[start..(start + len)];
+        digits.iter().all(u8::is_ascii_digit)
+            && (min..=max).contains(
+                &digits
+                    .iter()
+                    .fold(0, |acc, c| acc * 10 + u32::from(c - b'0')),
+            )
+    }
+    let s = value.as_bytes();
+    s.len() == 20
+        && s[4] == b'-'
+        && s[7] == b'-'
+        && s[10] == b'T'
+        && s[13] == b':'
+        && s[16] == b':'
+        && s[19] == b'Z'
+        && field(s, 0, 4, 0, 9999)
+        && field(s, 5, 2, 1, 12)
+        && field(s, 8, 2, 1, 31)
+        && field(s, 11, 2, 0, 23)
+        && field(s, 14, 2, 0, 59)
+        && field(s, 17, 2, 0, 60)
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -28,6 +272,34 @@ pub enum TagName {
     Plaintext(String),
 }
 
+impl TagName {
+    /// Plaintext tags with a name beginning with `#` hold numeric values,
+    /// and are compared numerically rather than as byte strings. Those with
+    /// a name beginning with `@` hold dates
+    pub fn tag_type(&self) -> TagType {
+        match self {
+            Self::Encrypted(_) => TagType::Encrypted,
+            Self::Plaintext(name) if name.starts_with('#') => TagType::Numeric,
+            Self::Plaintext(name) if name.starts_with('@') => TagType::Date,
+            Self::Plaintext(_) => TagType::Plaintext,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagType {
+    Encrypted,
+    Plaintext,
+    Numeric,
+    Date,
+}
+
+impl TagType {
+    pub fn is_plaintext(&self) -> bool {
+        *self != Self::Encrypted
+    }
+}
+
 impl ToString for TagName {
     fn to_string(&self) -> String {
         match self {
@@ -56,28 +328,28 @@ pub trait TagQueryEncoder {
 
     fn encode_name(&mut self, name: &TagName) -> Result<Self::Arg, Error>;
 
-    fn encode_value(&mut self, value: &String, is_plaintext: bool) -> Result<Self::Arg, Error>;
+    fn encode_value(&mut self, value: &String, tag_type: TagType) -> Result<Self::Arg, Error>;
 
     fn encode_op_clause(
         &mut self,
         op: CompareOp,
         enc_name: Self::Arg,
         enc_value: Self::Arg,
-        is_plaintext: bool,
+        tag_type: TagType,
     ) -> Result<Option<Self::Clause>, Error>;
 
     fn encode_in_clause(
         &mut self,
         enc_name: Self::Arg,
         enc_values: Vec<Self::Arg>,
-        is_plaintext: bool,
+        tag_type: TagType,
         negate: bool,
     ) -> Result<Option<Self::Clause>, Error>;
 
     fn encode_exist_clause(
         &mut self,
         enc_name: Self::Arg,
-        is_plaintext: bool,
+        tag_type: TagType,
         negate: bool,
     ) -> Result<Option<Self::Clause>, Error>;
 
@@ -206,15 +478,12 @@ fn encode_tag_op<V, E>(
 where
     E: TagQueryEncoder<Clause = V>,
 {
-    let is_plaintext = match &name {
-        TagName::Plaintext(_) => true,
-        _ => false,
-    };
+    let tag_type = name.tag_type();
     let enc_name = enc.encode_name(name)?;
-    let enc_value = enc.encode_value(value, is_plaintext)?;
+    let enc_value = enc.encode_value(value, tag_type)?;
     let op = if negate { op.negate() } else { op };
 
-    enc.encode_op_clause(op, enc_name, enc_value, is_plaintext)
+    enc.encode_op_clause(op, enc_name, enc_value, tag_type)
 }
 
 fn encode_tag_in<V, E>(
@@ -226,17 +495,14 @@ fn encode_tag_in<V, E>(
 where
     E: TagQueryEncoder<Clause = V>,
 {
-    let is_plaintext = match &name {
-        TagName::Plaintext(_) => true,
-        _ => false,
-    };
+    let tag_type = name.tag_type();
     let enc_name = enc.encode_name(name)?;
     let enc_values = values
         .into_iter()
-        .map(|val| enc.encode_value(val, is_plaintext))
+        .map(|val| enc.encode_value(val, tag_type))
         .collect::<Result<Vec<_>, Error>>()?;
 
-    enc.encode_in_clause(enc_name, enc_values, is_plaintext, negate)
+    enc.encode_in_clause(enc_name, enc_values, tag_type, negate)
 }
 
 fn encode_tag_exist<V, E>(names: &[TagName], enc: &mut E, negate: bool) -> Result<Option<V>, Error>
@@ -246,12 +512,9 @@ where
     match names.len() {
         0 => Ok(None),
         1 => {
-            let is_plaintext = match names[0] {
-                TagName::Plaintext(_) => true,
-                _ => false,
-            };
+            let tag_type = names[0].tag_type();
             let enc_name = enc.encode_name(&names[0])?;
-            enc.encode_exist_clause(enc_name, is_plaintext, negate)
+            enc.encode_exist_clause(enc_name, tag_type, negate)
         }
         n => {
             let mut cs = Vec::with_capacity(n);
@@ -299,7 +562,7 @@ mod tests {
             Ok(name.to_string())
         }
 
-        fn encode_value(&mut self, value: &String, _is_plaintext: bool) -> Result<String, Error> {
+        fn encode_value(&mut self, value: &String, _tag_type: TagType) -> Result<String, Error> {
             Ok(value.clone())
         }
 
@@ -308,7 +571,7 @@ mod tests {
             op: CompareOp,
             name: Self::Arg,
             value: Self::Arg,
-            _is_plaintext: bool,
+            _tag_type: TagType,
         ) -> Result<Option<Self::Clause>, Error> {
             Ok(Some(format!("{} {} {}", name, op.as_sql_str(), value)))
         }
@@ -316,7 +579,7 @@ mod tests {
         fn encode_exist_clause(
             &mut self,
             name: Self::Arg,
-            _is_plaintext: bool,
+            _tag_type: TagType,
             negate: bool,
         ) -> Result<Option<Self::Clause>, Error> {
             let op = if negate { "NOT EXIST" } else { "EXIST" };
@@ -327,7 +590,7 @@ mod tests {
             &mut self,
             name: Self::Arg,
             values: Vec<Self::Arg>,
-            _is_plaintext: bool,
+            _tag_type: TagType,
             negate: bool,
         ) -> Result<Option<Self::Clause>, Error> {
             let op = if negate { "NOT IN" } else { "IN" };
@@ -374,6 +637,138 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_validate_numeric() {
+        for value in &["10", "-2.5", "+.5", "1e5", "3.E-2"] {
+            assert!(is_numeric_value(value), "{}", value);
+        }
+        for value in &["", "-", ".", "1e", "0x10", " 1", "inf", "1.2.3"] {
+            assert!(!is_numeric_value(value), "{}", value);
+        }
+
+        let query = Query::Or(vec![
+            Query::Gt("~#seq".to_string(), "10".to_string()),
+            Query::In(
+                "~#seq".to_string(),
+                vec!["1".to_string(), "2.5".to_string()],
+            ),
+            Query::Lt("~plain".to_string(), "b".to_string()),
+        ]);
+        assert!(tag_query(query).is_ok());
+        for query in [
+            Query::Gt("enctag".to_string(), "10".to_string()),
+            Query::Eq("~#seq".to_string(), "ten".to_string()),
+            Query::Like("~#seq".to_string(), "1%".to_string()),
+            Query::Not(Box::new(Query::In(
+                "~#seq".to_string(),
+                vec!["1".to_string(), "x".to_string()],
+            ))),
+        ] {
+            assert_eq!(
+                tag_query(query).unwrap_err().kind(),
+                crate::ErrorKind::Input
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_date() {
+        for value in &["2021-03-04T05:06:07Z", "0001-12-31T23:59:60Z"] {
+            assert!(is_date_value(value), "{}", value);
+        }
+        for value in &[
+            "",
+            "2021-03-04",
+            "2021-03-04T05:06:07",
+            "2021-03-04T05:06:07.5Z",
+            "2021-03-04T05:06:07+00:00",
+            "2021-3-04T05:06:07Z",
+            "2021-13-04T05:06:07Z",
+            "2021-03-00T05:06:07Z",
+            "2021-03-04T24:06:07Z",
+            "2021-03-04 05:06:07Z",
+        ] {
+            assert!(!is_date_value(value), "{}", value);
+        }
+
+        let query = Query::And(vec![
+            Query::Gte("~@created".to_string(), "2021-01-01T00:00:00Z".to_string()),
+            Query::Like("~@created".to_string(), "2021-03-%".to_string()),
+        ]);
+        assert!(tag_query(query).is_ok());
+        for query in [
+            Query::Lt("~@created".to_string(), "2021-01-01".to_string()),
+            Query::In(
+                "~@created".to_string(),
+                vec!["2021-01-01T00:00:00Z".to_string(), "today".to_string()],
+            ),
+        ] {
+            let err = tag_query(query).unwrap_err();
+            assert_eq!(err.kind(), crate::ErrorKind::Input);
+            assert!(err.to_string().starts_with("Invalid value for date tag"));
+        }
+    }
+
+    #[test]
+    fn test_validate_path() {
+        let query = Query::Or(vec![
+            Query::Eq("enctag".to_string(), "encval".to_string()),
+            Query::And(vec![
+                Query::Like("~plaintag".to_string(), "plain%".to_string()),
+                Query::Not(Box::new(Query::Like(
+                    "enctag".to_string(),
+                    "enc%".to_string(),
+                ))),
+            ]),
+        ]);
+        let err = tag_query(query).unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::Input);
+        assert_eq!(
+            err.to_string(),
+            "Pattern matching is not supported for encrypted tags, at '$or[1].$and[1].$not.enctag.$like'"
+        );
+    }
+
+    #[test]
+    fn test_query_limits() {
+        let limits = TagQueryLimits {
+            max_depth: 3,
+            max_clauses: 5,
+            max_arguments: 6,
+        };
+        let eq = |idx: usize| Query::Eq(format!("tag{}", idx), "value".to_string());
+
+        let query = Query::And(vec![eq(0), Query::Not(Box::new(eq(1)))]);
+        assert!(check_query_limits(&query, &limits).is_ok());
+        let query = Query::And(vec![
+            eq(0),
+            Query::Not(Box::new(Query::Not(Box::new(eq(1))))),
+        ]);
+        assert_eq!(
+            check_query_limits(&query, &limits).unwrap_err().to_string(),
+            "Tag query exceeds the maximum depth of 3, at '$and[1].$not.$not.tag1.$eq'"
+        );
+
+        let query = Query::Or((0..4).map(eq).collect());
+        assert_eq!(
+            check_query_limits(&query, &limits).unwrap_err().to_string(),
+            "Tag query exceeds the maximum of 6 arguments, at '$or[3].tag3.$eq'"
+        );
+        let query = Query::Or(vec![
+            Query::Exist(vec!["tag0".to_string()]),
+            Query::Exist(vec!["tag1".to_string()]),
+            Query::Exist(vec!["tag2".to_string()]),
+            Query::Exist(vec!["tag3".to_string()]),
+            Query::Exist(vec!["tag4".to_string()]),
+        ]);
+        assert_eq!(
+            check_query_limits(&query, &limits).unwrap_err().to_string(),
+            "Tag query exceeds the maximum of 5 clauses, at '$or[4].$exist'"
+        );
+        let query = Query::In("tag".to_string(), vec!["value".to_string(); 6]);
+        assert!(check_query_limits(&query, &limits).is_err());
+    }
+
     #[test]
     fn test_serialize() {
         let query = TagQuery::And(vec![
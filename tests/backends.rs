@@ -17,6 +17,30 @@ macro_rules! backend_tests {
             })
         }
 
+        #[test]
+        fn rename_copy_profile() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_rename_copy_profile(&db).await;
+            })
+        }
+
+        #[test]
+        fn rotate_profile_key() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_rotate_profile_key(&db).await;
+            })
+        }
+
+        #[test]
+        fn rotate_profile_key_after_failed_update() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_rotate_profile_key_after_failed_update(&db).await;
+            })
+        }
+
         #[test]
         fn fetch_fail() {
             block_on(async {
@@ -73,6 +97,54 @@ macro_rules! backend_tests {
             })
         }
 
+        #[test]
+        fn replace_if_version() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_replace_if_version(&db).await;
+            })
+        }
+
+        #[test]
+        fn update_tags() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_update_tags(&db).await;
+            })
+        }
+
+        #[test]
+        fn entry_timestamps() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_entry_timestamps(&db).await;
+            })
+        }
+
+        #[test]
+        fn set_expiry() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_set_expiry(&db).await;
+            })
+        }
+
+        #[test]
+        fn watch() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_watch(&db).await;
+            })
+        }
+
+        #[test]
+        fn verify() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_verify(&db).await;
+            })
+        }
+
         #[test]
         fn count() {
             block_on(async {
@@ -81,6 +153,54 @@ macro_rules! backend_tests {
             })
         }
 
+        #[test]
+        fn stats() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_stats(&db).await;
+            })
+        }
+
+        #[test]
+        fn tag_names() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_tag_names(&db).await;
+            })
+        }
+
+        #[test]
+        fn numeric_tags() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_numeric_tags(&db).await;
+            })
+        }
+
+        #[test]
+        fn date_tags() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_date_tags(&db).await;
+            })
+        }
+
+        #[test]
+        fn query_limits() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_query_limits(&db).await;
+            })
+        }
+
+        #[test]
+        fn order_by() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_order_by(&db).await;
+            })
+        }
+
         #[test]
         fn count_exist() {
             block_on(async {
@@ -97,6 +217,22 @@ macro_rules! backend_tests {
             })
         }
 
+        #[test]
+        fn scan_resume() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_scan_resume(&db).await;
+            })
+        }
+
+        #[test]
+        fn apply_batch() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_apply_batch(&db).await;
+            })
+        }
+
         #[test]
         fn remove_all() {
             block_on(async {
@@ -176,13 +312,334 @@ macro_rules! backend_tests {
                 super::utils::db_txn_fetch_for_update(&db).await;
             })
         }
+
+        #[test]
+        fn export_import() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_export_import(&db).await;
+            })
+        }
+
+        #[test]
+        fn purge_expired() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_purge_expired(&db).await;
+            })
+        }
+
+        #[cfg(feature = "memory")]
+        #[test]
+        fn migrate_from_memory() {
+            block_on(async {
+                let db = $init.await;
+                super::utils::db_migrate_from_memory(&db).await;
+            })
+        }
     };
 }
 
+#[cfg(feature = "memory")]
+mod memory {
+    use aries_askar::backend::memory::{MemoryStore, MemoryStoreOptions};
+    use aries_askar::{generate_raw_store_key, ErrorKind, ManageBackend, Store, StoreKeyMethod};
+
+    #[test]
+    fn create_open_remove() {
+        let name = format!("memory://test-{}", uuid::Uuid::new_v4());
+        let key = generate_raw_store_key(None).expect("Error creating raw key");
+        block_on(async move {
+            name.as_str()
+                .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None, false)
+                .await
+                .expect_err("Expected open failure");
+
+            let store = name
+                .as_str()
+                .provision_backend(StoreKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect("Error provisioning memory store");
+            let mut session = store.session(None).await.expect("Error starting session");
+            session
+                .insert("category", "name", b"value", None, None)
+                .await
+                .expect("Error inserting entry");
+            drop(session);
+
+            let store2 = name
+                .as_str()
+                .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None, false)
+                .await
+                .expect("Error opening memory store");
+            assert_eq!(store2.get_profile_name(), store.get_profile_name());
+            let mut session = store2.session(None).await.expect("Error starting session");
+            assert!(session
+                .fetch("category", "name", false)
+                .await
+                .expect("Error fetching entry")
+                .is_some());
+            drop(session);
+
+            let ro_name = format!("{}?mode=ro", name);
+            let store3 = ro_name
+                .as_str()
+                .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None, false)
+                .await
+                .expect("Error opening read-only memory store");
+            assert!(store3.is_read_only());
+            let mut session = store3.session(None).await.expect("Error starting session");
+            let err = session
+                .insert("category", "other", b"value", None, None)
+                .await
+                .expect_err("Expected error for read-only insert");
+            assert_eq!(err.kind(), ErrorKind::ReadOnly);
+            drop(session);
+            let err = std::sync::Arc::new(store3)
+                .start_expiry_sweeper(std::time::Duration::from_secs(1), |_| ())
+                .expect_err("Expected error for read-only expiry sweeper");
+            assert_eq!(err.kind(), ErrorKind::ReadOnly);
+
+            let key2 = generate_raw_store_key(None).expect("Error creating raw key");
+            name.as_str()
+                .open_backend(Some(StoreKeyMethod::RawKey), key2.as_ref(), None, false)
+                .await
+                .expect_err("Expected open failure with incorrect key");
+
+            assert!(name
+                .as_str()
+                .remove_backend()
+                .await
+                .expect("Error removing memory store"));
+            name.as_str()
+                .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None, false)
+                .await
+                .expect_err("Expected open failure after removal");
+        })
+    }
+
+    #[test]
+    fn query_limits_option() {
+        use aries_askar::TagFilter;
+
+        let key = generate_raw_store_key(None).expect("Error creating raw key");
+        block_on(async move {
+            let store = "memory://?max_query_args=4"
+                .provision_backend(StoreKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect("Error provisioning memory store");
+            assert_eq!(store.query_limits().max_arguments, 4);
+            let mut session = store.session(None).await.expect("Error starting session");
+            let values = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+            session
+                .count("category", Some(TagFilter::is_in("enc", values.clone())))
+                .await
+                .expect("Error performing count");
+            let filter = TagFilter::all_of(vec![
+                TagFilter::is_eq("plain", "a"),
+                TagFilter::is_in("enc", values),
+            ]);
+            let err = session
+                .count("category", Some(filter))
+                .await
+                .expect_err("Expected error for query limit");
+            assert_eq!(err.kind(), ErrorKind::Input);
+            assert!(err.to_string().contains("$and[1].enc.$in"));
+        })
+    }
+
+    async fn init_db() -> Store<MemoryStore> {
+        env_logger::builder().is_test(true).try_init().unwrap_or(());
+        let key = generate_raw_store_key(None).expect("Error creating raw key");
+        MemoryStoreOptions::default()
+            .provision(StoreKeyMethod::RawKey, key, None, false)
+            .await
+            .expect("Error provisioning memory store")
+    }
+
+    backend_tests!(init_db());
+
+    #[test]
+    fn rotate_profile_key_stale_session() {
+        block_on(async {
+            let db = init_db().await;
+            let mut writer = db.session(None).await.expect("Error starting session");
+            writer
+                .insert("category", "a", b"value", None, None)
+                .await
+                .expect("Error inserting test row");
+            assert_eq!(
+                db.rotate_profile_key(db.get_profile_name().to_string())
+                    .await
+                    .expect("Error rotating profile key"),
+                1
+            );
+
+            // the key held by the writer has been replaced
+            let errors = vec![
+                writer
+                    .insert("category", "b", b"value", None, None)
+                    .await
+                    .err(),
+                writer
+                    .replace("category", "a", b"value", None, None)
+                    .await
+                    .err(),
+                writer.remove("category", "a").await.err(),
+                writer
+                    .update_tags("category", "a", &[], &["tag"])
+                    .await
+                    .err(),
+                writer.set_expiry("category", "a", Some(1000)).await.err(),
+                writer
+                    .set_expiry_all("category", None, Some(1000))
+                    .await
+                    .err(),
+                writer.remove_all("category", None).await.err(),
+            ];
+            for err in errors {
+                assert_eq!(
+                    err.expect("Expected error for replaced profile key").kind(),
+                    ErrorKind::Conflict
+                );
+            }
+            drop(writer);
+
+            let mut session = db.session(None).await.expect("Error starting session");
+            session
+                .insert("category", "b", b"value", None, None)
+                .await
+                .expect("Error inserting test row");
+            let found = session
+                .fetch_all("category", None, None, false)
+                .await
+                .expect("Error fetching test rows");
+            assert_eq!(found.len(), 2);
+        })
+    }
+
+    #[test]
+    fn expiry_sweeper() {
+        block_on(async {
+            let db = init_db().await;
+            super::utils::db_expiry_sweeper(db).await;
+        })
+    }
+}
+
+#[cfg(all(feature = "any", feature = "memory", feature = "sqlite"))]
+mod migrate {
+    use aries_askar::{future::block_on, generate_raw_store_key, ManageBackend, StoreKeyMethod};
+
+    #[test]
+    fn sqlite_to_memory() {
+        let fname = format!("sqlite-test-{}.db", uuid::Uuid::new_v4());
+        let target_name = format!("memory://test-{}", uuid::Uuid::new_v4());
+        let key1 = generate_raw_store_key(None).expect("Error creating raw key");
+        let key2 = generate_raw_store_key(None).expect("Error creating raw key");
+
+        block_on(async move {
+            let source = format!("sqlite://{}", fname)
+                .as_str()
+                .provision_backend(StoreKeyMethod::RawKey, key1.as_ref(), None, false)
+                .await
+                .expect("Error provisioning sqlite store");
+            let profile = source
+                .create_profile(None)
+                .await
+                .expect("Error creating profile");
+            let mut session = source
+                .session(Some(profile.clone()))
+                .await
+                .expect("Error starting session");
+            for idx in 0..50 {
+                session
+                    .insert("category", &format!("name-{}", idx), b"value", None, None)
+                    .await
+                    .expect("Error inserting entry");
+            }
+            drop(session);
+            let mut session = source.session(None).await.expect("Error starting session");
+            session
+                .insert("other", "name", b"value", None, None)
+                .await
+                .expect("Error inserting entry");
+            drop(session);
+
+            let target = target_name
+                .as_str()
+                .provision_backend(
+                    StoreKeyMethod::RawKey,
+                    key2.as_ref(),
+                    Some(source.get_profile_name()),
+                    false,
+                )
+                .await
+                .expect("Error provisioning memory store");
+            let mut updates = 0;
+            let result = source
+                .migrate_to(&target, |_| updates += 1)
+                .await
+                .expect("Error migrating store");
+            assert_eq!(result.profiles_done, 2);
+            assert_eq!(result.entries_copied, 51);
+            assert_eq!(result.entries_skipped, 0);
+            assert!(updates >= 4);
+
+            // a repeated migration only skips the existing records
+            let result = source
+                .migrate_to(&target, |_| ())
+                .await
+                .expect("Error migrating store");
+            assert_eq!(result.entries_copied, 0);
+            assert_eq!(result.entries_skipped, 51);
+            target.close().await.expect("Error closing store");
+
+            let target = target_name
+                .as_str()
+                .open_backend(Some(StoreKeyMethod::RawKey), key2.as_ref(), None, false)
+                .await
+                .expect("Error opening memory store");
+            assert_eq!(target.get_profile_name(), source.get_profile_name());
+            let mut session = target
+                .session(Some(profile))
+                .await
+                .expect("Error starting session");
+            assert_eq!(
+                session
+                    .count("category", None)
+                    .await
+                    .expect("Error counting entries"),
+                50
+            );
+            let entry = session
+                .fetch("category", "name-0", false)
+                .await
+                .expect("Error fetching entry")
+                .expect("Expected entry");
+            assert_eq!(entry.value.as_ref(), b"value");
+            drop(session);
+
+            target.close().await.expect("Error closing store");
+            target_name
+                .as_str()
+                .remove_backend()
+                .await
+                .expect("Error removing memory store");
+            source.close().await.expect("Error closing store");
+            format!("sqlite://{}", fname)
+                .as_str()
+                .remove_backend()
+                .await
+                .expect("Error removing sqlite store");
+        })
+    }
+}
+
 #[cfg(feature = "sqlite")]
 mod sqlite {
     use aries_askar::backend::sqlite::{SqliteStore, SqliteStoreOptions};
-    use aries_askar::{generate_raw_store_key, ManageBackend, Store, StoreKeyMethod};
+    use aries_askar::{generate_raw_store_key, ErrorKind, ManageBackend, Store, StoreKeyMethod};
     use std::path::Path;
 
     #[test]
@@ -215,7 +672,7 @@ mod sqlite {
 
             let store2 = SqliteStoreOptions::new(fname.as_str())
                 .expect("Error initializing sqlite store options")
-                .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None)
+                .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None, false)
                 .await
                 .expect("Error opening sqlite store");
             store2.close().await.expect("Error closing sqlite store");
@@ -257,7 +714,7 @@ mod sqlite {
 
             SqliteStoreOptions::new(fname.as_str())
                 .expect("Error initializing sqlite store options")
-                .open_backend(Some(StoreKeyMethod::RawKey), key2.as_ref(), None)
+                .open_backend(Some(StoreKeyMethod::RawKey), key2.as_ref(), None, false)
                 .await
                 .expect("Error opening rekeyed store")
                 .close()
@@ -274,6 +731,202 @@ mod sqlite {
         })
     }
 
+    #[test]
+    fn pending_migrations() {
+        let fname = format!("sqlite-test-{}.db", uuid::Uuid::new_v4());
+        let key = generate_raw_store_key(None).expect("Error creating raw key");
+
+        block_on(async move {
+            SqliteStoreOptions::new(fname.as_str())
+                .expect("Error initializing sqlite store options")
+                .pending_migrations()
+                .await
+                .expect_err("Expected error for missing store");
+
+            let store = SqliteStoreOptions::new(fname.as_str())
+                .expect("Error initializing sqlite store options")
+                .provision_backend(StoreKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect("Error provisioning sqlite store");
+            store.close().await.expect("Error closing store");
+
+            let pending = SqliteStoreOptions::new(fname.as_str())
+                .expect("Error initializing sqlite store options")
+                .pending_migrations()
+                .await
+                .expect("Error checking migrations");
+            assert!(pending.is_empty());
+
+            SqliteStoreOptions::new(format!("{}?migrate=false", fname).as_str())
+                .expect("Error initializing sqlite store options")
+                .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None, false)
+                .await
+                .expect("Error opening sqlite store")
+                .close()
+                .await
+                .expect("Error closing store");
+
+            SqliteStoreOptions::new(fname.as_str())
+                .expect("Error initializing sqlite store options")
+                .remove_backend()
+                .await
+                .expect("Error removing sqlite store");
+        })
+    }
+
+    #[test]
+    fn backup_compact_db() {
+        env_logger::builder().is_test(true).try_init().unwrap_or(());
+        let fname = format!("sqlite-test-{}.db", uuid::Uuid::new_v4());
+        let backup_fname = format!("sqlite-test-{}.db", uuid::Uuid::new_v4());
+        let key = generate_raw_store_key(None).expect("Error creating raw key");
+
+        block_on(async move {
+            let mem_store = init_db().await;
+            let err = mem_store
+                .backup_to(backup_fname.as_str())
+                .await
+                .expect_err("Expected error for in-memory backup");
+            assert_eq!(err.kind(), ErrorKind::Unsupported);
+            mem_store.close().await.expect("Error closing store");
+
+            let store = SqliteStoreOptions::new(fname.as_str())
+                .expect("Error initializing sqlite store options")
+                .provision_backend(StoreKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect("Error provisioning sqlite store");
+
+            let mut conn = store.session(None).await.expect("Error starting session");
+            for idx in 0..10 {
+                conn.insert("category", &format!("name-{}", idx), b"value", None, None)
+                    .await
+                    .expect("Error inserting test row");
+            }
+            drop(conn);
+
+            store
+                .backup_to(backup_fname.as_str())
+                .await
+                .expect("Error performing backup");
+            assert!(Path::new(&backup_fname).exists());
+            let err = store
+                .backup_to(backup_fname.as_str())
+                .await
+                .expect_err("Expected error for existing backup file");
+            assert_eq!(err.kind(), ErrorKind::Duplicate);
+
+            let mut conn = store.session(None).await.expect("Error starting session");
+            conn.remove_all("category", None)
+                .await
+                .expect("Error removing test rows");
+            drop(conn);
+            store.compact().await.expect("Error compacting store");
+            store.close().await.expect("Error closing store");
+
+            let backup = SqliteStoreOptions::new(backup_fname.as_str())
+                .expect("Error initializing sqlite store options")
+                .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None, false)
+                .await
+                .expect("Error opening backup store");
+            let mut conn = backup.session(None).await.expect("Error starting session");
+            assert_eq!(
+                conn.count("category", None)
+                    .await
+                    .expect("Error counting rows"),
+                10
+            );
+            drop(conn);
+            backup.close().await.expect("Error closing backup store");
+
+            for path in [fname, backup_fname].iter() {
+                SqliteStoreOptions::new(path.as_str())
+                    .expect("Error initializing sqlite store options")
+                    .remove_backend()
+                    .await
+                    .expect("Error removing sqlite store");
+            }
+        })
+    }
+
+    #[test]
+    fn read_only_open() {
+        env_logger::builder().is_test(true).try_init().unwrap_or(());
+        let fname = format!("sqlite-test-{}.db", uuid::Uuid::new_v4());
+        let ro_uri = format!("sqlite://{}?mode=ro", fname);
+        let key = generate_raw_store_key(None).expect("Error creating raw key");
+
+        block_on(async move {
+            let err = SqliteStoreOptions::new(ro_uri.as_str())
+                .expect("Error initializing sqlite store options")
+                .provision_backend(StoreKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect_err("Expected error for read-only provision");
+            assert_eq!(err.kind(), ErrorKind::Input);
+
+            let store = SqliteStoreOptions::new(fname.as_str())
+                .expect("Error initializing sqlite store options")
+                .provision_backend(StoreKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect("Error provisioning sqlite store");
+            assert!(!store.is_read_only());
+            let mut conn = store.session(None).await.expect("Error starting session");
+            conn.insert("category", "name", b"value", None, None)
+                .await
+                .expect("Error inserting test row");
+            drop(conn);
+            store.close().await.expect("Error closing store");
+
+            for (uri, read_only) in [(ro_uri.as_str(), false), (fname.as_str(), true)].iter() {
+                let mut store = SqliteStoreOptions::new(*uri)
+                    .expect("Error initializing sqlite store options")
+                    .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None, *read_only)
+                    .await
+                    .expect("Error opening read-only store");
+                assert!(store.is_read_only());
+
+                let mut conn = store
+                    .transaction(None)
+                    .await
+                    .expect("Error starting transaction");
+                assert!(conn
+                    .fetch("category", "name", false)
+                    .await
+                    .expect("Error fetching test row")
+                    .is_some());
+                let err = conn
+                    .insert("category", "other", b"value", None, None)
+                    .await
+                    .expect_err("Expected error for read-only insert");
+                assert_eq!(err.kind(), ErrorKind::ReadOnly);
+                let err = conn
+                    .remove_all("category", None)
+                    .await
+                    .expect_err("Expected error for read-only removal");
+                assert_eq!(err.kind(), ErrorKind::ReadOnly);
+                conn.rollback().await.expect("Error rolling back");
+
+                let err = store
+                    .create_profile(None)
+                    .await
+                    .expect_err("Expected error for read-only profile creation");
+                assert_eq!(err.kind(), ErrorKind::ReadOnly);
+                let new_key = generate_raw_store_key(None).expect("Error creating raw key");
+                let err = store
+                    .rekey(StoreKeyMethod::RawKey, new_key.as_ref())
+                    .await
+                    .expect_err("Expected error for read-only rekey");
+                assert_eq!(err.kind(), ErrorKind::ReadOnly);
+                store.close().await.expect("Error closing store");
+            }
+
+            SqliteStoreOptions::new(fname.as_str())
+                .expect("Error initializing sqlite store options")
+                .remove_backend()
+                .await
+                .expect("Error removing sqlite store");
+        })
+    }
+
     async fn init_db() -> Store<SqliteStore> {
         env_logger::builder().is_test(true).try_init().unwrap_or(());
         let key = generate_raw_store_key(None).expect("Error creating raw key");
@@ -285,6 +938,14 @@ mod sqlite {
 
     backend_tests!(init_db());
 
+    #[test]
+    fn expiry_sweeper() {
+        block_on(async {
+            let db = init_db().await;
+            super::utils::db_expiry_sweeper(db).await;
+        })
+    }
+
     #[test]
     fn provision_from_str() {
         let key = generate_raw_store_key(None).expect("Error creating raw key");
@@ -319,4 +980,189 @@ mod postgres {
     }
 
     backend_tests!(init_db());
+
+    #[test]
+    fn watch_notify() {
+        use aries_askar::{future::timeout, EntryOperation};
+        use futures_lite::StreamExt;
+        use std::time::Duration;
+
+        block_on(async {
+            let db = init_db().await;
+            let publisher = db.open_notify().await.expect("Error opening store");
+            let listener = db.open_notify().await.expect("Error opening store");
+            let mut watch = listener
+                .watch(None, "category", None)
+                .await
+                .expect("Error starting watch");
+
+            let mut txn = publisher
+                .transaction(None)
+                .await
+                .expect("Error starting transaction");
+            txn.insert("category", "name", b"value", None, None)
+                .await
+                .expect("Error inserting test row");
+            txn.commit().await.expect("Error committing transaction");
+
+            let change = timeout(Duration::from_secs(10), watch.next())
+                .await
+                .expect("Timed out waiting for change")
+                .expect("Expected change");
+            assert_eq!(change.category, "category");
+            assert_eq!(change.name, "name");
+            assert_eq!(change.operation, EntryOperation::Insert);
+            assert_eq!(change.tags, Some(Vec::new()));
+
+            publisher.close().await.expect("Error closing store");
+            listener.close().await.expect("Error closing store");
+            // the stream ends once the store is closed
+            assert!(watch.next().await.is_none());
+        })
+    }
+    #[test]
+    fn schema_provision_remove() {
+        use aries_askar::{
+            backend::postgres::PostgresStoreOptions, generate_raw_store_key, ErrorKind,
+            ManageBackend, StoreKeyMethod,
+        };
+
+        env_logger::builder().is_test(true).try_init().unwrap_or(());
+        let url = std::env::var("POSTGRES_URL").expect("'POSTGRES_URL' must be defined");
+        let schema = format!("askar-test-{}", uuid::Uuid::new_v4());
+        let uri = format!(
+            "{}{}schema={}",
+            url,
+            if url.contains('?') { '&' } else { '?' },
+            schema
+        );
+        let key = generate_raw_store_key(None).expect("Error creating raw key");
+
+        block_on(async move {
+            let store = PostgresStoreOptions::new(uri.as_str())
+                .expect("Error initializing postgres store options")
+                .provision_backend(StoreKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect("Error provisioning postgres store");
+            let mut conn = store.session(None).await.expect("Error starting session");
+            conn.insert("category", "name", b"value", None, None)
+                .await
+                .expect("Error inserting test row");
+            drop(conn);
+            store.close().await.expect("Error closing store");
+
+            let store = PostgresStoreOptions::new(uri.as_str())
+                .expect("Error initializing postgres store options")
+                .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None, false)
+                .await
+                .expect("Error opening postgres store");
+            let mut conn = store.session(None).await.expect("Error starting session");
+            assert!(conn
+                .fetch("category", "name", false)
+                .await
+                .expect("Error fetching test row")
+                .is_some());
+            drop(conn);
+            store.close().await.expect("Error closing store");
+
+            assert!(PostgresStoreOptions::new(uri.as_str())
+                .expect("Error initializing postgres store options")
+                .remove_backend()
+                .await
+                .expect("Error removing postgres store"));
+            assert!(!PostgresStoreOptions::new(uri.as_str())
+                .expect("Error initializing postgres store options")
+                .remove_backend()
+                .await
+                .expect("Error removing postgres store"));
+            let err = PostgresStoreOptions::new(uri.as_str())
+                .expect("Error initializing postgres store options")
+                .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None, false)
+                .await
+                .expect_err("Expected error for removed store");
+            assert_eq!(err.kind(), ErrorKind::NotFound);
+        })
+    }
+
+    #[test]
+    fn read_only_open() {
+        use aries_askar::{
+            backend::postgres::PostgresStoreOptions, generate_raw_store_key, EntryOperation,
+            ErrorKind, ManageBackend, StoreKeyMethod,
+        };
+
+        env_logger::builder().is_test(true).try_init().unwrap_or(());
+        let url = std::env::var("POSTGRES_URL").expect("'POSTGRES_URL' must be defined");
+        let uri = format!(
+            "{}{}schema=askar-test-{}",
+            url,
+            if url.contains('?') { '&' } else { '?' },
+            uuid::Uuid::new_v4()
+        );
+        let ro_uri = format!("{}&mode=ro", uri);
+        let key = generate_raw_store_key(None).expect("Error creating raw key");
+
+        block_on(async move {
+            let err = PostgresStoreOptions::new(ro_uri.as_str())
+                .expect("Error initializing postgres store options")
+                .provision_backend(StoreKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect_err("Expected error for read-only provision");
+            assert_eq!(err.kind(), ErrorKind::Input);
+
+            let store = PostgresStoreOptions::new(uri.as_str())
+                .expect("Error initializing postgres store options")
+                .provision_backend(StoreKeyMethod::RawKey, key.as_ref(), None, false)
+                .await
+                .expect("Error provisioning postgres store");
+            let mut conn = store.session(None).await.expect("Error starting session");
+            conn.insert("category", "name", b"value", None, None)
+                .await
+                .expect("Error inserting test row");
+            drop(conn);
+            store.close().await.expect("Error closing store");
+
+            for (uri, read_only) in [(ro_uri.as_str(), false), (uri.as_str(), true)].iter() {
+                let store = PostgresStoreOptions::new(*uri)
+                    .expect("Error initializing postgres store options")
+                    .open_backend(Some(StoreKeyMethod::RawKey), key.as_ref(), None, *read_only)
+                    .await
+                    .expect("Error opening read-only store");
+                assert!(store.is_read_only());
+                let mut conn = store
+                    .transaction(None)
+                    .await
+                    .expect("Error starting transaction");
+                assert!(conn
+                    .fetch("category", "name", false)
+                    .await
+                    .expect("Error fetching test row")
+                    .is_some());
+                // update locks are rejected by the server
+                let err = conn
+                    .fetch("category", "name", true)
+                    .await
+                    .expect_err("Expected error for read-only update lock");
+                assert_eq!(err.kind(), ErrorKind::ReadOnly);
+                let err = conn
+                    .update(EntryOperation::Remove, "category", "name", None, None, None)
+                    .await
+                    .expect_err("Expected error for read-only update");
+                assert_eq!(err.kind(), ErrorKind::ReadOnly);
+                conn.rollback().await.expect("Error rolling back");
+                let err = store
+                    .create_profile(None)
+                    .await
+                    .expect_err("Expected error for read-only profile creation");
+                assert_eq!(err.kind(), ErrorKind::ReadOnly);
+                store.close().await.expect("Error closing store");
+            }
+
+            assert!(PostgresStoreOptions::new(uri.as_str())
+                .expect("Error initializing postgres store options")
+                .remove_backend()
+                .await
+                .expect("Error removing postgres store"));
+        })
+    }
 }
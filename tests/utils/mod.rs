@@ -1,4 +1,14 @@
-use aries_askar::{Backend, Entry, EntryTag, ErrorKind, Store, TagFilter};
+use std::io::Cursor;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use aries_askar::{
+    generate_raw_store_key,
+    kms::{KeyAlg, LocalKey},
+    Backend, Entry, EntryChange, EntryOperation, EntryTag, EntryUpdate, ErrorKind, OrderBy, Store,
+    StoreKeyMethod, TagFilter,
+};
+use futures_lite::{future::poll_once, StreamExt};
 
 const ERR_PROFILE: &'static str = "Error creating profile";
 const ERR_SESSION: &'static str = "Error starting session";
@@ -11,8 +21,16 @@ const ERR_REQ_ERR: &'static str = "Expected error";
 const ERR_INSERT: &'static str = "Error inserting test row";
 const ERR_REPLACE: &'static str = "Error replacing test row";
 const ERR_REMOVE_ALL: &'static str = "Error removing test rows";
+const ERR_BATCH: &'static str = "Error applying batch";
+const ERR_UPDATE_TAGS: &'static str = "Error updating tags";
+const ERR_SET_EXPIRY: &'static str = "Error updating expiry";
+const ERR_VERIFY: &'static str = "Error verifying store";
+const ERR_WATCH: &'static str = "Error starting watch";
+const ERR_REQ_CHANGE: &'static str = "Expected change";
 const ERR_SCAN: &'static str = "Error starting scan";
 const ERR_SCAN_NEXT: &'static str = "Error fetching scan rows";
+const ERR_STATS: &'static str = "Error collecting statistics";
+const ERR_TAG_NAMES: &'static str = "Error collecting tag names";
 // const ERR_CREATE_KEYPAIR: &'static str = "Error creating keypair";
 // const ERR_FETCH_KEY: &'static str = "Error fetching key";
 // const ERR_SIGN: &'static str = "Error signing message";
@@ -20,8 +38,13 @@ const ERR_SCAN_NEXT: &'static str = "Error fetching scan rows";
 
 pub async fn db_create_remove_profile<DB: Backend>(db: &Store<DB>) {
     let profile = db.create_profile(None).await.expect(ERR_PROFILE);
+    let mut conn = db.session(Some(profile.clone())).await.expect(ERR_SESSION);
+    conn.insert("category", "name", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    drop(conn);
     assert_eq!(
-        db.remove_profile(profile)
+        db.remove_profile(profile.clone())
             .await
             .expect("Error removing profile"),
         true
@@ -32,6 +55,217 @@ pub async fn db_create_remove_profile<DB: Backend>(db: &Store<DB>) {
             .expect("Error removing profile"),
         false
     );
+
+    // the key of the removed profile is no longer used
+    let err = db
+        .session(Some(profile.clone()))
+        .await
+        .expect(ERR_SESSION)
+        .count("category", None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    db.create_profile(Some(profile.clone()))
+        .await
+        .expect(ERR_PROFILE);
+    let mut conn = db.session(Some(profile)).await.expect(ERR_SESSION);
+    conn.insert("category", "other", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    assert_eq!(conn.count("category", None).await.expect(ERR_COUNT), 1);
+}
+
+pub async fn db_rename_copy_profile<DB: Backend>(db: &Store<DB>) {
+    let profile = db.create_profile(None).await.expect(ERR_PROFILE);
+    let tags = vec![EntryTag::Encrypted("enc".into(), "v1".into())];
+    let mut conn = db.session(Some(profile.clone())).await.expect(ERR_SESSION);
+    conn.insert("category", "name", b"value", Some(tags.as_slice()), None)
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("category", "expired", b"value", None, Some(-1000))
+        .await
+        .expect(ERR_INSERT);
+    drop(conn);
+
+    let renamed = format!("{}-renamed", profile);
+    let copied = format!("{}-copied", profile);
+    assert!(db
+        .rename_profile(profile.clone(), renamed.clone())
+        .await
+        .expect("Error renaming profile"));
+    assert!(!db
+        .rename_profile(profile.clone(), copied.clone())
+        .await
+        .expect("Error renaming profile"));
+    let err = db
+        .rename_profile(db.get_profile_name().to_string(), profile.clone())
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+
+    assert!(db
+        .copy_profile(renamed.clone(), copied.clone())
+        .await
+        .expect("Error copying profile"));
+    assert!(!db
+        .copy_profile(profile.clone(), copied.clone())
+        .await
+        .expect("Error copying profile"));
+    let err = db
+        .copy_profile(renamed.clone(), copied.clone())
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Duplicate);
+
+    let profiles = db.list_profiles().await.expect("Error listing profiles");
+    assert!(!profiles.contains(&profile));
+    assert!(profiles.contains(&renamed) && profiles.contains(&copied));
+
+    let err = db
+        .session(Some(profile))
+        .await
+        .expect(ERR_SESSION)
+        .count("category", None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let mut conn = db.session(Some(copied.clone())).await.expect(ERR_SESSION);
+    assert_eq!(conn.count("category", None).await.expect(ERR_COUNT), 1);
+    let row = conn
+        .fetch_all("category", Some(TagFilter::is_eq("enc", "v1")), None, false)
+        .await
+        .expect(ERR_FETCH_ALL);
+    assert_eq!(row.len(), 1);
+    assert_eq!(row[0].value.as_ref(), b"value");
+    drop(conn);
+
+    // the source profile is unaffected by changes to the copy
+    assert!(db
+        .remove_profile(copied)
+        .await
+        .expect("Error removing profile"));
+    let mut conn = db.session(Some(renamed)).await.expect(ERR_SESSION);
+    assert!(conn
+        .fetch("category", "name", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_some());
+}
+
+pub async fn db_rotate_profile_key<DB: Backend>(db: &Store<DB>) {
+    let profile = db.create_profile(None).await.expect(ERR_PROFILE);
+    let tags = vec![
+        EntryTag::Encrypted("enc".into(), "v1".into()),
+        EntryTag::Plaintext("plain".into(), "v2".into()),
+    ];
+    let mut conn = db.session(Some(profile.clone())).await.expect(ERR_SESSION);
+    for idx in 0..40 {
+        conn.insert(
+            "category",
+            &format!("name-{}", idx),
+            b"value",
+            Some(tags.as_slice()),
+            None,
+        )
+        .await
+        .expect(ERR_INSERT);
+    }
+    drop(conn);
+    let mut other = db.session(None).await.expect(ERR_SESSION);
+    other
+        .insert("category", "other", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    drop(other);
+
+    assert_eq!(
+        db.rotate_profile_key(profile.clone())
+            .await
+            .expect("Error rotating profile key"),
+        40
+    );
+    let err = db
+        .rotate_profile_key("not a profile".to_string())
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let mut conn = db.session(Some(profile.clone())).await.expect(ERR_SESSION);
+    assert_eq!(conn.count("category", None).await.expect(ERR_COUNT), 40);
+    let row = conn
+        .fetch("category", "name-7", false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(row.value.as_ref(), b"value");
+    let mut found_tags = row.tags.clone();
+    found_tags.sort();
+    assert_eq!(found_tags, tags);
+    for tag_filter in [
+        TagFilter::is_eq("enc", "v1"),
+        TagFilter::is_eq("~plain", "v2"),
+    ] {
+        assert_eq!(
+            conn.count("category", Some(tag_filter))
+                .await
+                .expect(ERR_COUNT),
+            40
+        );
+    }
+    drop(conn);
+
+    let mut other = db.session(None).await.expect(ERR_SESSION);
+    assert!(other
+        .fetch("category", "other", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_some());
+}
+
+pub async fn db_rotate_profile_key_after_failed_update<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    conn.insert("category", "name", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    let err = conn
+        .insert("category", "name", b"value", None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Duplicate);
+    let err = conn
+        .replace("category", "missing", b"value", None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    let err = conn
+        .remove("category", "missing")
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    let err = conn
+        .set_expiry("category", "missing", Some(1000))
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    drop(conn);
+
+    // the failed updates must not leave a transaction open
+    assert_eq!(
+        db.rotate_profile_key(db.get_profile_name().to_string())
+            .await
+            .expect("Error rotating profile key"),
+        1
+    );
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    assert!(conn
+        .fetch("category", "name", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_some());
+    conn.insert("category", "other", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
 }
 
 pub async fn db_fetch_fail<DB: Backend>(db: &Store<DB>) {
@@ -183,34 +417,907 @@ pub async fn db_replace_missing<DB: Backend>(db: &Store<DB>) {
         )
         .await
         .expect_err(ERR_REQ_ERR);
-    assert_eq!(err.kind(), ErrorKind::NotFound);
-}
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+pub async fn db_replace_if_version<DB: Backend>(db: &Store<DB>) {
+    let test_row = Entry::new("category", "name", "value", Vec::new());
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    conn.insert(
+        &test_row.category,
+        &test_row.name,
+        &test_row.value,
+        None,
+        None,
+    )
+    .await
+    .expect(ERR_INSERT);
+    let row = conn
+        .fetch(&test_row.category, &test_row.name, false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(row.version, 1);
+
+    conn.replace(&test_row.category, &test_row.name, b"second", None, None)
+        .await
+        .expect(ERR_REPLACE);
+    conn.replace_if_version(&test_row.category, &test_row.name, 2, b"third", None, None)
+        .await
+        .expect(ERR_REPLACE);
+
+    // a stale version is rejected
+    let err = conn
+        .replace_if_version(&test_row.category, &test_row.name, 2, b"stale", None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Conflict);
+    let err = conn
+        .apply_batch(vec![EntryUpdate::remove(
+            &test_row.category,
+            &test_row.name,
+        )
+        .with_expected_version(1)])
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Conflict);
+
+    let err = conn
+        .replace_if_version(&test_row.category, "missing", 1, b"value", None, None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let rows = conn
+        .fetch_all(&test_row.category, None, None, false)
+        .await
+        .expect(ERR_FETCH_ALL);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].value.as_ref(), b"third");
+    assert_eq!(rows[0].version, 3);
+}
+
+pub async fn db_update_tags<DB: Backend>(db: &Store<DB>) {
+    let test_row = Entry::new(
+        "category",
+        "name",
+        "value",
+        vec![
+            EntryTag::Encrypted("a".to_string(), "1".to_string()),
+            EntryTag::Plaintext("b".to_string(), "x".to_string()),
+            EntryTag::Encrypted("c".to_string(), "2".to_string()),
+        ],
+    );
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    conn.insert(
+        &test_row.category,
+        &test_row.name,
+        &test_row.value,
+        Some(test_row.tags.as_slice()),
+        None,
+    )
+    .await
+    .expect(ERR_INSERT);
+
+    conn.update_tags(
+        &test_row.category,
+        &test_row.name,
+        &[
+            EntryTag::Encrypted("a".to_string(), "1".to_string()),
+            EntryTag::Encrypted("a".to_string(), "2".to_string()),
+            EntryTag::Plaintext("d".to_string(), "y".to_string()),
+        ],
+        &["c", "~b"],
+    )
+    .await
+    .expect(ERR_UPDATE_TAGS);
+
+    let row = conn
+        .fetch(&test_row.category, &test_row.name, false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    let updated_row = Entry::new(
+        "category",
+        "name",
+        "value",
+        vec![
+            EntryTag::Encrypted("a".to_string(), "1".to_string()),
+            EntryTag::Encrypted("a".to_string(), "2".to_string()),
+            EntryTag::Plaintext("d".to_string(), "y".to_string()),
+        ],
+    );
+    assert_eq!(row, updated_row);
+    assert_eq!(row.version, 2);
+
+    assert_eq!(
+        conn.count(&test_row.category, Some(TagFilter::is_eq("~d", "y")))
+            .await
+            .expect(ERR_COUNT),
+        1
+    );
+    assert_eq!(
+        conn.count(&test_row.category, Some(TagFilter::is_eq("c", "2")))
+            .await
+            .expect(ERR_COUNT),
+        0
+    );
+
+    let err = conn
+        .update_tags(&test_row.category, "missing", &[], &["a"])
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+pub async fn db_entry_timestamps<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    conn.insert("category", "name", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("category", "expiring", b"value", None, Some(60 * 60 * 1000))
+        .await
+        .expect(ERR_INSERT);
+
+    let row = conn
+        .fetch("category", "name", false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    let created = row.created.expect("Expected creation time");
+    assert_eq!(row.updated, Some(created));
+    assert_eq!(row.expiry, None);
+
+    let expiring = conn
+        .fetch("category", "expiring", false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert!(expiring.expiry.expect("Expected expiry time") > created);
+
+    std::thread::sleep(Duration::from_millis(10));
+    conn.replace("category", "name", b"updated", None, None)
+        .await
+        .expect(ERR_REPLACE);
+    let row = conn
+        .fetch("category", "name", false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(row.created, Some(created));
+    let replaced = row.updated.expect("Expected update time");
+    assert!(replaced > created);
+
+    std::thread::sleep(Duration::from_millis(10));
+    conn.update_tags(
+        "category",
+        "name",
+        &[EntryTag::Encrypted("a".to_string(), "1".to_string())],
+        &[],
+    )
+    .await
+    .expect(ERR_UPDATE_TAGS);
+    let rows = conn
+        .fetch_all("category", None, None, false)
+        .await
+        .expect(ERR_FETCH_ALL);
+    let row = rows
+        .iter()
+        .find(|row| row.name == "name")
+        .expect(ERR_REQ_ROW);
+    assert_eq!(row.created, Some(created));
+    assert!(row.updated.expect("Expected update time") > replaced);
+
+    let key = LocalKey::generate(KeyAlg::Ed25519, false).expect("Error creating key");
+    conn.insert_key("key", &key, None, None, Some(60 * 60 * 1000))
+        .await
+        .expect("Error inserting key");
+    let key_entry = conn
+        .fetch_key("key", false)
+        .await
+        .expect("Error fetching key")
+        .expect(ERR_REQ_ROW);
+    assert!(key_entry.created().is_some());
+    assert_eq!(key_entry.updated(), key_entry.created());
+    assert!(key_entry.expiry().is_some());
+}
+
+pub async fn db_set_expiry<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    for (name, tag) in [("a", "x"), ("b", "x"), ("c", "y")].iter() {
+        conn.insert(
+            "category",
+            name,
+            b"value",
+            Some(&[EntryTag::Encrypted("t".to_string(), tag.to_string())]),
+            None,
+        )
+        .await
+        .expect(ERR_INSERT);
+    }
+
+    std::thread::sleep(Duration::from_millis(10));
+    conn.set_expiry("category", "a", Some(60 * 60 * 1000))
+        .await
+        .expect(ERR_SET_EXPIRY);
+    let row = conn
+        .fetch("category", "a", false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(row.value.as_ref(), b"value");
+    assert_eq!(row.version, 1);
+    let created = row.created.expect("Expected creation time");
+    let expiry = row.expiry.expect("Expected expiry time");
+    assert!(expiry > created);
+    assert!(row.updated.expect("Expected update time") > created);
+
+    conn.set_expiry("category", "a", None)
+        .await
+        .expect(ERR_SET_EXPIRY);
+    let row = conn
+        .fetch("category", "a", false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(row.expiry, None);
+
+    let err = conn
+        .set_expiry("category", "missing", None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let updated = conn
+        .set_expiry_all("category", Some(TagFilter::is_eq("t", "x")), Some(-1000))
+        .await
+        .expect(ERR_SET_EXPIRY);
+    assert_eq!(updated, 2);
+    assert_eq!(conn.count("category", None).await.expect(ERR_COUNT), 1);
+
+    // expired records are not revived
+    std::thread::sleep(Duration::from_millis(10));
+    let updated = conn
+        .set_expiry_all("category", None, None)
+        .await
+        .expect(ERR_SET_EXPIRY);
+    assert_eq!(updated, 1);
+    let row = conn
+        .fetch("category", "c", false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert!(
+        row.updated.expect("Expected update time") > row.created.expect("Expected creation time")
+    );
+    assert!(conn
+        .fetch("category", "b", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_none());
+}
+
+pub async fn db_watch<DB: Backend>(db: &Store<DB>) {
+    let mut watch = db.watch(None, "category", None).await.expect(ERR_WATCH);
+    let mut filtered = db
+        .watch(None, "category", Some(TagFilter::is_eq("t", "x")))
+        .await
+        .expect(ERR_WATCH);
+    let tags = [EntryTag::Encrypted("t".to_string(), "x".to_string())];
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    conn.insert("category", "a", b"value", Some(&tags), None)
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("category", "b", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("other", "c", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    conn.remove("category", "b")
+        .await
+        .expect("Error removing test row");
+    drop(conn);
+
+    let expected = [
+        EntryChange {
+            category: "category".to_string(),
+            name: "a".to_string(),
+            operation: EntryOperation::Insert,
+            tags: Some(tags.to_vec()),
+        },
+        EntryChange {
+            category: "category".to_string(),
+            name: "b".to_string(),
+            operation: EntryOperation::Insert,
+            tags: Some(Vec::new()),
+        },
+        EntryChange {
+            category: "category".to_string(),
+            name: "b".to_string(),
+            operation: EntryOperation::Remove,
+            tags: None,
+        },
+    ];
+    for change in expected.iter() {
+        assert_eq!(watch.next().await.as_ref(), Some(change));
+    }
+    // removals are reported regardless of the tag filter
+    assert_eq!(filtered.next().await.as_ref(), Some(&expected[0]));
+    assert_eq!(filtered.next().await.as_ref(), Some(&expected[2]));
+
+    // changes are reported once the transaction is committed
+    let mut txn = db.transaction(None).await.expect(ERR_TRANSACTION);
+    txn.replace("category", "a", b"new value", Some(&tags), None)
+        .await
+        .expect(ERR_REPLACE);
+    assert!(poll_once(watch.next()).await.is_none());
+    txn.commit().await.expect("Error committing transaction");
+    let change = watch.next().await.expect(ERR_REQ_CHANGE);
+    assert_eq!(change.name, "a");
+    assert_eq!(change.operation, EntryOperation::Replace);
+
+    // changes are discarded when the transaction is rolled back
+    let mut txn = db.transaction(None).await.expect(ERR_TRANSACTION);
+    txn.insert("category", "d", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    txn.rollback()
+        .await
+        .expect("Error rolling back transaction");
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    conn.apply_batch(vec![EntryUpdate::remove("category", "a")])
+        .await
+        .expect(ERR_BATCH);
+    let change = watch.next().await.expect(ERR_REQ_CHANGE);
+    assert_eq!(change.name, "a");
+    assert_eq!(change.operation, EntryOperation::Remove);
+
+    // expiry updates are reported as replacements
+    conn.insert("category", "e", b"value", Some(&tags), None)
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("category", "f", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    conn.set_expiry("category", "f", Some(60000))
+        .await
+        .expect(ERR_SET_EXPIRY);
+    assert_eq!(
+        conn.set_expiry_all("category", Some(TagFilter::is_eq("t", "x")), Some(60000))
+            .await
+            .expect(ERR_SET_EXPIRY),
+        1
+    );
+    for (name, operation) in [
+        ("e", EntryOperation::Insert),
+        ("f", EntryOperation::Insert),
+        ("f", EntryOperation::Replace),
+    ] {
+        let change = watch.next().await.expect(ERR_REQ_CHANGE);
+        assert_eq!((change.name.as_str(), change.operation), (name, operation));
+    }
+    let change = watch.next().await.expect(ERR_REQ_CHANGE);
+    assert_eq!(change.name, "e");
+    assert_eq!(change.operation, EntryOperation::Replace);
+    assert_eq!(change.tags, Some(tags.to_vec()));
+
+    // each record removed by remove_all is reported
+    assert_eq!(
+        conn.remove_all("category", None)
+            .await
+            .expect(ERR_REMOVE_ALL),
+        2
+    );
+    let mut removed = Vec::new();
+    for _ in 0..2 {
+        let change = watch.next().await.expect(ERR_REQ_CHANGE);
+        assert_eq!(change.operation, EntryOperation::Remove);
+        removed.push(change.name);
+    }
+    removed.sort();
+    assert_eq!(removed, ["e", "f"]);
+    assert!(poll_once(watch.next()).await.is_none());
+}
+
+pub async fn db_verify<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    for name in ["a", "b", "c"].iter() {
+        conn.insert(
+            "category",
+            name,
+            b"value",
+            Some(&[
+                EntryTag::Encrypted("enc".to_string(), name.to_string()),
+                EntryTag::Plaintext("plain".to_string(), name.to_string()),
+            ]),
+            None,
+        )
+        .await
+        .expect(ERR_INSERT);
+    }
+    drop(conn);
+
+    let report = db.verify(None, false).await.expect(ERR_VERIFY);
+    assert!(report.is_valid(), "Unexpected issues: {:?}", report.issues);
+    assert!(report.profiles_checked >= 1);
+    assert_eq!(report.entries_checked, 3);
+    assert_eq!(report.tags_checked, 6);
+    assert_eq!(report.rows_removed, 0);
+
+    let report = db
+        .verify(Some(db.get_profile_name().to_string()), true)
+        .await
+        .expect(ERR_VERIFY);
+    assert!(report.is_valid());
+    assert_eq!(report.profiles_checked, 1);
+    assert_eq!(report.rows_removed, 0);
+
+    let err = db
+        .verify(Some("not a profile".to_string()), false)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+pub async fn db_count<DB: Backend>(db: &Store<DB>) {
+    let category = "category".to_string();
+    let test_rows = vec![Entry::new(&category, "name", "value", Vec::new())];
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    for upd in test_rows.iter() {
+        conn.insert(
+            &upd.category,
+            &upd.name,
+            &upd.value,
+            Some(upd.tags.as_slice()),
+            None,
+        )
+        .await
+        .expect(ERR_INSERT);
+    }
+
+    let tag_filter = None;
+    let count = conn.count(&category, tag_filter).await.expect(ERR_COUNT);
+    assert_eq!(count, 1);
+
+    let tag_filter = Some(TagFilter::is_eq("sometag", "someval"));
+    let count = conn.count(&category, tag_filter).await.expect(ERR_COUNT);
+    assert_eq!(count, 0);
+}
+
+pub async fn db_stats<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    let stats = conn.stats().await.expect(ERR_STATS);
+    assert_eq!(stats.profile, db.get_profile_name());
+    assert!(stats.categories.is_empty());
+    assert_eq!(stats.keys.count, 0);
+
+    let tags = vec![EntryTag::Encrypted("enc".to_string(), "v1".to_string())];
+    conn.insert("cat-a", "name-1", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("cat-a", "name-2", b"value", Some(tags.as_slice()), None)
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("cat-b", "name-1", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    let key = LocalKey::generate(KeyAlg::Ed25519, false).expect("Error creating key");
+    conn.insert_key("key", &key, None, None, None)
+        .await
+        .expect("Error inserting key");
+
+    let stats = conn.stats().await.expect(ERR_STATS);
+    assert_eq!(
+        stats.categories.keys().collect::<Vec<_>>(),
+        vec!["cat-a", "cat-b"]
+    );
+    let (cat_a, cat_b) = (&stats.categories["cat-a"], &stats.categories["cat-b"]);
+    assert_eq!((cat_a.count, cat_b.count), (2, 1));
+    // the second record of cat-a includes an encrypted tag
+    assert!(cat_a.size > 2 * cat_b.size);
+    assert_eq!(stats.keys.count, 1);
+    assert!(stats.keys.size > 0);
+}
+
+pub async fn db_tag_names<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    let tags_1 = vec![
+        EntryTag::Encrypted("enc".to_string(), "v1".to_string()),
+        EntryTag::Encrypted("enc".to_string(), "v2".to_string()),
+        EntryTag::Plaintext("plain".to_string(), "v3".to_string()),
+    ];
+    let tags_2 = vec![
+        EntryTag::Encrypted("enc".to_string(), "v1".to_string()),
+        EntryTag::Encrypted("plain".to_string(), "v3".to_string()),
+    ];
+    conn.insert(
+        "category",
+        "name-1",
+        b"value",
+        Some(tags_1.as_slice()),
+        None,
+    )
+    .await
+    .expect(ERR_INSERT);
+    conn.insert(
+        "category",
+        "name-2",
+        b"value",
+        Some(tags_2.as_slice()),
+        None,
+    )
+    .await
+    .expect(ERR_INSERT);
+    conn.insert("other", "name-1", b"value", Some(tags_1.as_slice()), None)
+        .await
+        .expect(ERR_INSERT);
+
+    let names = conn.tag_names("category").await.expect(ERR_TAG_NAMES);
+    assert_eq!(
+        names
+            .iter()
+            .map(|tag| (tag.name.as_str(), tag.plaintext, tag.count))
+            .collect::<Vec<_>>(),
+        vec![("enc", false, 2), ("plain", false, 1), ("plain", true, 1)]
+    );
+    assert!(conn
+        .tag_names("missing")
+        .await
+        .expect(ERR_TAG_NAMES)
+        .is_empty());
+}
+
+pub async fn db_numeric_tags<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    for (idx, seq) in ["9", "10", "100", "2.5"].iter().enumerate() {
+        let tags = vec![
+            EntryTag::Plaintext("#seq".to_string(), seq.to_string()),
+            EntryTag::Plaintext("plain".to_string(), "text".to_string()),
+        ];
+        conn.insert(
+            "category",
+            &format!("name-{}", idx),
+            b"value",
+            Some(tags.as_slice()),
+            None,
+        )
+        .await
+        .expect(ERR_INSERT);
+    }
+
+    let count = conn
+        .count("category", Some(TagFilter::is_gt("~#seq", "9")))
+        .await
+        .expect(ERR_COUNT);
+    assert_eq!(count, 2);
+    let count = conn
+        .count("category", Some(TagFilter::is_lte("~#seq", "1e1")))
+        .await
+        .expect(ERR_COUNT);
+    assert_eq!(count, 3);
+    let count = conn
+        .count(
+            "category",
+            Some(TagFilter::is_in(
+                "~#seq",
+                vec!["2.50".to_string(), "100".to_string()],
+            )),
+        )
+        .await
+        .expect(ERR_COUNT);
+    assert_eq!(count, 2);
+    let count = conn
+        .count(
+            "category",
+            Some(TagFilter::not(TagFilter::is_eq("~#seq", "10.0"))),
+        )
+        .await
+        .expect(ERR_COUNT);
+    assert_eq!(count, 3);
+
+    // values which are not numbers, such as those stored before numeric
+    // comparisons were supported, are never matched by numeric comparisons
+    for (idx, seq) in ["ten", " 12", "1e", ""].iter().enumerate() {
+        let tags = vec![EntryTag::Plaintext("#seq".to_string(), seq.to_string())];
+        conn.insert(
+            "category",
+            &format!("legacy-{}", idx),
+            b"value",
+            Some(tags.as_slice()),
+            None,
+        )
+        .await
+        .expect(ERR_INSERT);
+    }
+    let count = conn
+        .count("category", Some(TagFilter::is_lt("~#seq", "1e6")))
+        .await
+        .expect(ERR_COUNT);
+    assert_eq!(count, 4);
+    let count = conn
+        .count(
+            "category",
+            Some(TagFilter::is_in(
+                "~#seq",
+                vec!["0".to_string(), "12".to_string(), "1".to_string()],
+            )),
+        )
+        .await
+        .expect(ERR_COUNT);
+    assert_eq!(count, 0);
+    let rows = conn
+        .fetch_all_ordered("category", None, OrderBy::tag("~#seq"), Some(5), false)
+        .await
+        .expect(ERR_FETCH_ALL);
+    assert_eq!(
+        rows.iter().map(|row| row.name.as_str()).collect::<Vec<_>>(),
+        vec!["name-3", "name-0", "name-1", "name-2", "legacy-0"]
+    );
+
+    let err = conn
+        .count("category", Some(TagFilter::is_gt("~#seq", "ten")))
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+    let err = conn
+        .count("category", Some(TagFilter::is_gt("enc", "9")))
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+}
+
+pub async fn db_date_tags<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    for (idx, created) in [
+        "2021-03-04T05:06:07Z",
+        "2020-12-31T23:59:59Z",
+        "2021-11-01T00:00:00Z",
+        // not a well-formed date, although it sorts between the others
+        "2021-06-01",
+    ]
+    .iter()
+    .enumerate()
+    {
+        let tags = vec![EntryTag::Plaintext(
+            "@created".to_string(),
+            created.to_string(),
+        )];
+        conn.insert(
+            "category",
+            &format!("name-{}", idx),
+            b"value",
+            Some(tags.as_slice()),
+            None,
+        )
+        .await
+        .expect(ERR_INSERT);
+    }
+
+    let count = conn
+        .count(
+            "category",
+            Some(TagFilter::is_gte("~@created", "2021-01-01T00:00:00Z")),
+        )
+        .await
+        .expect(ERR_COUNT);
+    assert_eq!(count, 2);
+    let count = conn
+        .count(
+            "category",
+            Some(TagFilter::not(TagFilter::is_in(
+                "~@created",
+                vec!["2021-03-04T05:06:07Z".to_string()],
+            ))),
+        )
+        .await
+        .expect(ERR_COUNT);
+    assert_eq!(count, 2);
+    let count = conn
+        .count("category", Some(TagFilter::is_like("~@created", "2021-%")))
+        .await
+        .expect(ERR_COUNT);
+    assert_eq!(count, 2);
+    let rows = conn
+        .fetch_all_ordered(
+            "category",
+            None,
+            OrderBy::tag("~@created").desc(),
+            None,
+            false,
+        )
+        .await
+        .expect(ERR_FETCH_ALL);
+    assert_eq!(
+        rows.iter().map(|row| row.name.as_str()).collect::<Vec<_>>(),
+        vec!["name-2", "name-0", "name-1", "name-3"]
+    );
+
+    let err = conn
+        .count(
+            "category",
+            Some(TagFilter::is_lt("~@created", "2021-01-01")),
+        )
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+}
+
+pub async fn db_query_limits<DB: Backend>(db: &Store<DB>) {
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+
+    let limits = db.query_limits();
+    let mut filter = TagFilter::is_eq("enc", "v1");
+    for _ in 1..limits.max_depth {
+        filter = TagFilter::not(filter);
+    }
+    conn.count("category", Some(filter.clone()))
+        .await
+        .expect(ERR_COUNT);
+    let err = conn
+        .count("category", Some(TagFilter::not(filter)))
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+
+    let values = (0..limits.max_arguments)
+        .map(|idx| idx.to_string())
+        .collect::<Vec<_>>();
+    let err = conn
+        .fetch_all(
+            "category",
+            Some(TagFilter::is_in("enc", values)),
+            None,
+            false,
+        )
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+    let err = db
+        .scan(
+            None,
+            "category".to_string(),
+            Some(TagFilter::any_of(
+                (0..limits.max_clauses)
+                    .map(|idx| TagFilter::is_eq("enc", idx.to_string()))
+                    .collect(),
+            )),
+            None,
+            None,
+        )
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
 
-pub async fn db_count<DB: Backend>(db: &Store<DB>) {
-    let category = "category".to_string();
-    let test_rows = vec![Entry::new(&category, "name", "value", Vec::new())];
+    let err = conn
+        .count("category", Some(TagFilter::is_like("enc", "v%")))
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+}
 
+pub async fn db_order_by<DB: Backend>(db: &Store<DB>) {
     let mut conn = db.session(None).await.expect(ERR_SESSION);
 
-    for upd in test_rows.iter() {
+    for (idx, (seq, label)) in [
+        ("9", Some("b")),
+        ("10", Some("a")),
+        ("100", None),
+        ("2.5", Some("c")),
+    ]
+    .iter()
+    .enumerate()
+    {
+        let mut tags = vec![EntryTag::Plaintext("#seq".to_string(), seq.to_string())];
+        if let Some(label) = label {
+            tags.push(EntryTag::Plaintext("label".to_string(), label.to_string()));
+        }
         conn.insert(
-            &upd.category,
-            &upd.name,
-            &upd.value,
-            Some(upd.tags.as_slice()),
+            "category",
+            &format!("name-{}", idx),
+            b"value",
+            Some(tags.as_slice()),
             None,
         )
         .await
         .expect(ERR_INSERT);
     }
 
-    let tag_filter = None;
-    let count = conn.count(&category, tag_filter).await.expect(ERR_COUNT);
-    assert_eq!(count, 1);
+    let cases = [
+        (
+            OrderBy::id(),
+            None,
+            vec!["name-0", "name-1", "name-2", "name-3"],
+        ),
+        (OrderBy::id().desc(), Some(2), vec!["name-3", "name-2"]),
+        (
+            OrderBy::tag("~#seq"),
+            None,
+            vec!["name-3", "name-0", "name-1", "name-2"],
+        ),
+        (
+            OrderBy::tag("~#seq").desc(),
+            Some(2),
+            vec!["name-2", "name-1"],
+        ),
+        // records without the tag are ordered last
+        (
+            OrderBy::tag("~label"),
+            None,
+            vec!["name-1", "name-0", "name-3", "name-2"],
+        ),
+        (
+            OrderBy::tag("~label").desc(),
+            None,
+            vec!["name-3", "name-0", "name-1", "name-2"],
+        ),
+    ];
+    for (order_by, limit, expected) in cases {
+        let rows = conn
+            .fetch_all_ordered("category", None, order_by, limit, false)
+            .await
+            .expect(ERR_FETCH_ALL);
+        assert_eq!(
+            rows.iter().map(|row| row.name.as_str()).collect::<Vec<_>>(),
+            expected
+        );
+    }
 
-    let tag_filter = Some(TagFilter::is_eq("sometag", "someval"));
-    let count = conn.count(&category, tag_filter).await.expect(ERR_COUNT);
-    assert_eq!(count, 0);
+    let err = conn
+        .fetch_all_ordered("category", None, OrderBy::tag("label"), None, false)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+    drop(conn);
+
+    let mut scan = db
+        .scan_ordered(
+            None,
+            "category".to_string(),
+            Some(TagFilter::exist(vec!["~label".to_string()])),
+            OrderBy::tag("~#seq").desc(),
+            Some(1),
+            Some(1),
+        )
+        .await
+        .expect(ERR_SCAN);
+    let rows = scan
+        .fetch_next()
+        .await
+        .expect(ERR_SCAN_NEXT)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].name, "name-0");
+    let token = scan
+        .continuation_token()
+        .expect("Expected token")
+        .to_string();
+    drop(scan);
+
+    // the resumed scan keeps the order and tag filter of the original scan
+    let mut scan = db.resume_scan(token, None).await.expect(ERR_SCAN);
+    let rows = scan
+        .fetch_next()
+        .await
+        .expect(ERR_SCAN_NEXT)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(
+        rows.iter().map(|row| row.name.as_str()).collect::<Vec<_>>(),
+        vec!["name-3"]
+    );
 }
 
 pub async fn db_count_exist<DB: Backend>(db: &Store<DB>) {
@@ -400,6 +1507,196 @@ pub async fn db_scan<DB: Backend>(db: &Store<DB>) {
     assert_eq!(rows, None);
 }
 
+pub async fn db_scan_resume<DB: Backend>(db: &Store<DB>) {
+    let category = "category".to_string();
+    let tags = vec![EntryTag::Encrypted("t1".to_string(), "v1".to_string())];
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    for idx in 0..40 {
+        conn.insert(
+            &category,
+            &format!("name-{:02}", idx),
+            b"value",
+            Some(tags.as_slice()),
+            None,
+        )
+        .await
+        .expect(ERR_INSERT);
+        if idx == 10 {
+            conn.insert(&category, "untagged", b"value", None, None)
+                .await
+                .expect(ERR_INSERT);
+        }
+    }
+    drop(conn);
+
+    let tag_filter = Some(TagFilter::is_eq("t1", "v1"));
+    let mut scan = db
+        .scan(None, category.clone(), tag_filter, None, Some(5))
+        .await
+        .expect(ERR_SCAN);
+    assert!(scan.continuation_token().is_none());
+    let rows = scan
+        .fetch_next()
+        .await
+        .expect(ERR_SCAN_NEXT)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(rows.len(), 5);
+    assert_eq!(rows[4].name, "name-04");
+    let token = scan
+        .continuation_token()
+        .expect("Expected token")
+        .to_string();
+    drop(scan);
+
+    // removing an earlier record must not shift the position of the scan
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    conn.remove(&category, "name-00")
+        .await
+        .expect("Error removing test row");
+    drop(conn);
+
+    let mut scan = db.resume_scan(token.clone(), None).await.expect(ERR_SCAN);
+    assert_eq!(scan.continuation_token(), Some(token.as_str()));
+    let mut names = vec![];
+    while let Some(rows) = scan.fetch_next().await.expect(ERR_SCAN_NEXT) {
+        names.extend(rows.into_iter().map(|row| row.name));
+    }
+    assert_eq!(
+        names,
+        (5..40)
+            .map(|idx| format!("name-{:02}", idx))
+            .collect::<Vec<_>>()
+    );
+    let token = scan
+        .continuation_token()
+        .expect("Expected token")
+        .to_string();
+    drop(scan);
+
+    let mut scan = db.resume_scan(token.clone(), None).await.expect(ERR_SCAN);
+    assert_eq!(scan.fetch_next().await.expect(ERR_SCAN_NEXT), None);
+    drop(scan);
+
+    // records added after the scan completed are returned by a later resume
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    conn.insert(&category, "name-40", b"value", Some(tags.as_slice()), None)
+        .await
+        .expect(ERR_INSERT);
+    drop(conn);
+
+    let mut scan = db.resume_scan(token, None).await.expect(ERR_SCAN);
+    let rows = scan
+        .fetch_next()
+        .await
+        .expect(ERR_SCAN_NEXT)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].name, "name-40");
+    drop(scan);
+
+    let err = db
+        .resume_scan("not a token".to_string(), None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+}
+
+pub async fn db_apply_batch<DB: Backend>(db: &Store<DB>) {
+    let category = "category";
+    let tags = vec![EntryTag::Plaintext("idx".to_string(), "all".to_string())];
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    let mut updates = (0..150)
+        .map(|idx| {
+            EntryUpdate::insert(
+                category,
+                format!("name-{}", idx),
+                "value",
+                Some(tags.clone()),
+                None,
+            )
+        })
+        .collect::<Vec<_>>();
+    updates.push(EntryUpdate::remove(category, "name-0"));
+    updates.push(EntryUpdate::replace(
+        category, "name-1", "replaced", None, None,
+    ));
+    conn.apply_batch(updates).await.expect(ERR_BATCH);
+
+    assert_eq!(
+        conn.count(category, Some(TagFilter::is_eq("~idx", "all")))
+            .await
+            .expect(ERR_COUNT),
+        148
+    );
+    assert!(conn
+        .fetch(category, "name-0", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_none());
+    let row = conn
+        .fetch(category, "name-1", false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(row.value.as_ref(), b"replaced");
+    assert!(row.tags.is_empty());
+
+    // a failed update leaves the store unchanged
+    let err = conn
+        .apply_batch(vec![
+            EntryUpdate::remove(category, "name-2"),
+            EntryUpdate::insert(category, "new", "value", None, None),
+            EntryUpdate::insert(category, "name-3", "value", None, None),
+        ])
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Duplicate);
+    let err = conn
+        .apply_batch(vec![
+            EntryUpdate::insert(category, "new", "value", None, None),
+            EntryUpdate::replace(category, "missing", "value", None, None),
+        ])
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert_eq!(conn.count(category, None).await.expect(ERR_COUNT), 149);
+    assert!(conn
+        .fetch(category, "new", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_none());
+    drop(conn);
+
+    // within a transaction, the batch is committed along with the transaction
+    let mut conn = db.transaction(None).await.expect(ERR_TRANSACTION);
+    conn.apply_batch(vec![EntryUpdate::insert(
+        category, "txn", "value", None, None,
+    )])
+    .await
+    .expect(ERR_BATCH);
+    conn.apply_batch(vec![
+        EntryUpdate::insert(category, "txn-failed", "value", None, None),
+        EntryUpdate::insert(category, "txn", "value", None, None),
+    ])
+    .await
+    .expect_err(ERR_REQ_ERR);
+    conn.commit().await.expect("Error committing transaction");
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    assert!(conn
+        .fetch(category, "txn", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_some());
+    assert!(conn
+        .fetch(category, "txn-failed", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_none());
+}
+
 pub async fn db_remove_all<DB: Backend>(db: &Store<DB>) {
     let test_rows = vec![
         Entry::new(
@@ -713,3 +2010,314 @@ pub async fn db_txn_fetch_for_update<DB: Backend>(db: &Store<DB>) {
 
     conn.commit().await.expect("Error committing transaction");
 }
+
+pub async fn db_export_import<DB: Backend>(db: &Store<DB>) {
+    let profile = db.create_profile(None).await.expect(ERR_PROFILE);
+    let test_rows = [
+        Entry::new(
+            "category",
+            "name",
+            "value",
+            vec![
+                EntryTag::Encrypted("t1".to_string(), "v1".to_string()),
+                EntryTag::Plaintext("t2".to_string(), "v2".to_string()),
+            ],
+        ),
+        Entry::new("other", "name", "value2", Vec::new()),
+    ];
+    let key = LocalKey::generate(KeyAlg::Ed25519, false).expect("Error creating key");
+
+    let mut conn = db.session(Some(profile.clone())).await.expect(ERR_SESSION);
+    for row in test_rows.iter() {
+        conn.insert(
+            &row.category,
+            &row.name,
+            &row.value,
+            Some(row.tags.as_slice()),
+            None,
+        )
+        .await
+        .expect(ERR_INSERT);
+    }
+    conn.insert("category", "expiring", b"value", None, Some(60 * 60 * 1000))
+        .await
+        .expect(ERR_INSERT);
+    conn.insert_key("key", &key, Some("meta"), None, None)
+        .await
+        .expect("Error inserting key");
+    drop(conn);
+
+    let pass_key = generate_raw_store_key(None).expect("Error generating archive key");
+    let archive_path = std::env::temp_dir().join(format!("askar-archive-{}", uuid::Uuid::new_v4()));
+    let exported = db
+        .export(
+            std::fs::File::create(&archive_path).expect("Error creating archive file"),
+            StoreKeyMethod::RawKey,
+            pass_key.as_ref(),
+            Some(&[profile.as_str()]),
+        )
+        .await
+        .expect("Error exporting store");
+    assert_eq!(exported, 4);
+    let archive = std::fs::read(&archive_path).expect("Error reading archive file");
+    std::fs::remove_file(&archive_path).expect("Error removing archive file");
+
+    let wrong_key = generate_raw_store_key(None).expect("Error generating archive key");
+    let err = db
+        .import(Cursor::new(archive.clone()), wrong_key.as_ref(), None)
+        .await
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Encryption);
+
+    assert!(db
+        .remove_profile(profile.clone())
+        .await
+        .expect("Error removing profile"));
+    let imported = db
+        .import(Cursor::new(archive.clone()), pass_key.as_ref(), None)
+        .await
+        .expect("Error importing store");
+    assert_eq!(imported, 4);
+
+    let mut conn = db.session(Some(profile.clone())).await.expect(ERR_SESSION);
+    for row in test_rows.iter() {
+        let found = conn
+            .fetch(&row.category, &row.name, false)
+            .await
+            .expect(ERR_FETCH)
+            .expect(ERR_REQ_ROW);
+        assert_eq!(&found, row);
+    }
+    let expiring = conn
+        .fetch("category", "expiring", false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert!(expiring.expiry.is_some());
+    let key_entry = conn
+        .fetch_key("key", false)
+        .await
+        .expect("Error fetching key")
+        .expect(ERR_REQ_ROW);
+    assert_eq!(key_entry.metadata(), Some("meta"));
+    assert_eq!(
+        key_entry
+            .load_local_key()
+            .expect("Error loading key")
+            .to_jwk_public(None)
+            .expect("Error encoding key"),
+        key.to_jwk_public(None).expect("Error encoding key")
+    );
+    conn.replace("other", "name", b"changed", None, None)
+        .await
+        .expect(ERR_REPLACE);
+    conn.insert("other", "unarchived", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+    drop(conn);
+
+    // existing entries are replaced by the archived entries
+    let imported = db
+        .import(Cursor::new(archive.clone()), pass_key.as_ref(), None)
+        .await
+        .expect("Error importing store");
+    assert_eq!(imported, 4);
+    let mut conn = db.session(Some(profile)).await.expect(ERR_SESSION);
+    let found = conn
+        .fetch("other", "name", false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(found, test_rows[1]);
+    assert!(conn
+        .fetch("other", "unarchived", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_some());
+}
+
+#[cfg(feature = "memory")]
+pub async fn db_migrate_from_memory<DB: Backend>(db: &Store<DB>) {
+    use aries_askar::backend::memory::MemoryStoreOptions;
+
+    let source = MemoryStoreOptions::default()
+        .provision(
+            StoreKeyMethod::RawKey,
+            generate_raw_store_key(None).expect("Error generating store key"),
+            Some(db.get_profile_name()),
+            false,
+        )
+        .await
+        .expect("Error provisioning memory store");
+    let profile = source.create_profile(None).await.expect(ERR_PROFILE);
+    let test_row = Entry::new(
+        "category",
+        "name",
+        "value",
+        vec![EntryTag::Encrypted("t1".to_string(), "v1".to_string())],
+    );
+    for profile in [None, Some(profile.clone())].iter() {
+        let mut conn = source.session(profile.clone()).await.expect(ERR_SESSION);
+        conn.insert(
+            &test_row.category,
+            &test_row.name,
+            &test_row.value,
+            Some(test_row.tags.as_slice()),
+            None,
+        )
+        .await
+        .expect(ERR_INSERT);
+    }
+
+    let result = source
+        .migrate_to(db, |_| ())
+        .await
+        .expect("Error migrating store");
+    assert_eq!(result.profiles_done, 2);
+    assert_eq!(result.entries_copied, 2);
+
+    for profile in [None, Some(profile.clone())].iter() {
+        let mut conn = db.session(profile.clone()).await.expect(ERR_SESSION);
+        let row = conn
+            .fetch(&test_row.category, &test_row.name, false)
+            .await
+            .expect(ERR_FETCH)
+            .expect(ERR_REQ_ROW);
+        assert_eq!(row, test_row);
+    }
+
+    let result = source
+        .migrate_to(db, |_| ())
+        .await
+        .expect("Error migrating store");
+    assert_eq!(result.entries_copied, 0);
+    assert_eq!(result.entries_skipped, 2);
+
+    // records updated since the previous migration are replaced
+    let mut conn = source.session(None).await.expect(ERR_SESSION);
+    conn.replace(
+        &test_row.category,
+        &test_row.name,
+        b"new value",
+        Some(&[EntryTag::Plaintext("t2".to_string(), "v2".to_string())]),
+        Some(60 * 60 * 1000),
+    )
+    .await
+    .expect(ERR_REPLACE);
+    drop(conn);
+    let result = source
+        .migrate_to(db, |_| ())
+        .await
+        .expect("Error migrating store");
+    assert_eq!(result.entries_copied, 1);
+    assert_eq!(result.entries_skipped, 1);
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    let row = conn
+        .fetch(&test_row.category, &test_row.name, false)
+        .await
+        .expect(ERR_FETCH)
+        .expect(ERR_REQ_ROW);
+    assert_eq!(row.value.as_ref(), b"new value");
+    assert_eq!(
+        row.tags,
+        vec![EntryTag::Plaintext("t2".to_string(), "v2".to_string())]
+    );
+    assert!(row.expiry.is_some());
+    drop(conn);
+    let result = source
+        .migrate_to(db, |_| ())
+        .await
+        .expect("Error migrating store");
+    assert_eq!(result.entries_copied, 0);
+    assert_eq!(result.entries_skipped, 2);
+
+    // a profile with existing records must not be assigned a different key
+    assert!(source
+        .remove_profile(profile.clone())
+        .await
+        .expect("Error removing profile"));
+    source
+        .create_profile(Some(profile))
+        .await
+        .expect(ERR_PROFILE);
+    let err = source.migrate_to(db, |_| ()).await.expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Duplicate);
+}
+
+pub async fn db_purge_expired<DB: Backend>(db: &Store<DB>) {
+    let profile = db.get_profile_name().to_string();
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    conn.insert("category", "expired", b"value", None, Some(-1000))
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("category", "current", b"value", None, Some(60 * 60 * 1000))
+        .await
+        .expect(ERR_INSERT);
+    conn.insert("category", "permanent", b"value", None, None)
+        .await
+        .expect(ERR_INSERT);
+
+    assert!(conn
+        .fetch("category", "expired", false)
+        .await
+        .expect(ERR_FETCH)
+        .is_none());
+    assert_eq!(conn.count("category", None).await.expect(ERR_COUNT), 2);
+    drop(conn);
+
+    let removed = db
+        .purge_expired()
+        .await
+        .expect("Error purging expired rows");
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed.get(&profile), Some(&1));
+    let removed = db
+        .purge_expired()
+        .await
+        .expect("Error purging expired rows");
+    assert!(removed.is_empty());
+
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    assert_eq!(
+        conn.remove_all("category", None)
+            .await
+            .expect(ERR_REMOVE_ALL),
+        2
+    );
+}
+
+pub async fn db_expiry_sweeper<DB: Backend + 'static>(db: Store<DB>) {
+    let db = Arc::new(db);
+    let mut conn = db.session(None).await.expect(ERR_SESSION);
+    conn.insert("category", "expired", b"value", None, Some(-1000))
+        .await
+        .expect(ERR_INSERT);
+    drop(conn);
+
+    let err = db
+        .start_expiry_sweeper(Duration::from_secs(0), |_| ())
+        .expect_err(ERR_REQ_ERR);
+    assert_eq!(err.kind(), ErrorKind::Input);
+
+    let (sender, receiver) = mpsc::channel();
+    let sweeper = db
+        .start_expiry_sweeper(Duration::from_millis(10), move |result| {
+            sender.send(result).ok();
+        })
+        .expect("Error starting expiry sweeper");
+    let removed = receiver
+        .recv_timeout(Duration::from_secs(5))
+        .expect("Expected purge result")
+        .expect("Error purging expired rows");
+    assert_eq!(removed.values().sum::<i64>(), 1);
+
+    // the callback is released once the task has stopped
+    sweeper.stop().await;
+    loop {
+        match receiver.try_recv() {
+            Ok(_) => continue,
+            Err(mpsc::TryRecvError::Disconnected) => break,
+            Err(mpsc::TryRecvError::Empty) => panic!("Expected the sweeper to be stopped"),
+        }
+    }
+}
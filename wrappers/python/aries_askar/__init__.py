@@ -3,7 +3,15 @@
 from .bindings import version, Encrypted
 from .error import AskarError, AskarErrorCode
 from .key import Key
-from .store import Entry, EntryList, KeyEntry, KeyEntryList, Session, Store
+from .store import (
+    Entry,
+    EntryBatch,
+    EntryList,
+    KeyEntry,
+    KeyEntryList,
+    Session,
+    Store,
+)
 from .types import KeyAlg
 from . import crypto_box
 from . import ecdh
@@ -16,6 +24,7 @@ __all__ = (
     "AskarErrorCode",
     "Encrypted",
     "Entry",
+    "EntryBatch",
     "EntryList",
     "Key",
     "KeyAlg",
@@ -21,7 +21,7 @@ from ctypes import (
     c_ubyte,
 )
 from ctypes.util import find_library
-from typing import Optional, Tuple, Union
+from typing import Optional, Sequence, Tuple, Union
 
 from .error import AskarError, AskarErrorCode
 from .types import EntryOperation, KeyAlg
@@ -143,6 +143,30 @@ class EntryListHandle(c_size_t):
             tags = dict()
         return tags
 
+    def get_version(self, index: int) -> int:
+        """Get the entry version."""
+        version = c_int64()
+        do_call("askar_entry_list_get_version", self, c_int32(index), byref(version))
+        return version.value
+
+    def get_expiry(self, index: int) -> Optional[int]:
+        """Get the entry expiry timestamp in milliseconds, if any."""
+        expiry = c_int64()
+        do_call("askar_entry_list_get_expiry", self, c_int32(index), byref(expiry))
+        return expiry.value if expiry.value >= 0 else None
+
+    def get_created(self, index: int) -> Optional[int]:
+        """Get the entry creation timestamp in milliseconds, if any."""
+        created = c_int64()
+        do_call("askar_entry_list_get_created", self, c_int32(index), byref(created))
+        return created.value if created.value >= 0 else None
+
+    def get_updated(self, index: int) -> Optional[int]:
+        """Get the entry update timestamp in milliseconds, if any."""
+        updated = c_int64()
+        do_call("askar_entry_list_get_updated", self, c_int32(index), byref(updated))
+        return updated.value if updated.value >= 0 else None
+
     def __repr__(self) -> str:
         """Format entry list handle as a string."""
         return f"{self.__class__.__name__}({self.value})"
@@ -200,6 +224,34 @@ class KeyEntryListHandle(c_size_t):
         )
         return json.loads(tags.value) if tags else None
 
+    def get_expiry(self, index: int) -> Optional[int]:
+        """Get the key entry expiry timestamp in milliseconds, if any."""
+        expiry = c_int64()
+        do_call("askar_key_entry_list_get_expiry", self, c_int32(index), byref(expiry))
+        return expiry.value if expiry.value >= 0 else None
+
+    def get_created(self, index: int) -> Optional[int]:
+        """Get the key entry creation timestamp in milliseconds, if any."""
+        created = c_int64()
+        do_call(
+            "askar_key_entry_list_get_created",
+            self,
+            c_int32(index),
+            byref(created),
+        )
+        return created.value if created.value >= 0 else None
+
+    def get_updated(self, index: int) -> Optional[int]:
+        """Get the key entry update timestamp in milliseconds, if any."""
+        updated = c_int64()
+        do_call(
+            "askar_key_entry_list_get_updated",
+            self,
+            c_int32(index),
+            byref(updated),
+        )
+        return updated.value if updated.value >= 0 else None
+
     def load_key(self, index: int) -> "LocalKeyHandle":
         """Load the key instance."""
         handle = LocalKeyHandle()
@@ -221,6 +273,55 @@ class KeyEntryListHandle(c_size_t):
             get_library().askar_key_entry_list_free(self)
 
 
+class EntryBatchHandle(c_size_t):
+    """Pointer to an active EntryBatch instance."""
+
+    def __len__(self) -> int:
+        """Get the number of updates in the batch."""
+        count = c_int32()
+        do_call("askar_entry_batch_count", self, byref(count))
+        return count.value
+
+    def __repr__(self) -> str:
+        """Format entry batch handle as a string."""
+        return f"{self.__class__.__name__}({self.value})"
+
+    def __del__(self):
+        """Free the entry batch when there are no more references."""
+        if self:
+            get_library().askar_entry_batch_free(self)
+
+
+class StringListHandle(c_size_t):
+    """Pointer to an active StringList instance."""
+
+    def get_item(self, index: int) -> str:
+        """Get one of the string values."""
+        item = StrBuffer()
+        do_call(
+            "askar_string_list_get_item",
+            self,
+            c_int32(index),
+            byref(item),
+        )
+        return str(item)
+
+    def __len__(self) -> int:
+        """Get the number of strings in the list."""
+        count = c_int32()
+        do_call("askar_string_list_count", self, byref(count))
+        return count.value
+
+    def __repr__(self) -> str:
+        """Format string list handle as a string."""
+        return f"{self.__class__.__name__}({self.value})"
+
+    def __del__(self):
+        """Free the string list when there are no more references."""
+        if self:
+            get_library().askar_string_list_free(self)
+
+
 class LocalKeyHandle(c_size_t):
     """Pointer to an active LocalKey instance."""
 
@@ -711,6 +812,58 @@ async def store_remove_profile(handle: StoreHandle, name: str) -> bool:
     )
 
 
+async def store_list_profiles(handle: StoreHandle) -> Sequence[str]:
+    """List the profile names in a Store."""
+    names = await do_call_async(
+        "askar_store_list_profiles",
+        handle,
+        return_type=StringListHandle,
+    )
+    return [names.get_item(idx) for idx in range(len(names))]
+
+
+async def store_rename_profile(
+    handle: StoreHandle, from_name: str, to_name: str
+) -> bool:
+    """Rename an existing profile in a Store."""
+    return (
+        await do_call_async(
+            "askar_store_rename_profile",
+            handle,
+            encode_str(from_name),
+            encode_str(to_name),
+            return_type=c_int8,
+        )
+        != 0
+    )
+
+
+async def store_copy_profile(handle: StoreHandle, from_name: str, to_name: str) -> bool:
+    """Copy an existing profile to a new profile in a Store."""
+    return (
+        await do_call_async(
+            "askar_store_copy_profile",
+            handle,
+            encode_str(from_name),
+            encode_str(to_name),
+            return_type=c_int8,
+        )
+        != 0
+    )
+
+
+async def store_rotate_profile_key(handle: StoreHandle, name: str) -> int:
+    """Replace the profile key of an existing profile in a Store."""
+    return int(
+        await do_call_async(
+            "askar_store_rotate_profile_key",
+            handle,
+            encode_str(name),
+            return_type=c_int64,
+        )
+    )
+
+
 async def store_rekey(
     handle: StoreHandle,
     key_method: str = None,
@@ -725,6 +878,16 @@ async def store_rekey(
     )
 
 
+async def store_backup(handle: StoreHandle, path: str):
+    """Write a consistent copy of a Store to a new database file."""
+    await do_call_async("askar_store_backup", handle, encode_str(path))
+
+
+async def store_compact(handle: StoreHandle):
+    """Rebuild the database file of a Store to reclaim unused space."""
+    await do_call_async("askar_store_compact", handle)
+
+
 async def store_remove(uri: str) -> bool:
     """Remove an existing Store, if any."""
     return (
@@ -765,6 +928,13 @@ async def session_count(
     )
 
 
+async def session_stats(handle: SessionHandle) -> dict:
+    """Collect usage statistics for the records and keys of the Session profile."""
+    return json.loads(
+        str(await do_call_async("askar_session_stats", handle, return_type=StrBuffer))
+    )
+
+
 async def session_fetch(
     handle: SessionHandle, category: str, name: str, for_update: bool = False
 ) -> EntryListHandle:
@@ -844,6 +1014,122 @@ async def session_update(
     )
 
 
+async def session_replace_if_version(
+    handle: SessionHandle,
+    category: str,
+    name: str,
+    expected_version: int,
+    value: Union[str, bytes] = None,
+    tags: dict = None,
+    expiry_ms: Optional[int] = None,
+):
+    """Replace a record in the Store if its version matches."""
+
+    return await do_call_async(
+        "askar_session_replace_if_version",
+        handle,
+        encode_str(category),
+        encode_str(name),
+        c_int64(expected_version),
+        encode_bytes(value),
+        encode_tags(tags),
+        c_int64(-1 if expiry_ms is None else expiry_ms),
+    )
+
+
+async def session_update_tags(
+    handle: SessionHandle,
+    category: str,
+    name: str,
+    add: dict = None,
+    remove: Sequence[str] = None,
+):
+    """Add and remove tags of a record in the Store."""
+
+    return await do_call_async(
+        "askar_session_update_tags",
+        handle,
+        encode_str(category),
+        encode_str(name),
+        encode_tags(add),
+        encode_str(None if remove is None else json.dumps(list(remove))),
+    )
+
+
+async def session_set_expiry(
+    handle: SessionHandle,
+    category: str,
+    name: str,
+    expiry_ms: Optional[int] = None,
+):
+    """Update the expiry time of a record in the Store."""
+
+    return await do_call_async(
+        "askar_session_set_expiry",
+        handle,
+        encode_str(category),
+        encode_str(name),
+        c_int64(-1 if expiry_ms is None else expiry_ms),
+    )
+
+
+async def session_set_expiry_all(
+    handle: SessionHandle,
+    category: str,
+    tag_filter: Union[str, dict] = None,
+    expiry_ms: Optional[int] = None,
+) -> int:
+    """Update the expiry time of all matching rows in the Store."""
+    if isinstance(tag_filter, dict):
+        tag_filter = json.dumps(tag_filter)
+    return int(
+        await do_call_async(
+            "askar_session_set_expiry_all",
+            handle,
+            encode_str(category),
+            encode_str(tag_filter),
+            c_int64(-1 if expiry_ms is None else expiry_ms),
+            return_type=c_int64,
+        )
+    )
+
+
+def entry_batch_new() -> EntryBatchHandle:
+    """Create a new, empty batch of record updates."""
+    handle = EntryBatchHandle()
+    do_call("askar_entry_batch_new", byref(handle))
+    return handle
+
+
+def entry_batch_push(
+    handle: EntryBatchHandle,
+    operation: EntryOperation,
+    category: str,
+    name: str,
+    value: Union[str, bytes] = None,
+    tags: dict = None,
+    expiry_ms: Optional[int] = None,
+    expected_version: Optional[int] = None,
+):
+    """Add a record update to a batch."""
+    do_call(
+        "askar_entry_batch_push",
+        handle,
+        c_int8(operation.value),
+        encode_str(category),
+        encode_str(name),
+        encode_bytes(value),
+        encode_tags(tags),
+        c_int64(-1 if expiry_ms is None else expiry_ms),
+        c_int64(-1 if expected_version is None else expected_version),
+    )
+
+
+async def session_apply_batch(handle: SessionHandle, batch: EntryBatchHandle):
+    """Apply a batch of record updates to the Store as a single operation."""
+    await do_call_async("askar_session_apply_batch", handle, batch)
+
+
 async def session_insert_key(
     handle: SessionHandle,
     key_handle: LocalKeyHandle,
@@ -935,11 +1221,24 @@ async def scan_start(
     tag_filter: Union[str, dict] = None,
     offset: int = None,
     limit: int = None,
+    order_by: str = None,
 ) -> ScanHandle:
     """Create a new Scan against the Store."""
     if isinstance(tag_filter, dict):
         tag_filter = json.dumps(tag_filter)
     tag_filter = encode_str(tag_filter)
+    if order_by:
+        return await do_call_async(
+            "askar_scan_start_ordered",
+            handle,
+            encode_str(profile),
+            encode_str(category),
+            tag_filter,
+            encode_str(order_by),
+            c_int64(offset or 0),
+            c_int64(limit if limit is not None else -1),
+            return_type=ScanHandle,
+        )
     return await do_call_async(
         "askar_scan_start",
         handle,
@@ -952,11 +1251,34 @@ async def scan_start(
     )
 
 
+async def scan_resume(
+    handle: StoreHandle,
+    token: str,
+    limit: int = None,
+) -> ScanHandle:
+    """Resume a Scan against the Store from a continuation token."""
+    return await do_call_async(
+        "askar_scan_resume",
+        handle,
+        encode_str(token),
+        c_int64(limit if limit is not None else -1),
+        return_type=ScanHandle,
+    )
+
+
 async def scan_next(handle: StoreHandle) -> Optional[EntryListHandle]:
     handle = await do_call_async("askar_scan_next", handle, return_type=EntryListHandle)
     return handle or None
 
 
+async def scan_continuation_token(handle: ScanHandle) -> Optional[str]:
+    """Get the continuation token for the last set of rows fetched by a Scan."""
+    token = await do_call_async(
+        "askar_scan_continuation_token", handle, return_type=StrBuffer
+    )
+    return token.opt_str()
+
+
 def entry_list_count(handle: EntryListHandle) -> int:
     len = c_int32()
     do_call("askar_entry_list_count", handle, byref(len))
@@ -13,6 +13,9 @@ class AskarErrorCode(IntEnum):
     NOT_FOUND = 6
     UNEXPECTED = 7
     UNSUPPORTED = 8
+    MIGRATION = 9
+    CONFLICT = 10
+    READ_ONLY = 11
     WRAPPER = 99
 
 
@@ -9,6 +9,7 @@ from cached_property import cached_property
 from . import bindings
 
 from .bindings import (
+    EntryBatchHandle,
     EntryListHandle,
     KeyEntryListHandle,
     ScanHandle,
@@ -60,6 +61,26 @@ class Entry:
         """Accessor for the entry tags."""
         return self._list.get_tags(self._pos)
 
+    @cached_property
+    def version(self) -> int:
+        """Accessor for the entry version."""
+        return self._list.get_version(self._pos)
+
+    @cached_property
+    def expiry(self) -> Optional[int]:
+        """Accessor for the entry expiry timestamp in milliseconds."""
+        return self._list.get_expiry(self._pos)
+
+    @cached_property
+    def created(self) -> Optional[int]:
+        """Accessor for the entry creation timestamp in milliseconds."""
+        return self._list.get_created(self._pos)
+
+    @cached_property
+    def updated(self) -> Optional[int]:
+        """Accessor for the entry update timestamp in milliseconds."""
+        return self._list.get_updated(self._pos)
+
     def keys(self):
         """Mapping keys."""
         return Entry._KEYS
@@ -151,6 +172,21 @@ class KeyEntry:
         """Accessor for the entry tags."""
         return self._list.get_tags(self._pos)
 
+    @cached_property
+    def expiry(self) -> Optional[int]:
+        """Accessor for the key entry expiry timestamp in milliseconds."""
+        return self._list.get_expiry(self._pos)
+
+    @cached_property
+    def created(self) -> Optional[int]:
+        """Accessor for the key entry creation timestamp in milliseconds."""
+        return self._list.get_created(self._pos)
+
+    @cached_property
+    def updated(self) -> Optional[int]:
+        """Accessor for the key entry update timestamp in milliseconds."""
+        return self._list.get_updated(self._pos)
+
     def __repr__(self) -> str:
         """Format key entry handle as a string."""
         return (
@@ -203,6 +239,77 @@ class KeyEntryList:
         )
 
 
+class EntryBatch:
+    """A sequence of record updates to be applied together."""
+
+    def __init__(self):
+        """Initialize the EntryBatch instance."""
+        self._handle: EntryBatchHandle = bindings.entry_batch_new()
+
+    @property
+    def handle(self) -> EntryBatchHandle:
+        """Accessor for the entry batch handle."""
+        return self._handle
+
+    def insert(
+        self,
+        category: str,
+        name: str,
+        value: Union[str, bytes] = None,
+        tags: dict = None,
+        expiry_ms: int = None,
+        value_json=None,
+    ) -> "EntryBatch":
+        if value is None and value_json is not None:
+            value = json.dumps(value_json)
+        bindings.entry_batch_push(
+            self._handle, EntryOperation.INSERT, category, name, value, tags, expiry_ms
+        )
+        return self
+
+    def replace(
+        self,
+        category: str,
+        name: str,
+        value: Union[str, bytes] = None,
+        tags: dict = None,
+        expiry_ms: int = None,
+        value_json=None,
+        expected_version: int = None,
+    ) -> "EntryBatch":
+        if value is None and value_json is not None:
+            value = json.dumps(value_json)
+        bindings.entry_batch_push(
+            self._handle,
+            EntryOperation.REPLACE,
+            category,
+            name,
+            value,
+            tags,
+            expiry_ms,
+            expected_version,
+        )
+        return self
+
+    def remove(
+        self, category: str, name: str, expected_version: int = None
+    ) -> "EntryBatch":
+        bindings.entry_batch_push(
+            self._handle,
+            EntryOperation.REMOVE,
+            category,
+            name,
+            expected_version=expected_version,
+        )
+        return self
+
+    def __len__(self) -> int:
+        return len(self._handle)
+
+    def __repr__(self) -> str:
+        return f"<EntryBatch(handle={self._handle})>"
+
+
 class Scan:
     """A scan of the Store."""
 
@@ -214,9 +321,12 @@ class Scan:
         tag_filter: Union[str, dict] = None,
         offset: int = None,
         limit: int = None,
+        token: str = None,
+        order_by: str = None,
     ):
         """Initialize the Scan instance."""
-        self.params = (store, profile, category, tag_filter, offset, limit)
+        self.params = (store, profile, category, tag_filter, offset, limit, token)
+        self.order_by = order_by
         self._handle: ScanHandle = None
         self._buffer: EntryList = None
 
@@ -230,14 +340,23 @@ class Scan:
 
     async def __anext__(self):
         if self._handle is None:
-            (store, profile, category, tag_filter, offset, limit) = self.params
+            (store, profile, category, tag_filter, offset, limit, token) = self.params
             if not store.handle:
                 raise AskarError(
                     AskarErrorCode.WRAPPER, "Cannot scan from closed store"
                 )
-            self._handle = await bindings.scan_start(
-                store.handle, profile, category, tag_filter, offset, limit
-            )
+            if token:
+                self._handle = await bindings.scan_resume(store.handle, token, limit)
+            else:
+                self._handle = await bindings.scan_start(
+                    store.handle,
+                    profile,
+                    category,
+                    tag_filter,
+                    offset,
+                    limit,
+                    self.order_by,
+                )
             list_handle = await bindings.scan_next(self._handle)
             self._buffer = EntryList(list_handle) if list_handle else None
         while True:
@@ -255,6 +374,16 @@ class Scan:
             rows.append(row)
         return rows
 
+    async def continuation_token(self) -> Optional[str]:
+        """Get a token for resuming the scan after the last fetched set of rows.
+
+        Rows are fetched in batches, so the token is only accurate once the
+        scan or the current batch has been fully consumed.
+        """
+        if not self._handle:
+            return self.params[-1]
+        return await bindings.scan_continuation_token(self._handle)
+
     def __repr__(self) -> str:
         return f"<Scan(handle={self._handle})>"
 
@@ -271,7 +400,7 @@ class Store:
     @classmethod
     def generate_raw_key(cls, seed: Union[str, bytes] = None) -> str:
         """Generate a new raw key for a Store."""
-        bindings.generate_raw_key(seed)
+        return bindings.generate_raw_key(seed)
 
     @property
     def handle(self) -> StoreHandle:
@@ -332,6 +461,18 @@ class Store:
     async def remove_profile(self, name: str) -> bool:
         return await bindings.store_remove_profile(self._handle, name)
 
+    async def list_profiles(self) -> Sequence[str]:
+        return await bindings.store_list_profiles(self._handle)
+
+    async def rename_profile(self, from_name: str, to_name: str) -> bool:
+        return await bindings.store_rename_profile(self._handle, from_name, to_name)
+
+    async def copy_profile(self, from_name: str, to_name: str) -> bool:
+        return await bindings.store_copy_profile(self._handle, from_name, to_name)
+
+    async def rotate_profile_key(self, name: str) -> int:
+        return await bindings.store_rotate_profile_key(self._handle, name)
+
     async def rekey(
         self,
         key_method: str = None,
@@ -339,6 +480,12 @@ class Store:
     ):
         await bindings.store_rekey(self._handle, key_method, pass_key)
 
+    async def backup(self, path: str):
+        await bindings.store_backup(self._handle, path)
+
+    async def compact(self):
+        await bindings.store_compact(self._handle)
+
     def scan(
         self,
         category: str,
@@ -346,8 +493,20 @@ class Store:
         offset: int = None,
         limit: int = None,
         profile: str = None,
+        order_by: str = None,
     ) -> Scan:
-        return Scan(self, profile, category, tag_filter, offset, limit)
+        """Create a new scan of the records in a category.
+
+        Records are returned in the order they were added, unless `order_by`
+        is given as `id` or the name of a plaintext tag such as `~created`,
+        optionally prefixed with `-` for descending order.
+        """
+        return Scan(
+            self, profile, category, tag_filter, offset, limit, order_by=order_by
+        )
+
+    def resume_scan(self, token: str, limit: int = None) -> Scan:
+        return Scan(self, None, None, None, None, limit, token)
 
     def session(self, profile: str = None) -> "OpenSession":
         return OpenSession(self, profile, False)
@@ -399,6 +558,13 @@ class Session:
             raise AskarError(AskarErrorCode.WRAPPER, "Cannot count from closed session")
         return await bindings.session_count(self._handle, category, tag_filter)
 
+    async def stats(self) -> dict:
+        if not self._handle:
+            raise AskarError(
+                AskarErrorCode.WRAPPER, "Cannot collect statistics from closed session"
+            )
+        return await bindings.session_stats(self._handle)
+
     async def fetch(
         self, category: str, name: str, *, for_update: bool = False
     ) -> Optional[Entry]:
@@ -459,6 +625,63 @@ class Session:
             self._handle, EntryOperation.REPLACE, category, name, value, tags, expiry_ms
         )
 
+    async def replace_if_version(
+        self,
+        category: str,
+        name: str,
+        expected_version: int,
+        value: Union[str, bytes] = None,
+        tags: dict = None,
+        expiry_ms: int = None,
+        value_json=None,
+    ):
+        if not self._handle:
+            raise AskarError(AskarErrorCode.WRAPPER, "Cannot update closed session")
+        if value is None and value_json is not None:
+            value = json.dumps(value_json)
+        await bindings.session_replace_if_version(
+            self._handle, category, name, expected_version, value, tags, expiry_ms
+        )
+
+    async def update_tags(
+        self,
+        category: str,
+        name: str,
+        add: dict = None,
+        remove: Sequence[str] = None,
+    ):
+        """Add and remove tags of a record, leaving its value unchanged.
+
+        Tags named in `remove` are removed before the tags in `add` are inserted.
+        """
+        if not self._handle:
+            raise AskarError(AskarErrorCode.WRAPPER, "Cannot update closed session")
+        await bindings.session_update_tags(self._handle, category, name, add, remove)
+
+    async def set_expiry(
+        self,
+        category: str,
+        name: str,
+        expiry_ms: int = None,
+    ):
+        """Update the expiry time of a record, leaving its value unchanged."""
+        if not self._handle:
+            raise AskarError(AskarErrorCode.WRAPPER, "Cannot update closed session")
+        await bindings.session_set_expiry(self._handle, category, name, expiry_ms)
+
+    async def set_expiry_all(
+        self,
+        category: str,
+        tag_filter: Union[str, dict] = None,
+        expiry_ms: int = None,
+    ) -> int:
+        """Update the expiry time of all matching records."""
+        if not self._handle:
+            raise AskarError(AskarErrorCode.WRAPPER, "Cannot update closed session")
+        return await bindings.session_set_expiry_all(
+            self._handle, category, tag_filter, expiry_ms
+        )
+
     async def remove(
         self,
         category: str,
@@ -470,6 +693,15 @@ class Session:
             self._handle, EntryOperation.REMOVE, category, name
         )
 
+    async def apply_batch(self, batch: EntryBatch):
+        """Apply a batch of updates, either all of them or none of them.
+
+        The batch is left empty afterward.
+        """
+        if not self._handle:
+            raise AskarError(AskarErrorCode.WRAPPER, "Cannot update closed session")
+        await bindings.session_apply_batch(self._handle, batch.handle)
+
     async def remove_all(
         self,
         category: str,
@@ -1,8 +1,11 @@
 import os
 
-from pytest import fixture, mark
+from pytest import fixture, mark, raises
 
 from aries_askar import (
+    AskarError,
+    AskarErrorCode,
+    EntryBatch,
     KeyAlg,
     Key,
     Store,
@@ -85,6 +88,130 @@ async def test_remove_all(store: Store):
         assert found is None
 
 
+@mark.asyncio
+async def test_stats(store: Store):
+
+    async with store as session:
+        await session.insert(
+            TEST_ENTRY["category"],
+            TEST_ENTRY["name"],
+            TEST_ENTRY["value"],
+            TEST_ENTRY["tags"],
+        )
+        await session.insert_key("testkey", Key.generate(KeyAlg.ED25519))
+
+        stats = await session.stats()
+        assert stats["profile"] == await store.get_profile_name()
+        category = stats["categories"][TEST_ENTRY["category"]]
+        assert category["count"] == 1 and category["size"] > len(TEST_ENTRY["value"])
+        assert stats["keys"]["count"] == 1
+
+
+@mark.asyncio
+async def test_apply_batch(store: Store):
+    batch = EntryBatch()
+    for idx in range(3):
+        batch.insert(TEST_ENTRY["category"], f"name-{idx}", b"value", {"~idx": str(idx)})
+    batch.remove(TEST_ENTRY["category"], "name-0")
+    assert len(batch) == 4
+
+    async with store as session:
+        await session.apply_batch(batch)
+        assert len(batch) == 0
+        assert await session.count(TEST_ENTRY["category"]) == 2
+
+        # a failed update reverts the whole batch
+        batch.insert(TEST_ENTRY["category"], "name-3", b"value")
+        batch.insert(TEST_ENTRY["category"], "name-1", b"value")
+        try:
+            await session.apply_batch(batch)
+        except AskarError as err:
+            assert err.code == AskarErrorCode.DUPLICATE
+        else:
+            raise Exception("Expected duplicate error")
+        assert await session.count(TEST_ENTRY["category"]) == 2
+
+
+@mark.asyncio
+async def test_replace_if_version(store: Store):
+    async with store as session:
+        await session.insert(TEST_ENTRY["category"], TEST_ENTRY["name"], b"first")
+        found = await session.fetch(TEST_ENTRY["category"], TEST_ENTRY["name"])
+        assert found.version == 1
+
+        await session.replace_if_version(
+            TEST_ENTRY["category"], TEST_ENTRY["name"], found.version, b"second"
+        )
+        try:
+            await session.replace_if_version(
+                TEST_ENTRY["category"], TEST_ENTRY["name"], found.version, b"stale"
+            )
+        except AskarError as err:
+            assert err.code == AskarErrorCode.CONFLICT
+        else:
+            raise Exception("Expected conflict error")
+
+        found = await session.fetch(TEST_ENTRY["category"], TEST_ENTRY["name"])
+        assert found.value == b"second" and found.version == 2
+
+
+@mark.asyncio
+async def test_update_tags(store: Store):
+    async with store as session:
+        await session.insert(
+            TEST_ENTRY["category"],
+            TEST_ENTRY["name"],
+            TEST_ENTRY["value"],
+            TEST_ENTRY["tags"],
+        )
+        await session.update_tags(
+            TEST_ENTRY["category"],
+            TEST_ENTRY["name"],
+            add={"~plaintag": "b", "newtag": "d"},
+            remove=["~plaintag"],
+        )
+        found = await session.fetch(TEST_ENTRY["category"], TEST_ENTRY["name"])
+        assert found.value == TEST_ENTRY["value"]
+        assert found.tags == {"~plaintag": "b", "enctag": {"b", "c"}, "newtag": "d"}
+
+
+@mark.asyncio
+async def test_entry_timestamps(store: Store):
+    async with store as session:
+        await session.insert(
+            TEST_ENTRY["category"],
+            TEST_ENTRY["name"],
+            TEST_ENTRY["value"],
+            TEST_ENTRY["tags"],
+            expiry_ms=60000,
+        )
+        found = await session.fetch(TEST_ENTRY["category"], TEST_ENTRY["name"])
+        assert found.created and found.updated == found.created
+        assert found.expiry > found.created
+
+
+@mark.asyncio
+async def test_set_expiry(store: Store):
+    async with store as session:
+        await session.insert(
+            TEST_ENTRY["category"],
+            TEST_ENTRY["name"],
+            TEST_ENTRY["value"],
+            TEST_ENTRY["tags"],
+        )
+        await session.set_expiry(TEST_ENTRY["category"], TEST_ENTRY["name"], 60000)
+        found = await session.fetch(TEST_ENTRY["category"], TEST_ENTRY["name"])
+        assert found.value == TEST_ENTRY["value"]
+        assert found.expiry is not None
+
+        updated = await session.set_expiry_all(
+            TEST_ENTRY["category"], {"~plaintag": "a"}, None
+        )
+        assert updated == 1
+        found = await session.fetch(TEST_ENTRY["category"], TEST_ENTRY["name"])
+        assert found.expiry is None
+
+
 @mark.asyncio
 async def test_scan(store: Store):
 
@@ -103,6 +230,40 @@ async def test_scan(store: Store):
     assert len(rows) == 1 and dict(rows[0]) == TEST_ENTRY
 
 
+@mark.asyncio
+async def test_scan_resume(store: Store):
+
+    async with store as session:
+        for idx in range(3):
+            await session.insert(TEST_ENTRY["category"], f"name-{idx}", b"value")
+
+    scan = store.scan(TEST_ENTRY["category"], limit=2)
+    rows = await scan.fetch_all()
+    assert [row.name for row in rows] == ["name-0", "name-1"]
+    token = await scan.continuation_token()
+
+    rows = await store.resume_scan(token).fetch_all()
+    assert [row.name for row in rows] == ["name-2"]
+
+
+@mark.asyncio
+async def test_scan_order(store: Store):
+
+    async with store as session:
+        for idx, seq in enumerate((2, 10, 1)):
+            await session.insert(
+                TEST_ENTRY["category"], f"name-{idx}", b"value", {"~#seq": str(seq)}
+            )
+
+    rows = await store.scan(TEST_ENTRY["category"], order_by="-id").fetch_all()
+    assert [row.name for row in rows] == ["name-2", "name-1", "name-0"]
+
+    rows = await store.scan(
+        TEST_ENTRY["category"], limit=2, order_by="-~#seq"
+    ).fetch_all()
+    assert [row.name for row in rows] == ["name-1", "name-0"]
+
+
 @mark.asyncio
 async def test_transaction(store: Store):
     async with store.transaction() as txn:
@@ -181,3 +342,56 @@ async def test_key_store(store: Store):
 async def test_profile(store: Store):
     profile = await store.create_profile()
     await store.remove_profile(profile)
+
+
+@mark.asyncio
+async def test_profile_rename_copy(store: Store):
+    profile = await store.create_profile()
+    assert profile in await store.list_profiles()
+
+    async with store.session(profile) as session:
+        await session.insert(
+            TEST_ENTRY["category"],
+            TEST_ENTRY["name"],
+            TEST_ENTRY["value"],
+            TEST_ENTRY["tags"],
+        )
+
+    assert await store.rename_profile(profile, "renamed")
+    assert await store.copy_profile("renamed", "copied")
+    profiles = await store.list_profiles()
+    assert profile not in profiles
+    assert "renamed" in profiles and "copied" in profiles
+
+    assert await store.rotate_profile_key("copied") == 1
+    async with store.session("copied") as session:
+        found = await session.fetch(TEST_ENTRY["category"], TEST_ENTRY["name"])
+        assert found.value == TEST_ENTRY["value"]
+
+
+
+@mark.asyncio
+async def test_backup_compact(tmp_path):
+    key = Store.generate_raw_key(b"00000000000000000000000000000My1")
+    store = await Store.provision(f"sqlite://{tmp_path / 'store.db'}", "raw", key)
+    async with store.session() as session:
+        await session.insert(
+            TEST_ENTRY["category"],
+            TEST_ENTRY["name"],
+            TEST_ENTRY["value"],
+            TEST_ENTRY["tags"],
+        )
+
+    backup_path = str(tmp_path / "backup.db")
+    await store.backup(backup_path)
+    with raises(AskarError) as err:
+        await store.backup(backup_path)
+    assert err.value.code == AskarErrorCode.DUPLICATE
+    await store.compact()
+    await store.close()
+
+    backup = await Store.open(f"sqlite://{backup_path}", "raw", key)
+    async with backup.session() as session:
+        found = await session.fetch(TEST_ENTRY["category"], TEST_ENTRY["name"])
+        assert found.value == TEST_ENTRY["value"]
+    await backup.close()